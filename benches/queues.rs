@@ -0,0 +1,52 @@
+#![feature(test)]
+
+extern crate atomic128;
+extern crate test;
+
+use std::sync::Arc;
+use std::thread;
+
+use atomic128::collections::{Lcrq, Queue};
+use test::Bencher;
+
+const THREADS: usize = 4;
+const OPS: usize = 1000;
+
+macro_rules! queue_benches {
+    ($single:ident, $contended:ident, $queue:ty) => {
+        #[bench]
+        fn $single(b: &mut Bencher) {
+            let q = <$queue>::new();
+            b.iter(|| {
+                for i in 0..OPS {
+                    q.push(i);
+                }
+                for _ in 0..OPS {
+                    test::black_box(q.pop());
+                }
+            });
+        }
+
+        #[bench]
+        fn $contended(b: &mut Bencher) {
+            let q = Arc::new(<$queue>::new());
+            b.iter(|| {
+                let handles: Vec<_> = (0..THREADS).map(|_| {
+                    let q = q.clone();
+                    thread::spawn(move || {
+                        for i in 0..OPS {
+                            q.push(i);
+                            test::black_box(q.pop());
+                        }
+                    })
+                }).collect();
+                for h in handles {
+                    h.join().unwrap();
+                }
+            });
+        }
+    };
+}
+
+queue_benches!(ms_queue_single, ms_queue_contended, Queue<usize>);
+queue_benches!(lcrq_single, lcrq_contended, Lcrq<usize>);
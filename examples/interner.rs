@@ -0,0 +1,85 @@
+// A lock-free string interner: an open-addressing table of `InternSlot`s,
+// each holding a string's hash next to a pointer to its one shared copy.
+// Several threads intern overlapping words at once and check that every
+// thread got the same pointer for the same word.
+//
+//   cargo run --example interner
+
+extern crate atomic128;
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+use std::thread;
+
+use atomic128::cells::InternSlot;
+
+struct Interner {
+    slots: Vec<InternSlot>,
+}
+
+fn hash(s: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    s.hash(&mut hasher);
+    hasher.finish()
+}
+
+impl Interner {
+    fn with_capacity(capacity: usize) -> Self {
+        Interner { slots: (0..capacity.next_power_of_two()).map(|_| InternSlot::new()).collect() }
+    }
+
+    // Interned strings live as long as the program, like most symbol tables.
+    fn intern(&self, s: &str) -> &'static str {
+        let h = hash(s);
+        let mask = self.slots.len() - 1;
+        let mut ours: Option<*mut String> = None;
+        for probe in 0..self.slots.len() {
+            let slot = &self.slots[(h as usize).wrapping_add(probe) & mask];
+            if let Some(found) = self.matching(slot, h, s) {
+                if let Some(ptr) = ours {
+                    drop(unsafe { Box::from_raw(ptr) });
+                }
+                return found;
+            }
+            if slot.is_empty() {
+                let ptr = *ours.get_or_insert_with(|| Box::into_raw(Box::new(s.to_string())));
+                if slot.claim_if_empty(h, ptr as u64).is_ok() {
+                    return unsafe { &*ptr };
+                }
+                // Someone claimed it first; it may have been for this string.
+                if let Some(found) = self.matching(slot, h, s) {
+                    drop(unsafe { Box::from_raw(ptr) });
+                    return found;
+                }
+            }
+        }
+        panic!("interner is full");
+    }
+
+    fn matching(&self, slot: &InternSlot, h: u64, s: &str) -> Option<&'static str> {
+        let ptr = slot.read_matching(h)? as *const String;
+        let interned: &'static String = unsafe { &*ptr };
+        if interned == s { Some(interned) } else { None }
+    }
+}
+
+fn main() {
+    let interner = Arc::new(Interner::with_capacity(1024));
+    let handles: Vec<_> = (0..4)
+        .map(|t| {
+            let interner = interner.clone();
+            thread::spawn(move || (0..500).map(|i| interner.intern(&format!("word{}", (i * 7 + t) % 300)) as *const str as *const u8 as usize).collect::<Vec<_>>())
+        })
+        .collect();
+    let results: Vec<Vec<usize>> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+    for i in 0..300 {
+        let word = format!("word{}", i);
+        assert_eq!(interner.intern(&word), word);
+    }
+    let mut distinct: Vec<usize> = results.into_iter().flatten().collect();
+    distinct.sort();
+    distinct.dedup();
+    assert_eq!(distinct.len(), 300);
+    println!("interned 300 words from 4 threads, one copy each");
+}
@@ -0,0 +1,71 @@
+// A sequence lock shared between two processes through a shared memory
+// segment. The parent writes rounds of data; a child process it spawns reads
+// them and checks that every read it accepts came from a single round.
+//
+//   cargo run --example shm_seqlock --features shm
+
+extern crate atomic128;
+
+use std::env;
+use std::process::{self, Command};
+use std::sync::atomic::Ordering::SeqCst;
+
+use atomic128::shm::SharedCells;
+
+// Word 0 holds the sequence number, odd while a write is under way. The rest
+// hold the round in the high half and their index in the low half.
+const WORDS: usize = 8;
+const READS: u64 = 100_000;
+
+fn write(cells: &SharedCells, round: u64) {
+    let seq = cells[0].load(SeqCst);
+    cells[0].store(seq + 1, SeqCst);
+    for i in 1..WORDS {
+        cells[i].store((round as u128) << 64 | i as u128, SeqCst);
+    }
+    cells[0].store(seq + 2, SeqCst);
+}
+
+fn read(cells: &SharedCells) -> Option<Vec<u128>> {
+    let before = cells[0].load(SeqCst);
+    if before & 1 == 1 {
+        return None;
+    }
+    let data: Vec<_> = cells[1..].iter().map(|cell| cell.load(SeqCst)).collect();
+    if cells[0].load(SeqCst) == before { Some(data) } else { None }
+}
+
+fn reader(name: &str) {
+    let cells = SharedCells::open(name).expect("open segment");
+    let mut accepted = 0;
+    while accepted < READS {
+        if let Some(data) = read(&cells) {
+            let round = data[0] >> 64;
+            assert!(data.iter().enumerate().all(|(i, &w)| w == round << 64 | (i as u128 + 1)), "torn read {:x?}", data);
+            accepted += 1;
+        }
+    }
+    println!("reader: {} consistent reads", accepted);
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() == 3 && args[1] == "reader" {
+        return reader(&args[2]);
+    }
+
+    let name = format!("/atomic128-seqlock-{}", process::id());
+    let cells = SharedCells::create(&name, WORDS).expect("create segment");
+    write(&cells, 0);
+    let mut child = Command::new(&args[0]).arg("reader").arg(&name).spawn().expect("spawn reader");
+    let mut round = 1;
+    loop {
+        write(&cells, round);
+        round += 1;
+        if let Some(status) = child.try_wait().expect("wait for reader") {
+            assert!(status.success(), "reader failed: {}", status);
+            break;
+        }
+    }
+    println!("writer: {} rounds", round);
+}
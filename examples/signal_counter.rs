@@ -0,0 +1,43 @@
+// Counting signals from inside their handler with a `SignalCounter128`. The
+// handler only touches the counter, which is async-signal-safe with the
+// `signal` feature; main raises a burst of SIGUSR1s and reads the total.
+//
+//   cargo run --example signal_counter --features signal
+
+extern crate atomic128;
+
+use atomic128::signal::SignalCounter128;
+
+static DELIVERED: SignalCounter128 = SignalCounter128::new();
+
+#[cfg(unix)]
+mod sys {
+    pub const SIGUSR1: i32 = if cfg!(any(target_os = "macos", target_os = "ios", target_os = "freebsd")) { 30 } else { 10 };
+
+    extern "C" {
+        pub fn signal(signum: i32, handler: extern "C" fn(i32)) -> usize;
+        pub fn raise(sig: i32) -> i32;
+    }
+}
+
+#[cfg(unix)]
+extern "C" fn on_signal(_: i32) {
+    DELIVERED.add(1);
+}
+
+#[cfg(unix)]
+fn main() {
+    const RAISED: u128 = 1000;
+    unsafe {
+        sys::signal(sys::SIGUSR1, on_signal);
+        for _ in 0..RAISED {
+            assert_eq!(sys::raise(sys::SIGUSR1), 0);
+        }
+    }
+    // raise returns after the handler has run on this thread.
+    assert_eq!(DELIVERED.get(), RAISED);
+    println!("counted {} signals from the handler", DELIVERED.take());
+}
+
+#[cfg(not(unix))]
+fn main() {}
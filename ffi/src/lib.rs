@@ -0,0 +1,5 @@
+// Everything is in `atomic128::ffi`; linking it in is enough for its
+// `#[no_mangle]` functions to end up in the archive.
+extern crate atomic128;
+
+pub use atomic128::ffi::*;
@@ -0,0 +1,109 @@
+// Decodes the input into a schedule of operations spread over a few threads
+// and checks that no thread ever sees a torn word and that a CAS-incremented
+// counter only moves forward and ends up with every increment.
+//
+//   cargo fuzz run ops
+#![no_main]
+
+use std::sync::atomic::Ordering::SeqCst;
+use std::sync::Arc;
+use std::thread;
+
+use atomic128::AtomicU128;
+use libfuzzer_sys::fuzz_target;
+
+const MAX_OPS: usize = 4096;
+
+#[derive(Clone, Copy, Debug)]
+enum Op {
+    Load,
+    Store(u64),
+    Swap(u64),
+    Cas(u64),
+    Increment,
+    ReadCounter,
+}
+
+fn decode(op: u8, arg: u8) -> Op {
+    let arg = arg as u64;
+    match op % 6 {
+        0 => Op::Load,
+        1 => Op::Store(arg),
+        2 => Op::Swap(arg),
+        3 => Op::Cas(arg),
+        4 => Op::Increment,
+        _ => Op::ReadCounter,
+    }
+}
+
+// Every value written to the cell has hi == !lo, so any other pair is torn.
+fn whole(v: u64) -> u128 {
+    (!v as u128) << 64 | v as u128
+}
+
+fn check_whole(w: u128) {
+    assert_eq!((w >> 64) as u64, !(w as u64), "torn value {:x}", w);
+}
+
+fn increment(counter: &AtomicU128) {
+    let mut current = counter.load(SeqCst);
+    loop {
+        let next = current + (1 << 64 | 1);
+        match counter.compare_exchange(current, next, SeqCst, SeqCst) {
+            Ok(_) => return,
+            Err(actual) => current = actual,
+        }
+    }
+}
+
+fn run(ops: Vec<Op>, cell: &AtomicU128, counter: &AtomicU128) -> u64 {
+    let mut increments = 0;
+    let mut last_count = 0;
+    for op in ops {
+        match op {
+            Op::Load => check_whole(cell.load(SeqCst)),
+            Op::Store(v) => cell.store(whole(v), SeqCst),
+            Op::Swap(v) => check_whole(cell.swap(whole(v), SeqCst)),
+            Op::Cas(v) => match cell.compare_exchange(whole(v), whole(v + 1), SeqCst, SeqCst) {
+                Ok(prev) | Err(prev) => check_whole(prev),
+            },
+            Op::Increment => {
+                increment(counter);
+                increments += 1;
+            }
+            Op::ReadCounter => {
+                let seen = counter.load(SeqCst);
+                let (lo, hi) = (seen as u64, (seen >> 64) as u64);
+                assert_eq!(lo, hi, "torn counter {:x}", seen);
+                assert!(lo >= last_count, "counter went back from {} to {}", last_count, lo);
+                last_count = lo;
+            }
+        }
+    }
+    increments
+}
+
+fuzz_target!(|data: &[u8]| {
+    let (&first, rest) = match data.split_first() {
+        Some(split) => split,
+        None => return,
+    };
+    let threads = first as usize % 4 + 1;
+    let mut schedules = vec![Vec::new(); threads];
+    for (i, pair) in rest.chunks_exact(2).take(MAX_OPS).enumerate() {
+        schedules[i % threads].push(decode(pair[0], pair[1]));
+    }
+
+    let cell = Arc::new(AtomicU128::new(whole(0)));
+    let counter = Arc::new(AtomicU128::new(0));
+    let handles: Vec<_> = schedules
+        .into_iter()
+        .map(|ops| {
+            let (cell, counter) = (cell.clone(), counter.clone());
+            thread::spawn(move || run(ops, &cell, &counter))
+        })
+        .collect();
+    let increments: u64 = handles.into_iter().map(|h| h.join().unwrap()).sum();
+    check_whole(cell.load(SeqCst));
+    assert_eq!(counter.load(SeqCst), (increments as u128) << 64 | increments as u128);
+});
@@ -0,0 +1,24 @@
+#ifndef ATOMIC128_H
+#define ATOMIC128_H
+
+/* Generated by cbindgen from src/ffi.rs; do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+unsigned __int128 atomic128_load(unsigned __int128 *ptr);
+
+void atomic128_store(unsigned __int128 *ptr, unsigned __int128 val);
+
+unsigned __int128 atomic128_swap(unsigned __int128 *ptr, unsigned __int128 val);
+
+/**
+ * Replaces `*ptr` with `desired` if it equals `*expected`. On failure the
+ * current value is written back to `*expected`, as with C11
+ * `atomic_compare_exchange_strong`.
+ */
+bool atomic128_cas(unsigned __int128 *ptr, unsigned __int128 *expected, unsigned __int128 desired);
+
+#endif /* ATOMIC128_H */
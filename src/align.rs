@@ -0,0 +1,71 @@
+use core::mem;
+
+use AtomicU128;
+
+const ALIGN: usize = mem::align_of::<AtomicU128>();
+
+/// A pointer that isn't 16-byte aligned, which cmpxchg16b would fault on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Misaligned {
+    pub addr: usize,
+}
+
+fn is_aligned<T>(ptr: *const T) -> bool {
+    ptr as usize & (ALIGN - 1) == 0
+}
+
+// A misaligned cmpxchg16b is a #GP fault with nothing pointing back at the
+// cause, so debug builds check every word that reaches a backend or arrives
+// as a raw pointer.
+#[inline]
+pub fn debug_check<T>(ptr: *const T) {
+    debug_assert!(is_aligned(ptr), "AtomicU128 at {:p} is not 16-byte aligned", ptr);
+}
+
+impl AtomicU128 {
+    /// Views `*ptr` as a word, like `AtomicU64::from_ptr`.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be 16-byte aligned (checked in debug builds), valid for
+    /// reads and writes for all of `'a`, and only accessed through atomic
+    /// operations while the reference is live.
+    pub unsafe fn from_ptr<'a>(ptr: *mut u128) -> &'a AtomicU128 {
+        debug_check(ptr);
+        &*(ptr as *const AtomicU128)
+    }
+
+    /// `from_ptr` that returns `Err` instead of asserting when `ptr` is
+    /// misaligned, for pointers from outside Rust.
+    ///
+    /// # Safety
+    ///
+    /// As for `from_ptr`, except for the alignment.
+    pub unsafe fn try_from_ptr<'a>(ptr: *mut u128) -> Result<&'a AtomicU128, Misaligned> {
+        if is_aligned(ptr) {
+            Ok(&*(ptr as *const AtomicU128))
+        } else {
+            Err(Misaligned { addr: ptr as usize })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering::SeqCst;
+
+    use super::Misaligned;
+    use AtomicU128;
+
+    #[test]
+    fn test_try_from_ptr() {
+        let mut buf = [0u128; 2];
+        let base = buf.as_mut_ptr();
+        let word = unsafe { AtomicU128::try_from_ptr(base.wrapping_add(1)) }.unwrap();
+        word.store(2 << 64 | 1, SeqCst);
+        assert_eq!(buf[1], 2 << 64 | 1);
+
+        let odd = (base as usize + 8) as *mut u128;
+        assert_eq!(unsafe { AtomicU128::try_from_ptr(odd) }.err(), Some(Misaligned { addr: odd as usize }));
+    }
+}
@@ -0,0 +1,51 @@
+// The native backend: `lock cmpxchg16b` for every operation, except loads on
+// CPUs with AVX, where an aligned 16-byte vector load is atomic too.
+
+use core::arch::asm;
+
+use super::cpu::has_avx;
+use align;
+use AtomicU128;
+
+pub fn cas128(src: &AtomicU128, cmp: &mut u128, with: u128) -> bool {
+    align::debug_check(src);
+    let result: u8;
+    let (mut lo, mut hi) = (*cmp as u64, (*cmp >> 64) as u64);
+    // LLVM keeps rbx for itself, so the new low half goes in through rsi and
+    // is swapped into rbx around the instruction.
+    unsafe {
+        asm!(
+            "xchg rsi, rbx",
+            "lock cmpxchg16b xmmword ptr [rdi]",
+            "setz {result}",
+            "mov rbx, rsi",
+            in("rdi") src.as_ptr(),
+            inout("rsi") with as u64 => _,
+            in("rcx") (with >> 64) as u64,
+            inout("rax") lo,
+            inout("rdx") hi,
+            result = out(reg_byte) result,
+            options(nostack),
+        );
+    }
+    *cmp = (hi as u128) << 64 | lo as u128;
+    result != 0
+}
+
+// Unlike cmpxchg16b the vector load never writes, so it works on read-only
+// pages.
+#[target_feature(enable = "avx")]
+unsafe fn load_avx(src: &AtomicU128) -> u128 {
+    use core::arch::x86_64::__m128i;
+    align::debug_check(src);
+    core::mem::transmute(core::ptr::read_volatile(src.as_ptr() as *const __m128i))
+}
+
+pub fn load(src: &AtomicU128) -> u128 {
+    if has_avx() {
+        return unsafe { load_avx(src) };
+    }
+    let mut ret = 0;
+    cas128(src, &mut ret, 0);
+    ret
+}
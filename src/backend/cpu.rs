@@ -0,0 +1,77 @@
+// What the CPU can do, for the cmpxchg16b backend and for callers that test
+// it, so the backend and its tests agree. With `std` this is the standard
+// library's detection; without it, cpuid read once and cached here.
+
+#[cfg(feature = "std")]
+pub fn has_cmpxchg16b() -> bool {
+    is_x86_feature_detected!("cmpxchg16b")
+}
+
+// Only the cmpxchg16b backend loads with AVX.
+#[cfg(all(feature = "std", any(test, all(feature = "nightly", not(feature = "portable-atomic"), not(loom), not(shuttle), not(replay)))))]
+pub fn has_avx() -> bool {
+    is_x86_feature_detected!("avx")
+}
+
+#[cfg(not(feature = "std"))]
+pub fn has_cmpxchg16b() -> bool {
+    features() & CMPXCHG16B != 0
+}
+
+#[cfg(all(not(feature = "std"), any(test, all(feature = "nightly", not(feature = "portable-atomic"), not(loom), not(shuttle), not(replay)))))]
+pub fn has_avx() -> bool {
+    features() & AVX != 0
+}
+
+#[cfg(not(feature = "std"))]
+const CMPXCHG16B: u8 = 1;
+#[cfg(not(feature = "std"))]
+const AVX: u8 = 2;
+#[cfg(not(feature = "std"))]
+const DETECTED: u8 = 4;
+
+#[cfg(not(feature = "std"))]
+fn features() -> u8 {
+    use core::sync::atomic::{AtomicU8, Ordering};
+
+    static FEATURES: AtomicU8 = AtomicU8::new(0);
+    let cached = FEATURES.load(Ordering::Relaxed);
+    if cached != 0 {
+        return cached;
+    }
+    let detected = detect() | DETECTED;
+    FEATURES.store(detected, Ordering::Relaxed);
+    detected
+}
+
+// Leaf 1 ECX: bit 13 is cmpxchg16b, bit 28 AVX. AVX also needs the OS to
+// save the vector state (OSXSAVE, bit 27, then XCR0 bits 1 and 2), or the
+// VEX-encoded load faults.
+#[cfg(not(feature = "std"))]
+fn detect() -> u8 {
+    use core::arch::asm;
+    use core::arch::x86_64::__cpuid;
+
+    let ecx = __cpuid(1).ecx;
+    let mut features = 0;
+    if ecx & 1 << 13 != 0 {
+        features |= CMPXCHG16B;
+    }
+    if ecx & 1 << 28 != 0 && ecx & 1 << 27 != 0 {
+        let xcr0: u32;
+        unsafe { asm!("xgetbv", in("ecx") 0, out("eax") xcr0, out("edx") _, options(nomem, nostack)) };
+        if xcr0 & 0b110 == 0b110 {
+            features |= AVX;
+        }
+    }
+    features
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_matches_std_detection() {
+        assert_eq!(super::has_cmpxchg16b(), is_x86_feature_detected!("cmpxchg16b"));
+        assert_eq!(super::has_avx(), is_x86_feature_detected!("avx"));
+    }
+}
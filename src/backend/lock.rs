@@ -0,0 +1,63 @@
+// The `fallback-lock` backend: every operation takes one of a fixed set of
+// spinlocks, picked by the word's address. Works on any target and never
+// writes to the word on a load, but isn't lock-free, so a thread preempted
+// while holding a stripe stalls every other word that hashes to it.
+
+use core::ptr;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use AtomicU128;
+use super::stripe_index;
+
+static STRIPES: [AtomicBool; super::STRIPES] = [const { AtomicBool::new(false) }; super::STRIPES];
+
+struct Guard(&'static AtomicBool);
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::Release);
+    }
+}
+
+fn lock(src: &AtomicU128) -> Guard {
+    let stripe = &STRIPES[stripe_index(src)];
+    let mut spins = 0u32;
+    while stripe.compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed).is_err() {
+        spins += 1;
+        super::relax(spins);
+    }
+    Guard(stripe)
+}
+
+pub fn cas128(src: &AtomicU128, cmp: &mut u128, with: u128) -> bool {
+    let _guard = lock(src);
+    let current = unsafe { ptr::read_volatile(src.as_ptr()) };
+    if current == *cmp {
+        unsafe { ptr::write_volatile(src.as_ptr(), with) };
+        true
+    } else {
+        *cmp = current;
+        false
+    }
+}
+
+pub fn load(src: &AtomicU128) -> u128 {
+    let _guard = lock(src);
+    unsafe { ptr::read_volatile(src.as_ptr()) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{cas128, load};
+    use AtomicU128;
+
+    #[test]
+    fn test_lock_ops() {
+        let a = AtomicU128::new(1 << 64 | 1);
+        let mut expected = 0;
+        assert!(!cas128(&a, &mut expected, 2));
+        assert_eq!(expected, 1 << 64 | 1);
+        assert!(cas128(&a, &mut expected, 2));
+        assert_eq!(load(&a), 2);
+    }
+}
@@ -0,0 +1,27 @@
+// Backend for `--cfg loom` builds: every CAS runs under one loom mutex, so
+// loom sees each 128-bit operation as a synchronization point and explores
+// the interleavings between them. Only meant for model checking; it is
+// neither lock-free nor fast.
+
+use std::ptr;
+
+use loom::sync::Mutex;
+
+use AtomicU128;
+
+::loom::lazy_static! {
+    static ref LOCK: Mutex<()> = Mutex::new(());
+}
+
+pub fn cas128(src: &AtomicU128, cmp: &mut u128, with: u128) -> bool {
+    let _guard = LOCK.lock().unwrap();
+    let word = src.as_ptr();
+    let current = unsafe { ptr::read_volatile(word) };
+    if current == *cmp {
+        unsafe { ptr::write_volatile(word, with) };
+        true
+    } else {
+        *cmp = current;
+        false
+    }
+}
@@ -0,0 +1,402 @@
+// Which backend runs the 128-bit CAS and load is decided at compile time:
+// a model backend under `--cfg loom`/`shuttle`/`replay`, then
+// `portable-atomic` if enabled, then `lock cmpxchg16b` on x86_64 with the
+// `nightly` feature, then whichever of `fallback-lock`/`fallback-seqlock` is
+// enabled. With `detect-runtime` the x86_64 build checks cpuid on each
+// operation and takes the fallback on CPUs without cmpxchg16b.
+
+#[cfg(not(all(feature = "portable-atomic", not(loom), not(shuttle), not(replay))))]
+use core::sync::atomic::Ordering;
+
+use AtomicU128;
+
+#[cfg(all(feature = "fallback-lock", feature = "fallback-seqlock"))]
+compile_error!("`fallback-lock` and `fallback-seqlock` are mutually exclusive");
+
+#[cfg(all(
+    feature = "detect-runtime",
+    target_arch = "x86_64",
+    feature = "nightly",
+    not(any(feature = "fallback-lock", feature = "fallback-seqlock"))
+))]
+compile_error!("`detect-runtime` needs `fallback-lock` or `fallback-seqlock` to fall back to");
+
+#[cfg(not(any(
+    loom,
+    shuttle,
+    replay,
+    feature = "portable-atomic",
+    all(target_arch = "x86_64", feature = "nightly"),
+    feature = "fallback-lock",
+    feature = "fallback-seqlock"
+)))]
+compile_error!(
+    "no 128-bit backend for this target: enable `nightly` (x86_64 only), `portable-atomic`, \
+     `fallback-lock` or `fallback-seqlock`"
+);
+
+#[cfg(all(target_arch = "x86_64", feature = "nightly", not(feature = "portable-atomic"), not(loom), not(shuttle), not(replay)))]
+mod cmpxchg16b;
+#[cfg(all(
+    feature = "fallback-lock",
+    not(feature = "portable-atomic"),
+    not(loom),
+    not(shuttle),
+    not(replay),
+    any(not(target_arch = "x86_64"), not(feature = "nightly"), feature = "detect-runtime")
+))]
+mod lock;
+#[cfg(loom)]
+mod loom;
+#[cfg(all(
+    feature = "fallback-seqlock",
+    not(feature = "fallback-lock"),
+    not(feature = "portable-atomic"),
+    not(loom),
+    not(shuttle),
+    not(replay),
+    any(not(target_arch = "x86_64"), not(feature = "nightly"), feature = "detect-runtime")
+))]
+mod seqlock;
+#[cfg(shuttle)]
+mod shuttle;
+#[cfg(target_arch = "x86_64")]
+pub(crate) mod cpu;
+#[cfg(target_arch = "x86_64")]
+use self::cpu::has_cmpxchg16b;
+
+#[cfg(all(
+    any(feature = "fallback-lock", feature = "fallback-seqlock"),
+    not(feature = "portable-atomic"),
+    not(loom),
+    not(shuttle),
+    not(replay),
+    any(not(target_arch = "x86_64"), not(feature = "nightly"), feature = "detect-runtime")
+))]
+mod stripes {
+    use core::hint;
+    use AtomicU128;
+
+    pub const STRIPES: usize = 64;
+
+    pub fn stripe_index(src: &AtomicU128) -> usize {
+        // Same hash as the wait table: words are 16 bytes, so the low four
+        // bits carry nothing.
+        let addr = (src as *const AtomicU128 as usize >> 4) as u64;
+        (addr.wrapping_mul(0x9e37_79b9_7f4a_7c15) >> 58) as usize
+    }
+
+    // Spins for a while, then gives the holder a chance to run if there's a
+    // scheduler to ask.
+    #[cfg(feature = "std")]
+    pub fn relax(spins: u32) {
+        if spins > 64 {
+            ::std::thread::yield_now();
+        } else {
+            hint::spin_loop();
+        }
+    }
+
+    #[cfg(not(feature = "std"))]
+    pub fn relax(_: u32) {
+        hint::spin_loop();
+    }
+}
+#[cfg(all(
+    any(feature = "fallback-lock", feature = "fallback-seqlock"),
+    not(feature = "portable-atomic"),
+    not(loom),
+    not(shuttle),
+    not(replay),
+    any(not(target_arch = "x86_64"), not(feature = "nightly"), feature = "detect-runtime")
+))]
+use self::stripes::{relax, stripe_index, STRIPES};
+
+#[cfg(loom)]
+pub(crate) use self::loom::cas128;
+#[cfg(shuttle)]
+pub(crate) use self::shuttle::cas128;
+#[cfg(replay)]
+pub(crate) use replay::cas128;
+#[cfg(all(feature = "portable-atomic", not(loom), not(shuttle), not(replay)))]
+pub(crate) use compat::portable_atomic::{cas128, load, store};
+#[cfg(all(
+    target_arch = "x86_64",
+    feature = "nightly",
+    not(feature = "detect-runtime"),
+    not(feature = "portable-atomic"),
+    not(loom),
+    not(shuttle),
+    not(replay)
+))]
+pub(crate) use self::cmpxchg16b::{cas128, load};
+#[cfg(all(
+    feature = "fallback-lock",
+    not(feature = "portable-atomic"),
+    not(loom),
+    not(shuttle),
+    not(replay),
+    any(not(target_arch = "x86_64"), not(feature = "nightly"))
+))]
+pub(crate) use self::lock::{cas128, load};
+#[cfg(all(
+    feature = "fallback-seqlock",
+    not(feature = "fallback-lock"),
+    not(feature = "portable-atomic"),
+    not(loom),
+    not(shuttle),
+    not(replay),
+    any(not(target_arch = "x86_64"), not(feature = "nightly"))
+))]
+pub(crate) use self::seqlock::{cas128, load};
+
+// Stand-ins for the builds the compile errors above reject, so that error is
+// the only one they report.
+#[cfg(any(
+    not(any(
+        loom,
+        shuttle,
+        replay,
+        feature = "portable-atomic",
+        all(target_arch = "x86_64", feature = "nightly"),
+        feature = "fallback-lock",
+        feature = "fallback-seqlock"
+    )),
+    all(
+        target_arch = "x86_64",
+        feature = "nightly",
+        feature = "detect-runtime",
+        not(any(feature = "fallback-lock", feature = "fallback-seqlock")),
+        not(feature = "portable-atomic"),
+        not(loom),
+        not(shuttle),
+        not(replay)
+    )
+))]
+mod unconfigured {
+    use AtomicU128;
+
+    pub fn cas128(_: &AtomicU128, _: &mut u128, _: u128) -> bool {
+        unreachable!()
+    }
+
+    pub fn load(_: &AtomicU128) -> u128 {
+        unreachable!()
+    }
+}
+#[cfg(any(
+    not(any(
+        loom,
+        shuttle,
+        replay,
+        feature = "portable-atomic",
+        all(target_arch = "x86_64", feature = "nightly"),
+        feature = "fallback-lock",
+        feature = "fallback-seqlock"
+    )),
+    all(
+        target_arch = "x86_64",
+        feature = "nightly",
+        feature = "detect-runtime",
+        not(any(feature = "fallback-lock", feature = "fallback-seqlock")),
+        not(feature = "portable-atomic"),
+        not(loom),
+        not(shuttle),
+        not(replay)
+    )
+))]
+pub(crate) use self::unconfigured::{cas128, load};
+
+// The model backends only provide a CAS.
+#[cfg(any(loom, shuttle, replay))]
+pub(crate) fn load(src: &AtomicU128) -> u128 {
+    let mut ret = 0;
+    cas128(src, &mut ret, 0);
+    ret
+}
+
+// Everything but `portable-atomic` stores with a swap.
+#[cfg(not(all(feature = "portable-atomic", not(loom), not(shuttle), not(replay))))]
+#[cfg_attr(feature = "tracing", track_caller)]
+pub(crate) fn store(src: &AtomicU128, val: u128) {
+    src.swap(val, Ordering::SeqCst);
+}
+
+#[cfg(all(
+    target_arch = "x86_64",
+    feature = "nightly",
+    feature = "detect-runtime",
+    any(feature = "fallback-lock", feature = "fallback-seqlock"),
+    not(feature = "portable-atomic"),
+    not(loom),
+    not(shuttle),
+    not(replay)
+))]
+mod runtime {
+    #[cfg(feature = "fallback-lock")]
+    use super::lock as fallback;
+    #[cfg(all(feature = "fallback-seqlock", not(feature = "fallback-lock")))]
+    use super::seqlock as fallback;
+    use super::{cmpxchg16b, has_cmpxchg16b};
+    use AtomicU128;
+
+    pub fn cas128(src: &AtomicU128, cmp: &mut u128, with: u128) -> bool {
+        if has_cmpxchg16b() {
+            cmpxchg16b::cas128(src, cmp, with)
+        } else {
+            fallback::cas128(src, cmp, with)
+        }
+    }
+
+    pub fn load(src: &AtomicU128) -> u128 {
+        if has_cmpxchg16b() {
+            cmpxchg16b::load(src)
+        } else {
+            fallback::load(src)
+        }
+    }
+}
+#[cfg(all(
+    target_arch = "x86_64",
+    feature = "nightly",
+    feature = "detect-runtime",
+    any(feature = "fallback-lock", feature = "fallback-seqlock"),
+    not(feature = "portable-atomic"),
+    not(loom),
+    not(shuttle),
+    not(replay)
+))]
+pub(crate) use self::runtime::{cas128, load};
+
+/// The implementation behind every 128-bit operation in this build.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backend {
+    /// `lock cmpxchg16b` on x86_64.
+    Cmpxchg16b,
+    /// Whatever `portable-atomic` picked, lock-free or not.
+    PortableAtomic,
+    /// The `--cfg loom` model backend.
+    Loom,
+    /// The `--cfg shuttle` model backend.
+    Shuttle,
+    /// The `--cfg replay` recording backend.
+    Replay,
+    /// The `fallback-lock` striped spinlocks.
+    Lock,
+    /// The `fallback-seqlock` striped sequence locks.
+    SeqLock,
+}
+
+/// The CPU can't run the backend this crate was built with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Unsupported;
+
+/// The backend this build's operations actually run on, after any runtime
+/// detection, so applications can log it and notice when they fell back
+/// to locks.
+pub fn backend() -> Backend {
+    if cfg!(loom) {
+        Backend::Loom
+    } else if cfg!(shuttle) {
+        Backend::Shuttle
+    } else if cfg!(replay) {
+        Backend::Replay
+    } else if cfg!(feature = "portable-atomic") {
+        Backend::PortableAtomic
+    } else if cfg!(all(target_arch = "x86_64", feature = "nightly"))
+        && (has_cmpxchg16b() || !cfg!(feature = "detect-runtime"))
+    {
+        Backend::Cmpxchg16b
+    } else if cfg!(feature = "fallback-lock") {
+        Backend::Lock
+    } else {
+        Backend::SeqLock
+    }
+}
+
+impl Backend {
+    /// The backend compiled in, if the CPU can run it. `None` means the first
+    /// operation would die with an illegal instruction, so callers should
+    /// switch to a fallback of their own instead.
+    pub fn detect() -> Option<Backend> {
+        match backend() {
+            Backend::Cmpxchg16b if !has_cmpxchg16b() => None,
+            b => Some(b),
+        }
+    }
+
+    /// Whether operations never wait on another thread's progress. False
+    /// for the fallbacks, and for `portable-atomic` when it had to use its
+    /// own locks.
+    pub fn is_lock_free(self) -> bool {
+        match self {
+            Backend::Cmpxchg16b => true,
+            #[cfg(feature = "portable-atomic")]
+            Backend::PortableAtomic => ::portable_atomic::AtomicU128::is_lock_free(),
+            #[cfg(not(feature = "portable-atomic"))]
+            Backend::PortableAtomic => false,
+            Backend::Loom | Backend::Shuttle | Backend::Replay | Backend::Lock | Backend::SeqLock => false,
+        }
+    }
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn has_cmpxchg16b() -> bool {
+    false
+}
+
+impl AtomicU128 {
+    /// Whether operations on this type can run on this CPU.
+    pub fn is_supported() -> bool {
+        Backend::detect().is_some()
+    }
+
+    /// Like `new`, but fails instead of handing out a word that would fault
+    /// on first use.
+    pub fn try_new(v: u128) -> Result<Self, Unsupported> {
+        if Self::is_supported() {
+            Ok(Self::new(v))
+        } else {
+            Err(Unsupported)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{backend, Backend};
+    use {Atomic, AtomicI128, AtomicU128};
+
+    #[test]
+    fn test_detect_matches_build() {
+        let expected = if cfg!(feature = "portable-atomic") {
+            Backend::PortableAtomic
+        } else if cfg!(all(target_arch = "x86_64", feature = "nightly")) {
+            Backend::Cmpxchg16b
+        } else if cfg!(feature = "fallback-lock") {
+            Backend::Lock
+        } else {
+            Backend::SeqLock
+        };
+        assert_eq!(Backend::detect(), Some(expected));
+        assert!(AtomicU128::is_supported());
+    }
+
+    #[test]
+    fn test_backend_in_debug() {
+        let a = AtomicU128::new(7);
+        assert_eq!(format!("{:?}", a), "7");
+        assert_eq!(format!("{:#?}", a), format!("AtomicU128 {{\n    value: 7,\n    backend: {:?},\n}}", backend()));
+        let generic = Atomic::new(7u128);
+        assert_eq!(format!("{:#?}", generic), format!("Atomic {{\n    value: 7,\n    backend: {:?},\n}}", backend()));
+        assert!(format!("{:#?}", AtomicI128::new(-7)).contains(&format!("backend: {:?}", backend())));
+        assert_eq!(Backend::detect(), Some(backend()));
+        assert_eq!(Backend::Cmpxchg16b.is_lock_free(), !Backend::Lock.is_lock_free());
+    }
+
+    #[test]
+    fn test_try_new() {
+        let a = AtomicU128::try_new(1).unwrap();
+        assert_eq!(a.into_inner(), 1);
+    }
+}
@@ -0,0 +1,140 @@
+// The `fallback-seqlock` backend: writers take one of a fixed set of
+// sequence counters, picked by the word's address, and make it odd while
+// they write; loads read the word optimistically and retry if the counter
+// moved. Loads never write to shared memory, so many readers of one word
+// don't contend, at the cost of a retry whenever a writer gets in between.
+
+use core::ptr;
+use core::sync::atomic::{fence, AtomicUsize, Ordering};
+
+use AtomicU128;
+use super::stripe_index;
+
+static STRIPES: [AtomicUsize; super::STRIPES] = [const { AtomicUsize::new(0) }; super::STRIPES];
+
+pub fn cas128(src: &AtomicU128, cmp: &mut u128, with: u128) -> bool {
+    let stripe = &STRIPES[stripe_index(src)];
+    let mut spins = 0u32;
+    let seq = loop {
+        let seq = stripe.load(Ordering::Relaxed);
+        if seq & 1 == 0
+            && stripe.compare_exchange_weak(seq, seq + 1, Ordering::Acquire, Ordering::Relaxed).is_ok()
+        {
+            break seq;
+        }
+        spins += 1;
+        super::relax(spins);
+    };
+    let current = unsafe { ptr::read_volatile(src.as_ptr()) };
+    if current == *cmp {
+        fence(Ordering::Release);
+        unsafe { ptr::write_volatile(src.as_ptr(), with) };
+        stripe.store(seq + 2, Ordering::Release);
+        true
+    } else {
+        // Nothing was written, so readers that overlapped still saw a
+        // consistent value.
+        stripe.store(seq, Ordering::Release);
+        *cmp = current;
+        false
+    }
+}
+
+pub fn load(src: &AtomicU128) -> u128 {
+    let stripe = &STRIPES[stripe_index(src)];
+    let mut spins = 0u32;
+    loop {
+        let seq = stripe.load(Ordering::Acquire);
+        if seq & 1 == 0 {
+            let value = unsafe { ptr::read_volatile(src.as_ptr()) };
+            fence(Ordering::Acquire);
+            if stripe.load(Ordering::Relaxed) == seq {
+                return value;
+            }
+        }
+        spins += 1;
+        super::relax(spins);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::{cas128, load};
+    use AtomicU128;
+
+    #[test]
+    fn test_loads_see_whole_writes() {
+        let a = Arc::new(AtomicU128::new(0));
+        let writer = {
+            let a = a.clone();
+            thread::spawn(move || {
+                for i in 1..1000u128 {
+                    let mut current = load(&a);
+                    while !cas128(&a, &mut current, i << 64 | i) {}
+                }
+            })
+        };
+        for _ in 0..1000 {
+            let v = load(&a);
+            assert_eq!(v >> 64, v as u64 as u128);
+        }
+        writer.join().unwrap();
+        assert_eq!(load(&a), 999 << 64 | 999);
+    }
+}
+
+#[cfg(kani)]
+mod proofs {
+    use std::sync::atomic::Ordering;
+
+    use super::{cas128, load, STRIPES};
+    use super::super::stripe_index;
+    use AtomicU128;
+
+    // A CAS writes exactly when the word matched, and leaves the stripe
+    // unlocked, two further on after a write and where it was otherwise.
+    #[kani::proof]
+    #[kani::unwind(2)]
+    fn cas_releases_stripe() {
+        let (initial, expected, new): (u128, u128, u128) = (kani::any(), kani::any(), kani::any());
+        let word = AtomicU128::new(initial);
+        let stripe = &STRIPES[stripe_index(&word)];
+        let before = stripe.load(Ordering::Relaxed);
+        let mut current = expected;
+        let written = cas128(&word, &mut current, new);
+        assert_eq!(written, initial == expected);
+        assert_eq!(current, initial);
+        let after = stripe.load(Ordering::Relaxed);
+        assert_eq!(after & 1, 0);
+        assert_eq!(after, if written { before + 2 } else { before });
+        assert_eq!(load(&word), if written { new } else { initial });
+    }
+
+    // A reader keeps its copy only if the stripe is even and unchanged
+    // around it; any write that lands in between fails that check, and
+    // one still in progress keeps the stripe odd.
+    #[kani::proof]
+    #[kani::unwind(2)]
+    fn overlapping_write_invalidates_read() {
+        let (initial, new): (u128, u128) = (kani::any(), kani::any());
+        let word = AtomicU128::new(initial);
+        let stripe = &STRIPES[stripe_index(&word)];
+        let seq = stripe.load(Ordering::Acquire);
+        assert_eq!(seq & 1, 0);
+        let (wrote, in_progress): (bool, bool) = (kani::any(), kani::any());
+        if wrote {
+            let mut current = initial;
+            assert!(cas128(&word, &mut current, new));
+        }
+        if in_progress {
+            // A writer that has locked the stripe and not yet released it.
+            let locked = stripe.load(Ordering::Relaxed);
+            stripe.store(locked + 1, Ordering::Relaxed);
+        }
+        let valid = stripe.load(Ordering::Relaxed) == seq;
+        assert_eq!(valid, !wrote && !in_progress);
+    }
+}
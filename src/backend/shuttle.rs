@@ -0,0 +1,22 @@
+// Backend for `--cfg shuttle` builds. Shuttle runs one thread at a time and
+// only switches at its own primitives, so each CAS yields to the scheduler
+// first and then does a plain read-compare-write, which nothing can
+// interleave with. That makes every 128-bit operation a point where shuttle
+// can preempt, including the ones inside spin-wait loops.
+
+use std::ptr;
+
+use AtomicU128;
+
+pub fn cas128(src: &AtomicU128, cmp: &mut u128, with: u128) -> bool {
+    ::shuttle::thread::yield_now();
+    let word = src.as_ptr();
+    let current = unsafe { ptr::read_volatile(word) };
+    if current == *cmp {
+        unsafe { ptr::write_volatile(word, with) };
+        true
+    } else {
+        *cmp = current;
+        false
+    }
+}
@@ -0,0 +1,152 @@
+//! What retry loops do between attempts.
+//!
+//! `swap`, `store`, `fetch_update` and the `fetch_*` methods built on it, and
+//! the blocking collection operations all wait through the policy installed
+//! with `set_backoff`, which defaults to `SpinYield::default()`. The `_with`
+//! variants take a policy for one call instead. Which one is right depends
+//! on the machine: yielding early wins with two threads on one core, while
+//! spinning longer wins on a large server where the holder is running
+//! elsewhere.
+//!
+//! Without `std` there is no scheduler to yield to or park on: `SpinYield`
+//! keeps spinning, `Park` and `set_backoff` don't exist, and every loop
+//! without an explicit policy uses the default.
+
+use core::hint;
+#[cfg(feature = "std")]
+use std::ptr;
+#[cfg(feature = "std")]
+use std::sync::atomic::{AtomicPtr, Ordering};
+#[cfg(feature = "std")]
+use std::thread;
+#[cfg(feature = "std")]
+use std::time::Duration;
+
+/// A waiting strategy for retry loops.
+///
+/// `wait` is called after each failed attempt, with `attempt` counting from
+/// 1, so policies can escalate. Any `Fn(u32) + Sync` is a policy too.
+pub trait Backoff: Sync {
+    fn wait(&self, attempt: u32);
+}
+
+impl<F: Fn(u32) + Sync> Backoff for F {
+    fn wait(&self, attempt: u32) {
+        self(attempt)
+    }
+}
+
+/// Busy-waits, doubling the number of spin hints per attempt up to 64.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Spin;
+
+impl Backoff for Spin {
+    fn wait(&self, attempt: u32) {
+        for _ in 0..1u32 << attempt.min(6) {
+            hint::spin_loop();
+        }
+    }
+}
+
+/// Spins like `Spin` for the first `spins` attempts, then yields the thread
+/// on every attempt after that.
+#[derive(Clone, Copy, Debug)]
+pub struct SpinYield {
+    pub spins: u32,
+}
+
+impl Default for SpinYield {
+    fn default() -> Self {
+        SpinYield { spins: 8 }
+    }
+}
+
+impl Backoff for SpinYield {
+    #[cfg(feature = "std")]
+    fn wait(&self, attempt: u32) {
+        if attempt <= self.spins {
+            Spin.wait(attempt);
+        } else {
+            thread::yield_now();
+        }
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn wait(&self, attempt: u32) {
+        Spin.wait(attempt);
+    }
+}
+
+/// Spins for the first `spins` attempts, then parks the thread for up to
+/// `timeout` on every attempt after that. Nothing unparks it early unless
+/// the caller arranges to, so this suits long waits on few cores.
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug)]
+pub struct Park {
+    pub spins: u32,
+    pub timeout: Duration,
+}
+
+#[cfg(feature = "std")]
+impl Backoff for Park {
+    fn wait(&self, attempt: u32) {
+        if attempt <= self.spins {
+            Spin.wait(attempt);
+        } else {
+            thread::park_timeout(self.timeout);
+        }
+    }
+}
+
+static DEFAULT: SpinYield = SpinYield { spins: 8 };
+
+// A leaked box holding the installed policy; null until `set_backoff`.
+#[cfg(feature = "std")]
+static POLICY: AtomicPtr<&'static dyn Backoff> = AtomicPtr::new(ptr::null_mut());
+
+/// Installs `policy` for every retry loop that wasn't handed one
+/// explicitly, in all threads.
+#[cfg(feature = "std")]
+pub fn set_backoff(policy: &'static dyn Backoff) {
+    // Replaced policies are leaked: another thread may still be waiting in
+    // one. Each is a single pointer and this is set rarely.
+    POLICY.store(Box::into_raw(Box::new(policy)), Ordering::Release);
+}
+
+/// The policy retry loops currently use.
+#[cfg(feature = "std")]
+pub fn current_backoff() -> &'static dyn Backoff {
+    let policy = POLICY.load(Ordering::Acquire);
+    if policy.is_null() {
+        &DEFAULT
+    } else {
+        unsafe { *policy }
+    }
+}
+
+/// The policy retry loops currently use.
+#[cfg(not(feature = "std"))]
+pub fn current_backoff() -> &'static dyn Backoff {
+    &DEFAULT
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use super::{current_backoff, set_backoff, Backoff, Spin, SpinYield};
+
+    #[test]
+    fn test_set_backoff() {
+        static CALLS: AtomicU32 = AtomicU32::new(0);
+        fn counting(attempt: u32) {
+            CALLS.fetch_add(1, Ordering::Relaxed);
+            Spin.wait(attempt);
+        }
+        set_backoff(&counting);
+        current_backoff().wait(1);
+        current_backoff().wait(100);
+        assert!(CALLS.load(Ordering::Relaxed) >= 2);
+        set_backoff(&SpinYield { spins: 8 });
+    }
+}
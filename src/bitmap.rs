@@ -0,0 +1,157 @@
+use core::sync::atomic::Ordering::SeqCst;
+
+use AtomicU128;
+
+fn bit(index: u32) -> u128 {
+    assert!(index < 128, "bit index out of range");
+    1 << index
+}
+
+/// A 128-wide atomic flag set.
+#[derive(Debug, Default)]
+pub struct AtomicBitmap128 {
+    word: AtomicU128,
+}
+
+impl AtomicBitmap128 {
+    pub fn new(bits: u128) -> Self {
+        AtomicBitmap128 { word: AtomicU128::new(bits) }
+    }
+
+    pub fn load(&self) -> u128 {
+        self.word.load(SeqCst)
+    }
+
+    pub fn store(&self, bits: u128) {
+        self.word.store(bits, SeqCst);
+    }
+
+    pub fn compare_exchange(&self, current: u128, new: u128) -> Result<u128, u128> {
+        self.word.compare_exchange(current, new, SeqCst, SeqCst)
+    }
+
+    pub fn test(&self, index: u32) -> bool {
+        self.load() & bit(index) != 0
+    }
+
+    pub fn set(&self, index: u32) {
+        self.fetch_or(bit(index));
+    }
+
+    pub fn clear(&self, index: u32) {
+        self.fetch_and(!bit(index));
+    }
+
+    /// Sets the bit and returns whether it was already set.
+    pub fn fetch_set(&self, index: u32) -> bool {
+        self.fetch_or(bit(index)) & bit(index) != 0
+    }
+
+    /// Clears the bit and returns whether it was set.
+    pub fn fetch_clear(&self, index: u32) -> bool {
+        self.fetch_and(!bit(index)) & bit(index) != 0
+    }
+
+    pub fn fetch_or(&self, mask: u128) -> u128 {
+        self.update(|bits| bits | mask)
+    }
+
+    pub fn fetch_and(&self, mask: u128) -> u128 {
+        self.update(|bits| bits & mask)
+    }
+
+    pub fn fetch_xor(&self, mask: u128) -> u128 {
+        self.update(|bits| bits ^ mask)
+    }
+
+    pub fn count_ones(&self) -> u32 {
+        self.load().count_ones()
+    }
+
+    /// Iterates the set bits of a single snapshot of the word.
+    pub fn iter_ones(&self) -> IterOnes {
+        IterOnes { bits: self.load() }
+    }
+
+    fn update<F: Fn(u128) -> u128>(&self, f: F) -> u128 {
+        match self.word.fetch_update(SeqCst, SeqCst, |bits| Some(f(bits))) {
+            Ok(previous) | Err(previous) => previous,
+        }
+    }
+}
+
+/// Indices of the set bits in a snapshot, in ascending order.
+pub struct IterOnes {
+    bits: u128,
+}
+
+impl IterOnes {
+    pub(crate) fn new(bits: u128) -> Self {
+        IterOnes { bits }
+    }
+}
+
+impl Iterator for IterOnes {
+    type Item = u32;
+
+    fn next(&mut self) -> Option<u32> {
+        if self.bits == 0 {
+            return None;
+        }
+        let index = self.bits.trailing_zeros();
+        self.bits &= self.bits - 1;
+        Some(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AtomicBitmap128;
+
+    #[test]
+    fn test_set_clear() {
+        let b = AtomicBitmap128::default();
+        b.set(3);
+        b.set(100);
+        assert!(b.test(3));
+        assert!(b.test(100));
+        assert!(!b.test(4));
+        assert_eq!(b.count_ones(), 2);
+        b.clear(3);
+        assert!(!b.test(3));
+        assert!(!b.fetch_set(127));
+        assert!(b.fetch_set(127));
+        assert!(b.fetch_clear(127));
+        assert!(!b.fetch_clear(127));
+    }
+
+    #[test]
+    fn test_masks() {
+        let b = AtomicBitmap128::new(0b1010);
+        assert_eq!(b.fetch_or(1 << 64), 0b1010);
+        assert_eq!(b.fetch_and(!0b10), 0b1010 | 1 << 64);
+        assert_eq!(b.fetch_xor(0b1001), 0b1000 | 1 << 64);
+        assert_eq!(b.load(), 1 | 1 << 64);
+        assert_eq!(b.compare_exchange(0, 5), Err(1 | 1 << 64));
+        assert_eq!(b.compare_exchange(1 | 1 << 64, 5), Ok(1 | 1 << 64));
+    }
+
+    #[test]
+    fn test_iter_ones() {
+        let b = AtomicBitmap128::new(1 | 1 << 63 | 1 << 64 | 1 << 127);
+        assert_eq!(b.iter_ones().collect::<Vec<_>>(), vec![0, 63, 64, 127]);
+    }
+}
+
+#[cfg(kani)]
+mod proofs {
+    use super::bit;
+
+    #[kani::proof]
+    fn bit_is_single() {
+        let index: u32 = kani::any();
+        kani::assume(index < 128);
+        assert_eq!(bit(index).count_ones(), 1);
+        assert_eq!(bit(index).trailing_zeros(), index);
+    }
+}
@@ -0,0 +1,262 @@
+use core::fmt::Debug;
+use core::sync::atomic::Ordering::SeqCst;
+use core::sync::atomic::{AtomicU16, AtomicU32, AtomicU64, AtomicU8};
+
+use bitmap::IterOnes;
+use AtomicU128;
+
+/// The atomic integers `AtomicBits` can sit on. Values travel as `u128`
+/// whatever the width.
+pub trait BitsWord: Debug + Default + Send + Sync {
+    fn new(bits: u128) -> Self;
+    fn load(&self) -> u128;
+    fn store(&self, bits: u128);
+    fn swap(&self, bits: u128) -> u128;
+    fn compare_exchange(&self, current: u128, new: u128) -> Result<u128, u128>;
+    fn fetch_or(&self, mask: u128) -> u128;
+    fn fetch_and(&self, mask: u128) -> u128;
+    fn fetch_xor(&self, mask: u128) -> u128;
+}
+
+macro_rules! native_word {
+    ($atomic:ident, $int:ident) => {
+        impl BitsWord for $atomic {
+            fn new(bits: u128) -> Self {
+                $atomic::new(bits as $int)
+            }
+
+            fn load(&self) -> u128 {
+                $atomic::load(self, SeqCst) as u128
+            }
+
+            fn store(&self, bits: u128) {
+                $atomic::store(self, bits as $int, SeqCst)
+            }
+
+            fn swap(&self, bits: u128) -> u128 {
+                $atomic::swap(self, bits as $int, SeqCst) as u128
+            }
+
+            fn compare_exchange(&self, current: u128, new: u128) -> Result<u128, u128> {
+                $atomic::compare_exchange(self, current as $int, new as $int, SeqCst, SeqCst)
+                    .map(|v| v as u128)
+                    .map_err(|v| v as u128)
+            }
+
+            fn fetch_or(&self, mask: u128) -> u128 {
+                $atomic::fetch_or(self, mask as $int, SeqCst) as u128
+            }
+
+            fn fetch_and(&self, mask: u128) -> u128 {
+                $atomic::fetch_and(self, mask as $int, SeqCst) as u128
+            }
+
+            fn fetch_xor(&self, mask: u128) -> u128 {
+                $atomic::fetch_xor(self, mask as $int, SeqCst) as u128
+            }
+        }
+    };
+}
+
+native_word!(AtomicU8, u8);
+native_word!(AtomicU16, u16);
+native_word!(AtomicU32, u32);
+native_word!(AtomicU64, u64);
+
+impl BitsWord for AtomicU128 {
+    fn new(bits: u128) -> Self {
+        AtomicU128::new(bits)
+    }
+
+    fn load(&self) -> u128 {
+        AtomicU128::load(self, SeqCst)
+    }
+
+    fn store(&self, bits: u128) {
+        AtomicU128::store(self, bits, SeqCst)
+    }
+
+    fn swap(&self, bits: u128) -> u128 {
+        AtomicU128::swap(self, bits, SeqCst)
+    }
+
+    fn compare_exchange(&self, current: u128, new: u128) -> Result<u128, u128> {
+        AtomicU128::compare_exchange(self, current, new, SeqCst, SeqCst)
+    }
+
+    fn fetch_or(&self, mask: u128) -> u128 {
+        AtomicU128::fetch_or(self, mask, SeqCst)
+    }
+
+    fn fetch_and(&self, mask: u128) -> u128 {
+        AtomicU128::fetch_and(self, mask, SeqCst)
+    }
+
+    fn fetch_xor(&self, mask: u128) -> u128 {
+        AtomicU128::fetch_xor(self, mask, SeqCst)
+    }
+}
+
+/// A bit width, for picking the word behind `AtomicBits<N>`.
+pub struct Width<const N: usize>;
+
+/// Implemented for `Width<1>` through `Width<128>`: the narrowest word that
+/// holds that many bits.
+pub trait SelectWidth {
+    type Word: BitsWord;
+}
+
+macro_rules! widths {
+    ($word:ident: $($n:literal)*) => {
+        $(
+            impl SelectWidth for Width<$n> {
+                type Word = $word;
+            }
+        )*
+    };
+}
+
+widths!(AtomicU8: 1 2 3 4 5 6 7 8);
+widths!(AtomicU16: 9 10 11 12 13 14 15 16);
+widths!(AtomicU32: 17 18 19 20 21 22 23 24 25 26 27 28 29 30 31 32);
+widths!(AtomicU64:
+    33 34 35 36 37 38 39 40 41 42 43 44 45 46 47 48 49 50 51 52 53 54 55 56 57 58 59 60 61 62 63 64);
+widths!(AtomicU128:
+    65 66 67 68 69 70 71 72 73 74 75 76 77 78 79 80 81 82 83 84 85 86 87 88 89 90 91 92 93 94 95 96
+    97 98 99 100 101 102 103 104 105 106 107 108 109 110 111 112 113 114 115 116 117 118 119 120
+    121 122 123 124 125 126 127 128);
+
+/// An `N`-wide atomic flag set on the narrowest word that fits, so code
+/// generic over the width doesn't need a separate path for the 128-bit case.
+///
+/// Values are `u128` at every width; bits at or above `N` are dropped on the
+/// way in.
+#[derive(Debug, Default)]
+pub struct AtomicBits<const N: usize>
+where
+    Width<N>: SelectWidth,
+{
+    word: <Width<N> as SelectWidth>::Word,
+}
+
+impl<const N: usize> AtomicBits<N>
+where
+    Width<N>: SelectWidth,
+{
+    pub const WIDTH: u32 = N as u32;
+
+    const MASK: u128 = if N == 128 { !0 } else { (1 << N) - 1 };
+
+    fn bit(index: u32) -> u128 {
+        assert!(index < Self::WIDTH, "bit index out of range");
+        1 << index
+    }
+
+    pub fn new(bits: u128) -> Self {
+        AtomicBits { word: BitsWord::new(bits & Self::MASK) }
+    }
+
+    pub fn load(&self) -> u128 {
+        self.word.load()
+    }
+
+    pub fn store(&self, bits: u128) {
+        self.word.store(bits & Self::MASK)
+    }
+
+    pub fn swap(&self, bits: u128) -> u128 {
+        self.word.swap(bits & Self::MASK)
+    }
+
+    pub fn compare_exchange(&self, current: u128, new: u128) -> Result<u128, u128> {
+        self.word.compare_exchange(current & Self::MASK, new & Self::MASK)
+    }
+
+    pub fn test(&self, index: u32) -> bool {
+        self.load() & Self::bit(index) != 0
+    }
+
+    pub fn set(&self, index: u32) {
+        self.word.fetch_or(Self::bit(index));
+    }
+
+    pub fn clear(&self, index: u32) {
+        self.word.fetch_and(!Self::bit(index));
+    }
+
+    /// Sets the bit and returns whether it was already set.
+    pub fn fetch_set(&self, index: u32) -> bool {
+        self.word.fetch_or(Self::bit(index)) & Self::bit(index) != 0
+    }
+
+    /// Clears the bit and returns whether it was set.
+    pub fn fetch_clear(&self, index: u32) -> bool {
+        self.word.fetch_and(!Self::bit(index)) & Self::bit(index) != 0
+    }
+
+    pub fn fetch_or(&self, mask: u128) -> u128 {
+        self.word.fetch_or(mask & Self::MASK)
+    }
+
+    pub fn fetch_and(&self, mask: u128) -> u128 {
+        self.word.fetch_and(mask)
+    }
+
+    pub fn fetch_xor(&self, mask: u128) -> u128 {
+        self.word.fetch_xor(mask & Self::MASK)
+    }
+
+    pub fn count_ones(&self) -> u32 {
+        self.load().count_ones()
+    }
+
+    /// Iterates the set bits of a single snapshot of the word.
+    pub fn iter_ones(&self) -> IterOnes {
+        IterOnes::new(self.load())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::mem;
+    use std::sync::atomic::{AtomicU64, AtomicU8};
+
+    use super::{AtomicBits, SelectWidth, Width};
+    use AtomicU128;
+
+    #[test]
+    fn test_picks_narrowest_word() {
+        assert_eq!(mem::size_of::<AtomicBits<1>>(), 1);
+        assert_eq!(mem::size_of::<AtomicBits<9>>(), 2);
+        assert_eq!(mem::size_of::<AtomicBits<32>>(), 4);
+        assert_eq!(mem::size_of::<AtomicBits<33>>(), 8);
+        assert_eq!(mem::size_of::<AtomicBits<65>>(), 16);
+        let _: <Width<8> as SelectWidth>::Word = AtomicU8::new(0);
+        let _: <Width<64> as SelectWidth>::Word = AtomicU64::new(0);
+        let _: <Width<128> as SelectWidth>::Word = AtomicU128::new(0);
+    }
+
+    #[test]
+    fn test_ops_at_widths() {
+        let b = AtomicBits::<5>::new(!0);
+        assert_eq!(b.load(), 0b11111);
+        assert_eq!(b.fetch_xor(!0), 0b11111);
+        assert!(!b.fetch_set(4));
+        assert_eq!(b.load(), 1 << 4);
+        assert_eq!(b.compare_exchange(1 << 4 | 1 << 5, 1), Ok(1 << 4));
+
+        let b = AtomicBits::<100>::default();
+        b.set(99);
+        b.set(3);
+        assert_eq!(b.iter_ones().collect::<Vec<_>>(), vec![3, 99]);
+        assert!(b.fetch_clear(99));
+        assert_eq!(b.swap(1 << 100 | 1), 1 << 3);
+        assert_eq!(b.count_ones(), 1);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_index_out_of_range() {
+        AtomicBits::<16>::default().set(16);
+    }
+}
@@ -0,0 +1,118 @@
+use halves::Halves;
+use AtomicU128;
+
+/// A term and the candidate voted for in it, if any.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Vote {
+    pub term: u64,
+    pub voted_for: Option<u64>,
+}
+
+// lo is the candidate id plus one (0 for no vote), hi the term.
+fn to_halves(vote: Vote) -> Halves {
+    let candidate = match vote.voted_for {
+        Some(id) => {
+            assert!(id != u64::MAX, "candidate id u64::MAX is reserved");
+            id + 1
+        }
+        None => 0,
+    };
+    Halves::new(candidate, vote.term)
+}
+
+fn from_halves(word: Halves) -> Vote {
+    Vote { term: word.hi, voted_for: word.lo.checked_sub(1) }
+}
+
+/// The current term and vote of a Raft or Paxos participant in one word.
+///
+/// Keeping them apart lets a thread that has just seen a higher term reset
+/// the vote after another thread already voted in that new term, handing out
+/// two votes in one term. Here both move together in a single CAS.
+#[derive(Debug)]
+pub struct Ballot {
+    word: AtomicU128,
+}
+
+impl Ballot {
+    /// Starts at `term` with no vote cast.
+    pub fn new(term: u64) -> Self {
+        Ballot { word: AtomicU128::from_halves(Halves::new(0, term)) }
+    }
+
+    pub fn load(&self) -> Vote {
+        from_halves(self.word.load_halves())
+    }
+
+    pub fn compare_exchange(&self, current: Vote, new: Vote) -> Result<Vote, Vote> {
+        self.word.cas_halves(to_halves(current), to_halves(new)).map(from_halves).map_err(from_halves)
+    }
+
+    /// Moves to `term` with no vote if it's newer than the current term.
+    /// Returns the previous vote on success and the current one, whose term
+    /// is at least `term`, otherwise.
+    pub fn advance_if_greater(&self, term: u64) -> Result<Vote, Vote> {
+        let mut current = self.word.load_halves();
+        while term > current.hi {
+            match self.word.cas_halves(current, Halves::new(0, term)) {
+                Ok(previous) => return Ok(from_halves(previous)),
+                Err(actual) => current = actual,
+            }
+        }
+        Err(from_halves(current))
+    }
+
+    /// Grants `candidate` the vote for `term` unless this participant has
+    /// moved past that term or already voted for someone else in it. A newer
+    /// term is adopted in the same step. Returns the previous vote when the
+    /// vote is granted, and the current one when it's refused.
+    pub fn vote_if_unvoted(&self, term: u64, candidate: u64) -> Result<Vote, Vote> {
+        let granted = to_halves(Vote { term, voted_for: Some(candidate) });
+        let mut current = self.word.load_halves();
+        loop {
+            if term < current.hi || (term == current.hi && current.lo != 0 && current.lo != granted.lo) {
+                return Err(from_halves(current));
+            }
+            match self.word.cas_halves(current, granted) {
+                Ok(previous) => return Ok(from_halves(previous)),
+                Err(actual) => current = actual,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::{Ballot, Vote};
+
+    #[test]
+    fn test_terms_and_votes() {
+        let b = Ballot::new(1);
+        assert_eq!(b.load(), Vote { term: 1, voted_for: None });
+        assert_eq!(b.vote_if_unvoted(1, 7), Ok(Vote { term: 1, voted_for: None }));
+        assert_eq!(b.vote_if_unvoted(1, 7).map(|v| v.voted_for), Ok(Some(7)));
+        assert_eq!(b.vote_if_unvoted(1, 8), Err(Vote { term: 1, voted_for: Some(7) }));
+        assert_eq!(b.vote_if_unvoted(3, 8), Ok(Vote { term: 1, voted_for: Some(7) }));
+        assert_eq!(b.vote_if_unvoted(2, 9), Err(Vote { term: 3, voted_for: Some(8) }));
+        assert_eq!(b.advance_if_greater(3), Err(Vote { term: 3, voted_for: Some(8) }));
+        assert_eq!(b.advance_if_greater(4), Ok(Vote { term: 3, voted_for: Some(8) }));
+        assert_eq!(b.load(), Vote { term: 4, voted_for: None });
+    }
+
+    #[test]
+    fn test_one_vote_per_term() {
+        let b = Arc::new(Ballot::new(0));
+        let handles: Vec<_> = (0..8)
+            .map(|candidate| {
+                let b = b.clone();
+                thread::spawn(move || b.vote_if_unvoted(5, candidate).is_ok())
+            })
+            .collect();
+        let granted = handles.into_iter().map(|h| h.join().unwrap()).filter(|&ok| ok).count();
+        assert_eq!(granted, 1);
+        assert_eq!(b.load().term, 5);
+    }
+}
@@ -0,0 +1,86 @@
+use std::sync::atomic::Ordering::SeqCst;
+
+use AtomicU128;
+
+/// Sixteen bytes updated as one atomic unit, for protocol headers and short
+/// keys that are handled as raw bytes rather than as an integer.
+///
+/// The word holds the bytes in memory order, so a pointer to the cell is a
+/// pointer to the `[u8; 16]` as well.
+#[derive(Debug, Default)]
+pub struct AtomicBytes16 {
+    word: AtomicU128,
+}
+
+impl AtomicBytes16 {
+    pub const fn new(bytes: [u8; 16]) -> Self {
+        AtomicBytes16 { word: AtomicU128::new(u128::from_ne_bytes(bytes)) }
+    }
+
+    pub fn load(&self) -> [u8; 16] {
+        self.word.load(SeqCst).to_ne_bytes()
+    }
+
+    pub fn store(&self, bytes: [u8; 16]) {
+        self.word.store(u128::from_ne_bytes(bytes), SeqCst);
+    }
+
+    pub fn swap(&self, bytes: [u8; 16]) -> [u8; 16] {
+        self.word.swap(u128::from_ne_bytes(bytes), SeqCst).to_ne_bytes()
+    }
+
+    pub fn compare_exchange(&self, current: [u8; 16], new: [u8; 16]) -> Result<[u8; 16], [u8; 16]> {
+        self.word
+            .compare_exchange(u128::from_ne_bytes(current), u128::from_ne_bytes(new), SeqCst, SeqCst)
+            .map(u128::to_ne_bytes)
+            .map_err(u128::to_ne_bytes)
+    }
+
+    /// Replaces byte `i` with `f` of its current value, leaving the other
+    /// fifteen as they are, and returns the old byte. `f` may run more than
+    /// once if another thread changes any byte in between.
+    pub fn update_byte<F: FnMut(u8) -> u8>(&self, i: usize, mut f: F) -> u8 {
+        assert!(i < 16, "byte index out of range");
+        let mut current = self.load();
+        loop {
+            let mut new = current;
+            new[i] = f(current[i]);
+            match self.compare_exchange(current, new) {
+                Ok(previous) => return previous[i],
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    pub fn into_inner(self) -> [u8; 16] {
+        self.word.into_inner().to_ne_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AtomicBytes16;
+
+    #[test]
+    fn test_bytes_ops() {
+        let mut header = [0u8; 16];
+        header[..4].copy_from_slice(b"ATOM");
+        let b = AtomicBytes16::new(header);
+        assert_eq!(&b.load()[..4], b"ATOM");
+        assert_eq!(b.compare_exchange([0; 16], [1; 16]), Err(header));
+        assert_eq!(b.update_byte(15, |v| v + 7), 0);
+        assert_eq!(b.update_byte(0, |v| v.to_ascii_lowercase()), b'A');
+        let bytes = b.swap([9; 16]);
+        assert_eq!(&bytes[..4], b"aTOM");
+        assert_eq!(bytes[15], 7);
+        assert_eq!(b.into_inner(), [9; 16]);
+    }
+
+    #[test]
+    fn test_bytes_match_memory() {
+        let b = AtomicBytes16::new([0; 16]);
+        b.update_byte(3, |_| 0xab);
+        let raw = unsafe { &*(&b as *const AtomicBytes16 as *const [u8; 16]) };
+        assert_eq!(raw[3], 0xab);
+    }
+}
@@ -0,0 +1,116 @@
+use std::marker::PhantomData;
+use std::mem;
+use std::ptr;
+
+use halves::Halves;
+use {AtomicU128, NoPadding};
+
+fn to_bits<T: NoPadding>(value: T) -> u64 {
+    let mut bits = 0u64;
+    unsafe { ptr::copy_nonoverlapping(&value as *const T as *const u8, &mut bits as *mut u64 as *mut u8, mem::size_of::<T>()) };
+    bits
+}
+
+fn from_bits<T: NoPadding>(bits: u64) -> T {
+    unsafe { ptr::read_unaligned(&bits as *const u64 as *const T) }
+}
+
+/// Coalesces a burst of events into one: producers `post` over each other
+/// and the consumer's `take` gets the latest payload and how many events it
+/// stands for.
+///
+/// Payload and pending count are one word, so `take` is a single swap and
+/// one call sees each event exactly once; `post` says whether it found the
+/// cell empty, which is when a producer needs to wake the consumer. `T` is
+/// any `NoPadding` value of at most 8 bytes, such as an id or a pointer;
+/// larger types fail to compile.
+pub struct CoalesceCell<T> {
+    // lo is the latest payload, hi the events since the last take.
+    word: AtomicU128,
+    _marker: PhantomData<T>,
+}
+
+unsafe impl<T: NoPadding + Send> Send for CoalesceCell<T> {}
+unsafe impl<T: NoPadding + Send> Sync for CoalesceCell<T> {}
+
+impl<T: NoPadding> CoalesceCell<T> {
+    pub fn new() -> Self {
+        const { assert!(mem::size_of::<T>() <= 8, "CoalesceCell needs a T of at most 8 bytes") };
+        CoalesceCell { word: AtomicU128::new(0), _marker: PhantomData }
+    }
+
+    /// Records an event carrying `value`, replacing any pending payload.
+    /// Returns true if nothing was pending, so the consumer needs a wakeup.
+    pub fn post(&self, value: T) -> bool {
+        let bits = to_bits(value);
+        let mut current = self.word.load_halves();
+        loop {
+            match self.word.cas_halves(current, Halves::new(bits, current.hi.saturating_add(1))) {
+                Ok(previous) => return previous.hi == 0,
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.word.load_halves().hi != 0
+    }
+
+    /// The latest payload and the number of events coalesced into it, if
+    /// any were posted since the last take.
+    pub fn take(&self) -> Option<(T, u64)> {
+        let previous = self.word.swap_halves(Halves::new(0, 0));
+        if previous.hi == 0 { None } else { Some((from_bits(previous.lo), previous.hi)) }
+    }
+}
+
+impl<T: NoPadding> Default for CoalesceCell<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::CoalesceCell;
+
+    #[test]
+    fn test_coalesce() {
+        let cell = CoalesceCell::<[u16; 2]>::new();
+        assert_eq!(cell.take(), None);
+        assert!(cell.post([1, 1]));
+        assert!(!cell.post([2, 3]));
+        assert_eq!(cell.take(), Some(([2, 3], 2)));
+        assert!(!cell.is_pending());
+        assert!(cell.post([0, 0]));
+    }
+
+    #[test]
+    fn test_every_event_counted_once() {
+        let rounds = if cfg!(miri) { 50 } else { 1000 };
+        let cell = Arc::new(CoalesceCell::<u64>::new());
+        let producers: Vec<_> = (0..4)
+            .map(|_| {
+                let cell = cell.clone();
+                thread::spawn(move || {
+                    for i in 0..rounds {
+                        cell.post(i);
+                    }
+                })
+            })
+            .collect();
+        let mut seen = 0;
+        while seen < 4 * rounds {
+            if let Some((_, n)) = cell.take() {
+                seen += n;
+            }
+        }
+        for producer in producers {
+            producer.join().unwrap();
+        }
+        assert_eq!((seen, cell.take()), (4 * rounds, None));
+    }
+}
@@ -0,0 +1,93 @@
+use halves::Halves;
+use AtomicU128;
+
+/// Where a transaction's commit stands.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CommitStatus {
+    InProgress,
+    Committed,
+    Aborted,
+}
+
+const IN_PROGRESS: u64 = 0;
+const COMMITTED: u64 = 1;
+const ABORTED: u64 = 2;
+
+fn unpack(word: Halves) -> (CommitStatus, u64) {
+    let status = match word.hi {
+        IN_PROGRESS => CommitStatus::InProgress,
+        COMMITTED => CommitStatus::Committed,
+        _ => CommitStatus::Aborted,
+    };
+    (status, word.lo)
+}
+
+/// A transaction's commit status and the LSN of its commit record.
+///
+/// Both are one word, so recovery or a reader checking visibility never
+/// sees "committed" without the LSN that goes with it. A record is decided
+/// once: committing or aborting only succeeds from in progress.
+#[derive(Debug, Default)]
+pub struct CommitRecord {
+    // lo is the LSN, hi the status.
+    word: AtomicU128,
+}
+
+impl CommitRecord {
+    pub fn new() -> Self {
+        CommitRecord { word: AtomicU128::new(0) }
+    }
+
+    /// The status and LSN as of one moment. The LSN is 0 until committed.
+    pub fn read(&self) -> (CommitStatus, u64) {
+        unpack(self.word.load_halves())
+    }
+
+    /// Records the commit at `lsn`. Fails with the current status and LSN
+    /// if the transaction was already decided.
+    pub fn mark_committed(&self, lsn: u64) -> Result<(), (CommitStatus, u64)> {
+        self.decide(Halves::new(lsn, COMMITTED))
+    }
+
+    pub fn mark_aborted(&self) -> Result<(), (CommitStatus, u64)> {
+        self.decide(Halves::new(0, ABORTED))
+    }
+
+    fn decide(&self, decided: Halves) -> Result<(), (CommitStatus, u64)> {
+        self.word.cas_halves(Halves::new(0, IN_PROGRESS), decided).map(|_| ()).map_err(unpack)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::{CommitRecord, CommitStatus};
+
+    #[test]
+    fn test_decided_once() {
+        let record = CommitRecord::new();
+        assert_eq!(record.read(), (CommitStatus::InProgress, 0));
+        assert_eq!(record.mark_committed(0x1f40), Ok(()));
+        assert_eq!(record.mark_aborted(), Err((CommitStatus::Committed, 0x1f40)));
+        assert_eq!(record.mark_committed(1), Err((CommitStatus::Committed, 0x1f40)));
+        assert_eq!(record.read(), (CommitStatus::Committed, 0x1f40));
+    }
+
+    #[test]
+    fn test_committed_always_has_lsn() {
+        let record = Arc::new(CommitRecord::new());
+        let committer = {
+            let record = record.clone();
+            thread::spawn(move || record.mark_committed(77).unwrap())
+        };
+        loop {
+            match record.read() {
+                (CommitStatus::Committed, lsn) => break assert_eq!(lsn, 77),
+                (status, lsn) => assert_eq!((status, lsn), (CommitStatus::InProgress, 0)),
+            }
+        }
+        committer.join().unwrap();
+    }
+}
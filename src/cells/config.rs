@@ -0,0 +1,185 @@
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::sync::atomic::{AtomicBool, AtomicIsize, Ordering};
+use std::thread;
+
+use halves::Halves;
+use AtomicU128;
+
+const READER: u128 = 1 << 64;
+
+// A published config and the epoch it was published in.
+struct Config<T> {
+    epoch: u64,
+    value: T,
+}
+
+/// A published configuration that writers replace and readers pin.
+///
+/// The reader count lives in the same word as the config pointer, so a
+/// reader pins the current config and learns which it is in one
+/// `fetch_add`, and unpins with a `fetch_add` on a departure count: `read`
+/// has no retry loop and never waits for a writer or for other readers.
+/// That makes it wait-free wherever the 128-bit `fetch_add` is; on the
+/// cmpxchg16b backend the `fetch_add` is itself a CAS loop underneath. The
+/// waiting is all the writer's: when it swaps in a new config it takes the
+/// old config's arrivals off the departure count, and once that is back to
+/// zero it knows the old config is free to hand back.
+pub struct ConfigCell<T> {
+    // lo is the current config, hi the readers that have pinned it.
+    word: AtomicU128,
+    // Per epoch parity, readers that have left minus the arrivals the writer
+    // has taken off. Only one epoch is ever draining: a writer waits for
+    // its old epoch before giving up the role.
+    departed: [AtomicIsize; 2],
+    writer: AtomicBool,
+    _marker: PhantomData<Box<Config<T>>>,
+}
+
+unsafe impl<T: Send + Sync> Send for ConfigCell<T> {}
+unsafe impl<T: Send + Sync> Sync for ConfigCell<T> {}
+
+/// A pinned view of the config that was current when it was taken.
+pub struct ConfigGuard<'a, T: 'a> {
+    cell: &'a ConfigCell<T>,
+    config: *const Config<T>,
+}
+
+impl<T> ConfigCell<T> {
+    pub fn new(value: T) -> Self {
+        let config = Box::into_raw(Box::new(Config { epoch: 0, value }));
+        ConfigCell {
+            word: AtomicU128::from_halves(Halves::new(config as u64, 0)),
+            departed: [AtomicIsize::new(0), AtomicIsize::new(0)],
+            writer: AtomicBool::new(false),
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn read(&self) -> ConfigGuard<'_, T> {
+        let pinned = Halves::from_bits(self.word.fetch_add(READER, Ordering::SeqCst));
+        ConfigGuard { cell: self, config: pinned.lo as *const Config<T> }
+    }
+
+    /// Epoch of the current config; bumped by every `replace`.
+    pub fn epoch(&self) -> u64 {
+        self.read().epoch()
+    }
+
+    /// Publishes `value`, waits for readers of the old config to leave and
+    /// hands the old config back.
+    pub fn replace(&self, value: T) -> T {
+        while self.writer.compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed).is_err() {
+            thread::yield_now();
+        }
+        let old = self.swap_in(value);
+        let departed = &self.departed[(unsafe { (*old).epoch } & 1) as usize];
+        while departed.load(Ordering::SeqCst) != 0 {
+            thread::yield_now();
+        }
+        self.writer.store(false, Ordering::Release);
+        unsafe { Box::from_raw(old) }.value
+    }
+
+    pub fn store(&self, value: T) {
+        drop(self.replace(value));
+    }
+
+    // Swaps in a new config and takes the old one's arrivals off its
+    // departure count. Only the writer changes lo, and readers only add to
+    // hi, so the swap can't lose a config.
+    fn swap_in(&self, value: T) -> *mut Config<T> {
+        let current = self.word.load_halves().lo as *mut Config<T>;
+        let epoch = unsafe { (*current).epoch };
+        let new = Box::into_raw(Box::new(Config { epoch: epoch + 1, value }));
+        let pinned = self.word.swap_halves(Halves::new(new as u64, 0));
+        self.departed[(epoch & 1) as usize].fetch_sub(pinned.hi as isize, Ordering::SeqCst);
+        pinned.lo as *mut Config<T>
+    }
+}
+
+impl<T> Drop for ConfigCell<T> {
+    fn drop(&mut self) {
+        drop(unsafe { Box::from_raw(self.word.load_halves().lo as *mut Config<T>) });
+    }
+}
+
+impl<'a, T> ConfigGuard<'a, T> {
+    pub fn epoch(&self) -> u64 {
+        unsafe { (*self.config).epoch }
+    }
+}
+
+impl<'a, T> Deref for ConfigGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &(*self.config).value }
+    }
+}
+
+impl<'a, T> Drop for ConfigGuard<'a, T> {
+    fn drop(&mut self) {
+        self.cell.departed[(self.epoch() & 1) as usize].fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::ConfigCell;
+
+    #[test]
+    fn test_read_replace() {
+        let cell = ConfigCell::new(String::from("a"));
+        assert_eq!(&*cell.read(), "a");
+        assert_eq!(cell.replace(String::from("b")), "a");
+        assert_eq!(cell.epoch(), 1);
+        let guard = cell.read();
+        assert_eq!(&*guard, "b");
+        assert_eq!(guard.epoch(), 1);
+    }
+
+    #[test]
+    fn test_reader_across_replace() {
+        let cell = ConfigCell::new(vec![1]);
+        let a = cell.read();
+        drop(cell.read());
+        let old = cell.swap_in(vec![2]);
+        assert_eq!(cell.departed[0].load(Ordering::SeqCst), -1);
+        assert_eq!(*a, vec![1]);
+        assert_eq!(*cell.read(), vec![2]);
+        drop(a);
+        assert_eq!(cell.departed[0].load(Ordering::SeqCst), 0);
+        drop(unsafe { Box::from_raw(old) });
+    }
+
+    #[test]
+    fn test_readers_during_replace() {
+        // Every config published is its epoch repeated.
+        let rounds = if cfg!(miri) { 30 } else { 1000 };
+        let cell = Arc::new(ConfigCell::new(vec![0u64; 8]));
+        let stop = Arc::new(AtomicBool::new(false));
+        let readers: Vec<_> = (0..3)
+            .map(|_| {
+                let (cell, stop) = (cell.clone(), stop.clone());
+                thread::spawn(move || {
+                    while !stop.load(Ordering::SeqCst) {
+                        let guard = cell.read();
+                        assert!(guard.iter().all(|&x| x == guard.epoch()));
+                    }
+                })
+            })
+            .collect();
+        for i in 1..=rounds {
+            cell.store(vec![i; 8]);
+        }
+        stop.store(true, Ordering::SeqCst);
+        for reader in readers {
+            reader.join().unwrap();
+        }
+    }
+}
@@ -0,0 +1,151 @@
+use halves::Halves;
+use AtomicU128;
+
+/// Two related 64-bit atomics laid out side by side, so they can also be
+/// compared and swapped as a pair.
+///
+/// For code that has a pair of `AtomicU64`s, like a head index and its
+/// count, and needs some updates to cover both without repacking them into
+/// a `u128` by hand. Each half keeps an `AtomicU64`-style API of its own
+/// through `first` and `second`. Those go through the 128-bit backend too:
+/// mixing 64-bit and 128-bit atomics on the same bytes is outside Rust's
+/// memory model, and on the lock and seqlock fallbacks a plain 64-bit store
+/// would race with the pairwise CAS.
+#[derive(Debug, Default)]
+pub struct Dcas64x2 {
+    // lo is the first value, hi the second.
+    word: AtomicU128,
+}
+
+/// One half of a `Dcas64x2`.
+#[derive(Clone, Copy, Debug)]
+pub struct Dcas64Half<'a> {
+    word: &'a AtomicU128,
+    second: bool,
+}
+
+impl Dcas64x2 {
+    pub fn new(a: u64, b: u64) -> Self {
+        Dcas64x2 { word: AtomicU128::from_halves(Halves::new(a, b)) }
+    }
+
+    /// Both values from one load.
+    pub fn load(&self) -> (u64, u64) {
+        let current = self.word.load_halves();
+        (current.lo, current.hi)
+    }
+
+    pub fn store(&self, new: (u64, u64)) {
+        self.word.store_halves(Halves::new(new.0, new.1));
+    }
+
+    /// Replaces both values if both still equal `current`.
+    pub fn compare_exchange_both(&self, current: (u64, u64), new: (u64, u64)) -> Result<(u64, u64), (u64, u64)> {
+        match self.word.cas_halves(Halves::new(current.0, current.1), Halves::new(new.0, new.1)) {
+            Ok(previous) => Ok((previous.lo, previous.hi)),
+            Err(actual) => Err((actual.lo, actual.hi)),
+        }
+    }
+
+    pub fn first(&self) -> Dcas64Half<'_> {
+        Dcas64Half { word: &self.word, second: false }
+    }
+
+    pub fn second(&self) -> Dcas64Half<'_> {
+        Dcas64Half { word: &self.word, second: true }
+    }
+}
+
+impl<'a> Dcas64Half<'a> {
+    fn get(&self, word: Halves) -> u64 {
+        if self.second { word.hi } else { word.lo }
+    }
+
+    fn with(&self, word: Halves, value: u64) -> Halves {
+        if self.second { Halves::new(word.lo, value) } else { Halves::new(value, word.hi) }
+    }
+
+    pub fn load(&self) -> u64 {
+        self.get(self.word.load_halves())
+    }
+
+    pub fn store(&self, value: u64) {
+        self.swap(value);
+    }
+
+    pub fn swap(&self, value: u64) -> u64 {
+        self.fetch_update(|_| Some(value)).unwrap()
+    }
+
+    /// Replaces this half if it equals `current`, whatever the other holds.
+    pub fn compare_exchange(&self, current: u64, new: u64) -> Result<u64, u64> {
+        self.fetch_update(|v| if v == current { Some(new) } else { None })
+    }
+
+    pub fn fetch_add(&self, value: u64) -> u64 {
+        self.fetch_update(|v| Some(v.wrapping_add(value))).unwrap()
+    }
+
+    pub fn fetch_sub(&self, value: u64) -> u64 {
+        self.fetch_update(|v| Some(v.wrapping_sub(value))).unwrap()
+    }
+
+    /// Like `AtomicU64::fetch_update`, leaving the other half as it is.
+    pub fn fetch_update<F: FnMut(u64) -> Option<u64>>(&self, mut f: F) -> Result<u64, u64> {
+        let mut current = self.word.load_halves();
+        loop {
+            let previous = self.get(current);
+            let next = match f(previous) {
+                Some(next) => self.with(current, next),
+                None => return Err(previous),
+            };
+            match self.word.cas_halves(current, next) {
+                Ok(_) => return Ok(previous),
+                Err(actual) => current = actual,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::Dcas64x2;
+
+    #[test]
+    fn test_halves_and_pair() {
+        let pair = Dcas64x2::new(1, 2);
+        assert_eq!(pair.first().fetch_add(10), 1);
+        assert_eq!(pair.second().swap(5), 2);
+        assert_eq!(pair.second().compare_exchange(4, 0), Err(5));
+        assert_eq!(pair.load(), (11, 5));
+        assert_eq!(pair.compare_exchange_both((11, 4), (0, 0)), Err((11, 5)));
+        assert_eq!(pair.compare_exchange_both((11, 5), (7, 8)), Ok((11, 5)));
+        assert_eq!((pair.first().load(), pair.second().load()), (7, 8));
+    }
+
+    #[test]
+    fn test_half_updates_dont_lose_pair_updates() {
+        // One thread counts in the first half while another moves both
+        // halves forward together; neither update may be lost.
+        let pair = Arc::new(Dcas64x2::new(0, 0));
+        let counter = {
+            let pair = pair.clone();
+            thread::spawn(move || {
+                for _ in 0..2000 {
+                    pair.first().fetch_add(1);
+                }
+            })
+        };
+        for _ in 0..2000 {
+            let mut current = pair.load();
+            while let Err(actual) = pair.compare_exchange_both(current, (current.0 + 1, current.1 + 1)) {
+                current = actual;
+            }
+        }
+        counter.join().unwrap();
+        assert_eq!(pair.load(), (4000, 2000));
+    }
+}
@@ -0,0 +1,184 @@
+use halves::Halves;
+use AtomicU128;
+
+const COLOR: u64 = 0b11;
+const AGE_SHIFT: u32 = 2;
+const AGE: u64 = 0b1111 << AGE_SHIFT;
+
+/// Tri-color marking state.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Color {
+    /// Not reached yet this cycle.
+    White,
+    /// Reached, its references not yet scanned.
+    Grey,
+    /// Reached and scanned.
+    Black,
+}
+
+fn color(bits: u64) -> Color {
+    match bits & COLOR {
+        0 => Color::White,
+        1 => Color::Grey,
+        _ => Color::Black,
+    }
+}
+
+fn color_bits(color: Color) -> u64 {
+    match color {
+        Color::White => 0,
+        Color::Grey => 1,
+        Color::Black => 2,
+    }
+}
+
+/// An object's header as of one moment.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GcHeader {
+    pub color: Color,
+    /// Collections survived, saturating at `GcWord::MAX_AGE`.
+    pub age: u8,
+    /// Where the object was copied to, if it has been.
+    pub forwarding: Option<usize>,
+}
+
+fn unpack(word: Halves) -> GcHeader {
+    GcHeader {
+        color: color(word.hi),
+        age: ((word.hi & AGE) >> AGE_SHIFT) as u8,
+        forwarding: if word.lo == 0 { None } else { Some(word.lo as usize) },
+    }
+}
+
+/// An object header for a concurrent collector: mark color, age and
+/// forwarding address in one word.
+///
+/// Marking, aging and forwarding each go in by one CAS over the whole
+/// header, so two markers can't both grey an object, two copiers can't both
+/// forward it, and a mutator that loads the header never sees a forwarding
+/// address from one cycle with the color of another.
+#[derive(Debug, Default)]
+pub struct GcWord {
+    // lo is the forwarding address (0 for none), hi age << 2 | color.
+    word: AtomicU128,
+}
+
+impl GcWord {
+    pub const MAX_AGE: u8 = 15;
+
+    /// A white, unforwarded header of age 0.
+    pub fn new() -> Self {
+        GcWord { word: AtomicU128::new(0) }
+    }
+
+    pub fn load(&self) -> GcHeader {
+        unpack(self.word.load_halves())
+    }
+
+    fn update<F: FnMut(Halves) -> Option<Halves>>(&self, mut f: F) -> Result<GcHeader, GcHeader> {
+        let mut current = self.word.load_halves();
+        loop {
+            let new = match f(current) {
+                Some(new) => new,
+                None => return Err(unpack(current)),
+            };
+            match self.word.cas_halves(current, new) {
+                Ok(_) => return Ok(unpack(current)),
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    /// Greys a white object. `Ok` means this call marked it and the caller
+    /// should queue it for scanning; `Err` means someone got there first.
+    /// Either way the header comes back, forwarding address included, so
+    /// the caller can fix up its reference at the same time.
+    pub fn try_mark_and_get(&self) -> Result<GcHeader, GcHeader> {
+        self.update(|current| {
+            if color(current.hi) != Color::White {
+                return None;
+            }
+            Some(Halves::new(current.lo, current.hi & !COLOR | color_bits(Color::Grey)))
+        })
+    }
+
+    /// Blackens a grey object once its references are scanned. Returns
+    /// whether it was grey.
+    pub fn blacken(&self) -> bool {
+        self.update(|current| {
+            if color(current.hi) != Color::Grey {
+                return None;
+            }
+            Some(Halves::new(current.lo, current.hi & !COLOR | color_bits(Color::Black)))
+        })
+        .is_ok()
+    }
+
+    /// Records where the object was copied to. Of several racing copiers
+    /// one wins; the others get its address back and should discard their
+    /// copy.
+    pub fn install_forwarding(&self, to: usize) -> Result<(), usize> {
+        assert!(to != 0, "forwarding address can't be null");
+        self.update(|current| if current.lo != 0 { None } else { Some(Halves::new(to as u64, current.hi)) })
+            .map(|_| ())
+            .map_err(|header| header.forwarding.unwrap())
+    }
+
+    /// Starts the object's next cycle: white, one collection older and not
+    /// forwarded. Returns the header it had.
+    pub fn survive(&self) -> GcHeader {
+        let result = self.update(|current| {
+            let age = ((current.hi & AGE) >> AGE_SHIFT).min(GcWord::MAX_AGE as u64 - 1) + 1;
+            Some(Halves::new(0, current.hi & !(AGE | COLOR) | age << AGE_SHIFT))
+        });
+        result.unwrap_or_else(|header| header)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::{Color, GcHeader, GcWord};
+
+    #[test]
+    fn test_cycle() {
+        let header = GcWord::new();
+        let white = GcHeader { color: Color::White, age: 0, forwarding: None };
+        assert_eq!(header.try_mark_and_get(), Ok(white));
+        assert_eq!(header.try_mark_and_get().unwrap_err().color, Color::Grey);
+        assert_eq!(header.install_forwarding(0x1000), Ok(()));
+        assert_eq!(header.install_forwarding(0x2000), Err(0x1000));
+        assert!(header.blacken() && !header.blacken());
+        assert_eq!(header.load(), GcHeader { color: Color::Black, age: 0, forwarding: Some(0x1000) });
+        for _ in 0..20 {
+            header.survive();
+        }
+        assert_eq!(header.load(), GcHeader { color: Color::White, age: GcWord::MAX_AGE, forwarding: None });
+    }
+
+    #[test]
+    fn test_one_marker_one_copier() {
+        let header = Arc::new(GcWord::new());
+        let marked = Arc::new(AtomicUsize::new(0));
+        let handles: Vec<_> = (1..=4usize)
+            .map(|t| {
+                let (header, marked) = (header.clone(), marked.clone());
+                thread::spawn(move || {
+                    if header.try_mark_and_get().is_ok() {
+                        marked.fetch_add(1, Ordering::SeqCst);
+                    }
+                    match header.install_forwarding(t * 0x100) {
+                        Ok(()) => t * 0x100,
+                        Err(winner) => winner,
+                    }
+                })
+            })
+            .collect();
+        let forwarded: Vec<usize> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+        assert_eq!(marked.load(Ordering::SeqCst), 1);
+        assert!(forwarded.iter().all(|&f| Some(f) == header.load().forwarding));
+    }
+}
@@ -0,0 +1,90 @@
+use halves::Halves;
+use AtomicU128;
+
+// Fingerprint of an empty slot. Real fingerprints of zero are stored as one.
+const EMPTY: u64 = 0;
+
+fn fingerprint(hash: u64) -> u64 {
+    hash.max(1)
+}
+
+/// One entry of an open-addressing intern table: a key's hash and the id or
+/// pointer it was interned as.
+///
+/// Both go in with one CAS, so a reader that finds a matching hash always
+/// gets the value that goes with it, and of several threads interning into
+/// the same empty slot one wins. Slots are never emptied once claimed.
+/// Hashes of zero and one share a fingerprint; a match still needs the key
+/// behind the value compared.
+#[derive(Debug, Default)]
+pub struct InternSlot {
+    // lo is the value, hi the fingerprint (EMPTY when free).
+    word: AtomicU128,
+}
+
+impl InternSlot {
+    pub fn new() -> Self {
+        InternSlot::default()
+    }
+
+    /// The `(fingerprint, value)` stored, if the slot is claimed.
+    pub fn load(&self) -> Option<(u64, u64)> {
+        let current = self.word.load_halves();
+        if current.hi == EMPTY { None } else { Some((current.hi, current.lo)) }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.word.load_halves().hi == EMPTY
+    }
+
+    /// Claims the slot for `hash` and `value` if nobody has. Otherwise
+    /// returns who did, as `(fingerprint, value)`.
+    pub fn claim_if_empty(&self, hash: u64, value: u64) -> Result<(), (u64, u64)> {
+        match self.word.cas_halves(Halves::new(0, EMPTY), Halves::new(value, fingerprint(hash))) {
+            Ok(_) => Ok(()),
+            Err(actual) => Err((actual.hi, actual.lo)),
+        }
+    }
+
+    /// The value stored, if the slot was claimed for `hash`.
+    pub fn read_matching(&self, hash: u64) -> Option<u64> {
+        let current = self.word.load_halves();
+        if current.hi == fingerprint(hash) { Some(current.lo) } else { None }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::InternSlot;
+
+    #[test]
+    fn test_claim_and_read() {
+        let slot = InternSlot::new();
+        assert_eq!((slot.load(), slot.read_matching(7)), (None, None));
+        slot.claim_if_empty(7, 70).unwrap();
+        assert_eq!(slot.claim_if_empty(8, 80), Err((7, 70)));
+        assert_eq!((slot.read_matching(7), slot.read_matching(8)), (Some(70), None));
+
+        let zero = InternSlot::new();
+        zero.claim_if_empty(0, 5).unwrap();
+        assert!(!zero.is_empty());
+        assert_eq!(zero.read_matching(0), Some(5));
+    }
+
+    #[test]
+    fn test_one_claim_wins() {
+        let slot = Arc::new(InternSlot::new());
+        let handles: Vec<_> = (1..5u64)
+            .map(|t| {
+                let slot = slot.clone();
+                thread::spawn(move || slot.claim_if_empty(t, t * 10).is_ok())
+            })
+            .collect();
+        let winners: usize = handles.into_iter().map(|h| h.join().unwrap() as usize).sum();
+        let (hash, value) = slot.load().unwrap();
+        assert_eq!((winners, value), (1, hash * 10));
+    }
+}
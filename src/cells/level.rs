@@ -0,0 +1,91 @@
+use halves::Halves;
+use AtomicU128;
+
+/// One side's price level in an order book: a price in ticks and the
+/// quantity resting at it.
+///
+/// Price and quantity are one word, so a reader never pairs a price with
+/// the quantity of the level it replaced, without a seqlock's retry loop on
+/// the read side.
+#[derive(Debug, Default)]
+pub struct LevelCell {
+    // lo is the quantity, hi the price.
+    word: AtomicU128,
+}
+
+impl LevelCell {
+    pub fn new(price: i64, qty: u64) -> Self {
+        LevelCell { word: AtomicU128::from_halves(Halves::new(qty, price as u64)) }
+    }
+
+    /// The `(price, qty)` as of one moment.
+    pub fn load(&self) -> (i64, u64) {
+        let current = self.word.load_halves();
+        (current.hi as i64, current.lo)
+    }
+
+    /// Moves the level to a new price and quantity, returning the old pair.
+    pub fn replace(&self, price: i64, qty: u64) -> (i64, u64) {
+        let previous = self.word.swap_halves(Halves::new(qty, price as u64));
+        (previous.hi as i64, previous.lo)
+    }
+
+    /// Adds `dq` to the quantity if the level is still at `price`, and
+    /// returns the new quantity. The quantity can't go below zero: a fill
+    /// larger than what's resting fails like a price mismatch does, with
+    /// the current pair.
+    pub fn add_qty_if_price(&self, price: i64, dq: i64) -> Result<u64, (i64, u64)> {
+        let mut current = self.word.load_halves();
+        loop {
+            let qty = match (current.hi as i64 == price, add_signed(current.lo, dq)) {
+                (true, Some(qty)) => qty,
+                _ => return Err((current.hi as i64, current.lo)),
+            };
+            match self.word.cas_halves(current, Halves::new(qty, current.hi)) {
+                Ok(_) => return Ok(qty),
+                Err(actual) => current = actual,
+            }
+        }
+    }
+}
+
+fn add_signed(qty: u64, dq: i64) -> Option<u64> {
+    if dq >= 0 { qty.checked_add(dq as u64) } else { qty.checked_sub(dq.unsigned_abs()) }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::LevelCell;
+
+    #[test]
+    fn test_level_updates() {
+        let level = LevelCell::new(10_050, 300);
+        assert_eq!(level.add_qty_if_price(10_050, -100), Ok(200));
+        assert_eq!(level.add_qty_if_price(10_049, 50), Err((10_050, 200)));
+        assert_eq!(level.add_qty_if_price(10_050, -201), Err((10_050, 200)));
+        assert_eq!(level.replace(-5, 7), (10_050, 200));
+        assert_eq!(level.load(), (-5, 7));
+    }
+
+    #[test]
+    fn test_reads_are_consistent() {
+        // Every level written has qty == price * 3.
+        let level = Arc::new(LevelCell::new(1, 3));
+        let writer = {
+            let level = level.clone();
+            thread::spawn(move || {
+                for price in 2..5000i64 {
+                    level.replace(price, price as u64 * 3);
+                }
+            })
+        };
+        for _ in 0..5000 {
+            let (price, qty) = level.load();
+            assert_eq!(qty, price as u64 * 3);
+        }
+        writer.join().unwrap();
+    }
+}
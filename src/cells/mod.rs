@@ -0,0 +1,41 @@
+mod ballot;
+mod bytes;
+mod coalesce;
+mod commit;
+mod mvcc;
+mod name;
+mod config;
+mod dcas;
+mod gc;
+mod intern;
+mod level;
+mod once;
+mod param;
+mod priority;
+mod region;
+mod sample;
+mod slice_ref;
+mod state;
+mod vec2;
+mod wide_register;
+
+pub use self::ballot::{Ballot, Vote};
+pub use self::bytes::AtomicBytes16;
+pub use self::coalesce::CoalesceCell;
+pub use self::commit::{CommitRecord, CommitStatus};
+pub use self::mvcc::MvccSlot;
+pub use self::name::{AtomicName16, Name16, NameTooLong};
+pub use self::config::{ConfigCell, ConfigGuard};
+pub use self::dcas::{Dcas64Half, Dcas64x2};
+pub use self::gc::{Color, GcHeader, GcWord};
+pub use self::intern::InternSlot;
+pub use self::level::LevelCell;
+pub use self::once::Once128;
+pub use self::param::ParamCell;
+pub use self::priority::PrioritySlot;
+pub use self::region::{Region, RegionRef};
+pub use self::sample::{Sample, SampleCell};
+pub use self::slice_ref::AtomicSliceRef;
+pub use self::state::{AtomicStateMachine, MachineState, TransitionError};
+pub use self::vec2::AtomicVec2;
+pub use self::wide_register::{WideRegister, WideWriter};
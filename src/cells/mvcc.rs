@@ -0,0 +1,72 @@
+use halves::Halves;
+use AtomicU128;
+
+/// Head of an MVCC version chain: (commit timestamp, record pointer or offset).
+///
+/// Both halves change in one CAS, so a reader never pairs a timestamp with
+/// another version's record and writers don't need to latch the chain head.
+#[derive(Debug, Default)]
+pub struct MvccSlot {
+    // lo is the commit timestamp, hi the record.
+    word: AtomicU128,
+}
+
+impl MvccSlot {
+    pub fn new(ts: u64, record: u64) -> Self {
+        MvccSlot { word: AtomicU128::from_halves(Halves::new(ts, record)) }
+    }
+
+    /// Returns (commit timestamp, record).
+    pub fn load(&self) -> (u64, u64) {
+        let word = self.word.load_halves();
+        (word.lo, word.hi)
+    }
+
+    /// Installs a new version if its timestamp is newer than the current one.
+    /// On failure returns the version that is installed.
+    pub fn install_if_ts_less(&self, ts: u64, record: u64) -> Result<(), (u64, u64)> {
+        let mut current = self.word.load_halves();
+        loop {
+            if current.lo >= ts {
+                return Err((current.lo, current.hi));
+            }
+            match self.word.cas_halves(current, Halves::new(ts, record)) {
+                Ok(_) => return Ok(()),
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    /// The record if the head version is visible to a reader at `ts`; `None`
+    /// means the reader has to go to older versions.
+    pub fn read_visible(&self, ts: u64) -> Option<u64> {
+        let (commit_ts, record) = self.load();
+        if commit_ts <= ts {
+            Some(record)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MvccSlot;
+
+    #[test]
+    fn test_install() {
+        let slot = MvccSlot::new(10, 100);
+        assert_eq!(slot.install_if_ts_less(10, 101), Err((10, 100)));
+        assert_eq!(slot.install_if_ts_less(5, 101), Err((10, 100)));
+        assert_eq!(slot.install_if_ts_less(12, 102), Ok(()));
+        assert_eq!(slot.load(), (12, 102));
+    }
+
+    #[test]
+    fn test_read_visible() {
+        let slot = MvccSlot::new(10, 100);
+        assert_eq!(slot.read_visible(9), None);
+        assert_eq!(slot.read_visible(10), Some(100));
+        assert_eq!(slot.read_visible(11), Some(100));
+    }
+}
@@ -0,0 +1,114 @@
+use std::fmt;
+use std::ops::Deref;
+use std::str;
+
+use super::AtomicBytes16;
+
+/// A string of at most 15 bytes held by value: one length byte followed by
+/// the bytes, zero padded, so equal strings have equal words.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Name16 {
+    bytes: [u8; 16],
+}
+
+/// The string doesn't fit in the 15 bytes a `Name16` has room for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NameTooLong;
+
+impl Name16 {
+    pub fn new(name: &str) -> Result<Self, NameTooLong> {
+        if name.len() > 15 {
+            return Err(NameTooLong);
+        }
+        let mut bytes = [0; 16];
+        bytes[0] = name.len() as u8;
+        bytes[1..=name.len()].copy_from_slice(name.as_bytes());
+        Ok(Name16 { bytes })
+    }
+
+    pub fn as_str(&self) -> &str {
+        // Only `new` builds these, from a whole `&str`.
+        unsafe { str::from_utf8_unchecked(&self.bytes[1..=self.bytes[0] as usize]) }
+    }
+}
+
+impl Deref for Name16 {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl fmt::Debug for Name16 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for Name16 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A short label stored inline in one word, so a thread can publish its
+/// status or a tag and monitoring threads read it without locks or
+/// allocation. Starts out empty.
+#[derive(Debug, Default)]
+pub struct AtomicName16 {
+    bytes: AtomicBytes16,
+}
+
+impl AtomicName16 {
+    pub fn new(name: Name16) -> Self {
+        AtomicName16 { bytes: AtomicBytes16::new(name.bytes) }
+    }
+
+    pub fn load(&self) -> Name16 {
+        Name16 { bytes: self.bytes.load() }
+    }
+
+    pub fn store(&self, name: &str) -> Result<(), NameTooLong> {
+        self.bytes.store(Name16::new(name)?.bytes);
+        Ok(())
+    }
+
+    pub fn swap(&self, name: Name16) -> Name16 {
+        Name16 { bytes: self.bytes.swap(name.bytes) }
+    }
+
+    pub fn compare_exchange(&self, current: Name16, new: Name16) -> Result<Name16, Name16> {
+        self.bytes
+            .compare_exchange(current.bytes, new.bytes)
+            .map(|bytes| Name16 { bytes })
+            .map_err(|bytes| Name16 { bytes })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AtomicName16, Name16, NameTooLong};
+
+    #[test]
+    fn test_name_ops() {
+        let a = AtomicName16::default();
+        assert_eq!(&*a.load(), "");
+        a.store("starting").unwrap();
+        assert_eq!(a.load().as_str(), "starting");
+        let starting = Name16::new("starting").unwrap();
+        let ready = Name16::new("ready").unwrap();
+        assert_eq!(a.compare_exchange(ready, ready), Err(starting));
+        assert_eq!(a.compare_exchange(starting, ready), Ok(starting));
+        assert_eq!(a.load().to_string(), "ready");
+        assert_eq!(a.swap(Name16::new("ünïcode").unwrap()), ready);
+        assert_eq!(format!("{:?}", a.load()), "\"ünïcode\"");
+    }
+
+    #[test]
+    fn test_too_long() {
+        let a = AtomicName16::new(Name16::new("fifteen bytes!!").unwrap());
+        assert_eq!(a.store("sixteen bytes!!!"), Err(NameTooLong));
+        assert_eq!(a.load().len(), 15);
+    }
+}
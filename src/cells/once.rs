@@ -0,0 +1,115 @@
+use std::marker::PhantomData;
+use std::thread;
+
+use halves::Halves;
+use AtomicU128;
+
+const UNINIT: u64 = 0;
+const RUNNING: u64 = 1;
+const READY: u64 = 2;
+
+/// One-shot initialization cell with the state and value pointer in one word.
+///
+/// The initializer's result is published by the same CAS that moves the state
+/// to ready, so a reader that sees "ready" can't see a stale or null pointer
+/// and no separate flag needs fencing against the data.
+pub struct Once128<T> {
+    // lo is the value pointer, hi the state.
+    word: AtomicU128,
+    _marker: PhantomData<Box<T>>,
+}
+
+unsafe impl<T: Send + Sync> Send for Once128<T> {}
+unsafe impl<T: Send + Sync> Sync for Once128<T> {}
+
+// Puts the cell back to uninitialized if the initializer panics, so another
+// caller can take over instead of waiting forever.
+struct Reset<'a> {
+    word: &'a AtomicU128,
+}
+
+impl<'a> Drop for Reset<'a> {
+    fn drop(&mut self) {
+        self.word.store_halves(Halves::new(0, UNINIT));
+    }
+}
+
+impl<T> Once128<T> {
+    pub fn new() -> Self {
+        Once128 { word: AtomicU128::from_halves(Halves::new(0, UNINIT)), _marker: PhantomData }
+    }
+
+    pub fn get(&self) -> Option<&T> {
+        let current = self.word.load_halves();
+        if current.hi == READY { Some(unsafe { &*(current.lo as *const T) }) } else { None }
+    }
+
+    pub fn is_initialized(&self) -> bool {
+        self.word.load_halves().hi == READY
+    }
+
+    /// Returns the value, running `f` to create it if nobody has yet. If
+    /// another thread is running its initializer, waits for that one instead.
+    pub fn get_or_init<F: FnOnce() -> T>(&self, f: F) -> &T {
+        loop {
+            let current = self.word.load_halves();
+            match current.hi {
+                READY => return unsafe { &*(current.lo as *const T) },
+                UNINIT => {
+                    if self.word.cas_halves(current, Halves::new(0, RUNNING)).is_ok() {
+                        break;
+                    }
+                }
+                _ => thread::yield_now(),
+            }
+        }
+        let reset = Reset { word: &self.word };
+        let ptr = Box::into_raw(Box::new(f()));
+        let published = self.word.cas_halves(Halves::new(0, RUNNING), Halves::new(ptr as u64, READY));
+        debug_assert!(published.is_ok());
+        ::std::mem::forget(reset);
+        unsafe { &*ptr }
+    }
+}
+
+impl<T> Default for Once128<T> {
+    fn default() -> Self {
+        Once128::new()
+    }
+}
+
+impl<T> Drop for Once128<T> {
+    fn drop(&mut self) {
+        let word = self.word.load_halves();
+        if word.hi == READY {
+            drop(unsafe { Box::from_raw(word.lo as *mut T) });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::panic;
+    use super::Once128;
+
+    #[test]
+    fn test_get_or_init() {
+        let once = Once128::new();
+        assert_eq!(once.get(), None);
+        assert_eq!(once.get_or_init(|| String::from("a")), "a");
+        assert_eq!(once.get_or_init(|| String::from("b")), "a");
+        assert!(once.is_initialized());
+        assert_eq!(once.get().map(|s| s.as_str()), Some("a"));
+    }
+
+    #[test]
+    fn test_panicking_init() {
+        let once: Once128<u32> = Once128::new();
+        let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+            once.get_or_init(|| panic!("init failed"));
+        }));
+        assert!(result.is_err());
+        assert!(!once.is_initialized());
+        assert_eq!(*once.get_or_init(|| 7), 7);
+    }
+}
@@ -0,0 +1,97 @@
+use halves::Halves;
+use AtomicU128;
+
+// lo is the current value, hi the target, both as f64 bits.
+fn unpack(word: Halves) -> (f64, f64) {
+    (f64::from_bits(word.lo), f64::from_bits(word.hi))
+}
+
+fn pack(current: f64, target: f64) -> Halves {
+    Halves::new(current.to_bits(), target.to_bits())
+}
+
+/// An automatable audio parameter: a target set from the UI thread and the
+/// current value the audio callback ramps towards it, at most `max_step` per
+/// sample so jumps don't click.
+///
+/// `next_sample` and `load` are wait-free on the lock-free backends: one
+/// load and at most one CAS, no retry loop, no lock and no allocation. If the
+/// UI changes the target at the same instant, that sample keeps the current
+/// value and the ramp goes on from there next sample.
+#[derive(Debug)]
+pub struct ParamCell {
+    word: AtomicU128,
+    max_step: f64,
+}
+
+impl ParamCell {
+    pub fn new(value: f64, max_step: f64) -> Self {
+        assert!(max_step > 0.0, "max_step must be positive");
+        ParamCell { word: AtomicU128::from_halves(pack(value, value)), max_step }
+    }
+
+    /// Where the ramp is heading.
+    pub fn target(&self) -> f64 {
+        unpack(self.word.load_halves()).1
+    }
+
+    /// Moves the target; the current value ramps from wherever it is.
+    pub fn set_target(&self, target: f64) {
+        let mut word = self.word.load_halves();
+        loop {
+            let (current, _) = unpack(word);
+            match self.word.cas_halves(word, pack(current, target)) {
+                Ok(_) => return,
+                Err(actual) => word = actual,
+            }
+        }
+    }
+
+    /// Jumps straight to `value`, without a ramp.
+    pub fn set_immediate(&self, value: f64) {
+        self.word.store_halves(pack(value, value));
+    }
+
+    /// The current value, without advancing the ramp.
+    pub fn load(&self) -> f64 {
+        unpack(self.word.load_halves()).0
+    }
+
+    /// Advances the ramp by one sample and returns the value for it. Meant
+    /// for the one audio thread; several callers would each advance it.
+    pub fn next_sample(&self) -> f64 {
+        let word = self.word.load_halves();
+        let (current, target) = unpack(word);
+        if current == target {
+            return current;
+        }
+        let next = if target > current {
+            (current + self.max_step).min(target)
+        } else {
+            (current - self.max_step).max(target)
+        };
+        match self.word.cas_halves(word, pack(next, target)) {
+            Ok(_) => next,
+            Err(actual) => unpack(actual).0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ParamCell;
+
+    #[test]
+    fn test_ramp() {
+        let p = ParamCell::new(0.0, 0.25);
+        p.set_target(1.0);
+        assert_eq!(p.load(), 0.0);
+        let samples: Vec<f64> = (0..6).map(|_| p.next_sample()).collect();
+        assert_eq!(samples, vec![0.25, 0.5, 0.75, 1.0, 1.0, 1.0]);
+        p.set_target(0.6);
+        assert_eq!(p.next_sample(), 0.75);
+        assert_eq!(p.next_sample(), 0.6);
+        p.set_immediate(-2.0);
+        assert_eq!((p.load(), p.target(), p.next_sample()), (-2.0, -2.0, -2.0));
+    }
+}
@@ -0,0 +1,88 @@
+use halves::Halves;
+use AtomicU128;
+
+/// The best candidate offered so far: a priority and the task handle or
+/// index that goes with it.
+///
+/// Both halves change in one CAS, so a reader never pairs one offer's
+/// priority with another's handle, and of several concurrent offers the
+/// highest always wins. Priority 0 means nothing has been offered.
+#[derive(Debug, Default)]
+pub struct PrioritySlot {
+    // lo is the handle, hi the priority.
+    word: AtomicU128,
+}
+
+impl PrioritySlot {
+    pub fn new() -> Self {
+        PrioritySlot { word: AtomicU128::new(0) }
+    }
+
+    /// The current `(priority, handle)`.
+    pub fn load(&self) -> (u64, u64) {
+        let current = self.word.load_halves();
+        (current.hi, current.lo)
+    }
+
+    /// Installs `handle` if `priority` is higher than the current one.
+    /// Returns whether it was installed.
+    pub fn offer(&self, priority: u64, handle: u64) -> bool {
+        let mut current = self.word.load_halves();
+        while priority > current.hi {
+            match self.word.cas_halves(current, Halves::new(handle, priority)) {
+                Ok(_) => return true,
+                Err(actual) => current = actual,
+            }
+        }
+        false
+    }
+
+    /// Empties the slot and returns what it held, if anything.
+    pub fn take(&self) -> Option<(u64, u64)> {
+        let previous = self.word.swap_halves(Halves::new(0, 0));
+        if previous.hi == 0 { None } else { Some((previous.hi, previous.lo)) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::PrioritySlot;
+
+    #[test]
+    fn test_offer_take() {
+        let slot = PrioritySlot::new();
+        assert_eq!(slot.take(), None);
+        assert!(slot.offer(5, 50));
+        assert!(!slot.offer(5, 51));
+        assert!(!slot.offer(3, 30));
+        assert!(slot.offer(9, 90));
+        assert_eq!(slot.load(), (9, 90));
+        assert_eq!(slot.take(), Some((9, 90)));
+        assert_eq!(slot.load(), (0, 0));
+    }
+
+    #[test]
+    fn test_highest_offer_wins() {
+        let slot = Arc::new(PrioritySlot::new());
+        let handles: Vec<_> = (0..4u64)
+            .map(|t| {
+                let slot = slot.clone();
+                thread::spawn(move || {
+                    for i in 0..1000u64 {
+                        let priority = (i * 7919 + t) % 4000 + 1;
+                        slot.offer(priority, priority * 10);
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        let (priority, handle) = slot.load();
+        assert_eq!(handle, priority * 10);
+        assert_eq!(priority, 4000);
+    }
+}
@@ -0,0 +1,111 @@
+use std::convert::TryFrom;
+use std::ops::Range;
+
+use halves::Halves;
+use AtomicU128;
+
+/// A byte range of a mapped file or buffer.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Region {
+    pub offset: u64,
+    pub len: u64,
+}
+
+impl Region {
+    pub fn new(offset: u64, len: u64) -> Self {
+        Region { offset, len }
+    }
+
+    pub fn range(&self) -> Range<u64> {
+        self.offset..self.offset + self.len
+    }
+}
+
+fn to_halves(region: Region) -> Halves {
+    Halves::new(region.offset, region.len)
+}
+
+fn from_halves(word: Halves) -> Region {
+    Region { offset: word.lo, len: word.hi }
+}
+
+/// Which region of a mapping readers should use, repointed in one store.
+///
+/// Offset and length are one word, so a server that compacts a file and
+/// moves readers to the rewritten region never has one read the new offset
+/// with the old length. Keeping the old region mapped until its readers
+/// are done is up to the caller.
+#[derive(Debug, Default)]
+pub struct RegionRef {
+    // lo is the offset, hi the length.
+    word: AtomicU128,
+}
+
+impl RegionRef {
+    pub fn new(region: Region) -> Self {
+        RegionRef { word: AtomicU128::from_halves(to_halves(region)) }
+    }
+
+    pub fn load(&self) -> Region {
+        from_halves(self.word.load_halves())
+    }
+
+    /// Repoints readers at `region` and returns the one they were using.
+    pub fn replace(&self, region: Region) -> Region {
+        from_halves(self.word.swap_halves(to_halves(region)))
+    }
+
+    pub fn compare_exchange(&self, current: Region, new: Region) -> Result<Region, Region> {
+        self.word.cas_halves(to_halves(current), to_halves(new)).map(from_halves).map_err(from_halves)
+    }
+
+    /// The current region's bytes within `mapping`, or `None` if it doesn't
+    /// fit there, including when it lies past what `usize` can address.
+    pub fn slice<'a>(&self, mapping: &'a [u8]) -> Option<&'a [u8]> {
+        let region = self.load();
+        let start = usize::try_from(region.offset).ok()?;
+        let end = usize::try_from(region.offset.checked_add(region.len)?).ok()?;
+        mapping.get(start..end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::{Region, RegionRef};
+
+    #[test]
+    fn test_replace_and_slice() {
+        let mapping: Vec<u8> = (0..64).collect();
+        let r = RegionRef::new(Region::new(8, 4));
+        assert_eq!(r.slice(&mapping), Some(&[8, 9, 10, 11][..]));
+        assert_eq!(r.replace(Region::new(60, 8)), Region::new(8, 4));
+        assert_eq!(r.slice(&mapping), None);
+        r.replace(Region::new(u64::MAX, 2));
+        assert_eq!(r.slice(&mapping), None);
+        r.replace(Region::new(60, 8));
+        assert_eq!(r.compare_exchange(Region::new(8, 4), Region::default()), Err(Region::new(60, 8)));
+        assert_eq!(r.load().range(), 60..68);
+    }
+
+    #[test]
+    fn test_pairs_are_never_mixed() {
+        // Every region written has len == offset / 2.
+        let r = Arc::new(RegionRef::new(Region::new(0, 0)));
+        let compactor = {
+            let r = r.clone();
+            thread::spawn(move || {
+                for i in 1..5000u64 {
+                    r.replace(Region::new(i * 2, i));
+                }
+            })
+        };
+        for _ in 0..5000 {
+            let region = r.load();
+            assert_eq!(region.len, region.offset / 2);
+        }
+        compactor.join().unwrap();
+    }
+}
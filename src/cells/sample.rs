@@ -0,0 +1,88 @@
+use halves::Halves;
+use AtomicU128;
+
+/// A reading and when it was taken, in whatever monotonic units the
+/// producer uses.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Sample {
+    pub value: f64,
+    pub timestamp: u64,
+}
+
+fn to_halves(sample: Sample) -> Halves {
+    Halves::new(sample.value.to_bits(), sample.timestamp)
+}
+
+fn from_halves(word: Halves) -> Sample {
+    Sample { value: f64::from_bits(word.lo), timestamp: word.hi }
+}
+
+/// The latest sample from one producer, for any number of consumers.
+///
+/// Value and timestamp are one word, so a reader never pairs a value with
+/// another sample's timestamp. Both ends are a single access: the producer
+/// stores, readers load, and neither ever waits on the other.
+#[derive(Debug, Default)]
+pub struct SampleCell {
+    // lo is the value's bits, hi the timestamp.
+    word: AtomicU128,
+}
+
+impl SampleCell {
+    pub fn new(sample: Sample) -> Self {
+        SampleCell { word: AtomicU128::from_halves(to_halves(sample)) }
+    }
+
+    pub fn store(&self, sample: Sample) {
+        self.word.store_halves(to_halves(sample));
+    }
+
+    pub fn load(&self) -> Sample {
+        from_halves(self.word.load_halves())
+    }
+
+    /// The latest sample if it's newer than `last_seen`, so a poller handles
+    /// each sample once.
+    pub fn load_if_newer(&self, last_seen: u64) -> Option<Sample> {
+        let sample = self.load();
+        if sample.timestamp > last_seen { Some(sample) } else { None }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::{Sample, SampleCell};
+
+    #[test]
+    fn test_load_if_newer() {
+        let cell = SampleCell::default();
+        assert_eq!(cell.load_if_newer(0), None);
+        cell.store(Sample { value: 21.5, timestamp: 10 });
+        assert_eq!(cell.load_if_newer(0), Some(Sample { value: 21.5, timestamp: 10 }));
+        assert_eq!(cell.load_if_newer(10), None);
+    }
+
+    #[test]
+    fn test_samples_are_untorn() {
+        let cell = Arc::new(SampleCell::default());
+        let producer = {
+            let cell = cell.clone();
+            thread::spawn(move || {
+                for t in 1..=10_000u64 {
+                    cell.store(Sample { value: t as f64 * 0.5, timestamp: t });
+                }
+            })
+        };
+        let mut last = 0;
+        while last < 10_000 {
+            if let Some(sample) = cell.load_if_newer(last) {
+                assert_eq!(sample.value, sample.timestamp as f64 * 0.5);
+                last = sample.timestamp;
+            }
+        }
+        producer.join().unwrap();
+    }
+}
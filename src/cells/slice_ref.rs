@@ -0,0 +1,114 @@
+use std::fmt;
+use std::marker::PhantomData;
+use std::slice;
+
+use fmt_atomic;
+use halves::Halves;
+use AtomicU128;
+
+fn to_halves<T>(s: &[T]) -> Halves {
+    Halves::new(s.as_ptr() as u64, s.len() as u64)
+}
+
+unsafe fn from_halves<'a, T>(word: Halves) -> &'a [T] {
+    slice::from_raw_parts(word.lo as *const T, word.hi as usize)
+}
+
+/// A `&'static [T]` that can be repointed, for small lookup tables read on
+/// hot paths.
+///
+/// Pointer and length are one word, so `load` is a single 16-byte load and
+/// never pairs a new pointer with an old length, where an `ArcSwap<Vec<T>>`
+/// would bump a reference count on every read. Tables built at runtime come
+/// from `Box::leak`; a replaced one can only be freed once no reader can
+/// still hold it, for example after a `Qsbr` grace period.
+pub struct AtomicSliceRef<T: 'static> {
+    // lo is the data pointer, hi the length.
+    word: AtomicU128,
+    _marker: PhantomData<&'static [T]>,
+}
+
+unsafe impl<T: Sync> Send for AtomicSliceRef<T> {}
+unsafe impl<T: Sync> Sync for AtomicSliceRef<T> {}
+
+impl<T: 'static> AtomicSliceRef<T> {
+    pub fn new(s: &'static [T]) -> Self {
+        AtomicSliceRef { word: AtomicU128::from_halves(to_halves(s)), _marker: PhantomData }
+    }
+
+    pub fn load(&self) -> &'static [T] {
+        unsafe { from_halves(self.word.load_halves()) }
+    }
+
+    pub fn store(&self, s: &'static [T]) {
+        self.word.store_halves(to_halves(s));
+    }
+
+    /// Repoints readers at `s` and returns the slice they were using.
+    pub fn swap(&self, s: &'static [T]) -> &'static [T] {
+        unsafe { from_halves(self.word.swap_halves(to_halves(s))) }
+    }
+
+    /// Replaces `current` with `new` if it's still the exact slice stored,
+    /// same pointer and length.
+    pub fn compare_exchange(&self, current: &'static [T], new: &'static [T]) -> Result<&'static [T], &'static [T]> {
+        match self.word.cas_halves(to_halves(current), to_halves(new)) {
+            Ok(previous) => Ok(unsafe { from_halves(previous) }),
+            Err(actual) => Err(unsafe { from_halves(actual) }),
+        }
+    }
+}
+
+impl<T: fmt::Debug + 'static> fmt::Debug for AtomicSliceRef<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt_atomic(f, "AtomicSliceRef", &self.load())
+    }
+}
+
+impl<T: 'static> Default for AtomicSliceRef<T> {
+    fn default() -> Self {
+        AtomicSliceRef::new(&[])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::AtomicSliceRef;
+
+    static PRIMES: [u32; 4] = [2, 3, 5, 7];
+
+    #[test]
+    fn test_load_swap_cas() {
+        let table = AtomicSliceRef::new(&PRIMES[..]);
+        assert_eq!(table.load(), &[2, 3, 5, 7]);
+        let grown: &'static [u32] = Box::leak(vec![2, 3, 5, 7, 11].into_boxed_slice());
+        assert_eq!(table.swap(grown), &PRIMES[..]);
+        assert_eq!(table.compare_exchange(&PRIMES[..2], &[]).unwrap_err(), grown);
+        assert!(table.compare_exchange(grown, &PRIMES[1..]).is_ok());
+        assert_eq!(table.load(), &[3, 5, 7]);
+        assert!(AtomicSliceRef::<u8>::default().load().is_empty());
+    }
+
+    #[test]
+    fn test_pointer_and_len_stay_paired() {
+        // Table n holds n copies of n.
+        let tables: Vec<&'static [u64]> = (1..64u64).map(|n| &*Box::leak(vec![n; n as usize].into_boxed_slice())).collect();
+        let table = Arc::new(AtomicSliceRef::new(tables[0]));
+        let writer = {
+            let (table, tables) = (table.clone(), tables.clone());
+            thread::spawn(move || {
+                for i in 0..5000 {
+                    table.store(tables[i % tables.len()]);
+                }
+            })
+        };
+        for _ in 0..5000 {
+            let s = table.load();
+            assert!(s.iter().all(|&v| v == s.len() as u64));
+        }
+        writer.join().unwrap();
+    }
+}
@@ -0,0 +1,223 @@
+use std::fmt;
+use std::marker::PhantomData;
+
+use halves::Halves;
+use AtomicU128;
+
+/// A state type for `AtomicStateMachine`, with its table of legal moves.
+/// `state_machine!` writes the impl from the table.
+pub trait MachineState: Copy + Eq + fmt::Debug {
+    fn index(self) -> u64;
+    /// The state `index` returned the value for.
+    fn from_index(index: u64) -> Self;
+    fn allows(self, to: Self) -> bool;
+}
+
+/// Declares a state enum and the moves an `AtomicStateMachine` allows
+/// between its states:
+///
+/// ```
+/// # #[macro_use] extern crate atomic128;
+/// state_machine! {
+///     pub enum Conn {
+///         Idle => [Connecting],
+///         Connecting => [Open, Closed],
+///         Open => [Closed],
+///         Closed => [],
+///     }
+/// }
+/// # fn main() {}
+/// ```
+#[macro_export]
+macro_rules! state_machine {
+    ($(#[$meta:meta])* $vis:vis enum $name:ident { $($from:ident => [$($to:ident),*]),* $(,)? }) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        $vis enum $name { $($from),* }
+
+        impl $crate::cells::MachineState for $name {
+            fn index(self) -> u64 {
+                self as u64
+            }
+
+            fn from_index(index: u64) -> Self {
+                [$($name::$from),*][index as usize]
+            }
+
+            #[allow(unreachable_patterns)]
+            fn allows(self, to: Self) -> bool {
+                match self {
+                    $($name::$from => match to {
+                        $($name::$to => true,)*
+                        _ => false,
+                    },)*
+                }
+            }
+        }
+    };
+}
+
+/// A move the machine refused.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TransitionError<S> {
+    /// The machine wasn't in the state the move was from.
+    WrongState { expected: S, actual: S },
+    /// The table has no move between the two states.
+    Illegal { from: S, to: S },
+    /// `transition_with`'s closure declined to move from this state.
+    Declined(S),
+}
+
+impl<S: fmt::Debug> fmt::Display for TransitionError<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TransitionError::WrongState { expected, actual } => {
+                write!(f, "expected state {:?} but the machine is in {:?}", expected, actual)
+            }
+            TransitionError::Illegal { from, to } => write!(f, "no transition from {:?} to {:?}", from, to),
+            TransitionError::Declined(state) => write!(f, "declined to move from {:?}", state),
+        }
+    }
+}
+
+/// A lifecycle state and a 64-bit payload, such as a connection id or a
+/// retry count, that only ever move along the state table.
+///
+/// Every move is a CAS on the whole word that is checked against the table
+/// first, so two threads racing to move a connection never both win and a
+/// payload is never left behind from another state.
+#[derive(Debug)]
+pub struct AtomicStateMachine<S> {
+    // lo is the payload, hi the state's index.
+    word: AtomicU128,
+    _marker: PhantomData<S>,
+}
+
+impl<S: MachineState> AtomicStateMachine<S> {
+    pub fn new(state: S, payload: u64) -> Self {
+        AtomicStateMachine { word: AtomicU128::from_halves(Halves::new(payload, state.index())), _marker: PhantomData }
+    }
+
+    /// The `(state, payload)` as of one moment.
+    pub fn load(&self) -> (S, u64) {
+        unpack(self.word.load_halves())
+    }
+
+    pub fn state(&self) -> S {
+        self.load().0
+    }
+
+    /// Moves from `from` to `to`, keeping the payload, which is returned.
+    pub fn transition(&self, from: S, to: S) -> Result<u64, TransitionError<S>> {
+        self.transition_to(from, to, None)
+    }
+
+    /// Moves from `from` to `to` and replaces the payload, returning the
+    /// old one.
+    pub fn transition_with_payload(&self, from: S, to: S, payload: u64) -> Result<u64, TransitionError<S>> {
+        self.transition_to(from, to, Some(payload))
+    }
+
+    fn transition_to(&self, from: S, to: S, payload: Option<u64>) -> Result<u64, TransitionError<S>> {
+        if !from.allows(to) {
+            return Err(TransitionError::Illegal { from, to });
+        }
+        let mut current = self.word.load_halves();
+        loop {
+            let (actual, old) = unpack::<S>(current);
+            if actual != from {
+                return Err(TransitionError::WrongState { expected: from, actual });
+            }
+            match self.word.cas_halves(current, Halves::new(payload.unwrap_or(old), to.index())) {
+                Ok(_) => return Ok(old),
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    /// Moves to whatever `f` picks for the current state and payload, or
+    /// nowhere if it returns `None`. `f` may run several times under
+    /// contention. Returns the `(state, payload)` moved from.
+    pub fn transition_with<F>(&self, mut f: F) -> Result<(S, u64), TransitionError<S>>
+    where
+        F: FnMut(S, u64) -> Option<(S, u64)>,
+    {
+        let mut current = self.word.load_halves();
+        loop {
+            let (from, payload) = unpack::<S>(current);
+            let (to, new_payload) = match f(from, payload) {
+                Some(next) => next,
+                None => return Err(TransitionError::Declined(from)),
+            };
+            if !from.allows(to) {
+                return Err(TransitionError::Illegal { from, to });
+            }
+            match self.word.cas_halves(current, Halves::new(new_payload, to.index())) {
+                Ok(_) => return Ok((from, payload)),
+                Err(actual) => current = actual,
+            }
+        }
+    }
+}
+
+fn unpack<S: MachineState>(word: Halves) -> (S, u64) {
+    (S::from_index(word.hi), word.lo)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::{AtomicStateMachine, TransitionError};
+
+    state_machine! {
+        enum Conn {
+            Idle => [Connecting],
+            Connecting => [Open, Closed],
+            Open => [Closed],
+            Closed => [Idle],
+        }
+    }
+
+    #[test]
+    fn test_transitions() {
+        let conn = AtomicStateMachine::new(Conn::Idle, 0);
+        assert_eq!(conn.transition(Conn::Idle, Conn::Open), Err(TransitionError::Illegal { from: Conn::Idle, to: Conn::Open }));
+        assert_eq!(conn.transition_with_payload(Conn::Idle, Conn::Connecting, 7), Ok(0));
+        assert_eq!(
+            conn.transition(Conn::Open, Conn::Closed),
+            Err(TransitionError::WrongState { expected: Conn::Open, actual: Conn::Connecting })
+        );
+        assert_eq!(conn.transition(Conn::Connecting, Conn::Open), Ok(7));
+        assert_eq!(conn.transition_with(|_, _| None), Err(TransitionError::Declined(Conn::Open)));
+        assert_eq!(conn.transition_with(|s, n| Some((s, n))), Err(TransitionError::Illegal { from: Conn::Open, to: Conn::Open }));
+        assert_eq!(conn.transition_with(|_, n| Some((Conn::Closed, n + 1))), Ok((Conn::Open, 7)));
+        assert_eq!(conn.load(), (Conn::Closed, 8));
+        assert_eq!(
+            TransitionError::Illegal { from: Conn::Closed, to: Conn::Open }.to_string(),
+            "no transition from Closed to Open"
+        );
+    }
+
+    #[test]
+    fn test_one_winner() {
+        let conn = Arc::new(AtomicStateMachine::new(Conn::Connecting, 0));
+        let wins = Arc::new(AtomicUsize::new(0));
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let (conn, wins) = (conn.clone(), wins.clone());
+                thread::spawn(move || {
+                    if conn.transition(Conn::Connecting, Conn::Open).is_ok() {
+                        wins.fetch_add(1, Ordering::SeqCst);
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!((wins.load(Ordering::SeqCst), conn.state()), (1, Conn::Open));
+    }
+}
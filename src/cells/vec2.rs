@@ -0,0 +1,124 @@
+use halves::Halves;
+use AtomicU128;
+
+fn to_halves((x, y): (i64, i64)) -> Halves {
+    Halves::new(x as u64, y as u64)
+}
+
+fn from_halves(word: Halves) -> (i64, i64) {
+    (word.lo as i64, word.hi as i64)
+}
+
+/// A 2D position of two `i64` fixed-point coordinates, for simulation state
+/// that physics or network threads move while render threads read it.
+///
+/// Both coordinates are one word, so a reader never sees `x` from one update
+/// and `y` from another, and concurrent deltas all land. The scale is the
+/// caller's choice; the `_f64` helpers use 32 fractional bits.
+#[derive(Debug, Default)]
+pub struct AtomicVec2 {
+    // lo is x, hi is y.
+    word: AtomicU128,
+}
+
+impl AtomicVec2 {
+    /// Fractional bits the `_f64` helpers convert with.
+    pub const FRAC_BITS: u32 = 32;
+
+    pub fn new(position: (i64, i64)) -> Self {
+        AtomicVec2 { word: AtomicU128::from_halves(to_halves(position)) }
+    }
+
+    pub fn load(&self) -> (i64, i64) {
+        from_halves(self.word.load_halves())
+    }
+
+    pub fn store(&self, position: (i64, i64)) {
+        self.word.store_halves(to_halves(position));
+    }
+
+    pub fn swap(&self, position: (i64, i64)) -> (i64, i64) {
+        from_halves(self.word.swap_halves(to_halves(position)))
+    }
+
+    pub fn compare_exchange(&self, current: (i64, i64), new: (i64, i64)) -> Result<(i64, i64), (i64, i64)> {
+        self.word.cas_halves(to_halves(current), to_halves(new)).map(from_halves).map_err(from_halves)
+    }
+
+    /// Moves by `(dx, dy)`, wrapping on overflow, and returns the position
+    /// before the move.
+    pub fn fetch_add(&self, (dx, dy): (i64, i64)) -> (i64, i64) {
+        let mut current = self.word.load_halves();
+        loop {
+            let (x, y) = from_halves(current);
+            match self.word.cas_halves(current, to_halves((x.wrapping_add(dx), y.wrapping_add(dy)))) {
+                Ok(_) => return (x, y),
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    pub fn load_f64(&self) -> (f64, f64) {
+        let (x, y) = self.load();
+        (from_fixed(x), from_fixed(y))
+    }
+
+    pub fn fetch_add_f64(&self, (dx, dy): (f64, f64)) -> (f64, f64) {
+        let (x, y) = self.fetch_add((to_fixed(dx), to_fixed(dy)));
+        (from_fixed(x), from_fixed(y))
+    }
+}
+
+fn to_fixed(v: f64) -> i64 {
+    (v * (1u64 << AtomicVec2::FRAC_BITS) as f64) as i64
+}
+
+fn from_fixed(v: i64) -> f64 {
+    v as f64 / (1u64 << AtomicVec2::FRAC_BITS) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::AtomicVec2;
+
+    #[test]
+    fn test_vec2_ops() {
+        let p = AtomicVec2::new((-5, 7));
+        assert_eq!(p.fetch_add((10, -10)), (-5, 7));
+        assert_eq!(p.load(), (5, -3));
+        assert_eq!(p.compare_exchange((0, 0), (1, 1)), Err((5, -3)));
+        assert_eq!(p.swap((i64::MAX, 0)), (5, -3));
+        p.fetch_add((1, 0));
+        assert_eq!(p.load(), (i64::MIN, 0));
+
+        let p = AtomicVec2::default();
+        p.fetch_add_f64((1.5, -0.25));
+        assert_eq!(p.load_f64(), (1.5, -0.25));
+    }
+
+    #[test]
+    fn test_reads_are_untorn() {
+        let p = Arc::new(AtomicVec2::default());
+        let movers: Vec<_> = (0..2)
+            .map(|_| {
+                let p = p.clone();
+                thread::spawn(move || {
+                    for _ in 0..1000 {
+                        p.fetch_add((1, -1));
+                    }
+                })
+            })
+            .collect();
+        for _ in 0..1000 {
+            let (x, y) = p.load();
+            assert_eq!(x, -y);
+        }
+        for mover in movers {
+            mover.join().unwrap();
+        }
+        assert_eq!(p.load(), (2000, -2000));
+    }
+}
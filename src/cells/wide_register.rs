@@ -0,0 +1,150 @@
+use std::cell::UnsafeCell;
+use std::sync::atomic::{AtomicBool, AtomicIsize, Ordering};
+use std::thread;
+
+use halves::Halves;
+use AtomicU128;
+
+const READER: u128 = 1 << 64;
+
+/// A single-writer register for values of any size, kept in two slots the
+/// writer alternates between.
+///
+/// The word holds the version, whose low bit names the slot readers should
+/// use, next to the number of readers that have pinned that version. A
+/// reader pins the current slot and learns which it is in one `fetch_add`,
+/// and leaves with a plain `fetch_add` on that slot's departure count. So a
+/// read never waits for the writer and never sees a slot being written,
+/// where a seqlock reader of a large value can be sent back indefinitely.
+/// It is lock-free rather than wait-free: the 128-bit `fetch_add` is a CAS
+/// underneath, which retries when another reader pins at the same moment.
+/// The waiting is all the writer's: publishing takes the old version's
+/// arrivals off its slot's count, and the next write into that slot waits
+/// for the count to come back to zero.
+pub struct WideRegister<T> {
+    // lo is the version, hi the readers that have pinned it.
+    word: AtomicU128,
+    // Per slot, readers that have left minus the arrivals the writer has
+    // taken off; above zero while the writer has yet to take them off.
+    departed: [AtomicIsize; 2],
+    writer: AtomicBool,
+    slots: [UnsafeCell<Option<T>>; 2],
+}
+
+unsafe impl<T: Send + Sync> Send for WideRegister<T> {}
+unsafe impl<T: Send + Sync> Sync for WideRegister<T> {}
+
+/// The register's one writer; gives the role back on drop.
+pub struct WideWriter<'a, T: 'a> {
+    register: &'a WideRegister<T>,
+}
+
+impl<T: Clone> WideRegister<T> {
+    pub fn new(value: T) -> Self {
+        WideRegister {
+            word: AtomicU128::new(0),
+            departed: [AtomicIsize::new(0), AtomicIsize::new(0)],
+            writer: AtomicBool::new(false),
+            slots: [UnsafeCell::new(Some(value)), UnsafeCell::new(None)],
+        }
+    }
+
+    /// A copy of the latest value written.
+    pub fn read(&self) -> T {
+        let pinned = Halves::from_bits(self.word.fetch_add(READER, Ordering::SeqCst));
+        let value = unsafe { (*self.slots[(pinned.lo & 1) as usize].get()).clone() };
+        self.unpin(pinned.lo);
+        value.expect("current slot is always written")
+    }
+
+    /// How many values have been written since `new`.
+    pub fn version(&self) -> u64 {
+        self.word.load_halves().lo
+    }
+
+    /// Takes the writer role, unless another `WideWriter` has it.
+    pub fn writer(&self) -> Option<WideWriter<'_, T>> {
+        if self.writer.compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed).is_ok() {
+            Some(WideWriter { register: self })
+        } else {
+            None
+        }
+    }
+
+    fn unpin(&self, version: u64) {
+        self.departed[(version & 1) as usize].fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+impl<'a, T: Clone> WideWriter<'a, T> {
+    /// Writes `value` into the spare slot and points readers at it. Waits
+    /// first for readers still copying out of that slot from two writes ago.
+    pub fn write(&mut self, value: T) {
+        let register = self.register;
+        let version = register.word.load_halves().lo;
+        let spare = (version.wrapping_add(1) & 1) as usize;
+        while register.departed[spare].load(Ordering::SeqCst) != 0 {
+            thread::yield_now();
+        }
+        unsafe { *register.slots[spare].get() = Some(value) };
+        // Only readers change the word while the writer holds the role, and
+        // only by adding to hi, so the swap can't lose a version.
+        let pinned = register.word.swap_halves(Halves::new(version.wrapping_add(1), 0));
+        register.departed[(version & 1) as usize].fetch_sub(pinned.hi as isize, Ordering::SeqCst);
+    }
+}
+
+impl<'a, T> Drop for WideWriter<'a, T> {
+    fn drop(&mut self) {
+        self.register.writer.store(false, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::WideRegister;
+
+    #[test]
+    fn test_read_write() {
+        let register = WideRegister::new(vec![0u8; 3]);
+        let mut writer = register.writer().unwrap();
+        assert!(register.writer().is_none());
+        writer.write(vec![1; 40]);
+        writer.write(vec![2; 40]);
+        assert_eq!((register.read(), register.version()), (vec![2; 40], 2));
+        drop(writer);
+        register.writer().unwrap().write(vec![3]);
+        assert_eq!(register.read(), vec![3]);
+    }
+
+    #[test]
+    fn test_readers_never_see_a_partial_write() {
+        // Every value written is 64 copies of one number.
+        let register = Arc::new(WideRegister::new([0u64; 64]));
+        let stop = Arc::new(AtomicBool::new(false));
+        let readers: Vec<_> = (0..3)
+            .map(|_| {
+                let (register, stop) = (register.clone(), stop.clone());
+                thread::spawn(move || {
+                    while !stop.load(Ordering::SeqCst) {
+                        let value = register.read();
+                        assert!(value.iter().all(|&x| x == value[0]));
+                    }
+                })
+            })
+            .collect();
+        let mut writer = register.writer().unwrap();
+        for i in 1..2000 {
+            writer.write([i; 64]);
+        }
+        stop.store(true, Ordering::SeqCst);
+        for reader in readers {
+            reader.join().unwrap();
+        }
+        assert_eq!(register.read()[63], 1999);
+    }
+}
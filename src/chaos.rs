@@ -0,0 +1,94 @@
+//! Spurious CAS failures for testing retry loops, behind the `chaos` feature.
+//!
+//! On x86 `compare_exchange_weak` never fails spuriously, so a loop that
+//! mishandles a spurious failure passes every test here and then breaks on an
+//! LL/SC machine. With this feature the weak CAS fails (returning the current
+//! value without writing) at a configurable rate. The strong CAS can be made
+//! to fail too, which breaks its guarantee on purpose: only turn that on to
+//! test code that must cope with any failed CAS.
+//!
+//! Rates are in failures per million calls. The weak rate starts at one in
+//! eight, the strong rate at zero. Each thread draws from its own fixed-seed
+//! generator, so a single-threaded run fails at the same calls every time.
+
+use std::cell::Cell;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+const MILLION: u32 = 1_000_000;
+
+static WEAK_RATE: AtomicU32 = AtomicU32::new(MILLION / 8);
+static STRONG_RATE: AtomicU32 = AtomicU32::new(0);
+
+thread_local!(static RNG: Cell<u64> = const { Cell::new(0x9e37_79b9_7f4a_7c15) });
+
+pub fn set_weak_failure_rate(per_million: u32) {
+    WEAK_RATE.store(per_million.min(MILLION), Ordering::Relaxed);
+}
+
+pub fn set_strong_failure_rate(per_million: u32) {
+    STRONG_RATE.store(per_million.min(MILLION), Ordering::Relaxed);
+}
+
+pub fn weak_failure_rate() -> u32 {
+    WEAK_RATE.load(Ordering::Relaxed)
+}
+
+pub fn strong_failure_rate() -> u32 {
+    STRONG_RATE.load(Ordering::Relaxed)
+}
+
+fn roll(rate: u32) -> bool {
+    if rate == 0 {
+        return false;
+    }
+    RNG.with(|rng| {
+        let mut x = rng.get();
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        rng.set(x);
+        (x % MILLION as u64) < rate as u64
+    })
+}
+
+pub(crate) fn fail_weak() -> bool {
+    roll(weak_failure_rate())
+}
+
+pub(crate) fn fail_strong() -> bool {
+    roll(strong_failure_rate())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering::SeqCst;
+
+    use super::{roll, MILLION};
+    use AtomicU128;
+
+    #[test]
+    fn test_roll_rates() {
+        assert!(!(0..1000).any(|_| roll(0)));
+        assert!((0..1000).all(|_| roll(MILLION)));
+        let hits = (0..10_000).filter(|_| roll(MILLION / 8)).count();
+        assert!(hits > 1000 && hits < 1500);
+    }
+
+    #[test]
+    fn test_weak_cas_loop_still_succeeds() {
+        let a = AtomicU128::new(0);
+        let mut current = a.load(SeqCst);
+        let mut failures = 0;
+        loop {
+            match a.compare_exchange_weak(current, 1 << 64 | 1, SeqCst, SeqCst) {
+                Ok(_) => break,
+                Err(actual) => {
+                    current = actual;
+                    failures += 1;
+                }
+            }
+        }
+        assert_eq!(a.load(SeqCst), 1 << 64 | 1);
+        assert!(failures < 100);
+    }
+}
@@ -0,0 +1,201 @@
+use std::marker::PhantomData;
+use std::ptr;
+use std::sync::atomic::{AtomicPtr, Ordering};
+use std::sync::Mutex;
+
+use current_backoff;
+use halves::Halves;
+use AtomicU128;
+
+// Set in the header's pointer while the buffer is being replaced.
+const RESIZING: u64 = 1;
+
+struct Buffer<T> {
+    // Null until the value pushed at that index has been written.
+    slots: Box<[AtomicPtr<T>]>,
+}
+
+impl<T> Buffer<T> {
+    fn alloc(capacity: usize) -> *mut Buffer<T> {
+        let slots = (0..capacity).map(|_| AtomicPtr::new(ptr::null_mut())).collect();
+        Box::into_raw(Box::new(Buffer { slots }))
+    }
+}
+
+fn buffer<'a, T>(header: Halves) -> &'a Buffer<T> {
+    unsafe { &*((header.hi & !RESIZING) as *const Buffer<T>) }
+}
+
+/// A vector that any number of threads push to and read from concurrently,
+/// never removing anything.
+///
+/// The length and buffer pointer are one header word, so a reader never
+/// pairs a length with a buffer too small for it, and `push` reserves its
+/// index with the same CAS that checks no reallocation is under way. Values
+/// are boxed so growing only copies pointers and references handed out by
+/// `get` stay valid; outgrown buffers are kept until the vector drops.
+pub struct AppendVec<T> {
+    // lo is the length reserved so far, hi the buffer pointer, with
+    // RESIZING set while it's changing.
+    header: AtomicU128,
+    // Outgrown buffers; readers may still be looking at them.
+    retired: Mutex<Vec<*mut Buffer<T>>>,
+    _marker: PhantomData<Box<T>>,
+}
+
+unsafe impl<T: Send + Sync> Send for AppendVec<T> {}
+unsafe impl<T: Send + Sync> Sync for AppendVec<T> {}
+
+impl<T> AppendVec<T> {
+    pub fn new() -> Self {
+        Self::with_capacity(16)
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        let buffer = Buffer::<T>::alloc(capacity.max(1));
+        AppendVec {
+            header: AtomicU128::from_halves(Halves::new(0, buffer as u64)),
+            retired: Mutex::new(Vec::new()),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Values pushed or being pushed.
+    pub fn len(&self) -> usize {
+        self.header.load_halves().lo as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        buffer::<T>(self.header.load_halves()).slots.len()
+    }
+
+    /// Appends `value` and returns its index.
+    pub fn push(&self, value: T) -> usize {
+        let value = Box::into_raw(Box::new(value));
+        let mut current = self.header.load_halves();
+        let mut attempt = 0;
+        loop {
+            if current.hi & RESIZING != 0 {
+                attempt += 1;
+                current_backoff().wait(attempt);
+                current = self.header.load_halves();
+                continue;
+            }
+            let buffer = buffer::<T>(current);
+            if current.lo as usize == buffer.slots.len() {
+                if self.header.cas_halves(current, Halves::new(current.lo, current.hi | RESIZING)).is_ok() {
+                    self.grow(current);
+                }
+                current = self.header.load_halves();
+                continue;
+            }
+            match self.header.cas_halves(current, Halves::new(current.lo + 1, current.hi)) {
+                Ok(_) => {
+                    // Growing waits for this, so the buffer is still current.
+                    buffer.slots[current.lo as usize].store(value, Ordering::Release);
+                    return current.lo as usize;
+                }
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    // Called with RESIZING set on `old`, so nothing new gets reserved.
+    fn grow(&self, old: Halves) {
+        let len = old.lo as usize;
+        let from = buffer::<T>(old);
+        let to = Buffer::<T>::alloc(len * 2);
+        for (i, slot) in from.slots.iter().enumerate() {
+            let mut attempt = 0;
+            let mut value = slot.load(Ordering::Acquire);
+            while value.is_null() {
+                attempt += 1;
+                current_backoff().wait(attempt);
+                value = slot.load(Ordering::Acquire);
+            }
+            unsafe { (*to).slots[i].store(value, Ordering::Relaxed) };
+        }
+        self.header.store_halves(Halves::new(old.lo, to as u64));
+        self.retired.lock().unwrap().push(old.hi as *mut Buffer<T>);
+    }
+
+    /// The value at `index`, if it's been pushed and written.
+    pub fn get(&self, index: usize) -> Option<&T> {
+        let header = self.header.load_halves();
+        if index >= header.lo as usize {
+            return None;
+        }
+        let value = buffer::<T>(header).slots[index].load(Ordering::Acquire);
+        if value.is_null() { None } else { Some(unsafe { &*value }) }
+    }
+}
+
+impl<T> Default for AppendVec<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for AppendVec<T> {
+    fn drop(&mut self) {
+        let header = self.header.load_halves();
+        let buffer = unsafe { Box::from_raw(header.hi as *mut Buffer<T>) };
+        for slot in &buffer.slots[..header.lo as usize] {
+            drop(unsafe { Box::from_raw(slot.load(Ordering::Relaxed)) });
+        }
+        for &old in self.retired.get_mut().unwrap().iter() {
+            drop(unsafe { Box::from_raw(old) });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::AppendVec;
+
+    #[test]
+    fn test_push_get_grow() {
+        let v = AppendVec::with_capacity(2);
+        assert_eq!(v.get(0), None);
+        v.push("a".to_string());
+        let first = v.get(0).unwrap() as *const String;
+        for i in 1..10 {
+            assert_eq!(v.push(i.to_string()), i);
+        }
+        assert_eq!((v.len(), v.capacity()), (10, 16));
+        // Growing moved pointers, not values.
+        assert_eq!(v.get(0).unwrap() as *const String, first);
+        assert_eq!(v.get(9).map(|s| s.as_str()), Some("9"));
+        assert_eq!(v.get(10), None);
+    }
+
+    #[test]
+    fn test_concurrent_push() {
+        let v = Arc::new(AppendVec::with_capacity(1));
+        let handles: Vec<_> = (0..4u64)
+            .map(|t| {
+                let v = v.clone();
+                thread::spawn(move || {
+                    for i in 0..1000u64 {
+                        let index = v.push(t << 32 | i);
+                        assert_eq!(v.get(index), Some(&(t << 32 | i)));
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        let mut all: Vec<u64> = (0..v.len()).map(|i| *v.get(i).unwrap()).collect();
+        all.sort();
+        let expected: Vec<u64> = (0..4u64).flat_map(|t| (0..1000).map(move |i| t << 32 | i)).collect();
+        assert_eq!(all, expected);
+    }
+}
@@ -0,0 +1,145 @@
+use std::fmt;
+use std::slice;
+use std::sync::atomic::Ordering::SeqCst;
+
+use fmt_atomic;
+use snapshot::{snapshot, try_snapshot};
+use AtomicU128;
+
+/// A fixed-length table of words with per-index operations.
+///
+/// Each index is an independent word; the only operations that look at
+/// several at once are the snapshots, which double-collect like
+/// `atomic128::snapshot` and share its ABA caveat.
+pub struct AtomicArray128 {
+    cells: Box<[AtomicU128]>,
+}
+
+impl AtomicArray128 {
+    /// `len` zeroed words.
+    pub fn new(len: usize) -> Self {
+        AtomicArray128 { cells: (0..len).map(|_| AtomicU128::new(0)).collect() }
+    }
+
+    pub fn from_vec(values: Vec<u128>) -> Self {
+        AtomicArray128 { cells: values.into_iter().map(AtomicU128::new).collect() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&AtomicU128> {
+        self.cells.get(index)
+    }
+
+    pub fn load(&self, index: usize) -> u128 {
+        self.cells[index].load(SeqCst)
+    }
+
+    pub fn store(&self, index: usize, val: u128) {
+        self.cells[index].store(val, SeqCst)
+    }
+
+    pub fn swap(&self, index: usize, val: u128) -> u128 {
+        self.cells[index].swap(val, SeqCst)
+    }
+
+    pub fn compare_exchange(&self, index: usize, current: u128, new: u128) -> Result<u128, u128> {
+        self.cells[index].compare_exchange(current, new, SeqCst, SeqCst)
+    }
+
+    /// Stores `val` into every word, one at a time.
+    pub fn fill(&self, val: u128) {
+        for cell in self.iter() {
+            cell.store(val, SeqCst);
+        }
+    }
+
+    /// Every word as one consistent cut.
+    pub fn snapshot(&self) -> Vec<u128> {
+        snapshot(&self.refs())
+    }
+
+    /// `snapshot`, giving up after `attempts` double collects that disagreed.
+    pub fn try_snapshot(&self, attempts: usize) -> Option<Vec<u128>> {
+        try_snapshot(&self.refs(), attempts)
+    }
+
+    fn refs(&self) -> Vec<&AtomicU128> {
+        self.cells.iter().collect()
+    }
+
+    pub fn iter(&self) -> slice::Iter<'_, AtomicU128> {
+        self.cells.iter()
+    }
+
+    /// Loads each word in turn. Unlike `snapshot`, the values needn't have
+    /// held at the same time.
+    pub fn values(&self) -> Values<'_> {
+        Values { cells: self.cells.iter() }
+    }
+}
+
+impl<'a> IntoIterator for &'a AtomicArray128 {
+    type Item = &'a AtomicU128;
+    type IntoIter = slice::Iter<'a, AtomicU128>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl fmt::Debug for AtomicArray128 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt_atomic(f, "AtomicArray128", &self.values().collect::<Vec<_>>())
+    }
+}
+
+/// Iterator returned by `AtomicArray128::values`.
+pub struct Values<'a> {
+    cells: slice::Iter<'a, AtomicU128>,
+}
+
+impl<'a> Iterator for Values<'a> {
+    type Item = u128;
+
+    fn next(&mut self) -> Option<u128> {
+        self.cells.next().map(|cell| cell.load(SeqCst))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.cells.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AtomicArray128;
+
+    #[test]
+    fn test_indexed_ops() {
+        let array = AtomicArray128::new(4);
+        assert_eq!(array.len(), 4);
+        array.store(1, 1 << 64 | 1);
+        assert_eq!(array.swap(1, 2 << 64 | 2), 1 << 64 | 1);
+        assert_eq!(array.compare_exchange(1, 2 << 64 | 2, 3 << 64 | 3), Ok(2 << 64 | 2));
+        assert_eq!(array.compare_exchange(0, 2 << 64 | 2, 3 << 64 | 3), Err(0));
+        assert_eq!(array.load(1), 3 << 64 | 3);
+        assert!(array.get(4).is_none());
+    }
+
+    #[test]
+    fn test_fill_and_snapshot() {
+        let array = AtomicArray128::from_vec(vec![1, 2]);
+        assert_eq!(array.snapshot(), vec![1, 2]);
+        array.fill(7 << 64 | 7);
+        assert!(array.values().all(|v| v == 7 << 64 | 7));
+        assert_eq!(array.try_snapshot(1), Some(vec![7 << 64 | 7; 2]));
+        assert_eq!((&array).into_iter().count(), 2);
+    }
+}
@@ -0,0 +1,132 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use halves::Halves;
+use AtomicU128;
+
+fn with_bit(word: Halves, bit: usize, set: bool) -> Halves {
+    let (mut lo, mut hi) = (word.lo, word.hi);
+    {
+        let half = if bit < 64 { &mut lo } else { &mut hi };
+        let mask = 1u64 << (bit % 64);
+        if set { *half |= mask } else { *half &= !mask }
+    }
+    Halves::new(lo, hi)
+}
+
+fn test_bit(word: Halves, bit: usize) -> bool {
+    let half = if bit < 64 { word.lo } else { word.hi };
+    half & (1 << (bit % 64)) != 0
+}
+
+fn first_zero(word: Halves) -> Option<usize> {
+    if word.lo != !0 {
+        Some((!word.lo).trailing_zeros() as usize)
+    } else if word.hi != !0 {
+        Some(64 + (!word.hi).trailing_zeros() as usize)
+    } else {
+        None
+    }
+}
+
+/// Lock-free bit allocator over an array of `AtomicU128` words.
+///
+/// A free bit is claimed with one CAS on the word containing it. The word that
+/// satisfied the last claim is remembered as a hint, so a mostly-full bitmap
+/// isn't rescanned from the start on every call.
+pub struct Bitmap {
+    words: Vec<AtomicU128>,
+    bits: usize,
+    hint: AtomicUsize,
+}
+
+impl Bitmap {
+    pub fn new(bits: usize) -> Self {
+        let count = bits.div_ceil(128);
+        let words = (0..count).map(|i| {
+            // Bits past the end start out claimed, so they're never handed out.
+            let mut word = Halves::default();
+            for bit in 0..128 {
+                if i * 128 + bit >= bits {
+                    word = with_bit(word, bit, true);
+                }
+            }
+            AtomicU128::from_halves(word)
+        }).collect();
+        Bitmap { words, bits, hint: AtomicUsize::new(0) }
+    }
+
+    pub fn len(&self) -> usize {
+        self.bits
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bits == 0
+    }
+
+    pub fn is_set(&self, index: usize) -> bool {
+        assert!(index < self.bits, "bit index out of range");
+        test_bit(self.words[index / 128].load_halves(), index % 128)
+    }
+
+    /// Atomically claims a clear bit and returns its index.
+    pub fn claim_first_zero(&self) -> Option<usize> {
+        let count = self.words.len();
+        let start = self.hint.load(Ordering::Relaxed);
+        for offset in 0..count {
+            let i = (start + offset) % count;
+            let mut current = self.words[i].load_halves();
+            while let Some(bit) = first_zero(current) {
+                match self.words[i].cas_halves(current, with_bit(current, bit, true)) {
+                    Ok(_) => {
+                        self.hint.store(i, Ordering::Relaxed);
+                        return Some(i * 128 + bit);
+                    }
+                    Err(actual) => current = actual,
+                }
+            }
+        }
+        None
+    }
+
+    pub fn release(&self, index: usize) {
+        assert!(index < self.bits, "bit index out of range");
+        let word = &self.words[index / 128];
+        let bit = index % 128;
+        let mut current = word.load_halves();
+        loop {
+            debug_assert!(test_bit(current, bit), "releasing a bit that isn't claimed");
+            match word.cas_halves(current, with_bit(current, bit, false)) {
+                Ok(_) => return,
+                Err(actual) => current = actual,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Bitmap;
+
+    #[test]
+    fn test_claim_release() {
+        let map = Bitmap::new(130);
+        assert_eq!(map.len(), 130);
+        for i in 0..130 {
+            assert_eq!(map.claim_first_zero(), Some(i));
+        }
+        assert_eq!(map.claim_first_zero(), None);
+        map.release(64);
+        map.release(129);
+        assert!(!map.is_set(64));
+        assert_eq!(map.claim_first_zero(), Some(129));
+        assert_eq!(map.claim_first_zero(), Some(64));
+        assert!(map.is_set(64));
+    }
+
+    #[test]
+    fn test_empty() {
+        let map = Bitmap::new(0);
+        assert!(map.is_empty());
+        assert_eq!(map.claim_first_zero(), None);
+    }
+}
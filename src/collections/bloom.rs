@@ -0,0 +1,91 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use AtomicBitmap128;
+
+const MAX_HASHES: u32 = 9;
+
+/// Concurrent blocked Bloom filter.
+///
+/// All bits for a key land in a single 128-bit block, so an insert is one
+/// `fetch_or` and a query is one load, and both touch one cache line.
+pub struct BloomFilter {
+    blocks: Vec<AtomicBitmap128>,
+    hashes: u32,
+}
+
+impl BloomFilter {
+    /// `hashes` is the number of bits set per key, at most 9.
+    pub fn new(blocks: usize, hashes: u32) -> Self {
+        assert!(blocks > 0, "need at least one block");
+        assert!(hashes > 0 && hashes <= MAX_HASHES, "hashes must be in 1..=9");
+        BloomFilter {
+            blocks: (0..blocks).map(|_| AtomicBitmap128::default()).collect(),
+            hashes,
+        }
+    }
+
+    pub fn blocks(&self) -> usize {
+        self.blocks.len()
+    }
+
+    fn locate<T: Hash + ?Sized>(&self, key: &T) -> (&AtomicBitmap128, u128) {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        let h = hasher.finish();
+        let block = &self.blocks[((h >> 32) % self.blocks.len() as u64) as usize];
+        // Bit positions come from 7-bit windows of a remixed hash.
+        let bits = h.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+        let mask = (0..self.hashes).fold(0u128, |mask, i| mask | 1 << ((bits >> (7 * i)) & 127));
+        (block, mask)
+    }
+
+    /// Adds `key` and returns whether it may have been present already.
+    pub fn insert<T: Hash + ?Sized>(&self, key: &T) -> bool {
+        let (block, mask) = self.locate(key);
+        block.fetch_or(mask) & mask == mask
+    }
+
+    pub fn contains<T: Hash + ?Sized>(&self, key: &T) -> bool {
+        let (block, mask) = self.locate(key);
+        block.load() & mask == mask
+    }
+
+    /// Not atomic as a whole: concurrent inserts may survive the clear.
+    pub fn clear(&self) {
+        for block in &self.blocks {
+            block.store(0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BloomFilter;
+
+    #[test]
+    fn test_insert_contains() {
+        let filter = BloomFilter::new(64, 4);
+        assert!(!filter.contains(&0u32));
+        for i in 0..100u32 {
+            filter.insert(&i);
+        }
+        for i in 0..100u32 {
+            assert!(filter.contains(&i));
+        }
+        assert!(filter.insert(&5u32));
+        assert_eq!(filter.blocks(), 64);
+        filter.clear();
+        assert!(!filter.contains(&5u32));
+    }
+
+    #[test]
+    fn test_false_positive_rate() {
+        let filter = BloomFilter::new(1024, 6);
+        for i in 0..2000u64 {
+            filter.insert(&i);
+        }
+        let hits = (2000..12000u64).filter(|i| filter.contains(i)).count();
+        assert!(hits < 500, "{} false positives", hits);
+    }
+}
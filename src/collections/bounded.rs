@@ -0,0 +1,232 @@
+use std::cell::UnsafeCell;
+use std::mem::MaybeUninit;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use current_backoff;
+use halves::Halves;
+use AtomicU128;
+
+// Slot states, in the word's lo half.
+const IDLE: u64 = 0;
+const BUSY: u64 = 1;
+
+/// Fixed-capacity MPMC queue (Vyukov-style).
+///
+/// Each slot's word holds its sequence next to whether a thread is moving a
+/// value in or out, claimed together in one CAS. The value sits beside the
+/// word rather than in it: a `T` of any size only fits the word's other
+/// half boxed, and this way nothing is allocated per item. A producer
+/// claims a slot, moves the shared position on and only then writes the
+/// value; a thread that finds the position behind a slot someone else
+/// claimed moves it on for them. So a producer stalled mid-write never
+/// holds up other producers, but consumers that reach its slot wait for it
+/// (`try_pop` says the queue is empty), and a stalled consumer likewise
+/// holds up producers a lap behind it.
+pub struct BoundedQueue<T> {
+    slots: Vec<Slot<T>>,
+    // Positions count every push and pop, as wide as the slot sequences
+    // they are compared with.
+    enqueue_pos: AtomicU64,
+    dequeue_pos: AtomicU64,
+}
+
+struct Slot<T> {
+    // lo is IDLE or BUSY, hi the sequence.
+    word: AtomicU128,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+unsafe impl<T: Send> Send for BoundedQueue<T> {}
+unsafe impl<T: Send> Sync for BoundedQueue<T> {}
+
+impl<T> BoundedQueue<T> {
+    pub fn with_capacity(capacity: usize) -> Self {
+        assert!(capacity >= 2, "capacity must be at least 2");
+        BoundedQueue {
+            slots: (0..capacity)
+                .map(|i| Slot { word: AtomicU128::from_halves(Halves::new(IDLE, i as u64)), value: UnsafeCell::new(MaybeUninit::uninit()) })
+                .collect(),
+            enqueue_pos: AtomicU64::new(0),
+            dequeue_pos: AtomicU64::new(0),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Number of items, only exact when there are no concurrent operations.
+    pub fn len(&self) -> usize {
+        let dequeued = self.dequeue_pos.load(Ordering::SeqCst);
+        let enqueued = self.enqueue_pos.load(Ordering::SeqCst);
+        (enqueued.wrapping_sub(dequeued) as usize).min(self.capacity())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len() == self.capacity()
+    }
+
+    fn slot(&self, pos: u64) -> &Slot<T> {
+        &self.slots[(pos % self.slots.len() as u64) as usize]
+    }
+
+    fn advance(position: &AtomicU64, pos: u64) {
+        let _ = position.compare_exchange(pos, pos.wrapping_add(1), Ordering::SeqCst, Ordering::SeqCst);
+    }
+
+    pub fn try_push(&self, value: T) -> Result<(), T> {
+        loop {
+            let pos = self.enqueue_pos.load(Ordering::SeqCst);
+            let slot = self.slot(pos);
+            let current = slot.word.load_halves();
+            let diff = current.hi.wrapping_sub(pos) as i64;
+            if diff == 0 && current.lo == IDLE {
+                if slot.word.cas_halves(current, Halves::new(BUSY, pos)).is_ok() {
+                    Self::advance(&self.enqueue_pos, pos);
+                    unsafe { (*slot.value.get()).as_mut_ptr().write(value) };
+                    slot.word.store_halves(Halves::new(IDLE, pos.wrapping_add(1)));
+                    return Ok(());
+                }
+            } else if diff >= 0 {
+                // Another producer has claimed this position, or finished
+                // with it, and hasn't moved it on yet.
+                Self::advance(&self.enqueue_pos, pos);
+            } else {
+                return Err(value);
+            }
+        }
+    }
+
+    pub fn try_pop(&self) -> Option<T> {
+        loop {
+            let pos = self.dequeue_pos.load(Ordering::SeqCst);
+            let slot = self.slot(pos);
+            let current = slot.word.load_halves();
+            let diff = current.hi.wrapping_sub(pos.wrapping_add(1)) as i64;
+            if diff == 0 && current.lo == IDLE {
+                if slot.word.cas_halves(current, Halves::new(BUSY, current.hi)).is_ok() {
+                    Self::advance(&self.dequeue_pos, pos);
+                    let value = unsafe { (*slot.value.get()).as_ptr().read() };
+                    slot.word.store_halves(Halves::new(IDLE, pos.wrapping_add(self.capacity() as u64)));
+                    return Some(value);
+                }
+            } else if diff >= 0 {
+                Self::advance(&self.dequeue_pos, pos);
+            } else {
+                return None;
+            }
+        }
+    }
+
+    /// Pushes `value`, waiting with the global backoff policy while the
+    /// queue is full.
+    pub fn push(&self, value: T) {
+        let mut value = value;
+        let mut attempt = 0;
+        loop {
+            match self.try_push(value) {
+                Ok(()) => return,
+                Err(v) => value = v,
+            }
+            attempt += 1;
+            current_backoff().wait(attempt);
+        }
+    }
+
+    /// Pops a value, waiting with the global backoff policy while the queue
+    /// is empty.
+    pub fn pop(&self) -> T {
+        let mut attempt = 0;
+        loop {
+            if let Some(value) = self.try_pop() {
+                return value;
+            }
+            attempt += 1;
+            current_backoff().wait(attempt);
+        }
+    }
+}
+
+impl<T> Drop for BoundedQueue<T> {
+    fn drop(&mut self) {
+        while self.try_pop().is_some() {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::BoundedQueue;
+
+    #[test]
+    fn test_push_pop() {
+        let q = BoundedQueue::with_capacity(3);
+        assert_eq!(q.capacity(), 3);
+        assert!(q.is_empty());
+        assert_eq!(q.try_push(1), Ok(()));
+        assert_eq!(q.try_push(2), Ok(()));
+        assert_eq!(q.try_push(3), Ok(()));
+        assert!(q.is_full());
+        assert_eq!(q.try_push(4), Err(4));
+        assert_eq!(q.try_pop(), Some(1));
+        q.push(4);
+        assert_eq!(q.len(), 3);
+        assert_eq!(q.pop(), 2);
+        assert_eq!(q.pop(), 3);
+        assert_eq!(q.pop(), 4);
+        assert_eq!(q.try_pop(), None);
+    }
+
+    #[test]
+    fn test_wrap_around() {
+        let q = BoundedQueue::with_capacity(2);
+        for i in 0..10 {
+            q.push(i);
+            assert_eq!(q.try_pop(), Some(i));
+        }
+        assert!(q.is_empty());
+    }
+
+    #[test]
+    fn test_threads() {
+        let q = Arc::new(BoundedQueue::with_capacity(4));
+        let producers: Vec<_> = (0..3u64)
+            .map(|p| {
+                let q = q.clone();
+                thread::spawn(move || {
+                    for i in 0..1000 {
+                        q.push(p * 1000 + i);
+                    }
+                })
+            })
+            .collect();
+        let mut seen = vec![false; 3000];
+        for _ in 0..3000 {
+            let v = q.pop() as usize;
+            assert!(!seen[v]);
+            seen[v] = true;
+        }
+        for producer in producers {
+            producer.join().unwrap();
+        }
+        assert!(q.is_empty());
+    }
+
+    #[test]
+    fn test_drop_values() {
+        let rc = Rc::new(());
+        {
+            let q = BoundedQueue::with_capacity(4);
+            q.push(rc.clone());
+            q.push(rc.clone());
+        }
+        assert_eq!(Rc::strong_count(&rc), 1);
+    }
+}
@@ -0,0 +1,147 @@
+use halves::Halves;
+use AtomicU128;
+
+/// Two 64-bit hash-table entries that are always compared and swapped together.
+///
+/// This is the per-bucket DWCAS that bucketized cuckoo tables rely on: a
+/// writer can check that a key isn't already in the bucket and claim a free
+/// entry in the same CAS.
+#[derive(Debug, Default)]
+pub struct Bucket2x64 {
+    word: AtomicU128,
+}
+
+impl Bucket2x64 {
+    pub fn new(entries: (u64, u64)) -> Self {
+        Bucket2x64 { word: AtomicU128::from_halves(Halves::new(entries.0, entries.1)) }
+    }
+
+    pub fn load(&self) -> (u64, u64) {
+        let word = self.word.load_halves();
+        (word.lo, word.hi)
+    }
+
+    pub fn store(&self, entries: (u64, u64)) {
+        self.word.store_halves(Halves::new(entries.0, entries.1));
+    }
+
+    pub fn compare_exchange(&self, current: (u64, u64), new: (u64, u64)) -> Result<(u64, u64), (u64, u64)> {
+        match self.word.cas_halves(Halves::new(current.0, current.1), Halves::new(new.0, new.1)) {
+            Ok(word) => Ok((word.lo, word.hi)),
+            Err(word) => Err((word.lo, word.hi)),
+        }
+    }
+}
+
+const EMPTY: u64 = 0;
+
+fn mix(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+#[derive(Debug, PartialEq)]
+pub enum InsertError {
+    /// Both candidate buckets for the key are full.
+    Full,
+    /// Zero is reserved to mark empty entries.
+    ReservedKey,
+}
+
+/// Grow-only concurrent set of `u64` keys on top of `Bucket2x64`.
+///
+/// Each key has two candidate buckets and always goes into the first one that
+/// has room. Since entries are never cleared, a bucket that is full stays full,
+/// which together with the in-bucket DWCAS is enough to rule out duplicates.
+pub struct HashSet64 {
+    buckets: Vec<Bucket2x64>,
+}
+
+impl HashSet64 {
+    pub fn with_buckets(buckets: usize) -> Self {
+        assert!(buckets > 0, "need at least one bucket");
+        HashSet64 {
+            buckets: (0..buckets).map(|_| Bucket2x64::default()).collect(),
+        }
+    }
+
+    fn candidates(&self, key: u64) -> [&Bucket2x64; 2] {
+        let h = mix(key);
+        let n = self.buckets.len() as u64;
+        [&self.buckets[(h % n) as usize], &self.buckets[((h >> 32 ^ h.rotate_left(17)) % n) as usize]]
+    }
+
+    pub fn contains(&self, key: u64) -> bool {
+        if key == EMPTY {
+            return false;
+        }
+        self.candidates(key).iter().any(|bucket| {
+            let (a, b) = bucket.load();
+            a == key || b == key
+        })
+    }
+
+    /// Returns `Ok(false)` if the key was already present.
+    pub fn insert(&self, key: u64) -> Result<bool, InsertError> {
+        if key == EMPTY {
+            return Err(InsertError::ReservedKey);
+        }
+        for bucket in self.candidates(key).iter() {
+            let mut current = bucket.load();
+            loop {
+                if current.0 == key || current.1 == key {
+                    return Ok(false);
+                }
+                let new = if current.0 == EMPTY {
+                    (key, current.1)
+                } else if current.1 == EMPTY {
+                    (current.0, key)
+                } else {
+                    break;
+                };
+                match bucket.compare_exchange(current, new) {
+                    Ok(_) => return Ok(true),
+                    Err(actual) => current = actual,
+                }
+            }
+        }
+        Err(InsertError::Full)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Bucket2x64, HashSet64, InsertError};
+
+    #[test]
+    fn test_bucket() {
+        let b = Bucket2x64::new((1, 2));
+        assert_eq!(b.load(), (1, 2));
+        assert_eq!(b.compare_exchange((1, 3), (4, 5)), Err((1, 2)));
+        assert_eq!(b.compare_exchange((1, 2), (4, 5)), Ok((1, 2)));
+        b.store((6, 7));
+        assert_eq!(b.load(), (6, 7));
+    }
+
+    #[test]
+    fn test_set() {
+        let set = HashSet64::with_buckets(64);
+        assert_eq!(set.insert(0), Err(InsertError::ReservedKey));
+        for key in 1..40 {
+            assert_eq!(set.insert(key), Ok(true));
+        }
+        assert_eq!(set.insert(7), Ok(false));
+        assert!(set.contains(39));
+        assert!(!set.contains(40));
+    }
+
+    #[test]
+    fn test_set_full() {
+        let set = HashSet64::with_buckets(1);
+        assert_eq!(set.insert(1), Ok(true));
+        assert_eq!(set.insert(2), Ok(true));
+        assert_eq!(set.insert(3), Err(InsertError::Full));
+        assert_eq!(set.insert(2), Ok(false));
+    }
+}
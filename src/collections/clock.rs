@@ -0,0 +1,121 @@
+use halves::Halves;
+use {AtomicBitmap128, AtomicU128};
+
+// Mask of bits `from..to` within one 128-bit word, `to` at most 128.
+fn range(from: usize, to: usize) -> u128 {
+    let below_to = if to >= 128 { !0 } else { (1u128 << to) - 1 };
+    below_to & !((1u128 << from) - 1)
+}
+
+/// CLOCK (second-chance) reference bits.
+///
+/// Reference bits live in `AtomicBitmap128` words and the hand is a packed
+/// (position, lap) word. A sweeper first claims a stretch of positions by
+/// moving the hand with one CAS, then clears that stretch's bits with one RMW,
+/// so concurrent sweepers never examine the same position twice.
+pub struct ClockBits {
+    words: Vec<AtomicBitmap128>,
+    hand: AtomicU128,
+    len: usize,
+}
+
+impl ClockBits {
+    pub fn new(len: usize) -> Self {
+        assert!(len > 0, "clock needs at least one entry");
+        ClockBits {
+            words: (0..len.div_ceil(128)).map(|_| AtomicBitmap128::default()).collect(),
+            hand: AtomicU128::new(0),
+            len,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn touch(&self, index: usize) {
+        assert!(index < self.len, "index out of range");
+        self.words[index / 128].set((index % 128) as u32);
+    }
+
+    pub fn is_referenced(&self, index: usize) -> bool {
+        assert!(index < self.len, "index out of range");
+        self.words[index / 128].test((index % 128) as u32)
+    }
+
+    /// Current hand position and the number of completed laps.
+    pub fn hand(&self) -> (usize, u64) {
+        let hand = self.hand.load_halves();
+        (hand.lo as usize, hand.hi)
+    }
+
+    /// Sweeps forward, clearing reference bits, and returns the first entry
+    /// found unreferenced; the hand is left just past it.
+    pub fn advance_hand(&self) -> usize {
+        loop {
+            let hand = self.hand.load_halves();
+            let position = hand.lo as usize;
+            let word = position / 128;
+            let start = position % 128;
+            let end = (self.len - word * 128).min(128);
+            let bits = self.words[word].load();
+            let unreferenced = !bits & range(start, end);
+            let (stop, victim) = if unreferenced != 0 {
+                let bit = unreferenced.trailing_zeros() as usize;
+                (bit + 1, Some(word * 128 + bit))
+            } else {
+                (end, None)
+            };
+            let mut next = word * 128 + stop;
+            let mut laps = hand.hi;
+            if next >= self.len {
+                next = 0;
+                laps = laps.wrapping_add(1);
+            }
+            if self.hand.cas_halves(hand, Halves::new(next as u64, laps)).is_err() {
+                continue;
+            }
+            // Only the bits this sweep saw set: a touch that landed since the
+            // load is a fresh reference and keeps its second chance.
+            self.words[word].fetch_and(!(bits & range(start, stop)));
+            if let Some(victim) = victim {
+                return victim;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ClockBits;
+
+    #[test]
+    fn test_second_chance() {
+        let clock = ClockBits::new(4);
+        clock.touch(0);
+        clock.touch(1);
+        assert_eq!(clock.advance_hand(), 2);
+        assert!(!clock.is_referenced(0));
+        assert!(!clock.is_referenced(1));
+        assert_eq!(clock.hand(), (3, 0));
+        clock.touch(3);
+        assert_eq!(clock.advance_hand(), 0);
+        assert_eq!(clock.hand(), (1, 1));
+        assert!(!clock.is_referenced(3));
+    }
+
+    #[test]
+    fn test_all_referenced() {
+        let clock = ClockBits::new(200);
+        for i in 0..200 {
+            clock.touch(i);
+        }
+        assert_eq!(clock.advance_hand(), 0);
+        assert_eq!(clock.hand(), (1, 1));
+        assert_eq!(clock.advance_hand(), 1);
+    }
+}
@@ -0,0 +1,261 @@
+use std::cell::UnsafeCell;
+use std::marker::PhantomData;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicIsize, AtomicPtr, AtomicUsize, Ordering};
+
+use halves::Halves;
+use AtomicU128;
+
+const MIN_CAPACITY: usize = 16;
+
+struct Buffer {
+    // Boxed items, so a stealer can read a slot before it wins the CAS.
+    slots: Vec<AtomicUsize>,
+}
+
+impl Buffer {
+    fn new(capacity: usize) -> *mut Buffer {
+        Box::into_raw(Box::new(Buffer {
+            slots: (0..capacity).map(|_| AtomicUsize::new(0)).collect(),
+        }))
+    }
+
+    fn slot(&self, index: isize) -> &AtomicUsize {
+        &self.slots[index as usize % self.slots.len()]
+    }
+}
+
+struct Inner<T> {
+    // lo is top, hi a tag bumped on every successful CAS.
+    anchor: AtomicU128,
+    bottom: AtomicIsize,
+    buffer: AtomicPtr<Buffer>,
+    // Buffers replaced by growth; only the worker touches this, freed on drop.
+    retired: UnsafeCell<Vec<*mut Buffer>>,
+    _marker: PhantomData<T>,
+}
+
+impl<T> Inner<T> {
+    fn top(&self) -> isize {
+        self.anchor.load_halves().lo as isize
+    }
+}
+
+impl<T> Drop for Inner<T> {
+    fn drop(&mut self) {
+        let buffer = unsafe { Box::from_raw(*self.buffer.get_mut()) };
+        let bottom = *self.bottom.get_mut();
+        let mut index = self.anchor.load_halves().lo as isize;
+        while index < bottom {
+            drop(unsafe { Box::from_raw(buffer.slot(index).load(Ordering::Relaxed) as *mut T) });
+            index += 1;
+        }
+        for old in unsafe { &*self.retired.get() } {
+            drop(unsafe { Box::from_raw(*old) });
+        }
+    }
+}
+
+/// Outcome of `Stealer::steal`.
+#[derive(Debug, PartialEq)]
+pub enum Steal<T> {
+    Empty,
+    Success(T),
+    Retry,
+}
+
+/// Owner side of a Chase-Lev work-stealing deque.
+///
+/// The owner pushes and pops at the bottom; stealers take from the top. The
+/// top index is paired with a tag in one `AtomicU128`, so a steal that read a
+/// stale anchor can never succeed even if top comes back around to it.
+pub struct Worker<T> {
+    inner: Arc<Inner<T>>,
+}
+
+/// Stealing side of the deque, cloneable and shareable between threads.
+pub struct Stealer<T> {
+    inner: Arc<Inner<T>>,
+}
+
+unsafe impl<T: Send> Send for Worker<T> {}
+unsafe impl<T: Send> Send for Stealer<T> {}
+unsafe impl<T: Send> Sync for Stealer<T> {}
+
+impl<T> Worker<T> {
+    pub fn new() -> Self {
+        Worker {
+            inner: Arc::new(Inner {
+                anchor: AtomicU128::new(0),
+                bottom: AtomicIsize::new(0),
+                buffer: AtomicPtr::new(Buffer::new(MIN_CAPACITY)),
+                retired: UnsafeCell::new(Vec::new()),
+                _marker: PhantomData,
+            }),
+        }
+    }
+
+    pub fn stealer(&self) -> Stealer<T> {
+        Stealer { inner: self.inner.clone() }
+    }
+
+    pub fn len(&self) -> usize {
+        let bottom = self.inner.bottom.load(Ordering::SeqCst);
+        (bottom - self.inner.top()).max(0) as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn push(&self, value: T) {
+        let bottom = self.inner.bottom.load(Ordering::Relaxed);
+        let top = self.inner.top();
+        let mut buffer = self.inner.buffer.load(Ordering::Relaxed);
+        if bottom - top >= unsafe { (*buffer).slots.len() } as isize {
+            buffer = self.grow(top, bottom);
+        }
+        let item = Box::into_raw(Box::new(value)) as usize;
+        unsafe { (*buffer).slot(bottom).store(item, Ordering::Relaxed) };
+        self.inner.bottom.store(bottom + 1, Ordering::SeqCst);
+    }
+
+    pub fn pop(&self) -> Option<T> {
+        let bottom = self.inner.bottom.load(Ordering::Relaxed) - 1;
+        self.inner.bottom.store(bottom, Ordering::SeqCst);
+        let anchor = self.inner.anchor.load_halves();
+        let top = anchor.lo as isize;
+        if top > bottom {
+            self.inner.bottom.store(bottom + 1, Ordering::SeqCst);
+            return None;
+        }
+        let buffer = self.inner.buffer.load(Ordering::Relaxed);
+        let item = unsafe { (*buffer).slot(bottom).load(Ordering::Relaxed) };
+        if top < bottom {
+            return Some(*unsafe { Box::from_raw(item as *mut T) });
+        }
+        // Last item: race the stealers for it on the anchor.
+        let won = self.inner.anchor.cas_halves(anchor, advance(anchor)).is_ok();
+        self.inner.bottom.store(bottom + 1, Ordering::SeqCst);
+        if won {
+            Some(*unsafe { Box::from_raw(item as *mut T) })
+        } else {
+            None
+        }
+    }
+
+    fn grow(&self, top: isize, bottom: isize) -> *mut Buffer {
+        let old = self.inner.buffer.load(Ordering::Relaxed);
+        let new = Buffer::new(unsafe { (*old).slots.len() } * 2);
+        let mut index = top;
+        while index < bottom {
+            unsafe {
+                let item = (*old).slot(index).load(Ordering::Relaxed);
+                (*new).slot(index).store(item, Ordering::Relaxed);
+            }
+            index += 1;
+        }
+        self.inner.buffer.store(new, Ordering::SeqCst);
+        // Stealers may still be reading the old buffer, so keep it around.
+        unsafe { (*self.inner.retired.get()).push(old) };
+        new
+    }
+}
+
+impl<T> Default for Worker<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn advance(anchor: Halves) -> Halves {
+    Halves::new(anchor.lo.wrapping_add(1), anchor.hi.wrapping_add(1))
+}
+
+impl<T> Stealer<T> {
+    pub fn is_empty(&self) -> bool {
+        self.inner.bottom.load(Ordering::SeqCst) <= self.inner.top()
+    }
+
+    pub fn steal(&self) -> Steal<T> {
+        let anchor = self.inner.anchor.load_halves();
+        let top = anchor.lo as isize;
+        let bottom = self.inner.bottom.load(Ordering::SeqCst);
+        if top >= bottom {
+            return Steal::Empty;
+        }
+        let buffer = self.inner.buffer.load(Ordering::SeqCst);
+        let item = unsafe { (*buffer).slot(top).load(Ordering::Relaxed) };
+        match self.inner.anchor.cas_halves(anchor, advance(anchor)) {
+            Ok(_) => Steal::Success(*unsafe { Box::from_raw(item as *mut T) }),
+            Err(_) => Steal::Retry,
+        }
+    }
+}
+
+impl<T> Clone for Stealer<T> {
+    fn clone(&self) -> Self {
+        Stealer { inner: self.inner.clone() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use super::{Steal, Worker};
+
+    #[test]
+    fn test_push_pop() {
+        let w = Worker::new();
+        assert_eq!(w.pop(), None);
+        w.push(1);
+        w.push(2);
+        assert_eq!(w.len(), 2);
+        assert_eq!(w.pop(), Some(2));
+        assert_eq!(w.pop(), Some(1));
+        assert_eq!(w.pop(), None);
+        assert!(w.is_empty());
+    }
+
+    #[test]
+    fn test_steal() {
+        let w = Worker::new();
+        let s = w.stealer();
+        assert_eq!(s.steal(), Steal::Empty);
+        w.push(1);
+        w.push(2);
+        w.push(3);
+        assert_eq!(s.steal(), Steal::Success(1));
+        assert_eq!(w.pop(), Some(3));
+        assert_eq!(s.clone().steal(), Steal::Success(2));
+        assert_eq!(w.pop(), None);
+        assert!(s.is_empty());
+    }
+
+    #[test]
+    fn test_grow() {
+        let w = Worker::new();
+        let s = w.stealer();
+        for i in 0..100 {
+            w.push(i);
+        }
+        assert_eq!(s.steal(), Steal::Success(0));
+        for i in (1..100).rev() {
+            assert_eq!(w.pop(), Some(i));
+        }
+        assert_eq!(w.pop(), None);
+    }
+
+    #[test]
+    fn test_drop_values() {
+        let rc = Rc::new(());
+        {
+            let w = Worker::new();
+            for _ in 0..40 {
+                w.push(rc.clone());
+            }
+            w.pop();
+        }
+        assert_eq!(Rc::strong_count(&rc), 1);
+    }
+}
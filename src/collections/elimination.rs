@@ -0,0 +1,184 @@
+use std::cell::Cell;
+
+use halves::Halves;
+use AtomicU128;
+use super::stack::{Node, Stack};
+
+const EMPTY: u64 = 0;
+const WAITING: u64 = 1;
+const TAKEN: u64 = 2;
+
+const DEFAULT_SLOTS: usize = 4;
+const SPINS: usize = 64;
+
+// Slot word: lo is the offered node, hi is (tag << 2) | state.
+fn state(slot: Halves) -> u64 {
+    slot.hi & 3
+}
+
+fn next(slot: Halves, node: u64, state: u64) -> Halves {
+    Halves::new(node, ((slot.hi >> 2).wrapping_add(1) << 2) | state)
+}
+
+thread_local! {
+    static SEED: Cell<u32> = const { Cell::new(0) };
+}
+
+fn random_index(len: usize) -> usize {
+    SEED.with(|seed| {
+        let mut x = seed.get();
+        if x == 0 {
+            // Each thread's cell has its own address, so threads start
+            // apart and don't all pick the same slots in step.
+            x = ((seed as *const Cell<u32> as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15) >> 32) as u32 | 1;
+        }
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        seed.set(x);
+        x as usize % len
+    })
+}
+
+/// Treiber stack with an elimination array.
+///
+/// When a CAS on the head fails, push and pop meet in a randomly chosen slot
+/// instead; a pusher offers its node there and a popper takes it directly,
+/// so the pair completes without touching the head at all.
+pub struct EliminationStack<T> {
+    stack: Stack<T>,
+    slots: Vec<AtomicU128>,
+}
+
+impl<T> EliminationStack<T> {
+    pub fn new() -> Self {
+        Self::with_slots(DEFAULT_SLOTS)
+    }
+
+    pub fn with_slots(slots: usize) -> Self {
+        assert!(slots > 0, "elimination array needs at least one slot");
+        EliminationStack {
+            stack: Stack::new(),
+            slots: (0..slots).map(|_| AtomicU128::new(0)).collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stack.is_empty()
+    }
+
+    pub fn push(&self, value: T) {
+        let node = self.stack.alloc_node(value);
+        while !self.stack.try_push_node(node) {
+            if self.offer(node) {
+                return;
+            }
+        }
+    }
+
+    pub fn pop(&self) -> Option<T> {
+        loop {
+            match self.stack.try_pop_node() {
+                Ok(node) if node.is_null() => return None,
+                Ok(node) => return self.stack.release_node(node),
+                Err(()) => {
+                    if let Some(node) = self.take() {
+                        return self.stack.release_node(node);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn try_pop_all(&self) -> Option<Vec<T>> {
+        self.stack.try_pop_all()
+    }
+
+    // Parks `node` in a slot for a while; returns true if a popper took it.
+    fn offer(&self, node: *mut Node<T>) -> bool {
+        let slot = &self.slots[random_index(self.slots.len())];
+        let current = slot.load_halves();
+        if state(current) != EMPTY {
+            return false;
+        }
+        let offered = next(current, node as u64, WAITING);
+        if slot.cas_halves(current, offered).is_err() {
+            return false;
+        }
+        for _ in 0..SPINS {
+            let seen = slot.load_halves();
+            if state(seen) == TAKEN {
+                // Only the offering side leaves TAKEN, so no one else races us here.
+                slot.store_halves(next(seen, 0, EMPTY));
+                return true;
+            }
+        }
+        match slot.cas_halves(offered, next(offered, 0, EMPTY)) {
+            Ok(_) => false,
+            Err(seen) => {
+                slot.store_halves(next(seen, 0, EMPTY));
+                true
+            }
+        }
+    }
+
+    fn take(&self) -> Option<*mut Node<T>> {
+        let slot = &self.slots[random_index(self.slots.len())];
+        let current = slot.load_halves();
+        if state(current) != WAITING {
+            return None;
+        }
+        match slot.cas_halves(current, next(current, 0, TAKEN)) {
+            Ok(_) => Some(current.lo as *mut Node<T>),
+            Err(_) => None,
+        }
+    }
+}
+
+impl<T> Default for EliminationStack<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{next, state, EliminationStack, EMPTY, TAKEN, WAITING};
+    use halves::Halves;
+
+    #[test]
+    fn test_push_pop() {
+        let s = EliminationStack::with_slots(2);
+        s.push(1);
+        s.push(2);
+        assert_eq!(s.pop(), Some(2));
+        assert_eq!(s.pop(), Some(1));
+        assert_eq!(s.pop(), None);
+        assert!(s.is_empty());
+    }
+
+    #[test]
+    fn test_offer_take() {
+        let s = EliminationStack::with_slots(1);
+        let node = s.stack.alloc_node(7);
+        // Nobody is popping, so the offer times out and is withdrawn.
+        assert!(!s.offer(node));
+        assert_eq!(state(s.slots[0].load_halves()), EMPTY);
+
+        let slot = s.slots[0].load_halves();
+        s.slots[0].store_halves(next(slot, node as u64, WAITING));
+        let taken = s.take().unwrap();
+        assert_eq!(taken, node);
+        assert_eq!(state(s.slots[0].load_halves()), TAKEN);
+        assert_eq!(s.stack.release_node(taken), Some(7));
+    }
+
+    #[test]
+    fn test_slot_tag() {
+        let slot = Halves::default();
+        let a = next(slot, 0, WAITING);
+        let b = next(a, 0, EMPTY);
+        assert_eq!(state(b), EMPTY);
+        assert!(b != slot);
+    }
+}
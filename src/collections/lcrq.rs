@@ -0,0 +1,277 @@
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicPtr, AtomicU64, Ordering};
+use std::ptr;
+
+use halves::Halves;
+use AtomicU128;
+
+const DEFAULT_RING_SIZE: usize = 1024;
+const CLOSED: u64 = 1 << 63;
+const UNSAFE_BIT: u64 = 1 << 63;
+const STARVATION: usize = 64;
+
+// Cell word: lo is the boxed value (0 for empty), hi is (unsafe << 63) | index.
+fn cell(safe: bool, index: u64, value: u64) -> Halves {
+    Halves::new(value, if safe { index } else { index | UNSAFE_BIT })
+}
+
+fn is_safe(c: Halves) -> bool {
+    c.hi & UNSAFE_BIT == 0
+}
+
+fn index(c: Halves) -> u64 {
+    c.hi & !UNSAFE_BIT
+}
+
+// Head and tail are 64-bit whatever the pointer width, so the closed bit
+// sits above any index and indices wrap no sooner than cell indices do.
+struct Crq {
+    head: AtomicU64,
+    tail: AtomicU64,
+    next: AtomicPtr<Crq>,
+    ring: Vec<AtomicU128>,
+}
+
+impl Crq {
+    // A non-zero `first` is placed in slot 0, which is how a successor ring starts.
+    fn new(size: usize, first: u64) -> *mut Crq {
+        let crq = Crq {
+            head: AtomicU64::new(0),
+            tail: AtomicU64::new(if first != 0 { 1 } else { 0 }),
+            next: AtomicPtr::new(ptr::null_mut()),
+            ring: (0..size).map(|i| AtomicU128::from_halves(cell(true, i as u64, 0))).collect(),
+        };
+        if first != 0 {
+            crq.ring[0].store_halves(cell(true, 0, first));
+        }
+        Box::into_raw(Box::new(crq))
+    }
+
+    fn size(&self) -> u64 {
+        self.ring.len() as u64
+    }
+
+    // Returns false once the ring is closed; the caller still owns `value`.
+    fn enqueue(&self, value: u64) -> bool {
+        let mut attempts = 0;
+        loop {
+            let t = self.tail.fetch_add(1, Ordering::SeqCst);
+            if t & CLOSED != 0 {
+                return false;
+            }
+            let slot = &self.ring[(t % self.size()) as usize];
+            let current = slot.load_halves();
+            if current.lo == 0 && index(current) <= t
+                && (is_safe(current) || self.head.load(Ordering::SeqCst) <= t)
+                && slot.cas_halves(current, cell(true, t, value)).is_ok() {
+                return true;
+            }
+            attempts += 1;
+            let h = self.head.load(Ordering::SeqCst);
+            if t.wrapping_sub(h) as i64 >= self.size() as i64 || attempts > STARVATION {
+                self.tail.fetch_or(CLOSED, Ordering::SeqCst);
+                return false;
+            }
+        }
+    }
+
+    fn dequeue(&self) -> Option<u64> {
+        loop {
+            let h = self.head.fetch_add(1, Ordering::SeqCst);
+            let slot = &self.ring[(h % self.size()) as usize];
+            loop {
+                let current = slot.load_halves();
+                let i = index(current);
+                if i > h {
+                    break;
+                }
+                if current.lo != 0 {
+                    if i == h {
+                        let empty = cell(is_safe(current), h + self.size(), 0);
+                        if slot.cas_halves(current, empty).is_ok() {
+                            return Some(current.lo);
+                        }
+                    } else if slot.cas_halves(current, cell(false, i, current.lo)).is_ok() {
+                        // An enqueuer from an older lap is still in flight here.
+                        break;
+                    }
+                } else {
+                    let skipped = cell(is_safe(current), h + self.size(), 0);
+                    if slot.cas_halves(current, skipped).is_ok() {
+                        break;
+                    }
+                }
+            }
+            let t = self.tail.load(Ordering::SeqCst) & !CLOSED;
+            if t <= h + 1 {
+                self.fix_state();
+                return None;
+            }
+        }
+    }
+
+    // Dequeuers that ran past the tail pull it forward again.
+    fn fix_state(&self) {
+        loop {
+            let h = self.head.load(Ordering::SeqCst);
+            let t = self.tail.load(Ordering::SeqCst);
+            if self.tail.load(Ordering::SeqCst) != t {
+                continue;
+            }
+            if h <= t {
+                return;
+            }
+            if self.tail.compare_exchange(t, h, Ordering::SeqCst, Ordering::SeqCst).is_ok() {
+                return;
+            }
+        }
+    }
+}
+
+/// Unbounded MPMC FIFO built from linked concurrent ring queues (LCRQ).
+///
+/// Enqueue and dequeue claim a ring index with fetch-and-add and then settle
+/// the (value, index/safe) cell with one 128-bit CAS, so operations rarely
+/// contend on the same word. A full or starving ring is closed and a new one
+/// is linked after it.
+///
+/// # Memory
+///
+/// Rings are never freed while the queue is alive: a drained ring stays
+/// allocated, with all its cells, until the `Lcrq` is dropped, since a
+/// lagging thread may still be working on it and nothing tracks when the
+/// last one leaves. Memory therefore grows with the number of rings ever
+/// closed, not with the number of values queued. A queue that lives long
+/// and keeps overflowing or starving its rings needs a larger ring size or
+/// a different queue.
+pub struct Lcrq<T> {
+    head: AtomicPtr<Crq>,
+    tail: AtomicPtr<Crq>,
+    first: *mut Crq,
+    ring_size: usize,
+    _marker: PhantomData<T>,
+}
+
+unsafe impl<T: Send> Send for Lcrq<T> {}
+unsafe impl<T: Send> Sync for Lcrq<T> {}
+
+impl<T> Lcrq<T> {
+    pub fn new() -> Self {
+        Self::with_ring_size(DEFAULT_RING_SIZE)
+    }
+
+    pub fn with_ring_size(ring_size: usize) -> Self {
+        assert!(ring_size >= 2, "ring size must be at least 2");
+        let crq = Crq::new(ring_size, 0);
+        Lcrq {
+            head: AtomicPtr::new(crq),
+            tail: AtomicPtr::new(crq),
+            first: crq,
+            ring_size,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn push(&self, value: T) {
+        let value = Box::into_raw(Box::new(value)) as u64;
+        loop {
+            let crq = self.tail.load(Ordering::SeqCst);
+            let next = unsafe { (*crq).next.load(Ordering::SeqCst) };
+            if !next.is_null() {
+                let _ = self.tail.compare_exchange(crq, next, Ordering::SeqCst, Ordering::SeqCst);
+                continue;
+            }
+            if unsafe { (*crq).enqueue(value) } {
+                return;
+            }
+            let new = Crq::new(self.ring_size, value);
+            match unsafe { (*crq).next.compare_exchange(ptr::null_mut(), new, Ordering::SeqCst, Ordering::SeqCst) } {
+                Ok(_) => {
+                    let _ = self.tail.compare_exchange(crq, new, Ordering::SeqCst, Ordering::SeqCst);
+                    return;
+                }
+                Err(_) => drop(unsafe { Box::from_raw(new) }),
+            }
+        }
+    }
+
+    pub fn pop(&self) -> Option<T> {
+        loop {
+            let crq = self.head.load(Ordering::SeqCst);
+            if let Some(value) = unsafe { (*crq).dequeue() } {
+                return Some(*unsafe { Box::from_raw(value as *mut T) });
+            }
+            let next = unsafe { (*crq).next.load(Ordering::SeqCst) };
+            if next.is_null() {
+                return None;
+            }
+            // An enqueuer may have slipped in before the ring was closed.
+            if let Some(value) = unsafe { (*crq).dequeue() } {
+                return Some(*unsafe { Box::from_raw(value as *mut T) });
+            }
+            let _ = self.head.compare_exchange(crq, next, Ordering::SeqCst, Ordering::SeqCst);
+        }
+    }
+}
+
+impl<T> Default for Lcrq<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for Lcrq<T> {
+    fn drop(&mut self) {
+        while self.pop().is_some() {}
+        let mut crq = self.first;
+        while !crq.is_null() {
+            let boxed = unsafe { Box::from_raw(crq) };
+            crq = boxed.next.load(Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use super::Lcrq;
+
+    #[test]
+    fn test_push_pop() {
+        let q = Lcrq::new();
+        assert_eq!(q.pop(), None);
+        q.push(1);
+        q.push(2);
+        assert_eq!(q.pop(), Some(1));
+        q.push(3);
+        assert_eq!(q.pop(), Some(2));
+        assert_eq!(q.pop(), Some(3));
+        assert_eq!(q.pop(), None);
+    }
+
+    #[test]
+    fn test_ring_overflow() {
+        let q = Lcrq::with_ring_size(4);
+        for i in 0..50 {
+            q.push(i);
+        }
+        for i in 0..50 {
+            assert_eq!(q.pop(), Some(i));
+        }
+        assert_eq!(q.pop(), None);
+        q.push(50);
+        assert_eq!(q.pop(), Some(50));
+    }
+
+    #[test]
+    fn test_drop_values() {
+        let rc = Rc::new(());
+        {
+            let q = Lcrq::with_ring_size(2);
+            for _ in 0..5 {
+                q.push(rc.clone());
+            }
+        }
+        assert_eq!(Rc::strong_count(&rc), 1);
+    }
+}
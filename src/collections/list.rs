@@ -0,0 +1,274 @@
+use std::marker::PhantomData;
+use std::ptr;
+use std::sync::atomic::{AtomicPtr, Ordering};
+
+use AtomicMarkableRef;
+
+// A successor and whether the node it was read from is marked deleted.
+type Link<T> = (*mut Node<T>, bool);
+
+struct Node<T> {
+    key: Option<T>,
+    // The successor, marked once this node is deleted.
+    next: AtomicMarkableRef<Node<T>>,
+    retired: AtomicPtr<Node<T>>,
+}
+
+impl<T> Node<T> {
+    fn alloc(key: Option<T>, next: *mut Node<T>) -> *mut Node<T> {
+        Box::into_raw(Box::new(Node {
+            key,
+            next: AtomicMarkableRef::new(next, false),
+            retired: AtomicPtr::new(ptr::null_mut()),
+        }))
+    }
+}
+
+/// Sorted lock-free linked list (Harris, with Michael's one-at-a-time unlinking).
+///
+/// Each node's `next` is an `AtomicMarkableRef` carrying the successor together
+/// with the node's own deletion mark, so marking a node and unlinking it are
+/// both single CASes.
+///
+/// # Memory
+///
+/// Removed nodes are never freed while the list is shared: each one goes on
+/// a retired list and stays allocated, key and all, until the list is
+/// dropped or `reclaim` is called. That is what lets readers and iterators
+/// traverse without any guards, but it means memory grows with the number
+/// of removals ever made, not with the number of keys present. A list that
+/// sees steady insert/remove churn needs a `reclaim` at points where the
+/// owner has it exclusively, or a different structure.
+pub struct SortedList<T> {
+    head: *mut Node<T>,
+    retired: AtomicPtr<Node<T>>,
+    _marker: PhantomData<T>,
+}
+
+unsafe impl<T: Send + Sync> Send for SortedList<T> {}
+unsafe impl<T: Send + Sync> Sync for SortedList<T> {}
+
+impl<T: Ord> SortedList<T> {
+    pub fn new() -> Self {
+        SortedList {
+            head: Node::alloc(None, ptr::null_mut()),
+            retired: AtomicPtr::new(ptr::null_mut()),
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.iter().next().is_none()
+    }
+
+    pub fn contains(&self, key: &T) -> bool {
+        let mut node = unsafe { (*self.head).next.load().0 };
+        while !node.is_null() {
+            let (next, marked) = unsafe { (*node).next.load() };
+            match unsafe { (*node).key.as_ref() }.unwrap().cmp(key) {
+                ::std::cmp::Ordering::Less => node = next,
+                ::std::cmp::Ordering::Equal => return !marked,
+                ::std::cmp::Ordering::Greater => return false,
+            }
+        }
+        false
+    }
+
+    pub fn insert(&self, key: T) -> bool {
+        let node = Node::alloc(Some(key), ptr::null_mut());
+        let key = unsafe { (*node).key.as_ref() }.unwrap();
+        loop {
+            let (prev, current, word) = self.find(key);
+            if !current.is_null() && unsafe { (*current).key.as_ref() } == Some(key) {
+                drop(unsafe { Box::from_raw(node) });
+                return false;
+            }
+            unsafe { (*node).next.store(current, false) };
+            if unsafe { (*prev).next.compare_exchange(word, (node, false)) }.is_ok() {
+                return true;
+            }
+        }
+    }
+
+    pub fn remove(&self, key: &T) -> bool {
+        loop {
+            let (prev, current, word) = self.find(key);
+            if current.is_null() || unsafe { (*current).key.as_ref() } != Some(key) {
+                return false;
+            }
+            let (next, marked) = unsafe { (*current).next.load() };
+            if marked || !unsafe { (*current).next.attempt_mark(next) } {
+                continue;
+            }
+            if unsafe { (*prev).next.compare_exchange(word, (next, false)) }.is_ok() {
+                self.retire(current);
+            } else {
+                // Let a fresh search do the unlinking.
+                self.find(key);
+            }
+            return true;
+        }
+    }
+
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            node: unsafe { (*self.head).next.load().0 },
+            _marker: PhantomData,
+        }
+    }
+
+    /// Frees the nodes removed so far. Taking `&mut self` is what makes this
+    /// safe: no reader or iterator can still be on one of them.
+    pub fn reclaim(&mut self) {
+        free_retired(*self.retired.get_mut());
+        *self.retired.get_mut() = ptr::null_mut();
+    }
+
+    // Returns (prev, current, prev.next as read) with current the first node
+    // whose key is >= `key`, unlinking marked nodes on the way.
+    fn find(&self, key: &T) -> (*mut Node<T>, *mut Node<T>, Link<T>) {
+        'restart: loop {
+            let mut prev = self.head;
+            let mut word = unsafe { (*prev).next.load() };
+            loop {
+                let current = word.0;
+                if current.is_null() {
+                    return (prev, current, word);
+                }
+                let next = unsafe { (*current).next.load() };
+                if unsafe { (*prev).next.load() } != word {
+                    continue 'restart;
+                }
+                if next.1 {
+                    let unlinked = (next.0, false);
+                    if unsafe { (*prev).next.compare_exchange(word, unlinked) }.is_err() {
+                        continue 'restart;
+                    }
+                    self.retire(current);
+                    word = unlinked;
+                } else {
+                    if unsafe { (*current).key.as_ref() }.unwrap() >= key {
+                        return (prev, current, word);
+                    }
+                    prev = current;
+                    word = next;
+                }
+            }
+        }
+    }
+
+    fn retire(&self, node: *mut Node<T>) {
+        let mut head = self.retired.load(Ordering::Relaxed);
+        loop {
+            unsafe { (*node).retired.store(head, Ordering::Relaxed) };
+            match self.retired.compare_exchange(head, node, Ordering::SeqCst, Ordering::Relaxed) {
+                Ok(_) => return,
+                Err(actual) => head = actual,
+            }
+        }
+    }
+}
+
+impl<T: Ord> Default for SortedList<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for SortedList<T> {
+    fn drop(&mut self) {
+        let mut node = self.head;
+        while !node.is_null() {
+            let boxed = unsafe { Box::from_raw(node) };
+            node = boxed.next.load().0;
+        }
+        free_retired(*self.retired.get_mut());
+    }
+}
+
+fn free_retired<T>(mut node: *mut Node<T>) {
+    while !node.is_null() {
+        let boxed = unsafe { Box::from_raw(node) };
+        node = boxed.retired.load(Ordering::Relaxed);
+    }
+}
+
+/// Iterator over the keys present in a `SortedList`, in ascending order.
+pub struct Iter<'a, T: 'a> {
+    node: *mut Node<T>,
+    _marker: PhantomData<&'a T>,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        while !self.node.is_null() {
+            let node = unsafe { &*self.node };
+            let (next, marked) = node.next.load();
+            self.node = next;
+            if !marked {
+                return node.key.as_ref();
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SortedList;
+
+    #[test]
+    fn test_insert_contains() {
+        let l = SortedList::new();
+        assert!(l.is_empty());
+        assert!(l.insert(3));
+        assert!(l.insert(1));
+        assert!(l.insert(2));
+        assert!(!l.insert(2));
+        assert!(l.contains(&1));
+        assert!(l.contains(&3));
+        assert!(!l.contains(&4));
+        assert_eq!(l.iter().cloned().collect::<Vec<_>>(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_remove() {
+        let l = SortedList::new();
+        for i in 0..10 {
+            l.insert(i);
+        }
+        assert!(l.remove(&0));
+        assert!(l.remove(&5));
+        assert!(l.remove(&9));
+        assert!(!l.remove(&5));
+        assert!(!l.contains(&5));
+        assert_eq!(l.iter().cloned().collect::<Vec<_>>(), vec![1, 2, 3, 4, 6, 7, 8]);
+        assert!(l.insert(5));
+        assert!(l.contains(&5));
+    }
+
+    #[test]
+    fn test_reclaim() {
+        let mut l = SortedList::new();
+        for i in 0..4 {
+            l.insert(i);
+        }
+        l.remove(&1);
+        l.remove(&2);
+        l.reclaim();
+        l.remove(&3);
+        l.reclaim();
+        assert_eq!(l.iter().cloned().collect::<Vec<_>>(), vec![0]);
+    }
+
+    #[test]
+    fn test_strings() {
+        let l = SortedList::new();
+        l.insert("b".to_string());
+        l.insert("a".to_string());
+        l.remove(&"b".to_string());
+        assert_eq!(l.iter().collect::<Vec<_>>(), vec!["a"]);
+    }
+}
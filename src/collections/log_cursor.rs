@@ -0,0 +1,122 @@
+use halves::Halves;
+use AtomicU128;
+
+/// Space handed out by `LogCursor::reserve`: `len` bytes at `offset` in
+/// `segment`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Reservation {
+    pub segment: u64,
+    pub offset: u64,
+    pub len: u64,
+    /// This reservation rolled the cursor over, so it's the first in
+    /// `segment` and the caller should seal `segment - 1` and open `segment`.
+    pub rolled_over: bool,
+}
+
+/// The reservation is bigger than a whole segment.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TooLarge;
+
+/// The append position of a segmented log, shared by every writer.
+///
+/// Segment and offset are one word, so a reservation that doesn't fit in the
+/// current segment moves to the start of the next one in the same CAS that
+/// claims it. No writer can claim space past the end of a segment or see a
+/// new segment paired with the old segment's offset.
+pub struct LogCursor {
+    // lo is the offset in the current segment, hi the segment id.
+    word: AtomicU128,
+    segment_size: u64,
+}
+
+impl LogCursor {
+    pub fn new(segment_size: u64) -> Self {
+        Self::starting_at(0, 0, segment_size)
+    }
+
+    /// Resumes a log at `offset` in `segment`, as found on recovery.
+    pub fn starting_at(segment: u64, offset: u64, segment_size: u64) -> Self {
+        assert!(offset <= segment_size, "offset past the end of the segment");
+        LogCursor { word: AtomicU128::from_halves(Halves::new(offset, segment)), segment_size }
+    }
+
+    pub fn segment_size(&self) -> u64 {
+        self.segment_size
+    }
+
+    /// The current segment and the offset the next reservation in it starts
+    /// at.
+    pub fn position(&self) -> (u64, u64) {
+        let current = self.word.load_halves();
+        (current.hi, current.lo)
+    }
+
+    /// Claims `len` bytes, starting a new segment if they don't fit in what's
+    /// left of this one. The tail of a segment left behind is unused.
+    pub fn reserve(&self, len: u64) -> Result<Reservation, TooLarge> {
+        if len > self.segment_size {
+            return Err(TooLarge);
+        }
+        let mut current = self.word.load_halves();
+        loop {
+            let fits = len <= self.segment_size - current.lo;
+            let next = if fits {
+                Halves::new(current.lo + len, current.hi)
+            } else {
+                Halves::new(len, current.hi + 1)
+            };
+            match self.word.cas_halves(current, next) {
+                Ok(_) => {
+                    return Ok(Reservation {
+                        segment: next.hi,
+                        offset: next.lo - len,
+                        len,
+                        rolled_over: !fits,
+                    });
+                }
+                Err(actual) => current = actual,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::{LogCursor, Reservation, TooLarge};
+
+    #[test]
+    fn test_reserve_rolls_over() {
+        let log = LogCursor::new(100);
+        assert_eq!(log.reserve(60), Ok(Reservation { segment: 0, offset: 0, len: 60, rolled_over: false }));
+        assert_eq!(log.reserve(40), Ok(Reservation { segment: 0, offset: 60, len: 40, rolled_over: false }));
+        assert_eq!(log.reserve(1), Ok(Reservation { segment: 1, offset: 0, len: 1, rolled_over: true }));
+        assert_eq!(log.reserve(100), Ok(Reservation { segment: 2, offset: 0, len: 100, rolled_over: true }));
+        assert_eq!(log.reserve(101), Err(TooLarge));
+        assert_eq!(log.position(), (2, 100));
+    }
+
+    #[test]
+    fn test_concurrent_reservations_are_disjoint() {
+        let log = Arc::new(LogCursor::starting_at(7, 90, 100));
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let log = log.clone();
+                thread::spawn(move || (0..500).map(|i| log.reserve(1 + i % 13).unwrap()).collect::<Vec<_>>())
+            })
+            .collect();
+        let mut all: Vec<Reservation> = handles.into_iter().flat_map(|h| h.join().unwrap()).collect();
+        all.sort_by_key(|r| (r.segment, r.offset));
+        for pair in all.windows(2) {
+            if pair[0].segment == pair[1].segment {
+                assert!(pair[0].offset + pair[0].len <= pair[1].offset);
+            } else {
+                assert_eq!(pair[1].offset, 0);
+                assert!(pair[1].rolled_over);
+            }
+        }
+        assert!(all.iter().all(|r| r.offset + r.len <= 100));
+    }
+}
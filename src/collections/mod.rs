@@ -0,0 +1,39 @@
+mod stack;
+mod elimination;
+mod queue;
+mod bounded;
+mod spsc;
+mod deque;
+mod lcrq;
+mod list;
+mod pool;
+mod slot_map;
+mod bucket;
+mod bitmap;
+mod bloom;
+mod clock;
+mod array;
+mod log_cursor;
+mod per_cpu;
+mod mpsc;
+mod append_vec;
+
+pub use self::stack::Stack;
+pub use self::elimination::EliminationStack;
+pub use self::queue::Queue;
+pub use self::bounded::BoundedQueue;
+pub use self::spsc::{spsc_ring, Producer, Consumer};
+pub use self::deque::{Worker, Stealer, Steal};
+pub use self::lcrq::Lcrq;
+pub use self::list::{SortedList, Iter};
+pub use self::pool::{Pool, Pooled};
+pub use self::slot_map::{SlotMap, Key, Ref};
+pub use self::bucket::{Bucket2x64, HashSet64, InsertError};
+pub use self::bitmap::Bitmap;
+pub use self::bloom::BloomFilter;
+pub use self::clock::ClockBits;
+pub use self::array::{AtomicArray128, Values};
+pub use self::log_cursor::{LogCursor, Reservation, TooLarge};
+pub use self::per_cpu::PerCpu;
+pub use self::mpsc::{Linked, MpscLink, MpscQueue};
+pub use self::append_vec::AppendVec;
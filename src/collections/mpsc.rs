@@ -0,0 +1,228 @@
+use std::cell::UnsafeCell;
+use std::marker::PhantomData;
+use std::ptr;
+use std::sync::atomic::{AtomicPtr, AtomicU64, Ordering};
+
+use halves::Halves;
+use AtomicU128;
+
+/// The hook a value embeds to sit in an `MpscQueue`.
+#[derive(Debug, Default)]
+pub struct MpscLink {
+    next: AtomicPtr<MpscLink>,
+}
+
+impl MpscLink {
+    pub const fn new() -> Self {
+        MpscLink { next: AtomicPtr::new(ptr::null_mut()) }
+    }
+}
+
+/// A type with an embedded `MpscLink`.
+///
+/// # Safety
+///
+/// `link` must always return the same link, embedded in `*this`, and
+/// `from_link` must map that link back to the value it's embedded in. Both
+/// work on raw pointers and should project with `ptr::addr_of_mut!`, not
+/// through a reference, so the link keeps the provenance of the whole value
+/// and the queue can turn it back into the `Box` it came from.
+pub unsafe trait Linked {
+    /// # Safety
+    ///
+    /// `this` must point to a live value.
+    unsafe fn link(this: *mut Self) -> *mut MpscLink;
+
+    /// # Safety
+    ///
+    /// `link` must have come from `link` on a live value.
+    unsafe fn from_link(link: *mut MpscLink) -> *mut Self;
+}
+
+/// Intrusive unbounded MPSC FIFO (Vyukov's), for actor mailboxes and the
+/// like: the queue never allocates, values bring their own link.
+///
+/// Producers swing the tail to their value with a CAS, which also counts
+/// the push, and then link it behind the previous tail; the consumer pops
+/// without any CAS. The count next to the tail is what lets `len` be exact
+/// rather than a walk or a guess. It costs the wait-free push of Vyukov's
+/// original, whose tail is a plain swap: a CAS can lose to other producers
+/// and retry, so pushes are lock-free.
+pub struct MpscQueue<T: Linked> {
+    // lo is the last link pushed, hi how many values have been pushed.
+    tail: AtomicU128,
+    // Only the consumer touches this.
+    head: UnsafeCell<*mut MpscLink>,
+    popped: AtomicU64,
+    // Raw rather than a `Box`, which moving the queue would retag out from
+    // under the pointers to it in the list.
+    stub: *mut MpscLink,
+    _marker: PhantomData<Box<T>>,
+}
+
+unsafe impl<T: Linked + Send> Send for MpscQueue<T> {}
+unsafe impl<T: Linked + Send> Sync for MpscQueue<T> {}
+
+impl<T: Linked> MpscQueue<T> {
+    pub fn new() -> Self {
+        let stub = Box::into_raw(Box::new(MpscLink::new()));
+        MpscQueue {
+            tail: AtomicU128::from_halves(Halves::new(stub as u64, 0)),
+            head: UnsafeCell::new(stub),
+            popped: AtomicU64::new(0),
+            stub,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn push(&self, value: Box<T>) {
+        let link = unsafe { T::link(Box::into_raw(value)) };
+        self.push_link(link, 1);
+    }
+
+    fn push_link(&self, link: *mut MpscLink, counted: u64) {
+        unsafe { (*link).next.store(ptr::null_mut(), Ordering::Relaxed) };
+        let mut current = self.tail.load_halves();
+        let previous = loop {
+            match self.tail.cas_halves(current, Halves::new(link as u64, current.hi + counted)) {
+                Ok(previous) => break previous,
+                Err(actual) => current = actual,
+            }
+        };
+        unsafe { (*(previous.lo as *mut MpscLink)).next.store(link, Ordering::Release) };
+    }
+
+    /// Pops the oldest value. `None` with a nonzero `len` means a producer
+    /// is between its CAS and linking its value in; try again shortly.
+    ///
+    /// # Safety
+    ///
+    /// Only one thread may pop at a time.
+    pub unsafe fn pop(&self) -> Option<Box<T>> {
+        let head = &mut *self.head.get();
+        let mut first = *head;
+        let mut next = (*first).next.load(Ordering::Acquire);
+        if first == self.stub {
+            if next.is_null() {
+                return None;
+            }
+            *head = next;
+            first = next;
+            next = (*first).next.load(Ordering::Acquire);
+        }
+        if next.is_null() {
+            if first as u64 != self.tail.load_halves().lo {
+                return None;
+            }
+            // `first` is the last value; put the stub behind it so it can
+            // be unlinked. The stub isn't counted.
+            self.push_link(self.stub, 0);
+            next = (*first).next.load(Ordering::Acquire);
+            if next.is_null() {
+                return None;
+            }
+        }
+        *head = next;
+        self.popped.fetch_add(1, Ordering::Release);
+        Some(Box::from_raw(T::from_link(first)))
+    }
+
+    /// Values pushed and not yet popped, counting pushes still in flight.
+    pub fn len(&self) -> usize {
+        // Popped first: pushes only grow after it, so this can't underflow.
+        let popped = self.popped.load(Ordering::Acquire);
+        (self.tail.load_halves().hi - popped) as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T: Linked> Default for MpscQueue<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Linked> Drop for MpscQueue<T> {
+    fn drop(&mut self) {
+        while unsafe { self.pop() }.is_some() {}
+        drop(unsafe { Box::from_raw(self.stub) });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ptr;
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::{Linked, MpscLink, MpscQueue};
+
+    #[repr(C)]
+    struct Message {
+        link: MpscLink,
+        n: u64,
+    }
+
+    unsafe impl Linked for Message {
+        unsafe fn link(this: *mut Self) -> *mut MpscLink {
+            ptr::addr_of_mut!((*this).link)
+        }
+
+        unsafe fn from_link(link: *mut MpscLink) -> *mut Self {
+            link as *mut Message
+        }
+    }
+
+    fn message(n: u64) -> Box<Message> {
+        Box::new(Message { link: MpscLink::new(), n })
+    }
+
+    #[test]
+    fn test_fifo_and_len() {
+        let q = MpscQueue::new();
+        assert!(unsafe { q.pop() }.is_none());
+        q.push(message(1));
+        q.push(message(2));
+        assert_eq!(q.len(), 2);
+        assert_eq!(unsafe { q.pop() }.map(|m| m.n), Some(1));
+        q.push(message(3));
+        assert_eq!(unsafe { q.pop() }.map(|m| m.n), Some(2));
+        assert_eq!(unsafe { q.pop() }.map(|m| m.n), Some(3));
+        assert!(unsafe { q.pop() }.is_none());
+        assert!(q.is_empty());
+        q.push(message(4));
+    }
+
+    #[test]
+    fn test_producers_in_order() {
+        let rounds = if cfg!(miri) { 50 } else { 1000 };
+        let q = Arc::new(MpscQueue::new());
+        let producers: Vec<_> = (0..4u64)
+            .map(|p| {
+                let q = q.clone();
+                thread::spawn(move || {
+                    for i in 0..rounds {
+                        q.push(message(p << 32 | i));
+                    }
+                })
+            })
+            .collect();
+        let mut last = [None; 4];
+        let mut received = 0;
+        while received < 4 * rounds {
+            if let Some(m) = unsafe { q.pop() } {
+                let (p, i) = ((m.n >> 32) as usize, m.n as u32);
+                assert!(last[p].is_none_or(|l| l < i));
+                last[p] = Some(i);
+                received += 1;
+            }
+        }
+        for producer in producers {
+            producer.join().unwrap();
+        }
+        assert!(q.is_empty());
+    }
+}
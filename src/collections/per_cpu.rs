@@ -0,0 +1,142 @@
+use std::cell::Cell;
+use std::sync::atomic::Ordering::SeqCst;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use AtomicU128;
+
+#[repr(align(64))]
+#[derive(Default)]
+struct Padded<T>(T);
+
+/// One value per CPU, each on its own cache line, for counters and
+/// statistics that every CPU updates and something occasionally sums.
+///
+/// `with_current` picks the calling CPU's shard: from `TSC_AUX` with the
+/// `irq` feature, where a kernel keeps the CPU index there, from
+/// `sched_getcpu` on Linux, and otherwise from a shard each thread is dealt
+/// on first use. A thread can migrate while it holds a shard, so the values
+/// are shared ones like `AtomicU128` and only contention, not correctness,
+/// depends on the pick.
+pub struct PerCpu<T> {
+    shards: Box<[Padded<T>]>,
+}
+
+impl<T: Default> PerCpu<T> {
+    /// A shard for each CPU the process can run on.
+    pub fn new() -> Self {
+        Self::with_shards(thread::available_parallelism().map(|n| n.get()).unwrap_or(1))
+    }
+
+    pub fn with_shards(shards: usize) -> Self {
+        assert!(shards > 0, "need at least one shard");
+        PerCpu { shards: (0..shards).map(|_| Padded::default()).collect() }
+    }
+}
+
+impl<T> PerCpu<T> {
+    pub fn shards(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Runs `f` on the current CPU's shard.
+    pub fn with_current<R, F: FnOnce(&T) -> R>(&self, f: F) -> R {
+        f(&self.shards[current_cpu() % self.shards.len()].0)
+    }
+
+    pub fn get(&self, shard: usize) -> &T {
+        &self.shards[shard].0
+    }
+
+    /// Combines every shard, in shard order.
+    pub fn fold<B, F: FnMut(B, &T) -> B>(&self, init: B, mut f: F) -> B {
+        self.shards.iter().fold(init, |acc, shard| f(acc, &shard.0))
+    }
+}
+
+impl PerCpu<AtomicU128> {
+    /// The wrapping sum of every shard.
+    pub fn sum(&self) -> u128 {
+        self.fold(0, |acc, cell| acc.wrapping_add(cell.load(SeqCst)))
+    }
+}
+
+impl<T: Default> Default for PerCpu<T> {
+    fn default() -> Self {
+        PerCpu::new()
+    }
+}
+
+#[cfg(all(feature = "irq", target_arch = "x86_64"))]
+fn current_cpu() -> usize {
+    // Linux-style TSC_AUX: the node above bit 12, the CPU below.
+    let mut aux = 0;
+    unsafe { ::std::arch::x86_64::__rdtscp(&mut aux) };
+    (aux & 0xfff) as usize
+}
+
+#[cfg(all(target_os = "linux", not(all(feature = "irq", target_arch = "x86_64"))))]
+fn current_cpu() -> usize {
+    extern "C" {
+        fn sched_getcpu() -> i32;
+    }
+    match unsafe { sched_getcpu() } {
+        cpu if cpu >= 0 => cpu as usize,
+        _ => dealt_shard(),
+    }
+}
+
+#[cfg(not(any(target_os = "linux", all(feature = "irq", target_arch = "x86_64"))))]
+fn current_cpu() -> usize {
+    dealt_shard()
+}
+
+#[cfg_attr(all(feature = "irq", target_arch = "x86_64"), allow(dead_code))]
+fn dealt_shard() -> usize {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    thread_local!(static SHARD: Cell<usize> = const { Cell::new(usize::MAX) });
+    SHARD.with(|shard| {
+        if shard.get() == usize::MAX {
+            shard.set(NEXT.fetch_add(1, Ordering::Relaxed));
+        }
+        shard.get()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering::SeqCst;
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::PerCpu;
+    use AtomicU128;
+
+    #[test]
+    fn test_counts_add_up() {
+        let counters: Arc<PerCpu<AtomicU128>> = Arc::new(PerCpu::with_shards(4));
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let counters = counters.clone();
+                thread::spawn(move || {
+                    for _ in 0..1000 {
+                        counters.with_current(|cell| cell.fetch_add(1 << 64 | 1, SeqCst));
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(counters.sum(), 4000 << 64 | 4000);
+        let max = counters.fold(0, |max, cell| max.max(cell.load(SeqCst) as u64));
+        assert!(max > 0 && max <= 4000);
+    }
+
+    #[test]
+    fn test_shards_are_padded() {
+        let p: PerCpu<AtomicU128> = PerCpu::with_shards(2);
+        assert_eq!(p.get(1) as *const _ as usize - p.get(0) as *const _ as usize, 64);
+        assert!(PerCpu::<AtomicU128>::new().shards() >= 1);
+    }
+}
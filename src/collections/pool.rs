@@ -0,0 +1,134 @@
+use std::cell::UnsafeCell;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use halves::Halves;
+use AtomicU128;
+
+const NIL: u64 = !0;
+
+/// Fixed-capacity lock-free object pool.
+///
+/// Free slots form an index-linked list whose head is a (slot index, tag) pair
+/// in one `AtomicU128`, so `acquire` and release are each a single CAS when
+/// uncontended and the tag rules out ABA on the head.
+pub struct Pool<T> {
+    head: AtomicU128,
+    next: Vec<AtomicUsize>,
+    slots: Vec<UnsafeCell<T>>,
+}
+
+unsafe impl<T: Send> Send for Pool<T> {}
+unsafe impl<T: Send> Sync for Pool<T> {}
+
+/// An object checked out of a `Pool`; it goes back to the pool when dropped.
+pub struct Pooled<'a, T: 'a> {
+    pool: &'a Pool<T>,
+    index: usize,
+    // Sharing a `Pooled` shares the `T`, which the pool's own bounds don't
+    // cover; the impls below put them back.
+    _not_auto: PhantomData<*const ()>,
+}
+
+unsafe impl<'a, T: Send> Send for Pooled<'a, T> {}
+unsafe impl<'a, T: Sync> Sync for Pooled<'a, T> {}
+
+impl<T> Pool<T> {
+    pub fn from_vec(objects: Vec<T>) -> Self {
+        let len = objects.len();
+        Pool {
+            head: AtomicU128::from_halves(Halves::new(if len == 0 { NIL } else { 0 }, 0)),
+            next: (0..len).map(|i| AtomicUsize::new(if i + 1 == len { NIL as usize } else { i + 1 })).collect(),
+            slots: objects.into_iter().map(UnsafeCell::new).collect(),
+        }
+    }
+
+    pub fn with_capacity<F: FnMut() -> T>(capacity: usize, mut f: F) -> Self {
+        Self::from_vec((0..capacity).map(|_| f()).collect())
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn acquire(&self) -> Option<Pooled<'_, T>> {
+        let mut current = self.head.load_halves();
+        loop {
+            if current.lo == NIL {
+                return None;
+            }
+            let index = current.lo as usize;
+            let next = self.next[index].load(Ordering::Relaxed) as u64;
+            match self.head.cas_halves(current, Halves::new(next, current.hi.wrapping_add(1))) {
+                Ok(_) => return Some(Pooled { pool: self, index, _not_auto: PhantomData }),
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    fn release(&self, index: usize) {
+        let mut current = self.head.load_halves();
+        loop {
+            self.next[index].store(current.lo as usize, Ordering::Relaxed);
+            match self.head.cas_halves(current, Halves::new(index as u64, current.hi.wrapping_add(1))) {
+                Ok(_) => return,
+                Err(actual) => current = actual,
+            }
+        }
+    }
+}
+
+impl<'a, T> Pooled<'a, T> {
+    /// Slot index of this object within the pool.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+}
+
+impl<'a, T> Deref for Pooled<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.pool.slots[self.index].get() }
+    }
+}
+
+impl<'a, T> DerefMut for Pooled<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.pool.slots[self.index].get() }
+    }
+}
+
+impl<'a, T> Drop for Pooled<'a, T> {
+    fn drop(&mut self) {
+        self.pool.release(self.index);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Pool;
+
+    #[test]
+    fn test_acquire_release() {
+        let pool = Pool::from_vec(vec![1, 2]);
+        assert_eq!(pool.capacity(), 2);
+        let mut a = pool.acquire().unwrap();
+        let b = pool.acquire().unwrap();
+        assert!(pool.acquire().is_none());
+        assert_eq!((*a, *b), (1, 2));
+        *a = 10;
+        let index = a.index();
+        drop(a);
+        let c = pool.acquire().unwrap();
+        assert_eq!(c.index(), index);
+        assert_eq!(*c, 10);
+    }
+
+    #[test]
+    fn test_empty_pool() {
+        let pool = Pool::with_capacity(0, Vec::<u8>::new);
+        assert!(pool.acquire().is_none());
+    }
+}
@@ -0,0 +1,217 @@
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use halves::Halves;
+use AtomicU128;
+
+struct Node<T> {
+    // Boxed payload, so it can be claimed by a plain pointer read before the CAS.
+    value: AtomicUsize,
+    next: AtomicU128,
+    free_next: AtomicUsize,
+    _marker: PhantomData<T>,
+}
+
+/// Unbounded MPMC FIFO (Michael-Scott queue).
+///
+/// `head`, `tail` and every `next` link are (pointer, count) pairs; the count
+/// is bumped on each successful CAS, which is what makes node reuse ABA-safe.
+/// Retired nodes are kept on an internal free list until the queue is dropped.
+pub struct Queue<T> {
+    head: AtomicU128,
+    tail: AtomicU128,
+    free: AtomicU128,
+    _marker: PhantomData<T>,
+}
+
+unsafe impl<T: Send> Send for Queue<T> {}
+unsafe impl<T: Send> Sync for Queue<T> {}
+
+fn link(ptr: u64, previous: Halves) -> Halves {
+    Halves::new(ptr, previous.hi.wrapping_add(1))
+}
+
+impl<T> Queue<T> {
+    pub fn new() -> Self {
+        let dummy = Box::into_raw(Box::new(Node::<T> {
+            value: AtomicUsize::new(0),
+            next: AtomicU128::new(0),
+            free_next: AtomicUsize::new(0),
+            _marker: PhantomData,
+        }));
+        Queue {
+            head: AtomicU128::from_halves(Halves::new(dummy as u64, 0)),
+            tail: AtomicU128::from_halves(Halves::new(dummy as u64, 0)),
+            free: AtomicU128::new(0),
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        let head = self.head.load_halves().lo as *mut Node<T>;
+        unsafe { (*head).next.load_halves().lo == 0 }
+    }
+
+    pub fn push(&self, value: T) {
+        let node = self.alloc_node(value);
+        let mut tail;
+        loop {
+            tail = self.tail.load_halves();
+            let tail_node = tail.lo as *mut Node<T>;
+            let next = unsafe { (*tail_node).next.load_halves() };
+            if tail != self.tail.load_halves() {
+                continue;
+            }
+            if next.lo == 0 {
+                if unsafe { (*tail_node).next.cas_halves(next, link(node as u64, next)) }.is_ok() {
+                    break;
+                }
+            } else {
+                // Tail is lagging behind; help the other enqueuer.
+                let _ = self.tail.cas_halves(tail, link(next.lo, tail));
+            }
+        }
+        let _ = self.tail.cas_halves(tail, link(node as u64, tail));
+    }
+
+    pub fn pop(&self) -> Option<T> {
+        loop {
+            let head = self.head.load_halves();
+            let tail = self.tail.load_halves();
+            let head_node = head.lo as *mut Node<T>;
+            let next = unsafe { (*head_node).next.load_halves() };
+            if head != self.head.load_halves() {
+                continue;
+            }
+            if head.lo == tail.lo {
+                if next.lo == 0 {
+                    return None;
+                }
+                let _ = self.tail.cas_halves(tail, link(next.lo, tail));
+            } else {
+                let next_node = next.lo as *mut Node<T>;
+                let value = unsafe { (*next_node).value.load(Ordering::Relaxed) };
+                if self.head.cas_halves(head, link(next.lo, head)).is_ok() {
+                    self.free_node(head_node);
+                    return Some(*unsafe { Box::from_raw(value as *mut T) });
+                }
+            }
+        }
+    }
+
+    fn alloc_node(&self, value: T) -> *mut Node<T> {
+        let value = Box::into_raw(Box::new(value)) as usize;
+        let mut current = self.free.load_halves();
+        loop {
+            let node = current.lo as *mut Node<T>;
+            if node.is_null() {
+                return Box::into_raw(Box::new(Node {
+                    value: AtomicUsize::new(value),
+                    next: AtomicU128::new(0),
+                    free_next: AtomicUsize::new(0),
+                    _marker: PhantomData,
+                }));
+            }
+            let free_next = unsafe { (*node).free_next.load(Ordering::Relaxed) } as u64;
+            match self.free.cas_halves(current, link(free_next, current)) {
+                Ok(_) => {
+                    unsafe {
+                        (*node).value.store(value, Ordering::Relaxed);
+                        // Clear the link but keep counting, so a stale CAS can't match.
+                        let next = (*node).next.load_halves();
+                        (*node).next.store_halves(link(0, next));
+                    }
+                    return node;
+                }
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    fn free_node(&self, node: *mut Node<T>) {
+        let mut current = self.free.load_halves();
+        loop {
+            unsafe { (*node).free_next.store(current.lo as usize, Ordering::Relaxed) };
+            match self.free.cas_halves(current, link(node as u64, current)) {
+                Ok(_) => return,
+                Err(actual) => current = actual,
+            }
+        }
+    }
+}
+
+impl<T> Default for Queue<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for Queue<T> {
+    fn drop(&mut self) {
+        // The head node is the dummy; every node after it owns a value.
+        let mut node = self.head.load_halves().lo as *mut Node<T>;
+        let mut dummy = true;
+        while !node.is_null() {
+            let boxed = unsafe { Box::from_raw(node) };
+            if !dummy {
+                drop(unsafe { Box::from_raw(boxed.value.load(Ordering::Relaxed) as *mut T) });
+            }
+            dummy = false;
+            node = boxed.next.load_halves().lo as *mut Node<T>;
+        }
+        let mut node = self.free.load_halves().lo as *mut Node<T>;
+        while !node.is_null() {
+            let boxed = unsafe { Box::from_raw(node) };
+            node = boxed.free_next.load(Ordering::Relaxed) as *mut Node<T>;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use super::Queue;
+
+    #[test]
+    fn test_push_pop() {
+        let q = Queue::new();
+        assert!(q.is_empty());
+        assert_eq!(q.pop(), None);
+        q.push(1);
+        q.push(2);
+        q.push(3);
+        assert_eq!(q.pop(), Some(1));
+        q.push(4);
+        assert_eq!(q.pop(), Some(2));
+        assert_eq!(q.pop(), Some(3));
+        assert_eq!(q.pop(), Some(4));
+        assert_eq!(q.pop(), None);
+        assert!(q.is_empty());
+    }
+
+    #[test]
+    fn test_node_reuse() {
+        let q = Queue::new();
+        for i in 0..100 {
+            q.push(i);
+            q.push(i + 1);
+            assert_eq!(q.pop(), Some(i));
+            assert_eq!(q.pop(), Some(i + 1));
+        }
+        assert_eq!(q.pop(), None);
+    }
+
+    #[test]
+    fn test_drop_values() {
+        let rc = Rc::new(());
+        {
+            let q = Queue::new();
+            q.push(rc.clone());
+            q.push(rc.clone());
+            q.push(rc.clone());
+            q.pop();
+            assert_eq!(Rc::strong_count(&rc), 3);
+        }
+        assert_eq!(Rc::strong_count(&rc), 1);
+    }
+}
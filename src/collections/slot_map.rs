@@ -0,0 +1,231 @@
+use std::cell::UnsafeCell;
+use std::ops::Deref;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use halves::Halves;
+use AtomicU128;
+
+const NIL: u64 = !0;
+
+const FREE: u64 = 0;
+const OCCUPIED: u64 = 1;
+const REMOVING: u64 = 2;
+
+// Slot header: lo is the generation, hi is (pins << 2) | state.
+fn header(generation: u64, state: u64, pins: u64) -> Halves {
+    Halves::new(generation, (pins << 2) | state)
+}
+
+fn state(h: Halves) -> u64 {
+    h.hi & 3
+}
+
+fn pins(h: Halves) -> u64 {
+    h.hi >> 2
+}
+
+/// Generation-checked handle to a `SlotMap` entry.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Key {
+    index: usize,
+    generation: u64,
+}
+
+/// Fixed-capacity concurrent slot map.
+///
+/// Every slot header packs (generation, state, pin count) into one
+/// `AtomicU128`. A lookup pins the slot with a CAS that also checks the
+/// generation, so a stale `Key` simply misses; removal bumps the generation
+/// and the value is dropped once the last pin is released.
+pub struct SlotMap<T> {
+    headers: Vec<AtomicU128>,
+    values: Vec<UnsafeCell<Option<T>>>,
+    free_head: AtomicU128,
+    free_next: Vec<AtomicUsize>,
+}
+
+unsafe impl<T: Send> Send for SlotMap<T> {}
+unsafe impl<T: Send + Sync> Sync for SlotMap<T> {}
+
+/// A pinned reference to a `SlotMap` value.
+pub struct Ref<'a, T: 'a> {
+    map: &'a SlotMap<T>,
+    index: usize,
+}
+
+impl<T> SlotMap<T> {
+    pub fn with_capacity(capacity: usize) -> Self {
+        SlotMap {
+            headers: (0..capacity).map(|_| AtomicU128::from_halves(header(0, FREE, 0))).collect(),
+            values: (0..capacity).map(|_| UnsafeCell::new(None)).collect(),
+            free_head: AtomicU128::from_halves(Halves::new(if capacity == 0 { NIL } else { 0 }, 0)),
+            free_next: (0..capacity).map(|i| AtomicUsize::new(if i + 1 == capacity { NIL as usize } else { i + 1 })).collect(),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.headers.len()
+    }
+
+    /// Inserts `value`, handing it back if every slot is in use.
+    pub fn insert(&self, value: T) -> Result<Key, T> {
+        let index = match self.pop_free() {
+            Some(index) => index,
+            None => return Err(value),
+        };
+        // A slot on the free list is ours alone until it is marked occupied.
+        let generation = self.headers[index].load_halves().lo;
+        unsafe { *self.values[index].get() = Some(value) };
+        self.headers[index].store_halves(header(generation, OCCUPIED, 0));
+        Ok(Key { index, generation })
+    }
+
+    pub fn get(&self, key: Key) -> Option<Ref<'_, T>> {
+        let slot = self.headers.get(key.index)?;
+        let mut current = slot.load_halves();
+        loop {
+            if current.lo != key.generation || state(current) != OCCUPIED {
+                return None;
+            }
+            let pinned = header(current.lo, OCCUPIED, pins(current) + 1);
+            match slot.cas_halves(current, pinned) {
+                Ok(_) => return Some(Ref { map: self, index: key.index }),
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    pub fn contains_key(&self, key: Key) -> bool {
+        match self.headers.get(key.index) {
+            Some(slot) => {
+                let current = slot.load_halves();
+                current.lo == key.generation && state(current) == OCCUPIED
+            }
+            None => false,
+        }
+    }
+
+    /// Removes the entry; its value is dropped once no `Ref` pins it.
+    pub fn remove(&self, key: Key) -> bool {
+        let slot = match self.headers.get(key.index) {
+            Some(slot) => slot,
+            None => return false,
+        };
+        let mut current = slot.load_halves();
+        loop {
+            if current.lo != key.generation || state(current) != OCCUPIED {
+                return false;
+            }
+            let removing = header(current.lo, REMOVING, pins(current));
+            match slot.cas_halves(current, removing) {
+                Ok(_) => {
+                    if pins(current) == 0 {
+                        self.reclaim(key.index, current.lo);
+                    }
+                    return true;
+                }
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    fn unpin(&self, index: usize) {
+        let slot = &self.headers[index];
+        let mut current = slot.load_halves();
+        loop {
+            let unpinned = header(current.lo, state(current), pins(current) - 1);
+            match slot.cas_halves(current, unpinned) {
+                Ok(_) => {
+                    if state(current) == REMOVING && pins(current) == 1 {
+                        self.reclaim(index, current.lo);
+                    }
+                    return;
+                }
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    // Called by whoever saw the slot reach (REMOVING, 0 pins).
+    fn reclaim(&self, index: usize, generation: u64) {
+        drop(unsafe { (*self.values[index].get()).take() });
+        self.headers[index].store_halves(header(generation.wrapping_add(1), FREE, 0));
+        self.push_free(index);
+    }
+
+    fn pop_free(&self) -> Option<usize> {
+        let mut current = self.free_head.load_halves();
+        loop {
+            if current.lo == NIL {
+                return None;
+            }
+            let next = self.free_next[current.lo as usize].load(Ordering::Relaxed) as u64;
+            match self.free_head.cas_halves(current, Halves::new(next, current.hi.wrapping_add(1))) {
+                Ok(_) => return Some(current.lo as usize),
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    fn push_free(&self, index: usize) {
+        let mut current = self.free_head.load_halves();
+        loop {
+            self.free_next[index].store(current.lo as usize, Ordering::Relaxed);
+            match self.free_head.cas_halves(current, Halves::new(index as u64, current.hi.wrapping_add(1))) {
+                Ok(_) => return,
+                Err(actual) => current = actual,
+            }
+        }
+    }
+}
+
+impl<'a, T> Deref for Ref<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { (*self.map.values[self.index].get()).as_ref().unwrap() }
+    }
+}
+
+impl<'a, T> Drop for Ref<'a, T> {
+    fn drop(&mut self) {
+        self.map.unpin(self.index);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use super::SlotMap;
+
+    #[test]
+    fn test_insert_get_remove() {
+        let map = SlotMap::with_capacity(2);
+        let a = map.insert("a").unwrap();
+        let b = map.insert("b").unwrap();
+        assert_eq!(map.insert("c"), Err("c"));
+        assert_eq!(*map.get(a).unwrap(), "a");
+        assert_eq!(*map.get(b).unwrap(), "b");
+        assert!(map.remove(a));
+        assert!(!map.remove(a));
+        assert!(map.get(a).is_none());
+        let c = map.insert("c").unwrap();
+        assert!(c != a);
+        assert!(!map.contains_key(a));
+        assert_eq!(*map.get(c).unwrap(), "c");
+    }
+
+    #[test]
+    fn test_remove_while_pinned() {
+        let rc = Rc::new(());
+        let map = SlotMap::with_capacity(1);
+        let key = map.insert(rc.clone()).unwrap();
+        let pinned = map.get(key).unwrap();
+        assert!(map.remove(key));
+        assert!(map.get(key).is_none());
+        assert_eq!(Rc::strong_count(&rc), 2);
+        drop(pinned);
+        assert_eq!(Rc::strong_count(&rc), 1);
+        assert!(map.insert(rc.clone()).is_ok());
+    }
+}
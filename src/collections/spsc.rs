@@ -0,0 +1,147 @@
+use std::cell::UnsafeCell;
+use std::sync::Arc;
+
+use halves::Halves;
+use AtomicU128;
+
+// lo is the consumer's head index, hi the producer's tail index.
+struct Ring<T> {
+    indices: AtomicU128,
+    buffer: Vec<UnsafeCell<Option<T>>>,
+}
+
+unsafe impl<T: Send> Send for Ring<T> {}
+unsafe impl<T: Send> Sync for Ring<T> {}
+
+impl<T> Ring<T> {
+    fn len(&self) -> usize {
+        let indices = self.indices.load_halves();
+        indices.hi.wrapping_sub(indices.lo) as usize
+    }
+
+    fn slot(&self, index: u64) -> *mut Option<T> {
+        self.buffer[(index % self.buffer.len() as u64) as usize].get()
+    }
+}
+
+/// Creates a single-producer single-consumer ring of the given capacity.
+///
+/// Head and tail share one `AtomicU128`: each side only ever rewrites its own
+/// half, but both halves are always read together, so occupancy is one load.
+pub fn spsc_ring<T>(capacity: usize) -> (Producer<T>, Consumer<T>) {
+    assert!(capacity > 0, "capacity must be non-zero");
+    let ring = Arc::new(Ring {
+        indices: AtomicU128::new(0),
+        buffer: (0..capacity).map(|_| UnsafeCell::new(None)).collect(),
+    });
+    (Producer { ring: ring.clone() }, Consumer { ring })
+}
+
+pub struct Producer<T> {
+    ring: Arc<Ring<T>>,
+}
+
+pub struct Consumer<T> {
+    ring: Arc<Ring<T>>,
+}
+
+unsafe impl<T: Send> Send for Producer<T> {}
+unsafe impl<T: Send> Send for Consumer<T> {}
+
+impl<T> Producer<T> {
+    pub fn push(&mut self, value: T) -> Result<(), T> {
+        let mut current = self.ring.indices.load_halves();
+        let tail = current.hi;
+        if tail.wrapping_sub(current.lo) as usize == self.ring.buffer.len() {
+            return Err(value);
+        }
+        unsafe { *self.ring.slot(tail) = Some(value) };
+        loop {
+            let new = Halves::new(current.lo, tail.wrapping_add(1));
+            match self.ring.indices.cas_halves(current, new) {
+                Ok(_) => return Ok(()),
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.ring.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len() == self.capacity()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.ring.buffer.len()
+    }
+}
+
+impl<T> Consumer<T> {
+    pub fn pop(&mut self) -> Option<T> {
+        let mut current = self.ring.indices.load_halves();
+        let head = current.lo;
+        if head == current.hi {
+            return None;
+        }
+        let value = unsafe { (*self.ring.slot(head)).take() };
+        loop {
+            let new = Halves::new(head.wrapping_add(1), current.hi);
+            match self.ring.indices.cas_halves(current, new) {
+                Ok(_) => return value,
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.ring.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.ring.buffer.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::spsc_ring;
+
+    #[test]
+    fn test_push_pop() {
+        let (mut tx, mut rx) = spsc_ring(2);
+        assert!(rx.is_empty());
+        assert_eq!(tx.push(1), Ok(()));
+        assert_eq!(tx.push(2), Ok(()));
+        assert!(tx.is_full());
+        assert_eq!(tx.push(3), Err(3));
+        assert_eq!(rx.len(), 2);
+        assert_eq!(rx.pop(), Some(1));
+        assert_eq!(tx.push(3), Ok(()));
+        assert_eq!(rx.pop(), Some(2));
+        assert_eq!(rx.pop(), Some(3));
+        assert_eq!(rx.pop(), None);
+        assert_eq!(tx.len(), 0);
+    }
+
+    #[test]
+    fn test_wrap_around() {
+        let (mut tx, mut rx) = spsc_ring(3);
+        for i in 0..10 {
+            tx.push(i).unwrap();
+            tx.push(i + 100).unwrap();
+            assert_eq!(rx.pop(), Some(i));
+            assert_eq!(rx.pop(), Some(i + 100));
+        }
+        assert_eq!(tx.capacity(), 3);
+    }
+}
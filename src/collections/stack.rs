@@ -0,0 +1,223 @@
+use std::cell::UnsafeCell;
+use std::marker::PhantomData;
+use std::ptr;
+use std::sync::atomic::{AtomicPtr, Ordering};
+
+use AtomicTaggedPtr;
+
+pub(super) struct Node<T> {
+    value: UnsafeCell<Option<T>>,
+    next: AtomicPtr<Node<T>>,
+}
+
+/// Lock-free Treiber stack.
+///
+/// The head is an `AtomicTaggedPtr`, whose tag is bumped on every successful
+/// CAS so a recycled node can't make a stale CAS succeed (ABA).
+/// Popped nodes go to an internal free list and are only deallocated on drop,
+/// so a racing pop never reads freed memory.
+pub struct Stack<T> {
+    head: AtomicTaggedPtr<Node<T>>,
+    free: AtomicTaggedPtr<Node<T>>,
+    _marker: PhantomData<T>,
+}
+
+unsafe impl<T: Send> Send for Stack<T> {}
+unsafe impl<T: Send> Sync for Stack<T> {}
+
+fn push_node<T>(list: &AtomicTaggedPtr<Node<T>>, node: *mut Node<T>) {
+    let mut current = list.load();
+    loop {
+        unsafe { (*node).next.store(current.0, Ordering::Relaxed) };
+        match list.compare_exchange(current, node) {
+            Ok(_) => return,
+            Err(actual) => current = actual,
+        }
+    }
+}
+
+fn pop_node<T>(list: &AtomicTaggedPtr<Node<T>>) -> *mut Node<T> {
+    let mut current = list.load();
+    loop {
+        let node = current.0;
+        if node.is_null() {
+            return node;
+        }
+        // The node may already be popped and recycled by someone else; that's fine
+        // since nodes stay allocated and the tag makes the CAS below fail.
+        let next = unsafe { (*node).next.load(Ordering::Relaxed) };
+        match list.compare_exchange(current, next) {
+            Ok(_) => return node,
+            Err(actual) => current = actual,
+        }
+    }
+}
+
+fn try_push_node<T>(list: &AtomicTaggedPtr<Node<T>>, node: *mut Node<T>) -> bool {
+    let current = list.load();
+    unsafe { (*node).next.store(current.0, Ordering::Relaxed) };
+    list.compare_exchange(current, node).is_ok()
+}
+
+fn try_pop_node<T>(list: &AtomicTaggedPtr<Node<T>>) -> Result<*mut Node<T>, ()> {
+    let current = list.load();
+    let node = current.0;
+    if node.is_null() {
+        return Ok(node);
+    }
+    let next = unsafe { (*node).next.load(Ordering::Relaxed) };
+    list.compare_exchange(current, next).map(|_| node).map_err(|_| ())
+}
+
+fn take_all<T>(list: &AtomicTaggedPtr<Node<T>>) -> *mut Node<T> {
+    let mut current = list.load();
+    loop {
+        if current.0.is_null() {
+            return current.0;
+        }
+        match list.compare_exchange(current, ptr::null_mut()) {
+            Ok(_) => return current.0,
+            Err(actual) => current = actual,
+        }
+    }
+}
+
+impl<T> Stack<T> {
+    pub fn new() -> Self {
+        Stack {
+            head: AtomicTaggedPtr::default(),
+            free: AtomicTaggedPtr::default(),
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.head.load().0.is_null()
+    }
+
+    pub fn push(&self, value: T) {
+        let node = self.alloc_node(value);
+        push_node(&self.head, node);
+    }
+
+    pub fn pop(&self) -> Option<T> {
+        let node = pop_node::<T>(&self.head);
+        if node.is_null() {
+            return None;
+        }
+        self.release_node(node)
+    }
+
+    pub(super) fn alloc_node(&self, value: T) -> *mut Node<T> {
+        let mut node = pop_node::<T>(&self.free);
+        if node.is_null() {
+            node = Box::into_raw(Box::new(Node {
+                value: UnsafeCell::new(None),
+                next: AtomicPtr::new(ptr::null_mut()),
+            }));
+        }
+        unsafe { *(*node).value.get() = Some(value) };
+        node
+    }
+
+    /// Takes the value out of a node the caller owns and recycles the node.
+    pub(super) fn release_node(&self, node: *mut Node<T>) -> Option<T> {
+        let value = unsafe { (*(*node).value.get()).take() };
+        push_node(&self.free, node);
+        value
+    }
+
+    /// Single CAS attempt on the head; `false` means it was contended.
+    pub(super) fn try_push_node(&self, node: *mut Node<T>) -> bool {
+        try_push_node(&self.head, node)
+    }
+
+    /// Single CAS attempt on the head; `Ok(null)` means empty, `Err` contended.
+    pub(super) fn try_pop_node(&self) -> Result<*mut Node<T>, ()> {
+        try_pop_node(&self.head)
+    }
+
+    /// Detaches the whole stack with a single CAS, returning the items in pop order.
+    pub fn try_pop_all(&self) -> Option<Vec<T>> {
+        let mut node = take_all(&self.head);
+        if node.is_null() {
+            return None;
+        }
+        let mut items = Vec::new();
+        while !node.is_null() {
+            let next = unsafe { (*node).next.load(Ordering::Relaxed) };
+            if let Some(value) = unsafe { (*(*node).value.get()).take() } {
+                items.push(value);
+            }
+            push_node(&self.free, node);
+            node = next;
+        }
+        Some(items)
+    }
+}
+
+impl<T> Default for Stack<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for Stack<T> {
+    fn drop(&mut self) {
+        for list in &[&self.head, &self.free] {
+            let mut node = list.load().0;
+            while !node.is_null() {
+                let boxed = unsafe { Box::from_raw(node) };
+                node = boxed.next.load(Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use super::Stack;
+
+    #[test]
+    fn test_push_pop() {
+        let s = Stack::new();
+        assert!(s.is_empty());
+        s.push(1);
+        s.push(2);
+        s.push(3);
+        assert_eq!(s.pop(), Some(3));
+        assert_eq!(s.pop(), Some(2));
+        s.push(4);
+        assert_eq!(s.pop(), Some(4));
+        assert_eq!(s.pop(), Some(1));
+        assert_eq!(s.pop(), None);
+        assert!(s.is_empty());
+    }
+
+    #[test]
+    fn test_try_pop_all() {
+        let s = Stack::new();
+        assert_eq!(s.try_pop_all(), None);
+        for i in 0..4 {
+            s.push(i);
+        }
+        assert_eq!(s.try_pop_all(), Some(vec![3, 2, 1, 0]));
+        assert!(s.is_empty());
+        s.push(5);
+        assert_eq!(s.pop(), Some(5));
+    }
+
+    #[test]
+    fn test_drop_values() {
+        let rc = Rc::new(());
+        {
+            let s = Stack::new();
+            s.push(rc.clone());
+            s.push(rc.clone());
+            s.pop();
+            assert_eq!(Rc::strong_count(&rc), 2);
+        }
+        assert_eq!(Rc::strong_count(&rc), 1);
+    }
+}
@@ -0,0 +1,119 @@
+use core::sync::atomic::Ordering;
+
+use ::atomic_traits::fetch::{Add, And, Max, Min, Nand, Or, Sub, Update, Xor};
+use ::atomic_traits::{Atomic, Bitwise, NumOps};
+
+use {AtomicI128, AtomicU128};
+
+macro_rules! impl_atomic_traits {
+    ($atomic:ident: $int:ty) => {
+        impl Atomic for $atomic {
+            type Type = $int;
+
+            fn new(v: $int) -> Self {
+                $atomic::new(v)
+            }
+
+            fn get_mut(&mut self) -> &mut $int {
+                $atomic::get_mut(self)
+            }
+
+            fn into_inner(self) -> $int {
+                $atomic::into_inner(self)
+            }
+
+            fn load(&self, order: Ordering) -> $int {
+                $atomic::load(self, order)
+            }
+
+            fn store(&self, val: $int, order: Ordering) {
+                $atomic::store(self, val, order)
+            }
+
+            fn swap(&self, val: $int, order: Ordering) -> $int {
+                $atomic::swap(self, val, order)
+            }
+
+            #[allow(deprecated)]
+            fn compare_and_swap(&self, current: $int, new: $int, order: Ordering) -> $int {
+                $atomic::compare_and_swap(self, current, new, order)
+            }
+
+            fn compare_exchange(&self, current: $int, new: $int, success: Ordering, failure: Ordering) -> Result<$int, $int> {
+                $atomic::compare_exchange(self, current, new, success, failure)
+            }
+
+            fn compare_exchange_weak(&self, current: $int, new: $int, success: Ordering, failure: Ordering) -> Result<$int, $int> {
+                $atomic::compare_exchange_weak(self, current, new, success, failure)
+            }
+        }
+
+        impl_atomic_traits!(__fetch $atomic: $int; Add fetch_add, Sub fetch_sub, Max fetch_max, Min fetch_min);
+        impl_atomic_traits!(__fetch $atomic: $int; And fetch_and, Nand fetch_nand, Or fetch_or, Xor fetch_xor);
+
+        impl Update for $atomic {
+            type Type = $int;
+
+            fn fetch_update<F>(&self, set_order: Ordering, fetch_order: Ordering, f: F) -> Result<$int, $int>
+            where
+                F: FnMut($int) -> Option<$int>,
+            {
+                $atomic::fetch_update(self, set_order, fetch_order, f)
+            }
+        }
+
+        impl NumOps for $atomic {}
+
+        impl Bitwise for $atomic {}
+    };
+    (__fetch $atomic:ident: $int:ty; $($trait_:ident $method:ident),*) => {
+        $(
+            impl $trait_ for $atomic {
+                type Type = $int;
+
+                fn $method(&self, val: $int, order: Ordering) -> $int {
+                    $atomic::$method(self, val, order)
+                }
+            }
+        )*
+    };
+}
+
+impl_atomic_traits!(AtomicU128: u128);
+impl_atomic_traits!(AtomicI128: i128);
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering::SeqCst;
+
+    use ::atomic_traits::{Atomic, Bitwise, NumOps};
+
+    use {AtomicI128, AtomicU128};
+
+    fn bump<A: NumOps + Bitwise + Atomic<Type = u128>>(a: &A) -> u128 {
+        a.fetch_add(1 << 64, SeqCst);
+        a.fetch_or(1, SeqCst);
+        a.load(SeqCst)
+    }
+
+    #[test]
+    fn test_generic_use() {
+        let mut a = <AtomicU128 as Atomic>::new(u64::MAX as u128);
+        assert_eq!(bump(&a), (1 << 64) | u64::MAX as u128);
+        assert_eq!(a.fetch_sub(u64::MAX as u128, SeqCst), (1 << 64) | u64::MAX as u128);
+        assert_eq!(a.fetch_max(3 << 64, SeqCst), 1 << 64);
+        *a.get_mut() += 1;
+        assert_eq!(a.into_inner(), (3 << 64) + 1);
+    }
+
+    #[test]
+    fn test_signed() {
+        fn lowest<A: NumOps + Atomic<Type = i128>>(a: &A, v: i128) -> i128 {
+            a.fetch_min(v, SeqCst);
+            a.load(SeqCst)
+        }
+        let a = <AtomicI128 as Atomic>::new(-1);
+        assert_eq!(lowest(&a, -7), -7);
+        assert_eq!(lowest(&a, 3), -7);
+    }
+}
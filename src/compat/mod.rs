@@ -0,0 +1,10 @@
+// Interop with other atomics crates, each behind the feature of the same
+// name. The inherent methods already have std's signatures, so the trait
+// impls only forward to them.
+
+#[cfg(feature = "atomic-traits")]
+mod atomic_traits;
+#[cfg(feature = "radium")]
+mod radium;
+#[cfg(feature = "portable-atomic")]
+pub mod portable_atomic;
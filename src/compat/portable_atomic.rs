@@ -0,0 +1,61 @@
+use core::sync::atomic::Ordering;
+
+use ::portable_atomic;
+
+use align;
+use AtomicU128;
+
+// Backend for the `portable-atomic` feature: the word is reinterpreted as a
+// `portable_atomic::AtomicU128`, which has the same size and alignment and
+// covers targets without cmpxchg16b. Loads and stores go to its own, so they
+// get whatever it does best for them, a plain vector load where it can.
+fn cell(src: &AtomicU128) -> &portable_atomic::AtomicU128 {
+    align::debug_check(src);
+    unsafe { &*(src as *const AtomicU128 as *const portable_atomic::AtomicU128) }
+}
+
+pub fn cas128(src: &AtomicU128, cmp: &mut u128, with: u128) -> bool {
+    match cell(src).compare_exchange(*cmp, with, Ordering::SeqCst, Ordering::SeqCst) {
+        Ok(_) => true,
+        Err(actual) => {
+            *cmp = actual;
+            false
+        }
+    }
+}
+
+pub fn load(src: &AtomicU128) -> u128 {
+    cell(src).load(Ordering::SeqCst)
+}
+
+pub fn store(src: &AtomicU128, val: u128) {
+    cell(src).store(val, Ordering::SeqCst)
+}
+
+impl From<portable_atomic::AtomicU128> for AtomicU128 {
+    fn from(cell: portable_atomic::AtomicU128) -> Self {
+        AtomicU128::new(cell.into_inner())
+    }
+}
+
+impl From<AtomicU128> for portable_atomic::AtomicU128 {
+    fn from(word: AtomicU128) -> Self {
+        portable_atomic::AtomicU128::new(word.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering;
+
+    use ::portable_atomic;
+
+    use AtomicU128;
+
+    #[test]
+    fn test_conversions() {
+        let theirs = portable_atomic::AtomicU128::from(AtomicU128::new(2 << 64 | 1));
+        assert_eq!(theirs.load(Ordering::SeqCst), (2 << 64) | 1);
+        assert_eq!(AtomicU128::from(theirs).into_inner(), 2 << 64 | 1);
+    }
+}
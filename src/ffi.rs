@@ -0,0 +1,71 @@
+//! C interface to `AtomicU128`.
+//!
+//! Words are plain `unsigned __int128`s, which have the same size and 16-byte
+//! alignment in C as `AtomicU128` has in Rust, so both sides can work on one
+//! shared word. The header is `include/atomic128.h`; regenerate it after
+//! changing this file with
+//! `cbindgen --config cbindgen.toml --output include/atomic128.h`. The
+//! static library to link is built by the `atomic128-ffi` crate in `ffi/`.
+//!
+//! Every function takes a mutable pointer rather than a const one, since
+//! without AVX even a load is a `cmpxchg16b` and needs the word to be
+//! writable.
+//!
+//! # Safety
+//!
+//! All pointers must be non-null and point to live, 16-byte aligned words.
+
+#![allow(clippy::missing_safety_doc)]
+
+use core::sync::atomic::Ordering::SeqCst;
+
+use AtomicU128;
+
+#[no_mangle]
+pub unsafe extern "C" fn atomic128_load(ptr: *mut u128) -> u128 {
+    AtomicU128::from_ptr(ptr).load(SeqCst)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn atomic128_store(ptr: *mut u128, val: u128) {
+    AtomicU128::from_ptr(ptr).store(val, SeqCst)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn atomic128_swap(ptr: *mut u128, val: u128) -> u128 {
+    AtomicU128::from_ptr(ptr).swap(val, SeqCst)
+}
+
+/// Replaces `*ptr` with `desired` if it equals `*expected`. On failure the
+/// current value is written back to `*expected`, as with C11
+/// `atomic_compare_exchange_strong`.
+#[no_mangle]
+pub unsafe extern "C" fn atomic128_cas(ptr: *mut u128, expected: *mut u128, desired: u128) -> bool {
+    match AtomicU128::from_ptr(ptr).compare_exchange(*expected, desired, SeqCst, SeqCst) {
+        Ok(_) => true,
+        Err(actual) => {
+            *expected = actual;
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{atomic128_cas, atomic128_load, atomic128_store, atomic128_swap};
+
+    #[test]
+    fn test_ffi_ops() {
+        let mut a: u128 = 2 << 64 | 1;
+        unsafe {
+            assert_eq!(atomic128_load(&mut a), 2 << 64 | 1);
+            atomic128_store(&mut a, 4 << 64 | 3);
+            assert_eq!(atomic128_swap(&mut a, 6 << 64 | 5), 4 << 64 | 3);
+            let mut expected = 0;
+            assert!(!atomic128_cas(&mut a, &mut expected, 8 << 64 | 7));
+            assert_eq!(expected, 6 << 64 | 5);
+            assert!(atomic128_cas(&mut a, &mut expected, 8 << 64 | 7));
+            assert_eq!(atomic128_load(&mut a), 8 << 64 | 7);
+        }
+    }
+}
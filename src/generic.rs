@@ -0,0 +1,141 @@
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem;
+use core::sync::atomic::Ordering::{self, SeqCst};
+
+use backend;
+use current_backoff;
+use trace;
+use {fmt_atomic, AtomicU128, NoPadding};
+
+/// `atomic::Atomic<T>`-shaped wrapper for 16-byte types without padding.
+///
+/// Generic code written against the `atomic` crate's `Atomic<T>` ends up on
+/// its spinlock fallback for 128-bit types; this has the same method set but
+/// is backed by `AtomicU128`. Values are stored and compared as their raw
+/// bytes, hence the `NoPadding` bound. Any other size fails to compile:
+///
+/// ```compile_fail
+/// atomic128::Atomic::new(0u64);
+/// ```
+///
+/// Orderings are accepted for compatibility and ignored.
+pub struct Atomic<T> {
+    word: AtomicU128,
+    _marker: PhantomData<T>,
+}
+
+unsafe impl<T: NoPadding + Send> Sync for Atomic<T> {}
+
+fn to_word<T: NoPadding>(value: T) -> u128 {
+    unsafe { mem::transmute_copy(&value) }
+}
+
+fn from_word<T: NoPadding>(word: u128) -> T {
+    unsafe { mem::transmute_copy(&word) }
+}
+
+impl<T: NoPadding> Atomic<T> {
+    pub fn new(value: T) -> Self {
+        const { assert!(mem::size_of::<T>() == 16, "Atomic<T> needs a 16-byte T") };
+        Atomic { word: AtomicU128::new(to_word(value)), _marker: PhantomData }
+    }
+
+    /// Whether operations compile down to lock-free instructions with the
+    /// backend in use, the same as `backend().is_lock_free()`.
+    pub fn is_lock_free() -> bool {
+        backend().is_lock_free()
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        unsafe { &mut *(self.word.get_mut() as *mut u128 as *mut T) }
+    }
+
+    pub fn into_inner(self) -> T {
+        from_word(self.word.into_inner())
+    }
+
+    pub fn load(&self, _: Ordering) -> T {
+        from_word(self.word.load(SeqCst))
+    }
+
+    pub fn store(&self, value: T, _: Ordering) {
+        self.word.store(to_word(value), SeqCst)
+    }
+
+    pub fn swap(&self, value: T, _: Ordering) -> T {
+        from_word(self.word.swap(to_word(value), SeqCst))
+    }
+
+    pub fn compare_exchange(&self, current: T, new: T, _: Ordering, _: Ordering) -> Result<T, T> {
+        self.word.compare_exchange(to_word(current), to_word(new), SeqCst, SeqCst).map(from_word).map_err(from_word)
+    }
+
+    pub fn compare_exchange_weak(&self, current: T, new: T, success: Ordering, failure: Ordering) -> Result<T, T> {
+        self.compare_exchange(current, new, success, failure)
+    }
+
+    #[cfg_attr(feature = "tracing", track_caller)]
+    pub fn fetch_update<F>(&self, _: Ordering, _: Ordering, mut f: F) -> Result<T, T>
+    where
+        F: FnMut(T) -> Option<T>,
+    {
+        let mut current = self.word.load(SeqCst);
+        let mut retries = 0;
+        loop {
+            let new = match f(from_word(current)) {
+                Some(new) => to_word(new),
+                None => return Err(from_word(current)),
+            };
+            match self.word.compare_exchange(current, new, SeqCst, SeqCst) {
+                Ok(previous) => return Ok(from_word(previous)),
+                Err(actual) => current = actual,
+            }
+            retries += 1;
+            trace::cas_retry(&self.word, retries);
+            current_backoff().wait(retries);
+        }
+    }
+}
+
+impl<T: NoPadding + fmt::Debug> fmt::Debug for Atomic<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt_atomic(f, "Atomic", &self.load(SeqCst))
+    }
+}
+
+impl<T: NoPadding + Default> Default for Atomic<T> {
+    fn default() -> Self {
+        Atomic::new(T::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering::SeqCst;
+    use super::Atomic;
+    use NoPadding;
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    struct Pair(u64, i64);
+
+    unsafe impl NoPadding for Pair {}
+
+    #[test]
+    fn test_ops() {
+        let a = Atomic::new(Pair(1, -1));
+        assert_eq!(a.load(SeqCst), Pair(1, -1));
+        assert_eq!(a.swap(Pair(2, -2), SeqCst), Pair(1, -1));
+        assert_eq!(a.compare_exchange(Pair(0, 0), Pair(3, -3), SeqCst, SeqCst), Err(Pair(2, -2)));
+        assert_eq!(a.compare_exchange(Pair(2, -2), Pair(3, -3), SeqCst, SeqCst), Ok(Pair(2, -2)));
+        assert_eq!(a.fetch_update(SeqCst, SeqCst, |p| Some(Pair(p.0 + 1, p.1))), Ok(Pair(3, -3)));
+        assert_eq!(a.into_inner(), Pair(4, -3));
+    }
+
+    #[test]
+    fn test_u128() {
+        let mut a: Atomic<u128> = Atomic::default();
+        *a.get_mut() = 1 << 100;
+        assert_eq!(a.load(SeqCst), 1 << 100);
+    }
+}
@@ -0,0 +1,78 @@
+use core::sync::atomic::Ordering::SeqCst;
+
+use AtomicU128;
+
+// Most structures in this crate keep two 64-bit fields in one word, with a
+// comment saying what `lo` and `hi` hold. They work on the value as a
+// `Halves` through these helpers rather than shifting by hand at every use.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Halves {
+    pub lo: u64,
+    pub hi: u64,
+}
+
+impl Halves {
+    pub const fn new(lo: u64, hi: u64) -> Self {
+        Halves { lo, hi }
+    }
+
+    pub const fn from_bits(bits: u128) -> Self {
+        Halves { lo: bits as u64, hi: (bits >> 64) as u64 }
+    }
+
+    pub const fn bits(self) -> u128 {
+        (self.hi as u128) << 64 | self.lo as u128
+    }
+}
+
+impl AtomicU128 {
+    pub(crate) const fn from_halves(value: Halves) -> Self {
+        AtomicU128::new(value.bits())
+    }
+
+    pub(crate) fn load_halves(&self) -> Halves {
+        Halves::from_bits(self.load(SeqCst))
+    }
+
+    pub(crate) fn store_halves(&self, value: Halves) {
+        self.store(value.bits(), SeqCst)
+    }
+
+    #[cfg(feature = "std")]
+    pub(crate) fn swap_halves(&self, value: Halves) -> Halves {
+        Halves::from_bits(self.swap(value.bits(), SeqCst))
+    }
+
+    pub(crate) fn cas_halves(&self, current: Halves, new: Halves) -> Result<Halves, Halves> {
+        self.compare_exchange(current.bits(), new.bits(), SeqCst, SeqCst)
+            .map(Halves::from_bits)
+            .map_err(Halves::from_bits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Halves;
+    use AtomicU128;
+
+    #[test]
+    fn test_halves_roundtrip() {
+        let h = Halves::new(1, 2);
+        assert_eq!(h.bits(), 2 << 64 | 1);
+        assert_eq!(Halves::from_bits(h.bits()), h);
+        let a = AtomicU128::from_halves(h);
+        assert_eq!(a.cas_halves(Halves::new(1, 2), Halves::new(3, 4)), Ok(h));
+        assert_eq!(a.load_halves(), Halves::new(3, 4));
+    }
+}
+
+#[cfg(kani)]
+mod proofs {
+    use super::Halves;
+
+    #[kani::proof]
+    fn from_bits_roundtrip() {
+        let bits: u128 = kani::any();
+        assert_eq!(Halves::from_bits(bits).bits(), bits);
+    }
+}
@@ -0,0 +1,130 @@
+//! Interrupt-context helpers for x86_64 kernels, behind the `irq` feature.
+//! Builds without `std`, for `no_std` kernels.
+//!
+//! On the native cmpxchg16b backend `AtomicU128::new`, `load`,
+//! `compare_exchange`, `compare_exchange_weak` and everything in this module
+//! are lock-free and safe to call from an interrupt handler. `store`, `swap`
+//! and the `fetch_*` methods are not: they wait through the installed
+//! `Backoff`, which with `std` may yield or park. The `store`, `swap`,
+//! `fetch_add` and `fetch_update` here spin instead. The lock and seqlock
+//! fallbacks, `portable-atomic` and `chaos` are never safe: a handler that
+//! interrupts a lock holder on the same CPU spins forever.
+//! `AtomicU128::is_irq_safe` says whether this build is the safe kind. For
+//! the rare update that can't be written as a CAS, `modify_local` runs it
+//! with interrupts off on per-CPU data. Nothing here allocates.
+
+use core::arch::asm;
+use core::ptr;
+
+pub use spin::{fetch_add, fetch_update, store, swap};
+use AtomicU128;
+
+const IF: u64 = 1 << 9;
+
+fn rflags() -> u64 {
+    let flags: u64;
+    unsafe { asm!("pushfq", "pop {}", out(reg) flags, options(nomem, preserves_flags)) };
+    flags
+}
+
+/// Whether interrupts are enabled on this CPU.
+pub fn interrupts_enabled() -> bool {
+    rflags() & IF != 0
+}
+
+/// Interrupts held off on this CPU until the guard drops, which re-enables
+/// them only if they were on when it was taken, so guards nest.
+pub struct IrqGuard {
+    was_enabled: bool,
+    // Interrupt state is per CPU; the guard mustn't move to another thread.
+    _not_send: *const (),
+}
+
+impl IrqGuard {
+    /// # Safety
+    ///
+    /// `cli` is privileged: this must run in ring 0 (or with IOPL 3).
+    pub unsafe fn new() -> Self {
+        let was_enabled = interrupts_enabled();
+        asm!("cli", options(nostack));
+        IrqGuard { was_enabled, _not_send: ptr::null() }
+    }
+}
+
+impl Drop for IrqGuard {
+    fn drop(&mut self) {
+        if self.was_enabled {
+            unsafe { asm!("sti", options(nostack)) };
+        }
+    }
+}
+
+/// Runs `f` with interrupts disabled on this CPU.
+///
+/// # Safety
+///
+/// As for `IrqGuard::new`.
+pub unsafe fn without_interrupts<R, F: FnOnce() -> R>(f: F) -> R {
+    let _guard = IrqGuard::new();
+    f()
+}
+
+impl AtomicU128 {
+    /// Whether `load`, `compare_exchange` and the spinning operations in
+    /// `irq` are lock-free in this build, so they're safe to call from
+    /// interrupt context. The backoff-driven `store`, `swap` and `fetch_*`
+    /// methods aren't covered either way.
+    pub const fn is_irq_safe() -> bool {
+        cfg!(all(
+            feature = "nightly",
+            not(feature = "detect-runtime"),
+            not(feature = "portable-atomic"),
+            not(feature = "chaos"),
+            not(loom),
+            not(shuttle),
+            not(replay)
+        ))
+    }
+
+    /// Applies `f` to the value with plain reads and writes while interrupts
+    /// are disabled, for read-modify-write sequences that can't be expressed
+    /// as a CAS. That makes the update atomic with respect to this CPU's
+    /// interrupt handlers, but not to other CPUs.
+    ///
+    /// # Safety
+    ///
+    /// The word must only ever be accessed from this CPU, like per-CPU run
+    /// queue or IST bookkeeping, and the caller must be allowed to execute
+    /// `cli` as for `IrqGuard::new`.
+    pub unsafe fn modify_local<R, F: FnOnce(&mut u128) -> R>(&self, f: F) -> R {
+        without_interrupts(|| {
+            let mut value = ptr::read_volatile(self.as_ptr());
+            let result = f(&mut value);
+            ptr::write_volatile(self.as_ptr(), value);
+            result
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fetch_update, interrupts_enabled, store, swap};
+    use AtomicU128;
+
+    #[test]
+    fn test_user_mode_state() {
+        // User space always runs with interrupts on; cli would fault here.
+        assert!(interrupts_enabled());
+        let locking = cfg!(any(feature = "portable-atomic", feature = "detect-runtime", feature = "chaos"));
+        assert_eq!(AtomicU128::is_irq_safe(), !locking);
+    }
+
+    #[test]
+    fn test_spinning_ops() {
+        let word = AtomicU128::new(1);
+        store(&word, 2);
+        assert_eq!(swap(&word, 3), 2);
+        assert_eq!(fetch_update(&word, |v| Some(v << 64)), Ok(3));
+        assert_eq!(word.load(::core::sync::atomic::Ordering::SeqCst), 3 << 64);
+    }
+}
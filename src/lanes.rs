@@ -0,0 +1,143 @@
+use core::sync::atomic::Ordering;
+
+use AtomicU128;
+
+macro_rules! lanes {
+    ($name:ident, $lane:ident, $n:expr) => {
+        /// A word of equal-width lanes, for small records of counters that
+        /// have to be read together.
+        ///
+        /// Each lane op is a CAS on the whole word, so adding to one lane
+        /// never carries into its neighbour and `load` returns every lane
+        /// from the same instant. Lane 0 is the least significant.
+        #[derive(Debug, Default)]
+        pub struct $name {
+            word: AtomicU128,
+        }
+
+        impl $name {
+            pub const LANES: usize = $n;
+
+            const BITS: usize = 128 / $n;
+
+            fn pack(lanes: [$lane; $n]) -> u128 {
+                lanes.iter().rev().fold(0, |word, &lane| word << Self::BITS | lane as u128)
+            }
+
+            fn unpack(word: u128) -> [$lane; $n] {
+                let mut lanes = [0; $n];
+                for (i, lane) in lanes.iter_mut().enumerate() {
+                    *lane = (word >> (i * Self::BITS)) as $lane;
+                }
+                lanes
+            }
+
+            fn shift(i: usize) -> usize {
+                assert!(i < $n, "lane index out of range");
+                i * Self::BITS
+            }
+
+            pub fn new(lanes: [$lane; $n]) -> Self {
+                $name { word: AtomicU128::new(Self::pack(lanes)) }
+            }
+
+            /// Every lane, from one load.
+            pub fn load(&self) -> [$lane; $n] {
+                Self::unpack(self.word.load(Ordering::Acquire))
+            }
+
+            pub fn store(&self, lanes: [$lane; $n]) {
+                self.word.store(Self::pack(lanes), Ordering::Release)
+            }
+
+            /// Every lane, reset to zero in the same swap.
+            pub fn take(&self) -> [$lane; $n] {
+                Self::unpack(self.word.swap(0, Ordering::AcqRel))
+            }
+
+            pub fn lane(&self, i: usize) -> $lane {
+                (self.word.load(Ordering::Acquire) >> Self::shift(i)) as $lane
+            }
+
+            fn update_lane<F: Fn($lane) -> $lane>(&self, i: usize, f: F) -> $lane {
+                let shift = Self::shift(i);
+                let mask = ($lane::MAX as u128) << shift;
+                let previous = self
+                    .word
+                    .fetch_update(Ordering::AcqRel, Ordering::Acquire, |word| {
+                        let lane = f((word >> shift) as $lane);
+                        Some(word & !mask | (lane as u128) << shift)
+                    })
+                    .unwrap();
+                (previous >> shift) as $lane
+            }
+
+            /// Adds `x` to lane `i`, wrapping within the lane, and returns
+            /// the lane's previous value.
+            pub fn fetch_add_lane(&self, i: usize, x: $lane) -> $lane {
+                self.update_lane(i, |lane| lane.wrapping_add(x))
+            }
+
+            /// Like `fetch_add_lane`, stopping at the lane's maximum.
+            pub fn fetch_saturating_add_lane(&self, i: usize, x: $lane) -> $lane {
+                self.update_lane(i, |lane| lane.saturating_add(x))
+            }
+
+            pub fn fetch_sub_lane(&self, i: usize, x: $lane) -> $lane {
+                self.update_lane(i, |lane| lane.wrapping_sub(x))
+            }
+        }
+    };
+}
+
+lanes!(AtomicU16x8, u16, 8);
+lanes!(AtomicU32x4, u32, 4);
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::{AtomicU16x8, AtomicU32x4};
+
+    #[test]
+    fn test_lanes_stay_apart() {
+        let a = AtomicU16x8::new([1, 2, 3, 4, 5, 6, 7, u16::MAX]);
+        assert_eq!(a.fetch_add_lane(7, 1), u16::MAX);
+        assert_eq!(a.fetch_saturating_add_lane(6, u16::MAX), 7);
+        assert_eq!(a.fetch_sub_lane(0, 2), 1);
+        assert_eq!(a.load(), [u16::MAX, 2, 3, 4, 5, 6, u16::MAX, 0]);
+        assert_eq!(a.lane(1), 2);
+
+        let b = AtomicU32x4::new([0, u32::MAX, 0, 9]);
+        b.fetch_add_lane(1, 1);
+        assert_eq!(b.take(), [0, 0, 0, 9]);
+        assert_eq!(b.load(), [0; 4]);
+    }
+
+    #[test]
+    fn test_concurrent_lane_counts() {
+        let stats = Arc::new(AtomicU32x4::default());
+        let handles: Vec<_> = (0..3)
+            .map(|t| {
+                let stats = stats.clone();
+                thread::spawn(move || {
+                    for _ in 0..1000 {
+                        stats.fetch_add_lane(t, 1);
+                        stats.fetch_add_lane(3, 1);
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(stats.load(), [1000, 1000, 1000, 3000]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_lane_out_of_range() {
+        AtomicU16x8::default().lane(8);
+    }
+}
@@ -1,64 +1,282 @@
-#![feature(asm)]
+// Without `std` the crate is `no_std` and has the word types, the backends
+// and the lock-free helpers; the collections, cells, sync and time modules
+// and everything that blocks or allocates need `std`.
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 
-#[derive(Clone, Copy, Debug)]
+#[cfg(any(feature = "std", test))]
+extern crate core;
+#[cfg(feature = "atomic-traits")]
+extern crate atomic_traits;
+#[cfg(feature = "radium")]
+extern crate radium;
+#[cfg(feature = "portable-atomic")]
+extern crate portable_atomic;
+#[cfg(feature = "tracing")]
+extern crate tracing;
+#[cfg(loom)]
+extern crate loom;
+#[cfg(shuttle)]
+extern crate shuttle;
+
+#[cfg(feature = "std")]
+pub mod collections;
+#[cfg(feature = "std")]
+pub mod cells;
+#[cfg(feature = "std")]
+pub mod stats;
+#[cfg(feature = "std")]
+pub mod sync;
+#[cfg(feature = "std")]
+pub mod time;
+mod align;
+mod backend;
+mod backoff;
+mod bitmap;
+mod bits;
+mod generic;
+mod halves;
+mod lanes;
+mod llsc;
+mod markable;
+mod padding;
+mod raw;
+mod rng;
+mod signed;
+#[cfg(feature = "std")]
+mod snapshot;
+mod tagged;
+mod trace;
+#[cfg(feature = "std")]
+mod wait;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "chaos")]
+pub mod chaos;
+#[cfg(feature = "pmem")]
+mod pmem;
+#[cfg(feature = "numa")]
+pub mod numa;
+#[cfg(feature = "shm")]
+pub mod shm;
+#[cfg(feature = "shm")]
+pub mod robust;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+#[cfg(feature = "signal")]
+pub mod signal;
+#[cfg(any(feature = "signal", all(feature = "irq", target_arch = "x86_64")))]
+mod spin;
+#[cfg(all(feature = "irq", target_arch = "x86_64"))]
+pub mod irq;
+#[cfg(any(feature = "atomic-traits", feature = "radium", feature = "portable-atomic"))]
+mod compat;
+#[cfg(replay)]
+pub mod replay;
+
+pub use align::Misaligned;
+pub use backoff::{current_backoff, Backoff, Spin, SpinYield};
+#[cfg(feature = "std")]
+pub use backoff::{set_backoff, Park};
+pub use backend::{backend, Backend, Unsupported};
+pub use bitmap::{AtomicBitmap128, IterOnes};
+pub use bits::{AtomicBits, BitsWord, SelectWidth, Width};
+pub use generic::Atomic;
+pub use lanes::{AtomicU16x8, AtomicU32x4};
+pub use llsc::{Link, TaggedLink, TaggedLlSc};
+pub use markable::AtomicMarkableRef;
+pub use padding::NoPadding;
+pub use raw::dwcas;
+pub use rng::{RngStream, SharedRng128};
+pub use signed::AtomicI128;
+#[cfg(feature = "std")]
+pub use snapshot::{snapshot, try_snapshot};
+pub use tagged::AtomicTaggedPtr;
+#[cfg(feature = "async")]
+pub use wait::WaitAsync;
+use backend::cas128;
+
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::sync::atomic::{compiler_fence, Ordering};
+
+/// A 128-bit integer that can be shared between threads, with the same
+/// methods as `std::sync::atomic::AtomicU64`.
+///
+/// Every backend is sequentially consistent, so the `Ordering` parameters are
+/// accepted for compatibility and otherwise ignored. The layout is a bare
+/// `u128` aligned to 16 bytes, which C sees as an aligned `unsigned __int128`.
+#[repr(C, align(16))]
 pub struct AtomicU128 {
-    pub lo: u64,
-    pub hi: u64,
+    v: UnsafeCell<u128>,
 }
 
-#[cfg(target_arch = "x86_64")]
-fn cas128(src: &AtomicU128, cmp: &mut AtomicU128, with: AtomicU128) -> bool {
-    let result: bool;
-    unsafe {
-        asm!("
-                lock cmpxchg16b $3
-                setz $0
-            "
-             : "=q"(result), "+{rdx}"(cmp.hi), "+{rax}"(cmp.lo)
-             : "*m"(src), "{rcx}"(with.hi), "{rbx}"(with.lo)
-             : "memory" "cc"
-        );
-    }
-    result
-}
+unsafe impl Sync for AtomicU128 {}
 
 impl AtomicU128 {
-    pub fn new(lo: u64, hi: u64) -> Self {
-        AtomicU128 { lo: lo, hi: hi }
+    pub const fn new(v: u128) -> Self {
+        AtomicU128 { v: UnsafeCell::new(v) }
     }
 
-    pub fn zero() -> Self {
-        AtomicU128 { lo: 0, hi: 0 }
+    pub fn get_mut(&mut self) -> &mut u128 {
+        unsafe { &mut *self.v.get() }
     }
 
-    pub fn load(&self) -> Self {
-        let mut ret = Self::zero();
-        cas128(self, &mut ret, Self::zero());
-        ret
+    pub fn into_inner(self) -> u128 {
+        self.v.into_inner()
     }
 
-    pub fn store(&self, val: AtomicU128) {
-        let mut current = Self::zero();
-        while !cas128(self, &mut current, val) {
-            // Loop until success
+    pub fn as_ptr(&self) -> *mut u128 {
+        self.v.get()
+    }
+
+    /// Whether `load` may write to the word's cache line, so the word has to
+    /// live in writable memory even if this process only reads it.
+    ///
+    /// False only when the build guarantees loads never write: x86_64
+    /// compiled with AVX, where a plain 16-byte load is atomic, or a build that
+    /// only has the lock or seqlock fallback. Without that, `load` still avoids
+    /// the write when it finds AVX at runtime, but callers can't rely on it up
+    /// front.
+    pub const fn needs_writable_memory() -> bool {
+        !cfg!(all(
+            not(feature = "portable-atomic"),
+            not(loom),
+            not(shuttle),
+            not(replay),
+            any(
+                all(target_arch = "x86_64", feature = "nightly", target_feature = "avx"),
+                all(
+                    any(feature = "fallback-lock", feature = "fallback-seqlock"),
+                    any(not(target_arch = "x86_64"), not(feature = "nightly"))
+                )
+            )
+        ))
+    }
+
+    pub fn load(&self, _: Ordering) -> u128 {
+        backend::load(self)
+    }
+
+    /// Reads the value with plain loads instead of a locked instruction, for
+    /// scans over many words while nothing else can write them.
+    ///
+    /// # Safety
+    ///
+    /// No other thread may modify the word for the duration of the call, for
+    /// example during startup or after a barrier that all writers have passed.
+    /// A concurrent write can produce a torn value and is a data race.
+    pub unsafe fn load_unsync(&self) -> u128 {
+        *self.v.get()
+    }
+
+    /// `load` that the compiler must perform on every call: it won't be
+    /// dropped, merged with a neighbouring load or hoisted out of a polling
+    /// loop, even when nothing else in the loop touches memory. For words
+    /// written by another process or a device.
+    pub fn load_volatile(&self, order: Ordering) -> u128 {
+        compiler_fence(Ordering::SeqCst);
+        let value = self.load(order);
+        compiler_fence(Ordering::SeqCst);
+        value
+    }
+
+    /// `store` with the same guarantee as `load_volatile`: every call writes,
+    /// even if the value is overwritten before anything in this program
+    /// reads it.
+    pub fn store_volatile(&self, val: u128, order: Ordering) {
+        compiler_fence(Ordering::SeqCst);
+        self.store(val, order);
+        compiler_fence(Ordering::SeqCst);
+    }
+
+    #[cfg_attr(feature = "tracing", track_caller)]
+    pub fn store(&self, val: u128, _: Ordering) {
+        backend::store(self, val)
+    }
+
+    /// `store` that skips the write when the word already holds `val`, so a
+    /// writer refreshing an unchanged value doesn't take the cache line away
+    /// from every reader each time. Returns whether it wrote.
+    ///
+    /// The check is a `load`, a plain read on CPUs with AVX, and the store
+    /// is a separate operation after it; use `swap_if_changed` to learn the
+    /// exact value that was replaced.
+    pub fn store_if_ne(&self, val: u128, order: Ordering) -> bool {
+        if self.load(Ordering::Acquire) == val {
+            return false;
+        }
+        self.store(val, order);
+        true
+    }
+
+    /// Replaces the value with `val` unless it already is `val`, in one
+    /// CAS, and returns the value replaced.
+    pub fn swap_if_changed(&self, val: u128, order: Ordering) -> Option<u128> {
+        let mut current = self.load(Ordering::Acquire);
+        while current != val {
+            match self.compare_exchange(current, val, order, Ordering::Acquire) {
+                Ok(previous) => return Some(previous),
+                Err(actual) => current = actual,
+            }
         }
+        None
+    }
+
+    #[cfg_attr(feature = "tracing", track_caller)]
+    pub fn swap(&self, val: u128, order: Ordering) -> u128 {
+        self.swap_with(val, order, current_backoff())
     }
 
-    pub fn swap(&self, val: AtomicU128) -> AtomicU128 {
-        let mut prev = Self::zero();
+    /// `swap` that waits between attempts with `backoff` instead of the
+    /// global policy.
+    #[cfg_attr(feature = "tracing", track_caller)]
+    pub fn swap_with<B: Backoff + ?Sized>(&self, val: u128, _: Ordering, backoff: &B) -> u128 {
+        let mut prev = 0;
+        let mut retries = 0;
         while !cas128(self, &mut prev, val) {
-            // Loop until success
+            retries += 1;
+            trace::cas_retry(self, retries);
+            // The first miss only learned the current value.
+            if retries > 1 {
+                backoff.wait(retries - 1);
+            }
         }
         prev
     }
 
-    pub fn compare_and_swap(&self, current: AtomicU128, new: AtomicU128) -> AtomicU128 {
-        let mut current = current;
-        cas128(self, &mut current, new);
-        current
+    /// `store` with a bounded number of attempts, for paths that can't spin
+    /// indefinitely. It loads the current value and makes at most
+    /// `max_retries + 1` CAS attempts in all, each from the value the last
+    /// one saw, then gives up with the last value it saw.
+    pub fn try_store(&self, val: u128, max_retries: u32, order: Ordering) -> Result<(), u128> {
+        self.try_swap(val, max_retries, order).map(|_| ())
+    }
+
+    /// `swap` with the same retry budget as `try_store`.
+    pub fn try_swap(&self, val: u128, max_retries: u32, _: Ordering) -> Result<u128, u128> {
+        let mut prev = backend::load(self);
+        for _ in 0..=max_retries {
+            if cas128(self, &mut prev, val) {
+                return Ok(prev);
+            }
+        }
+        Err(prev)
+    }
+
+    #[deprecated(note = "use `compare_exchange` or `compare_exchange_weak` instead")]
+    pub fn compare_and_swap(&self, current: u128, new: u128, order: Ordering) -> u128 {
+        match self.compare_exchange(current, new, order, Ordering::SeqCst) {
+            Ok(v) | Err(v) => v,
+        }
     }
 
-    pub fn compare_exchange(&self, current: AtomicU128, new: AtomicU128) -> Result<AtomicU128, AtomicU128> {
+    pub fn compare_exchange(&self, current: u128, new: u128, _: Ordering, _: Ordering) -> Result<u128, u128> {
+        #[cfg(feature = "chaos")]
+        {
+            if chaos::fail_strong() {
+                return Err(self.load(Ordering::SeqCst));
+            }
+        }
         let mut current = current;
         if cas128(self, &mut current, new) {
             Ok(current)
@@ -67,84 +285,294 @@ impl AtomicU128 {
         }
     }
 
-    pub fn compare_exchange_weak(&self, current: AtomicU128, new: AtomicU128) -> Result<AtomicU128, AtomicU128> {
-        self.compare_exchange(current, new)    
+    pub fn compare_exchange_weak(&self, current: u128, new: u128, success: Ordering, failure: Ordering) -> Result<u128, u128> {
+        #[cfg(feature = "chaos")]
+        {
+            if chaos::fail_weak() {
+                return Err(self.load(Ordering::SeqCst));
+            }
+        }
+        self.compare_exchange(current, new, success, failure)
+    }
+
+    #[cfg_attr(feature = "tracing", track_caller)]
+    pub fn fetch_update<F>(&self, set_order: Ordering, fetch_order: Ordering, f: F) -> Result<u128, u128>
+    where
+        F: FnMut(u128) -> Option<u128>,
+    {
+        self.fetch_update_with(set_order, fetch_order, current_backoff(), f)
     }
-}
 
-impl std::cmp::PartialEq for AtomicU128 {
-    fn eq(&self, other: &Self) -> bool {
-        self.lo == other.lo && self.hi == other.hi
+    /// `fetch_update` that waits between attempts with `backoff` instead of
+    /// the global policy.
+    #[cfg_attr(feature = "tracing", track_caller)]
+    pub fn fetch_update_with<B, F>(&self, _: Ordering, _: Ordering, backoff: &B, mut f: F) -> Result<u128, u128>
+    where
+        B: Backoff + ?Sized,
+        F: FnMut(u128) -> Option<u128>,
+    {
+        let mut current = self.load(Ordering::SeqCst);
+        let mut retries = 0;
+        loop {
+            let new = match f(current) {
+                Some(new) => new,
+                None => return Err(current),
+            };
+            if cas128(self, &mut current, new) {
+                return Ok(current);
+            }
+            retries += 1;
+            trace::cas_retry(self, retries);
+            backoff.wait(retries);
+        }
+    }
+
+    #[cfg_attr(feature = "tracing", track_caller)]
+    pub fn fetch_add(&self, val: u128, order: Ordering) -> u128 {
+        self.fetch(order, |v| v.wrapping_add(val))
+    }
+
+    #[cfg_attr(feature = "tracing", track_caller)]
+    pub fn fetch_sub(&self, val: u128, order: Ordering) -> u128 {
+        self.fetch(order, |v| v.wrapping_sub(val))
+    }
+
+    #[cfg_attr(feature = "tracing", track_caller)]
+    pub fn fetch_and(&self, val: u128, order: Ordering) -> u128 {
+        self.fetch(order, |v| v & val)
+    }
+
+    #[cfg_attr(feature = "tracing", track_caller)]
+    pub fn fetch_nand(&self, val: u128, order: Ordering) -> u128 {
+        self.fetch(order, |v| !(v & val))
+    }
+
+    #[cfg_attr(feature = "tracing", track_caller)]
+    pub fn fetch_or(&self, val: u128, order: Ordering) -> u128 {
+        self.fetch(order, |v| v | val)
+    }
+
+    #[cfg_attr(feature = "tracing", track_caller)]
+    pub fn fetch_xor(&self, val: u128, order: Ordering) -> u128 {
+        self.fetch(order, |v| v ^ val)
+    }
+
+    #[cfg_attr(feature = "tracing", track_caller)]
+    pub fn fetch_max(&self, val: u128, order: Ordering) -> u128 {
+        self.fetch(order, |v| v.max(val))
+    }
+
+    #[cfg_attr(feature = "tracing", track_caller)]
+    pub fn fetch_min(&self, val: u128, order: Ordering) -> u128 {
+        self.fetch(order, |v| v.min(val))
+    }
+
+    #[cfg_attr(feature = "tracing", track_caller)]
+    fn fetch<F: Fn(u128) -> u128>(&self, order: Ordering, f: F) -> u128 {
+        match self.fetch_update(order, Ordering::SeqCst, |v| Some(f(v))) {
+            Ok(v) | Err(v) => v,
+        }
     }
 }
 
-impl std::default::Default for AtomicU128 {
+impl Default for AtomicU128 {
     fn default() -> Self {
-        Self::zero()
+        Self::new(0)
     }
-} 
+}
 
-unsafe impl Sync for AtomicU128 {}
+impl From<u128> for AtomicU128 {
+    fn from(v: u128) -> Self {
+        Self::new(v)
+    }
+}
+
+// `{:#?}` also names the backend, for startup logs. The other atomic types
+// format through this too, so they all log it the same way.
+pub(crate) fn fmt_atomic<V: fmt::Debug>(f: &mut fmt::Formatter, name: &str, value: &V) -> fmt::Result {
+    if f.alternate() {
+        f.debug_struct(name).field("value", value).field("backend", &backend()).finish()
+    } else {
+        fmt::Debug::fmt(value, f)
+    }
+}
+
+impl fmt::Debug for AtomicU128 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt_atomic(f, "AtomicU128", &self.load(Ordering::SeqCst))
+    }
+}
 
 #[cfg(test)]
 mod tests {
-    use super::{AtomicU128, cas128};
+    use std::sync::atomic::Ordering::SeqCst;
+
+    use super::{backend, cas128, AtomicU128, Backend};
 
     #[test]
     fn test_cas_success() {
-        let mut a = AtomicU128::new(1, 2);
-        let mut b = a;
-        let c = AtomicU128::new(2, 3);
-        assert_eq!(cas128(&mut a, &mut b, c), true);
-        assert_eq!(a.lo, 2);
-        assert_eq!(a.hi, 3);
+        let a = AtomicU128::new(2 << 64 | 1);
+        let mut b = 2 << 64 | 1;
+        assert!(cas128(&a, &mut b, 3 << 64 | 2));
+        assert_eq!(a.into_inner(), 3 << 64 | 2);
     }
 
     #[test]
     fn test_cas_failure() {
-        let mut a = AtomicU128::new(1, 2);
-        let mut b = AtomicU128::new(2, 3);
-        let c = AtomicU128::zero();
-        assert_eq!(cas128(&mut a, &mut b, c), false);
-        assert_eq!(b.lo, 1);
-        assert_eq!(b.hi, 2);
+        let a = AtomicU128::new(2 << 64 | 1);
+        let mut b = 3 << 64 | 2;
+        assert!(!cas128(&a, &mut b, 0));
+        assert_eq!(b, 2 << 64 | 1);
     }
 
     #[test]
     fn test_load() {
-        let a = AtomicU128::new(1, 2);
-        let b = a.load();
-        assert_eq!(a, b);
+        let a = AtomicU128::new(1 << 100 | 2);
+        assert_eq!(a.load(SeqCst), 1 << 100 | 2);
+    }
+
+    #[test]
+    fn test_try_swap() {
+        let a = AtomicU128::new(1);
+        assert_eq!(a.try_swap(2, 0, SeqCst), Ok(1));
+        assert_eq!(a.try_store(0, 0, SeqCst), Ok(()));
+        assert_eq!(a.try_swap(3, 0, SeqCst), Ok(0));
+        assert_eq!(a.load(SeqCst), 3);
+    }
+
+    #[test]
+    fn test_load_read_only() {
+        // A plain u128 static lands in a read-only section.
+        #[repr(align(16))]
+        struct ReadOnly(u128);
+        static WORD: ReadOnly = ReadOnly(1 << 64 | 2);
+        // Only the fallbacks, and cmpxchg16b when it can load with AVX, are
+        // known to read without writing.
+        let plain_load = match backend() {
+            Backend::Lock | Backend::SeqLock => true,
+            #[cfg(target_arch = "x86_64")]
+            Backend::Cmpxchg16b => backend::cpu::has_avx(),
+            _ => false,
+        };
+        if plain_load {
+            let a = unsafe { AtomicU128::from_ptr(&WORD.0 as *const u128 as *mut u128) };
+            assert_eq!(a.load(SeqCst), 1 << 64 | 2);
+        }
     }
 
     #[test]
     fn test_store() {
-        let a = AtomicU128::zero();
-        let b = AtomicU128::new(2, 3);
-        a.store(b);
-        assert_eq!(a, b);
+        let a = AtomicU128::default();
+        a.store(3 << 64 | 2, SeqCst);
+        assert_eq!(a.load(SeqCst), 3 << 64 | 2);
+    }
+
+    #[test]
+    fn test_load_unsync() {
+        let a = AtomicU128::new(1);
+        a.store(2, SeqCst);
+        assert_eq!(unsafe { a.load_unsync() }, 2);
+    }
+
+    #[test]
+    fn test_volatile_polling() {
+        use std::sync::Arc;
+        use std::thread;
+
+        let a = Arc::new(AtomicU128::new(0));
+        let writer = {
+            let a = a.clone();
+            thread::spawn(move || a.store_volatile(1 << 64 | 1, SeqCst))
+        };
+        while a.load_volatile(SeqCst) == 0 {}
+        writer.join().unwrap();
+        assert_eq!(a.load_volatile(SeqCst), 1 << 64 | 1);
+    }
+
+    #[test]
+    fn test_store_if_ne() {
+        let a = AtomicU128::new(1 << 64);
+        assert!(!a.store_if_ne(1 << 64, SeqCst));
+        assert!(a.store_if_ne(2, SeqCst));
+        assert_eq!(a.swap_if_changed(2, SeqCst), None);
+        assert_eq!(a.swap_if_changed(3, SeqCst), Some(2));
+        assert_eq!(a.load(SeqCst), 3);
     }
 
     #[test]
     fn test_swap() {
-        let a = AtomicU128::new(2, 3);
-        let mut b = AtomicU128::new(4, 5);
-        b = a.swap(b);
-        assert_eq!(a, AtomicU128::new(4, 5));
-        assert_eq!(b, AtomicU128::new(2, 3));
+        let a = AtomicU128::new(3 << 64 | 2);
+        assert_eq!(a.swap(5 << 64 | 4, SeqCst), 3 << 64 | 2);
+        assert_eq!(a.load(SeqCst), 5 << 64 | 4);
     }
 
     #[test]
+    #[allow(deprecated)]
     fn test_compare_and_swap() {
-        let a = AtomicU128::new(1, 1);
-        assert_eq!(a.compare_and_swap(a, AtomicU128::zero()), AtomicU128::new(1, 1));
+        let a = AtomicU128::new(1 << 64 | 1);
+        assert_eq!(a.compare_and_swap(1 << 64 | 1, 0, SeqCst), 1 << 64 | 1);
+        assert_eq!(a.load(SeqCst), 0);
     }
 
     #[test]
     fn test_compare_exchange_weak() {
-        let a = AtomicU128::new(1, 1);
-        let b = AtomicU128::new(1, 2);
-        assert_eq!(a.compare_exchange_weak(b, b), Err(a));
-        assert_eq!(a.compare_exchange_weak(a, b), Ok(AtomicU128::new(1, 1)));
+        let a = AtomicU128::new(1 << 64 | 1);
+        let b = 2 << 64 | 1;
+        assert_eq!(a.compare_exchange_weak(b, b, SeqCst, SeqCst), Err(1 << 64 | 1));
+        let mut current = a.load(SeqCst);
+        while let Err(actual) = a.compare_exchange_weak(current, b, SeqCst, SeqCst) {
+            current = actual;
+        }
+        assert_eq!(a.load(SeqCst), b);
+    }
+
+    #[test]
+    fn test_fetch_ops() {
+        let a = AtomicU128::new(u64::MAX as u128);
+        assert_eq!(a.fetch_add(1, SeqCst), u64::MAX as u128);
+        assert_eq!(a.fetch_or(1, SeqCst), 1 << 64);
+        assert_eq!(a.fetch_sub(2, SeqCst), 1 << 64 | 1);
+        assert_eq!(a.fetch_and(!0 << 1, SeqCst), u64::MAX as u128);
+        assert_eq!(a.fetch_xor(!0, SeqCst), u64::MAX as u128 - 1);
+        assert_eq!(a.fetch_max(5, SeqCst), !(u64::MAX as u128 - 1));
+        assert_eq!(a.fetch_min(5, SeqCst), !(u64::MAX as u128 - 1));
+        assert_eq!(a.fetch_nand(0, SeqCst), 5);
+        assert_eq!(a.fetch_update(SeqCst, SeqCst, |_| None), Err(!0));
+        assert_eq!(format!("{:?}", a), format!("{}", !0u128));
+    }
+}
+
+// kani can't run the cmpxchg16b asm, so these check a fallback build:
+// `cargo kani --no-default-features --features std,fallback-seqlock`.
+#[cfg(kani)]
+mod proofs {
+    use std::sync::atomic::Ordering::SeqCst;
+
+    use halves::Halves;
+    use AtomicU128;
+
+    // The 128-bit add is the two 64-bit adds with the carry between them.
+    #[kani::proof]
+    #[kani::unwind(2)]
+    fn fetch_add_carries() {
+        let (a, b): (u128, u128) = (kani::any(), kani::any());
+        let word = AtomicU128::new(a);
+        assert_eq!(word.fetch_add(b, SeqCst), a);
+        let (a, b) = (Halves::from_bits(a), Halves::from_bits(b));
+        let (lo, carry) = a.lo.overflowing_add(b.lo);
+        let hi = a.hi.wrapping_add(b.hi).wrapping_add(carry as u64);
+        assert_eq!(word.into_inner(), Halves::new(lo, hi).bits());
     }
-}
\ No newline at end of file
+
+    #[kani::proof]
+    #[kani::unwind(2)]
+    fn fetch_sub_borrows() {
+        let (a, b): (u128, u128) = (kani::any(), kani::any());
+        let word = AtomicU128::new(a);
+        assert_eq!(word.fetch_sub(b, SeqCst), a);
+        let (a, b) = (Halves::from_bits(a), Halves::from_bits(b));
+        let (lo, borrow) = a.lo.overflowing_sub(b.lo);
+        let hi = a.hi.wrapping_sub(b.hi).wrapping_sub(borrow as u64);
+        assert_eq!(word.into_inner(), Halves::new(lo, hi).bits());
+    }
+}
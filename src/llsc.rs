@@ -0,0 +1,157 @@
+// Load-linked/store-conditional for algorithms written that way, on top of
+// the CAS backends. Real exclusives (ldaxp/stlxp on AArch64) aren't used
+// even where they exist: the caller's code runs between the two halves, and
+// any store, cache miss or context switch in there can clear the exclusive
+// monitor, so a loop that mostly works in assembly can fail every attempt
+// here. A store-conditional is a compare_exchange against the linked value
+// on every target instead.
+
+use core::sync::atomic::Ordering;
+
+use halves::Halves;
+use AtomicU128;
+
+/// The value a `load_linked` saw, to make a `store_conditional` against.
+#[derive(Debug)]
+pub struct Link<'a> {
+    word: &'a AtomicU128,
+    value: u128,
+}
+
+impl AtomicU128 {
+    /// Loads the value and links to it for a `store_conditional`.
+    ///
+    /// Unlike hardware LL/SC, the store succeeds whenever the word holds the
+    /// linked value again, even if other threads changed it and changed it
+    /// back in between. Algorithms that rely on SC failing after any
+    /// intervening write, such as lock-free stacks that reuse nodes, need
+    /// `TaggedLlSc`.
+    pub fn load_linked(&self, order: Ordering) -> Link<'_> {
+        Link { word: self, value: self.load(order) }
+    }
+}
+
+impl<'a> Link<'a> {
+    pub fn value(&self) -> u128 {
+        self.value
+    }
+
+    /// Stores `new` if the word still holds the linked value. On failure
+    /// returns what it holds now.
+    pub fn store_conditional(self, new: u128, success: Ordering, failure: Ordering) -> Result<(), u128> {
+        self.word.compare_exchange(self.value, new, success, failure).map(|_| ())
+    }
+}
+
+/// A 64-bit value next to a count of the stores made to it, for LL/SC
+/// algorithms that need a store-conditional to fail after any intervening
+/// store, even one that put the same value back.
+///
+/// Every store bumps the count, and a store-conditional compares value and
+/// count together, so ABA would take 2^64 stores between the link and the
+/// store.
+#[derive(Debug, Default)]
+pub struct TaggedLlSc {
+    // lo is the value, hi the store count.
+    word: AtomicU128,
+}
+
+/// The value and store count a `TaggedLlSc::load_linked` saw.
+#[derive(Debug)]
+pub struct TaggedLink<'a> {
+    cell: &'a TaggedLlSc,
+    linked: Halves,
+}
+
+impl TaggedLlSc {
+    pub fn new(value: u64) -> Self {
+        TaggedLlSc { word: AtomicU128::from_halves(Halves::new(value, 0)) }
+    }
+
+    pub fn load(&self) -> u64 {
+        self.word.load_halves().lo
+    }
+
+    /// Stores unconditionally, failing any store-conditional linked before.
+    pub fn store(&self, value: u64) {
+        let mut current = self.word.load_halves();
+        while let Err(actual) = self.word.cas_halves(current, Halves::new(value, current.hi.wrapping_add(1))) {
+            current = actual;
+        }
+    }
+
+    pub fn load_linked(&self) -> TaggedLink<'_> {
+        TaggedLink { cell: self, linked: self.word.load_halves() }
+    }
+}
+
+impl<'a> TaggedLink<'a> {
+    pub fn value(&self) -> u64 {
+        self.linked.lo
+    }
+
+    /// Stores `new` if nothing was stored since the link.
+    pub fn store_conditional(self, new: u64) -> bool {
+        let next = Halves::new(new, self.linked.hi.wrapping_add(1));
+        self.cell.word.cas_halves(self.linked, next).is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering::SeqCst;
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::TaggedLlSc;
+    use AtomicU128;
+
+    #[test]
+    fn test_untagged_allows_aba() {
+        let a = AtomicU128::new(1);
+        let link = a.load_linked(SeqCst);
+        a.store(2, SeqCst);
+        a.store(1, SeqCst);
+        assert_eq!(link.value(), 1);
+        assert_eq!(link.store_conditional(3, SeqCst, SeqCst), Ok(()));
+        assert_eq!(a.load_linked(SeqCst).store_conditional(4, SeqCst, SeqCst), Ok(()));
+        let stale = a.load_linked(SeqCst);
+        a.store(5, SeqCst);
+        assert_eq!(stale.store_conditional(6, SeqCst, SeqCst), Err(5));
+    }
+
+    #[test]
+    fn test_tagged_fails_after_any_store() {
+        let cell = TaggedLlSc::new(1);
+        let link = cell.load_linked();
+        cell.store(1);
+        assert!(!link.store_conditional(2));
+        assert!(cell.load_linked().store_conditional(2));
+        assert_eq!(cell.load(), 2);
+    }
+
+    #[test]
+    fn test_llsc_increment() {
+        let cell = Arc::new(TaggedLlSc::new(0));
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let cell = cell.clone();
+                thread::spawn(move || {
+                    for _ in 0..1000 {
+                        loop {
+                            let link = cell.load_linked();
+                            let next = link.value() + 1;
+                            if link.store_conditional(next) {
+                                break;
+                            }
+                        }
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(cell.load(), 4000);
+    }
+}
@@ -0,0 +1,92 @@
+use core::fmt;
+use core::marker::PhantomData;
+
+use halves::Halves;
+use {fmt_atomic, AtomicU128};
+
+/// A pointer and a mark bit that change together, like Java's
+/// `AtomicMarkableReference`, for lock-free lists that mark a node deleted
+/// before unlinking it.
+///
+/// The mark has a half of its own rather than the pointer's low bit, so any
+/// pointer can be marked whatever its alignment. There is no change count:
+/// a CAS succeeds whenever pointer and mark match again, which is what
+/// Harris-style lists expect as long as nodes aren't reused while the list
+/// is live. Structures that recycle nodes want `AtomicTaggedPtr`.
+pub struct AtomicMarkableRef<T> {
+    // lo is the pointer, hi the mark.
+    word: AtomicU128,
+    _marker: PhantomData<*mut T>,
+}
+
+unsafe impl<T> Send for AtomicMarkableRef<T> {}
+unsafe impl<T> Sync for AtomicMarkableRef<T> {}
+
+impl<T> AtomicMarkableRef<T> {
+    pub fn new(ptr: *mut T, marked: bool) -> Self {
+        AtomicMarkableRef { word: AtomicU128::from_halves(pack(ptr, marked)), _marker: PhantomData }
+    }
+
+    /// The pointer and whether it is marked.
+    pub fn load(&self) -> (*mut T, bool) {
+        unpack(self.word.load_halves())
+    }
+
+    pub fn store(&self, ptr: *mut T, marked: bool) {
+        self.word.store_halves(pack(ptr, marked))
+    }
+
+    /// Stores `new` if pointer and mark are still `current`. On failure
+    /// returns what they are now.
+    pub fn compare_exchange(&self, current: (*mut T, bool), new: (*mut T, bool)) -> Result<(*mut T, bool), (*mut T, bool)> {
+        self.word
+            .cas_halves(pack(current.0, current.1), pack(new.0, new.1))
+            .map(unpack)
+            .map_err(unpack)
+    }
+
+    /// Marks the pointer if it is still `expected` and unmarked; `false` if
+    /// it changed or someone else marked it first.
+    pub fn attempt_mark(&self, expected: *mut T) -> bool {
+        self.compare_exchange((expected, false), (expected, true)).is_ok()
+    }
+}
+
+fn pack<T>(ptr: *mut T, marked: bool) -> Halves {
+    Halves::new(ptr as u64, marked as u64)
+}
+
+fn unpack<T>(word: Halves) -> (*mut T, bool) {
+    (word.lo as *mut T, word.hi != 0)
+}
+
+impl<T> Default for AtomicMarkableRef<T> {
+    fn default() -> Self {
+        Self::new(::core::ptr::null_mut(), false)
+    }
+}
+
+impl<T> fmt::Debug for AtomicMarkableRef<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt_atomic(f, "AtomicMarkableRef", &self.load())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ptr;
+
+    use super::AtomicMarkableRef;
+
+    #[test]
+    fn test_mark_then_swing() {
+        let mut a = 1;
+        let a = &mut a as *mut i32;
+        let r = AtomicMarkableRef::new(a, false);
+        assert!(r.attempt_mark(a));
+        assert!(!r.attempt_mark(a));
+        assert_eq!(r.compare_exchange((a, false), (ptr::null_mut(), false)), Err((a, true)));
+        assert_eq!(r.compare_exchange((a, true), (ptr::null_mut(), false)), Ok((a, true)));
+        assert_eq!(r.load(), (ptr::null_mut(), false));
+    }
+}
@@ -0,0 +1,183 @@
+//! Named words rendered in the Prometheus text format, behind the `metrics`
+//! feature.
+//!
+//! A `Registry` holds shared words under metric names and renders them all
+//! on each scrape, so a service can expose its wide counters without
+//! mirroring them into a second metrics system. A whole-word metric is
+//! written as its exact decimal value; Prometheus reads it as a float, which
+//! is exact up to 2^53. A word packing two 64-bit counters, as many of the
+//! cells in this crate do, is registered as a pair and written as two
+//! samples telling the halves apart by a `part` label, both from one load.
+
+use std::fmt::Write;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+
+use halves::Halves;
+use AtomicU128;
+
+/// Why a metric couldn't be registered.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RegisterError {
+    /// Not a valid Prometheus metric or label name.
+    InvalidName,
+    /// A metric of that name is already registered.
+    Duplicate,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Kind {
+    Counter,
+    Gauge,
+}
+
+#[derive(Debug)]
+enum Layout {
+    // The unsigned value for counters, two's complement for gauges.
+    Whole,
+    // Names for the `part` label of the lo and hi samples.
+    Pair(String, String),
+}
+
+#[derive(Debug)]
+struct Metric {
+    name: String,
+    help: String,
+    kind: Kind,
+    layout: Layout,
+    word: Arc<AtomicU128>,
+}
+
+/// A set of named words to expose.
+#[derive(Debug, Default)]
+pub struct Registry {
+    metrics: Mutex<Vec<Metric>>,
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Registry::default()
+    }
+
+    /// Registers `word` as a counter holding one 128-bit total.
+    pub fn counter(&self, name: &str, help: &str, word: Arc<AtomicU128>) -> Result<(), RegisterError> {
+        self.register(name, help, Kind::Counter, Layout::Whole, word)
+    }
+
+    /// Registers `word` as a gauge, read as a signed 128-bit value.
+    pub fn gauge(&self, name: &str, help: &str, word: Arc<AtomicU128>) -> Result<(), RegisterError> {
+        self.register(name, help, Kind::Gauge, Layout::Whole, word)
+    }
+
+    /// Registers `word` as two 64-bit counters, written as `name{part="lo"}`
+    /// and `name{part="hi"}` with the given part names.
+    pub fn counter_pair(&self, name: &str, help: &str, parts: (&str, &str), word: Arc<AtomicU128>) -> Result<(), RegisterError> {
+        self.register(name, help, Kind::Counter, Layout::Pair(parts.0.to_string(), parts.1.to_string()), word)
+    }
+
+    fn register(&self, name: &str, help: &str, kind: Kind, layout: Layout, word: Arc<AtomicU128>) -> Result<(), RegisterError> {
+        if !is_valid_name(name) {
+            return Err(RegisterError::InvalidName);
+        }
+        let mut metrics = self.metrics.lock().unwrap();
+        if metrics.iter().any(|m| m.name == name) {
+            return Err(RegisterError::Duplicate);
+        }
+        metrics.push(Metric { name: name.to_string(), help: help.to_string(), kind, layout, word });
+        Ok(())
+    }
+
+    /// Every metric in registration order, in the text exposition format.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        for m in self.metrics.lock().unwrap().iter() {
+            let kind = if m.kind == Kind::Counter { "counter" } else { "gauge" };
+            let _ = writeln!(out, "# HELP {} {}", m.name, escape(&m.help, false));
+            let _ = writeln!(out, "# TYPE {} {}", m.name, kind);
+            match m.layout {
+                Layout::Whole => {
+                    let value = m.word.load(Ordering::Acquire);
+                    if m.kind == Kind::Gauge {
+                        let _ = writeln!(out, "{} {}", m.name, value as i128);
+                    } else {
+                        let _ = writeln!(out, "{} {}", m.name, value);
+                    }
+                }
+                Layout::Pair(ref lo, ref hi) => {
+                    let value = Halves::from_bits(m.word.load(Ordering::Acquire));
+                    let _ = writeln!(out, "{}{{part=\"{}\"}} {}", m.name, escape(lo, true), value.lo);
+                    let _ = writeln!(out, "{}{{part=\"{}\"}} {}", m.name, escape(hi, true), value.hi);
+                }
+            }
+        }
+        out
+    }
+}
+
+fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' || c == ':' => {}
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == ':')
+}
+
+// HELP text escapes backslashes and newlines; label values also quotes.
+fn escape(s: &str, quotes: bool) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '"' if quotes => out.push_str("\\\""),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering::SeqCst;
+    use std::sync::Arc;
+
+    use super::{RegisterError, Registry};
+    use halves::Halves;
+    use AtomicU128;
+
+    #[test]
+    fn test_render() {
+        let registry = Registry::new();
+        let bytes = Arc::new(AtomicU128::new(1 << 70));
+        let depth = Arc::new(AtomicU128::new(!0));
+        let results = Arc::new(AtomicU128::new(Halves::new(7, 2).bits()));
+        registry.counter("bytes_total", "Bytes\nsent", bytes.clone()).unwrap();
+        registry.gauge("queue_depth", "Depth", depth).unwrap();
+        registry.counter_pair("requests_total", "Requests", ("ok", "err\""), results).unwrap();
+        bytes.fetch_add(1, SeqCst);
+        assert_eq!(
+            registry.render(),
+            "# HELP bytes_total Bytes\\nsent\n\
+             # TYPE bytes_total counter\n\
+             bytes_total 1180591620717411303425\n\
+             # HELP queue_depth Depth\n\
+             # TYPE queue_depth gauge\n\
+             queue_depth -1\n\
+             # HELP requests_total Requests\n\
+             # TYPE requests_total counter\n\
+             requests_total{part=\"ok\"} 7\n\
+             requests_total{part=\"err\\\"\"} 2\n"
+        );
+    }
+
+    #[test]
+    fn test_register_errors() {
+        let registry = Registry::new();
+        let word = Arc::new(AtomicU128::new(0));
+        assert_eq!(registry.counter("9lives", "", word.clone()), Err(RegisterError::InvalidName));
+        assert_eq!(registry.counter("a-b", "", word.clone()), Err(RegisterError::InvalidName));
+        registry.counter("ns:hits", "", word.clone()).unwrap();
+        assert_eq!(registry.gauge("ns:hits", "", word), Err(RegisterError::Duplicate));
+    }
+}
@@ -0,0 +1,207 @@
+//! Arrays of words bound to one NUMA node, behind the `numa` feature.
+//!
+//! A cmpxchg16b on a line homed on another node costs several times a local
+//! one, so per-node counters and tables want their memory placed explicitly.
+//! On 64-bit x86 and Arm Linux the pages come from `mmap` and are bound with
+//! `mbind`; on Windows they come from `VirtualAllocExNuma`. Either way they
+//! start out zeroed. Elsewhere allocation fails with `Unsupported`.
+
+use std::io;
+use std::mem;
+use std::ops::Deref;
+use std::slice;
+
+use AtomicU128;
+
+/// A word alone on its cache line, so neighbouring words in an array don't
+/// contend with each other.
+#[repr(C, align(64))]
+#[derive(Debug, Default)]
+pub struct CachePadded128(pub AtomicU128);
+
+impl Deref for CachePadded128 {
+    type Target = AtomicU128;
+
+    fn deref(&self) -> &AtomicU128 {
+        &self.0
+    }
+}
+
+/// A fixed-length array of padded words whose memory lives on one node.
+pub struct NumaArray {
+    ptr: *mut CachePadded128,
+    len: usize,
+}
+
+unsafe impl Send for NumaArray {}
+unsafe impl Sync for NumaArray {}
+
+impl NumaArray {
+    /// `len` zeroed words on `node`. Fails if the platform can't allocate on
+    /// a specific node or the node doesn't exist.
+    pub fn on_node(len: usize, node: usize) -> io::Result<Self> {
+        let bytes = bytes_for(len)?;
+        let ptr = unsafe { sys::alloc_on_node(bytes, node)? } as *mut CachePadded128;
+        Ok(NumaArray { ptr, len })
+    }
+}
+
+fn bytes_for(len: usize) -> io::Result<usize> {
+    match len.max(1).checked_mul(mem::size_of::<CachePadded128>()) {
+        Some(bytes) => Ok(bytes),
+        None => Err(io::Error::new(io::ErrorKind::InvalidInput, "array too large")),
+    }
+}
+
+impl Deref for NumaArray {
+    type Target = [CachePadded128];
+
+    fn deref(&self) -> &[CachePadded128] {
+        unsafe { slice::from_raw_parts(self.ptr, self.len) }
+    }
+}
+
+impl Drop for NumaArray {
+    fn drop(&mut self) {
+        let bytes = self.len.max(1) * mem::size_of::<CachePadded128>();
+        unsafe { sys::free(self.ptr as *mut u8, bytes) }
+    }
+}
+
+// The syscall number is per architecture; only the ones listed are wired up.
+#[cfg(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64")))]
+mod sys {
+    use std::io;
+    use std::mem;
+    use std::os::raw::{c_int, c_long, c_ulong};
+    use std::ptr;
+
+    const PROT_READ: c_int = 1;
+    const PROT_WRITE: c_int = 2;
+    const MAP_PRIVATE: c_int = 2;
+    const MAP_ANONYMOUS: c_int = 0x20;
+    const MPOL_BIND: c_ulong = 2;
+    const MPOL_MF_STRICT: c_ulong = 1;
+    #[cfg(target_arch = "x86_64")]
+    const SYS_MBIND: c_long = 237;
+    #[cfg(target_arch = "aarch64")]
+    const SYS_MBIND: c_long = 235;
+
+    // Nodes `mbind` can be asked for; one bit each in the mask.
+    const MAX_NODES: usize = 1024;
+    const MASK_BITS: usize = mem::size_of::<c_ulong>() * 8;
+
+    extern "C" {
+        fn mmap(addr: *mut u8, len: usize, prot: c_int, flags: c_int, fd: c_int, offset: c_long) -> *mut u8;
+        fn munmap(addr: *mut u8, len: usize) -> c_int;
+        fn syscall(number: c_long, ...) -> c_long;
+    }
+
+    pub unsafe fn alloc_on_node(bytes: usize, node: usize) -> io::Result<*mut u8> {
+        if node >= MAX_NODES {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "NUMA node out of range"));
+        }
+        let addr = mmap(ptr::null_mut(), bytes, PROT_READ | PROT_WRITE, MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+        if addr as isize == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut mask = [0 as c_ulong; MAX_NODES / MASK_BITS];
+        mask[node / MASK_BITS] = 1 << (node % MASK_BITS);
+        // Every argument goes through the varargs as a full register. The
+        // kernel reads one bit less than `maxnode`.
+        let rc = syscall(
+            SYS_MBIND,
+            addr as c_ulong,
+            bytes as c_ulong,
+            MPOL_BIND,
+            mask.as_ptr() as c_ulong,
+            MAX_NODES as c_ulong + 1,
+            MPOL_MF_STRICT,
+        );
+        if rc != 0 {
+            let err = io::Error::last_os_error();
+            munmap(addr, bytes);
+            return Err(err);
+        }
+        Ok(addr)
+    }
+
+    pub unsafe fn free(addr: *mut u8, bytes: usize) {
+        munmap(addr, bytes);
+    }
+}
+
+#[cfg(windows)]
+mod sys {
+    use std::io;
+    use std::ptr;
+
+    const MEM_COMMIT: u32 = 0x1000;
+    const MEM_RESERVE: u32 = 0x2000;
+    const MEM_RELEASE: u32 = 0x8000;
+    const PAGE_READWRITE: u32 = 4;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetCurrentProcess() -> *mut u8;
+        fn VirtualAllocExNuma(process: *mut u8, addr: *mut u8, size: usize, kind: u32, protect: u32, node: u32) -> *mut u8;
+        fn VirtualFree(addr: *mut u8, size: usize, kind: u32) -> i32;
+    }
+
+    pub unsafe fn alloc_on_node(bytes: usize, node: usize) -> io::Result<*mut u8> {
+        if node > u32::MAX as usize {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "NUMA node out of range"));
+        }
+        let kind = MEM_RESERVE | MEM_COMMIT;
+        let addr = VirtualAllocExNuma(GetCurrentProcess(), ptr::null_mut(), bytes, kind, PAGE_READWRITE, node as u32);
+        if addr.is_null() {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(addr)
+        }
+    }
+
+    pub unsafe fn free(addr: *mut u8, _: usize) {
+        VirtualFree(addr, 0, MEM_RELEASE);
+    }
+}
+
+#[cfg(not(any(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64")), windows)))]
+mod sys {
+    use std::io;
+
+    pub unsafe fn alloc_on_node(_: usize, _: usize) -> io::Result<*mut u8> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "NUMA placement isn't supported on this platform"))
+    }
+
+    pub unsafe fn free(_: *mut u8, _: usize) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::ErrorKind;
+    use std::sync::atomic::Ordering::SeqCst;
+
+    use super::NumaArray;
+
+    #[test]
+    fn test_on_node_zero() {
+        let array = match NumaArray::on_node(100, 0) {
+            Ok(array) => array,
+            // Kernels built without NUMA have no mbind.
+            Err(ref e) if e.raw_os_error() == Some(38) => return,
+            Err(ref e) if e.kind() == ErrorKind::Unsupported => return,
+            Err(e) => panic!("{}", e),
+        };
+        assert_eq!(array.len(), 100);
+        assert!(array.iter().all(|cell| cell.load(SeqCst) == 0));
+        array[42].store(2 << 64 | 1, SeqCst);
+        assert_eq!(array[42].load(SeqCst), 2 << 64 | 1);
+        assert_eq!(&array[1] as *const _ as usize - &array[0] as *const _ as usize, 64);
+    }
+
+    #[test]
+    fn test_bad_node() {
+        assert!(NumaArray::on_node(1, 1 << 20).is_err());
+    }
+}
@@ -0,0 +1,26 @@
+/// Types whose every byte is part of the value, with no padding, so they
+/// can be copied into atomic words byte for byte.
+///
+/// The types that copy values into words byte by byte, such as `SeqLock`,
+/// need this: a padding byte is uninitialized, and reading it back as part
+/// of an integer is undefined behaviour.
+///
+/// # Safety
+///
+/// `Self` must have no padding bytes anywhere, including inside its fields.
+/// A `#[repr(C)]` struct qualifies when its fields are `NoPadding` and
+/// their sizes add up to the struct's size; add explicit filler fields where
+/// the compiler would otherwise pad.
+pub unsafe trait NoPadding: Copy {}
+
+macro_rules! no_padding {
+    ($($t:ty),*) => {
+        $(unsafe impl NoPadding for $t {})*
+    };
+}
+
+no_padding!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64, bool, char, ());
+
+unsafe impl<T: NoPadding, const N: usize> NoPadding for [T; N] {}
+unsafe impl<T> NoPadding for *const T {}
+unsafe impl<T> NoPadding for *mut T {}
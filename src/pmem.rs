@@ -0,0 +1,117 @@
+//! Stores that reach persistent memory, behind the `pmem` feature.
+//!
+//! A 16-byte aligned word never straddles a cache line, so one write-back of
+//! its line followed by a fence makes an update failure-atomic: after a crash
+//! the word holds either the old value or the new one. That's the usual PMDK
+//! pattern for small in-place updates.
+//!
+//! Write-back is wired up for x86_64 and aarch64. Other targets have no
+//! portable instruction for it, so there `persist` is only a fence: it
+//! orders the update but doesn't make it durable.
+
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+use core::arch::asm;
+use core::sync::atomic::Ordering;
+#[cfg(target_arch = "x86_64")]
+use core::sync::atomic::AtomicU8;
+
+use AtomicU128;
+
+impl AtomicU128 {
+    /// Writes the word's cache line back towards persistent memory and waits
+    /// for that to finish.
+    pub fn persist(&self) {
+        unsafe { flush(self as *const AtomicU128 as *const u8) }
+    }
+
+    /// `store` followed by `persist`.
+    pub fn store_persist(&self, val: u128, order: Ordering) {
+        self.store(val, order);
+        self.persist();
+    }
+
+    /// `compare_exchange` followed by `persist`. The line is flushed on
+    /// failure too, so a value the caller acts on is durable even when it was
+    /// written by another thread that hasn't flushed it yet.
+    pub fn compare_exchange_persist(
+        &self,
+        current: u128,
+        new: u128,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<u128, u128> {
+        let result = self.compare_exchange(current, new, success, failure);
+        self.persist();
+        result
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+const UNKNOWN: u8 = 0;
+#[cfg(target_arch = "x86_64")]
+const CLFLUSH: u8 = 1;
+#[cfg(target_arch = "x86_64")]
+const CLFLUSHOPT: u8 = 2;
+#[cfg(target_arch = "x86_64")]
+const CLWB: u8 = 3;
+
+#[cfg(target_arch = "x86_64")]
+static FLUSH: AtomicU8 = AtomicU8::new(UNKNOWN);
+
+// The best flush the CPU has: CLWB keeps the line cached, CLFLUSHOPT evicts
+// it but is weakly ordered, CLFLUSH is always there but serializing.
+#[cfg(target_arch = "x86_64")]
+fn flush_kind() -> u8 {
+    let kind = FLUSH.load(Ordering::Relaxed);
+    if kind != UNKNOWN {
+        return kind;
+    }
+    let ebx = ::core::arch::x86_64::__cpuid_count(7, 0).ebx;
+    let kind = if ebx & (1 << 24) != 0 {
+        CLWB
+    } else if ebx & (1 << 23) != 0 {
+        CLFLUSHOPT
+    } else {
+        CLFLUSH
+    };
+    FLUSH.store(kind, Ordering::Relaxed);
+    kind
+}
+
+#[cfg(target_arch = "x86_64")]
+unsafe fn flush(line: *const u8) {
+    match flush_kind() {
+        CLWB => asm!("clwb [{0}]", in(reg) line, options(nostack, preserves_flags)),
+        CLFLUSHOPT => asm!("clflushopt [{0}]", in(reg) line, options(nostack, preserves_flags)),
+        _ => asm!("clflush [{0}]", in(reg) line, options(nostack, preserves_flags)),
+    }
+    asm!("sfence", options(nostack, preserves_flags));
+}
+
+// Clean to the point of persistence, then wait for it.
+#[cfg(target_arch = "aarch64")]
+unsafe fn flush(line: *const u8) {
+    asm!("dc cvap, {0}", in(reg) line, options(nostack, preserves_flags));
+    asm!("dsb sy", options(nostack, preserves_flags));
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+unsafe fn flush(_: *const u8) {
+    core::sync::atomic::fence(Ordering::SeqCst);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering::SeqCst;
+
+    use AtomicU128;
+
+    #[test]
+    fn test_persist_ops() {
+        let a = AtomicU128::new(0);
+        a.store_persist(2 << 64 | 1, SeqCst);
+        assert_eq!(a.compare_exchange_persist(2 << 64 | 1, 4 << 64 | 3, SeqCst, SeqCst), Ok(2 << 64 | 1));
+        assert_eq!(a.compare_exchange_persist(0, 0, SeqCst, SeqCst), Err(4 << 64 | 3));
+        assert_eq!(a.load(SeqCst), 4 << 64 | 3);
+    }
+}
@@ -0,0 +1,40 @@
+use core::sync::atomic::Ordering;
+
+use cas128;
+use AtomicU128;
+
+/// Compare-and-swap on a `u128` in memory the caller manages, without going
+/// through an `AtomicU128`.
+///
+/// Replaces `*ptr` with `new` if it equals `*expected` and returns true;
+/// otherwise writes the current value to `*expected` and returns false.
+/// Every backend is sequentially consistent, so `ord` is accepted for
+/// signature compatibility and otherwise ignored.
+///
+/// # Safety
+///
+/// `ptr` must be non-null, 16-byte aligned (checked in debug builds) and
+/// valid for reads and writes. For the duration of the call nothing may
+/// access `*ptr` other than through this function or an `AtomicU128` over
+/// the same memory.
+pub unsafe fn dwcas(ptr: *mut u128, expected: &mut u128, new: u128, ord: Ordering) -> bool {
+    let _ = ord;
+    cas128(AtomicU128::from_ptr(ptr), expected, new)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::dwcas;
+    use std::sync::atomic::Ordering;
+
+    #[test]
+    fn test_dwcas() {
+        let mut slot: u128 = 1 << 64 | 7;
+        let mut expected = 1 << 64 | 7;
+        assert!(unsafe { dwcas(&mut slot, &mut expected, 42, Ordering::SeqCst) });
+        assert_eq!(slot, 42);
+        assert!(!unsafe { dwcas(&mut slot, &mut expected, 0, Ordering::AcqRel) });
+        assert_eq!(expected, 42);
+        assert_eq!(slot, 42);
+    }
+}
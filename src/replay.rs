@@ -0,0 +1,211 @@
+//! Record/replay backend for `--cfg replay` builds.
+//!
+//! Every 128-bit operation runs under one global lock and, while recording,
+//! appends an `Event` stamped with its position in the global order. A
+//! recorded `Schedule` can then be replayed: each thread blocks before its
+//! next operation until the schedule says it's that thread's turn, so the
+//! same interleaving of atomic operations happens again. Replaying only a
+//! prefix of a failing schedule and letting the rest run freely is a cheap
+//! way to find how much of it the failure actually depends on.
+//!
+//! Threads are told apart by the id they pass to `set_thread`; threads that
+//! never call it all count as thread 0. Only the thread and the outcome of
+//! each CAS are checked during replay: addresses and observed values are
+//! kept for diagnostics, since pointers stored in a word differ between runs.
+
+use std::cell::Cell;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::str::FromStr;
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use AtomicU128;
+
+// How long a replaying thread waits for its turn before giving up on a
+// schedule the program no longer follows.
+const STALL: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Event {
+    /// Position in the global order of operations.
+    pub time: u64,
+    pub thread: usize,
+    pub addr: usize,
+    /// The word as the operation found it.
+    pub observed: u128,
+    /// Whether the underlying CAS wrote.
+    pub succeeded: bool,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Schedule {
+    events: Vec<Event>,
+}
+
+impl Schedule {
+    pub fn events(&self) -> &[Event] {
+        &self.events
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// The first `len` events only; replaying it forces those and then lets
+    /// the threads run freely.
+    pub fn prefix(&self, len: usize) -> Schedule {
+        Schedule { events: self.events[..len.min(self.events.len())].to_vec() }
+    }
+}
+
+// One event per line: time, thread, address, observed value, outcome.
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for e in &self.events {
+            writeln!(f, "{} {} {:x} {:x} {}", e.time, e.thread, e.addr, e.observed, e.succeeded as u8)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Schedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut events = Vec::new();
+        for (n, line) in s.lines().enumerate().filter(|&(_, l)| !l.trim().is_empty()) {
+            let bad = || format!("line {}: bad event {:?}", n + 1, line);
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() != 5 {
+                return Err(bad());
+            }
+            events.push(Event {
+                time: fields[0].parse().map_err(|_| bad())?,
+                thread: fields[1].parse().map_err(|_| bad())?,
+                addr: usize::from_str_radix(fields[2], 16).map_err(|_| bad())?,
+                observed: u128::from_str_radix(fields[3], 16).map_err(|_| bad())?,
+                succeeded: match fields[4] {
+                    "0" => false,
+                    "1" => true,
+                    _ => return Err(bad()),
+                },
+            });
+        }
+        Ok(Schedule { events })
+    }
+}
+
+enum Mode {
+    Off,
+    Record,
+    Replay(Vec<Event>),
+}
+
+struct State {
+    mode: Mode,
+    events: Vec<Event>,
+}
+
+static STATE: Mutex<State> = Mutex::new(State { mode: Mode::Off, events: Vec::new() });
+static TURN: Condvar = Condvar::new();
+
+thread_local!(static THREAD: Cell<usize> = const { Cell::new(0) });
+
+/// The id this thread is recorded and replayed under.
+pub fn set_thread(id: usize) {
+    THREAD.with(|t| t.set(id));
+}
+
+fn lock() -> MutexGuard<'static, State> {
+    STATE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Runs `f` with every operation recorded, and returns the schedule along
+/// with how `f` finished.
+pub fn record<F: FnOnce()>(f: F) -> (Schedule, thread::Result<()>) {
+    run(Mode::Record, f)
+}
+
+/// Runs `f` following `schedule`, recording as it goes, and returns what
+/// actually happened along with how `f` finished. Panics in the thread that
+/// strays from the schedule, or in one that waits too long for its turn.
+pub fn replay<F: FnOnce()>(schedule: &Schedule, f: F) -> (Schedule, thread::Result<()>) {
+    run(Mode::Replay(schedule.events.clone()), f)
+}
+
+fn run<F: FnOnce()>(mode: Mode, f: F) -> (Schedule, thread::Result<()>) {
+    {
+        let mut state = lock();
+        state.mode = mode;
+        state.events.clear();
+    }
+    let result = panic::catch_unwind(AssertUnwindSafe(f));
+    let mut state = lock();
+    state.mode = Mode::Off;
+    TURN.notify_all();
+    (Schedule { events: state.events.split_off(0) }, result)
+}
+
+// Blocks until the schedule reaches one of this thread's events, or the
+// schedule runs out. Returns the outcome the schedule expects, if any.
+fn wait_turn(mut state: MutexGuard<'static, State>, thread: usize) -> (MutexGuard<'static, State>, Option<bool>) {
+    let deadline = Instant::now() + STALL;
+    loop {
+        let time = state.events.len();
+        let expected = match state.mode {
+            Mode::Replay(ref planned) => planned.get(time).map(|e| (e.thread, e.succeeded)),
+            _ => None,
+        };
+        match expected {
+            None => return (state, None),
+            Some((t, succeeded)) if t == thread => return (state, Some(succeeded)),
+            Some(_) => {}
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            drop(state);
+            panic!("replay stalled at event {} waiting for thread {}", time, thread);
+        }
+        state = TURN.wait_timeout(state, deadline - now).unwrap_or_else(|e| e.into_inner()).0;
+    }
+}
+
+pub(crate) fn cas128(src: &AtomicU128, cmp: &mut u128, with: u128) -> bool {
+    let thread = THREAD.with(|t| t.get());
+    let (mut state, expected) = wait_turn(lock(), thread);
+    let word = src.as_ptr();
+    let current = unsafe { ptr::read_volatile(word) };
+    let succeeded = current == *cmp;
+    if let Some(planned) = expected {
+        if planned != succeeded {
+            let time = state.events.len();
+            drop(state);
+            panic!("replay diverged at event {}: thread {} CAS succeeded = {}", time, thread, succeeded);
+        }
+    }
+    if succeeded {
+        unsafe { ptr::write_volatile(word, with) };
+    } else {
+        *cmp = current;
+    }
+    if let Mode::Off = state.mode {
+        return succeeded;
+    }
+    let time = state.events.len() as u64;
+    state.events.push(Event {
+        time,
+        thread,
+        addr: src as *const AtomicU128 as usize,
+        observed: current,
+        succeeded,
+    });
+    TURN.notify_all();
+    succeeded
+}
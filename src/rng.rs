@@ -0,0 +1,146 @@
+use core::sync::atomic::Ordering;
+
+use AtomicU128;
+
+// The PCG 128-bit LCG, with the XSL-RR output function of pcg64.
+const MULTIPLIER: u128 = 0x2360_ed05_1fc6_5da4_4385_df64_9fcc_f645;
+const INCREMENT: u128 = 0x5851_f42d_4c95_7f2d_1405_7b7e_f767_814f;
+
+fn step(state: u128) -> u128 {
+    state.wrapping_mul(MULTIPLIER).wrapping_add(INCREMENT)
+}
+
+// The state `n` steps on, in log2(n) squarings.
+fn advance(state: u128, mut n: u128) -> u128 {
+    let (mut acc_mult, mut acc_plus) = (1u128, 0u128);
+    let (mut cur_mult, mut cur_plus) = (MULTIPLIER, INCREMENT);
+    while n > 0 {
+        if n & 1 == 1 {
+            acc_mult = acc_mult.wrapping_mul(cur_mult);
+            acc_plus = acc_plus.wrapping_mul(cur_mult).wrapping_add(cur_plus);
+        }
+        cur_plus = cur_mult.wrapping_add(1).wrapping_mul(cur_plus);
+        cur_mult = cur_mult.wrapping_mul(cur_mult);
+        n >>= 1;
+    }
+    acc_mult.wrapping_mul(state).wrapping_add(acc_plus)
+}
+
+fn output(state: u128) -> u64 {
+    ((state >> 64) as u64 ^ state as u64).rotate_right((state >> 122) as u32)
+}
+
+/// A private PCG stream, handed out by `SharedRng128::split`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RngStream {
+    state: u128,
+}
+
+impl RngStream {
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = step(self.state);
+        output(self.state)
+    }
+}
+
+/// One PCG generator shared by many threads, for reproducible streams
+/// handed out concurrently.
+///
+/// The whole 128-bit state is the word, so `next_u64` and `next_block` are
+/// a CAS from the state to the one after the outputs taken, and no output is
+/// given out twice. `split` jumps the shared state 2^64 steps ahead and
+/// hands the skipped stretch to the caller as its own stream. Which thread
+/// gets which outputs depends on scheduling; the sequence of outputs and
+/// splits handed out from a seed doesn't.
+#[derive(Debug)]
+pub struct SharedRng128 {
+    state: AtomicU128,
+}
+
+impl SharedRng128 {
+    pub fn new(seed: u128) -> Self {
+        SharedRng128 { state: AtomicU128::new(seed) }
+    }
+
+    // Moves the shared state `n` steps on and returns where it was.
+    fn reserve(&self, n: u128) -> u128 {
+        let mut current = self.state.load(Ordering::Relaxed);
+        loop {
+            match self.state.compare_exchange_weak(current, advance(current, n), Ordering::Relaxed, Ordering::Relaxed) {
+                Ok(previous) => return previous,
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    pub fn next_u64(&self) -> u64 {
+        let mut stream = RngStream { state: self.reserve(1) };
+        stream.next_u64()
+    }
+
+    /// Fills `out` with the next `out.len()` outputs, taken in one CAS.
+    pub fn next_block(&self, out: &mut [u64]) {
+        let mut stream = RngStream { state: self.reserve(out.len() as u128) };
+        for x in out.iter_mut() {
+            *x = stream.next_u64();
+        }
+    }
+
+    /// A stream of its own for the caller, good for 2^64 outputs before it
+    /// runs into outputs handed out after it.
+    pub fn split(&self) -> RngStream {
+        RngStream { state: self.reserve(1 << 64) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::{advance, step, RngStream, SharedRng128};
+
+    #[test]
+    fn test_matches_one_stream() {
+        let shared = SharedRng128::new(42);
+        let mut expected = RngStream { state: 42 };
+        let mut block = [0u64; 5];
+        assert_eq!(shared.next_u64(), expected.next_u64());
+        shared.next_block(&mut block);
+        assert!(block.iter().all(|&x| x == expected.next_u64()));
+        assert_eq!(shared.next_u64(), expected.next_u64());
+
+        let mut state = 7;
+        for n in 0..100 {
+            assert_eq!(advance(7, n), state);
+            state = step(state);
+        }
+        let mut first = shared.split();
+        assert_eq!(first.next_u64(), expected.next_u64());
+        let second = shared.split();
+        assert_eq!(second, RngStream { state: advance(expected.state, (1 << 64) - 1) });
+    }
+
+    #[test]
+    fn test_concurrent_outputs_unique() {
+        let shared = Arc::new(SharedRng128::new(1));
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let shared = shared.clone();
+                thread::spawn(move || {
+                    let mut out = vec![0u64; 1000];
+                    for chunk in out.chunks_mut(10) {
+                        shared.next_block(chunk);
+                    }
+                    out
+                })
+            })
+            .collect();
+        let mut all: Vec<u64> = handles.into_iter().flat_map(|h| h.join().unwrap()).collect();
+        let mut expected = RngStream { state: 1 };
+        let mut sequence: Vec<u64> = (0..4000).map(|_| expected.next_u64()).collect();
+        all.sort();
+        sequence.sort();
+        assert_eq!(all, sequence);
+    }
+}
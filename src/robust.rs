@@ -0,0 +1,184 @@
+//! Claims that survive their holder crashing, for structures in shared
+//! memory, behind the `shm` feature.
+//!
+//! A claim word holds the owning process's pid next to a generation that
+//! every claim bumps. If a process dies holding one, any other process can
+//! `recover` it in one CAS, much as a robust futex hands a dead owner's lock
+//! to the next waiter with `EOWNERDEAD`; the recovered `Claim` says so, and
+//! the new holder repairs whatever the dead one left half-done before
+//! releasing it. Deciding that a pid is dead is the caller's filter, since
+//! pids are reused; `pid_is_dead` is the usual `kill(pid, 0)` probe.
+//! Like the segment itself, claims are only atomic across processes on a
+//! lock-free backend.
+
+use std::process;
+
+use current_backoff;
+use halves::Halves;
+use AtomicU128;
+
+/// A claim word, typically one of a `SharedCells` segment's.
+#[derive(Clone, Copy)]
+pub struct RobustLock<'a> {
+    // lo is the owner's pid (0 when free), hi the generation.
+    word: &'a AtomicU128,
+}
+
+/// Who held a claim that couldn't be taken.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Owner {
+    pub pid: u32,
+    pub generation: u64,
+}
+
+/// A held claim; releases it on drop.
+#[derive(Debug)]
+pub struct Claim<'a> {
+    word: &'a AtomicU128,
+    generation: u64,
+    recovered_from: Option<u32>,
+}
+
+impl<'a> RobustLock<'a> {
+    pub fn new(word: &'a AtomicU128) -> Self {
+        RobustLock { word }
+    }
+
+    pub fn owner(&self) -> Option<Owner> {
+        let current = self.word.load_halves();
+        if current.lo == 0 { None } else { Some(Owner { pid: current.lo as u32, generation: current.hi }) }
+    }
+
+    pub fn try_claim(&self) -> Result<Claim<'a>, Owner> {
+        let current = self.word.load_halves();
+        if current.lo != 0 {
+            return Err(Owner { pid: current.lo as u32, generation: current.hi });
+        }
+        self.take_over(current, None)
+    }
+
+    /// Waits for the claim, recovering it if the holder turns out to be dead
+    /// by `is_dead`.
+    pub fn claim<F: Fn(u32) -> bool>(&self, is_dead: F) -> Claim<'a> {
+        let mut attempt = 0;
+        loop {
+            match self.try_claim() {
+                Ok(claim) => return claim,
+                Err(owner) if is_dead(owner.pid) => {
+                    if let Some(claim) = self.recover(&is_dead) {
+                        return claim;
+                    }
+                }
+                Err(_) => {}
+            }
+            attempt += 1;
+            current_backoff().wait(attempt);
+        }
+    }
+
+    /// Takes the claim over from a holder `is_dead` says has died. Of
+    /// several processes recovering at once, one gets it.
+    pub fn recover<F: Fn(u32) -> bool>(&self, is_dead: F) -> Option<Claim<'a>> {
+        let current = self.word.load_halves();
+        let pid = current.lo as u32;
+        if current.lo == 0 || !is_dead(pid) {
+            return None;
+        }
+        self.take_over(current, Some(pid)).ok()
+    }
+
+    fn take_over(&self, current: Halves, recovered_from: Option<u32>) -> Result<Claim<'a>, Owner> {
+        let generation = current.hi.wrapping_add(1);
+        match self.word.cas_halves(current, Halves::new(process::id() as u64, generation)) {
+            Ok(_) => Ok(Claim { word: self.word, generation, recovered_from }),
+            Err(actual) => Err(Owner { pid: actual.lo as u32, generation: actual.hi }),
+        }
+    }
+}
+
+/// Recovers every claim in `words` whose holder `is_dead`, returning the
+/// index and dead pid with each, for a process that scans a segment after
+/// noticing a participant is gone.
+pub fn recover_all<'a, F: Fn(u32) -> bool>(words: &'a [AtomicU128], is_dead: F) -> Vec<(usize, Claim<'a>)> {
+    words.iter().enumerate().filter_map(|(i, word)| RobustLock::new(word).recover(&is_dead).map(|claim| (i, claim))).collect()
+}
+
+impl<'a> Claim<'a> {
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// The dead holder's pid if this claim was recovered, in which case the
+    /// data it guards may be mid-update and needs repair.
+    pub fn recovered_from(&self) -> Option<u32> {
+        self.recovered_from
+    }
+}
+
+impl<'a> Drop for Claim<'a> {
+    fn drop(&mut self) {
+        let held = Halves::new(process::id() as u64, self.generation);
+        let _ = self.word.cas_halves(held, Halves::new(0, self.generation));
+    }
+}
+
+/// Whether no process `pid` exists, by `kill(pid, 0)`. Only as good as pids
+/// are unique: a reused one looks alive.
+#[cfg(unix)]
+pub fn pid_is_dead(pid: u32) -> bool {
+    use std::io;
+
+    const ESRCH: i32 = 3;
+    extern "C" {
+        fn kill(pid: i32, sig: i32) -> i32;
+    }
+    unsafe { kill(pid as i32, 0) != 0 && io::Error::last_os_error().raw_os_error() == Some(ESRCH) }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process;
+
+    use super::{recover_all, Owner, RobustLock};
+    use halves::Halves;
+    use AtomicU128;
+
+    const DEAD: u32 = 0x7fff_fff0;
+
+    #[test]
+    fn test_claim_release() {
+        let word = AtomicU128::new(0);
+        let lock = RobustLock::new(&word);
+        let claim = lock.try_claim().unwrap();
+        assert_eq!((claim.generation(), claim.recovered_from()), (1, None));
+        assert_eq!(lock.try_claim().unwrap_err(), Owner { pid: process::id(), generation: 1 });
+        assert!(lock.recover(|pid| pid != process::id()).is_none());
+        drop(claim);
+        assert_eq!(lock.owner(), None);
+    }
+
+    #[test]
+    fn test_recover_dead_holders() {
+        let words: Vec<AtomicU128> = (0..3).map(|_| AtomicU128::new(0)).collect();
+        words[0].store_halves(Halves::new(DEAD as u64, 5));
+        words[2].store_halves(Halves::new(DEAD as u64 + 1, 9));
+        assert!(RobustLock::new(&words[0]).recover(|_| false).is_none());
+
+        let recovered = recover_all(&words, |pid| pid == DEAD);
+        assert_eq!(recovered.len(), 1);
+        let (index, claim) = &recovered[0];
+        assert_eq!((*index, claim.recovered_from(), claim.generation()), (0, Some(DEAD), 6));
+        drop(recovered);
+        assert_eq!(RobustLock::new(&words[0]).owner(), None);
+
+        let claim = RobustLock::new(&words[2]).claim(|pid| pid == DEAD + 1);
+        assert_eq!(claim.recovered_from(), Some(DEAD + 1));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_pid_probe() {
+        assert!(!super::pid_is_dead(process::id()));
+        assert!(super::pid_is_dead(DEAD));
+    }
+}
@@ -0,0 +1,229 @@
+//! Words in POSIX shared memory, behind the `shm` feature.
+//!
+//! `AtomicU128` is a 16-byte aligned `u128` and a mapping is page aligned, so a
+//! segment is just an array of words that every process mapping it sees with
+//! the same layout, and a CAS from any of them is atomic with respect to the
+//! others. See `examples/shm_seqlock.rs` for a cross-process sequence lock.
+//!
+//! That only holds when the backend is lock-free: the lock and seqlock
+//! fallbacks keep their locks in per-process tables, so two processes would
+//! each take their own. `create` and `open` fail with `Unsupported` unless
+//! `backend().is_lock_free()`, which also covers `robust` and the other
+//! types that view a segment's words.
+
+use std::ffi::CString;
+use std::io;
+use std::mem;
+use std::ops::Deref;
+use std::slice;
+
+use {backend, AtomicU128};
+
+/// A mapped shared memory segment viewed as words.
+///
+/// The process that created the segment removes its name on drop; ones
+/// that opened it only unmap it.
+pub struct SharedCells {
+    ptr: *mut AtomicU128,
+    len: usize,
+    name: CString,
+    owner: bool,
+}
+
+unsafe impl Send for SharedCells {}
+unsafe impl Sync for SharedCells {}
+
+fn c_name(name: &str) -> io::Result<CString> {
+    if !backend().is_lock_free() {
+        return Err(io::Error::new(io::ErrorKind::Unsupported, "shared memory needs a lock-free backend"));
+    }
+    CString::new(name).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "segment name contains a NUL"))
+}
+
+impl SharedCells {
+    /// Creates a segment of `len` zeroed words named `name` (conventionally
+    /// starting with `/`). Fails if the name is already taken.
+    pub fn create(name: &str, len: usize) -> io::Result<Self> {
+        let name = c_name(name)?;
+        let bytes = len.checked_mul(mem::size_of::<AtomicU128>())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "segment too large"))?;
+        let ptr = unsafe { sys::create(&name, bytes)? };
+        Ok(SharedCells { ptr: ptr as *mut AtomicU128, len, name, owner: true })
+    }
+
+    /// Maps an existing segment; the length comes from its size.
+    pub fn open(name: &str) -> io::Result<Self> {
+        let name = c_name(name)?;
+        let (ptr, bytes) = unsafe { sys::open(&name)? };
+        Ok(SharedCells { ptr: ptr as *mut AtomicU128, len: bytes / mem::size_of::<AtomicU128>(), name, owner: false })
+    }
+}
+
+impl Deref for SharedCells {
+    type Target = [AtomicU128];
+
+    fn deref(&self) -> &[AtomicU128] {
+        unsafe { slice::from_raw_parts(self.ptr, self.len) }
+    }
+}
+
+impl Drop for SharedCells {
+    fn drop(&mut self) {
+        unsafe {
+            sys::unmap(self.ptr as *mut u8, self.len * mem::size_of::<AtomicU128>());
+            if self.owner {
+                sys::unlink(&self.name);
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod sys {
+    use std::ffi::CStr;
+    use std::io;
+    use std::os::raw::c_long;
+    use std::ptr;
+
+    const O_RDWR: i32 = 2;
+    const O_CREAT: i32 = 0o100;
+    const O_EXCL: i32 = 0o200;
+    const SEEK_END: i32 = 2;
+    const PROT_READ: i32 = 1;
+    const PROT_WRITE: i32 = 2;
+    const MAP_SHARED: i32 = 1;
+
+    // glibc before 2.34 keeps these in librt; later it's an empty stub.
+    #[link(name = "rt")]
+    extern "C" {
+        fn shm_open(name: *const i8, flags: i32, mode: u32) -> i32;
+        fn shm_unlink(name: *const i8) -> i32;
+    }
+
+    // `off_t` is a C long on Linux without large-file offsets.
+    extern "C" {
+        fn ftruncate(fd: i32, len: c_long) -> i32;
+        fn lseek(fd: i32, offset: c_long, whence: i32) -> c_long;
+        fn close(fd: i32) -> i32;
+        fn mmap(addr: *mut u8, len: usize, prot: i32, flags: i32, fd: i32, offset: c_long) -> *mut u8;
+        fn munmap(addr: *mut u8, len: usize) -> i32;
+    }
+
+    unsafe fn map(fd: i32, bytes: usize) -> io::Result<*mut u8> {
+        // An empty mapping isn't allowed; a dangling aligned pointer views
+        // the empty segment just as well.
+        if bytes == 0 {
+            return Ok(16 as *mut u8);
+        }
+        let addr = mmap(ptr::null_mut(), bytes, PROT_READ | PROT_WRITE, MAP_SHARED, fd, 0);
+        if addr as isize == -1 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(addr)
+        }
+    }
+
+    pub unsafe fn create(name: &CStr, bytes: usize) -> io::Result<*mut u8> {
+        let fd = shm_open(name.as_ptr(), O_RDWR | O_CREAT | O_EXCL, 0o600);
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let result = if ftruncate(fd, bytes as c_long) != 0 { Err(io::Error::last_os_error()) } else { map(fd, bytes) };
+        close(fd);
+        if result.is_err() {
+            shm_unlink(name.as_ptr());
+        }
+        result
+    }
+
+    // Maps whole words only, so the length handed back is the one `unmap`
+    // gets; a segment someone else sized oddly loses its trailing bytes.
+    pub unsafe fn open(name: &CStr) -> io::Result<(*mut u8, usize)> {
+        let fd = shm_open(name.as_ptr(), O_RDWR, 0);
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let size = lseek(fd, 0, SEEK_END);
+        let bytes = size as usize & !15;
+        let result = if size < 0 { Err(io::Error::last_os_error()) } else { map(fd, bytes) };
+        close(fd);
+        result.map(|addr| (addr, bytes))
+    }
+
+    pub unsafe fn unmap(addr: *mut u8, bytes: usize) {
+        if bytes > 0 {
+            munmap(addr, bytes);
+        }
+    }
+
+    pub unsafe fn unlink(name: &CStr) {
+        shm_unlink(name.as_ptr());
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod sys {
+    use std::ffi::CStr;
+    use std::io;
+
+    fn unsupported() -> io::Error {
+        io::Error::new(io::ErrorKind::Other, "shared memory segments aren't supported on this platform")
+    }
+
+    pub unsafe fn create(_: &CStr, _: usize) -> io::Result<*mut u8> {
+        Err(unsupported())
+    }
+
+    pub unsafe fn open(_: &CStr) -> io::Result<(*mut u8, usize)> {
+        Err(unsupported())
+    }
+
+    pub unsafe fn unmap(_: *mut u8, _: usize) {}
+
+    pub unsafe fn unlink(_: &CStr) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sys, SharedCells};
+    use std::ffi::CString;
+    use std::io;
+    use std::process;
+    use std::sync::atomic::Ordering::SeqCst;
+
+    use backend;
+
+    #[test]
+    fn test_create_and_open_share_words() {
+        let name = format!("/atomic128-test-{}", process::id());
+        if !backend().is_lock_free() {
+            assert_eq!(SharedCells::create(&name, 4).err().unwrap().kind(), io::ErrorKind::Unsupported);
+            return;
+        }
+        let created = SharedCells::create(&name, 4).unwrap();
+        assert!(SharedCells::create(&name, 4).is_err());
+        let opened = SharedCells::open(&name).unwrap();
+        assert_eq!(opened.len(), 4);
+        assert_eq!(opened[3].load(SeqCst), 0);
+        created[3].store(2 << 64 | 1, SeqCst);
+        assert_eq!(opened[3].compare_exchange(2 << 64 | 1, 4 << 64 | 3, SeqCst, SeqCst), Ok(2 << 64 | 1));
+        assert_eq!(created[3].load(SeqCst), 4 << 64 | 3);
+        drop(opened);
+        drop(created);
+        assert!(SharedCells::open(&name).is_err());
+    }
+
+    #[test]
+    fn test_open_maps_whole_words() {
+        let name = format!("/atomic128-test-odd-{}", process::id());
+        if !backend().is_lock_free() {
+            return;
+        }
+        let c_name = CString::new(name.clone()).unwrap();
+        unsafe { sys::unmap(sys::create(&c_name, 40).unwrap(), 40) };
+        let opened = SharedCells::open(&name).unwrap();
+        assert_eq!(opened.len(), 2);
+        drop(opened);
+        unsafe { sys::unlink(&c_name) };
+    }
+}
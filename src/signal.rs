@@ -0,0 +1,100 @@
+//! Operations that are safe to call from a signal handler, behind the
+//! `signal` feature.
+//!
+//! A handler can interrupt its own thread anywhere, including halfway
+//! through another operation on the same word, so it may only use code that
+//! never takes a lock, allocates or blocks. The feature needs the native
+//! x86_64 backend and refuses to build with one that could: the lock and
+//! seqlock fallbacks, runtime detection (which falls back to them),
+//! `portable-atomic` (which may use locks of its own) and `chaos`.
+//!
+//! With it, these are async-signal-safe: `AtomicU128::new`, `load`,
+//! `compare_exchange`, `compare_exchange_weak`, and everything in this
+//! module. `store`, `swap` and the `fetch_*` methods are not, since they wait
+//! through the installed `Backoff`, which may park, and so are the blocking
+//! and allocating types elsewhere in the crate. The retry loops here spin
+//! instead, which can't deadlock: the handler only preempts its own thread,
+//! and every other thread keeps making progress.
+
+#[cfg(any(feature = "fallback-lock", feature = "fallback-seqlock", feature = "detect-runtime"))]
+compile_error!("`signal` needs a lock-free backend and can't be combined with the lock or seqlock fallbacks");
+#[cfg(feature = "portable-atomic")]
+compile_error!("`signal` can't be combined with `portable-atomic`, which may fall back to locks");
+#[cfg(not(target_arch = "x86_64"))]
+compile_error!("`signal` needs the native cmpxchg16b backend, which is x86_64 only");
+#[cfg(feature = "chaos")]
+compile_error!("`signal` can't be combined with `chaos`");
+
+use core::sync::atomic::Ordering;
+
+pub use spin::{fetch_add, fetch_update, swap};
+use AtomicU128;
+
+/// A counter crash handlers and sampling profilers can bump from signal
+/// context, and normal code can read.
+#[derive(Debug, Default)]
+pub struct SignalCounter128 {
+    word: AtomicU128,
+}
+
+impl SignalCounter128 {
+    pub const fn new() -> Self {
+        SignalCounter128 { word: AtomicU128::new(0) }
+    }
+
+    /// Adds `n` and returns the previous count. Async-signal-safe.
+    pub fn add(&self, n: u128) -> u128 {
+        fetch_add(&self.word, n)
+    }
+
+    /// Async-signal-safe.
+    pub fn get(&self) -> u128 {
+        self.word.load(Ordering::SeqCst)
+    }
+
+    /// Resets the count to zero and returns it. Async-signal-safe.
+    pub fn take(&self) -> u128 {
+        swap(&self.word, 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::{fetch_update, swap, SignalCounter128};
+    use AtomicU128;
+
+    static SAMPLES: SignalCounter128 = SignalCounter128::new();
+
+    #[test]
+    fn test_spinning_ops() {
+        let word = AtomicU128::new(1 << 64);
+        assert_eq!(swap(&word, 2), 1 << 64);
+        assert_eq!(fetch_update(&word, |v| if v == 2 { Some(3) } else { None }), Ok(2));
+        assert_eq!(fetch_update(&word, |_| None), Err(3));
+        SAMPLES.add(u64::MAX as u128);
+        SAMPLES.add(1);
+        assert_eq!(SAMPLES.take(), 1 << 64);
+    }
+
+    #[test]
+    fn test_counter_from_threads() {
+        let counter = Arc::new(SignalCounter128::new());
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let counter = counter.clone();
+                thread::spawn(move || {
+                    for _ in 0..1000 {
+                        counter.add(1);
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(counter.get(), 4000);
+    }
+}
@@ -0,0 +1,142 @@
+use core::fmt;
+use core::sync::atomic::Ordering;
+
+use {fmt_atomic, AtomicU128};
+
+/// A signed 128-bit integer that can be shared between threads, with the
+/// same methods as `std::sync::atomic::AtomicI64`.
+///
+/// The bits live in an `AtomicU128`, so every operation runs on the same
+/// backend; only `fetch_max` and `fetch_min` need to know the sign.
+#[repr(transparent)]
+#[derive(Default)]
+pub struct AtomicI128 {
+    inner: AtomicU128,
+}
+
+impl AtomicI128 {
+    pub const fn new(v: i128) -> Self {
+        AtomicI128 { inner: AtomicU128::new(v as u128) }
+    }
+
+    pub fn get_mut(&mut self) -> &mut i128 {
+        unsafe { &mut *(self.inner.get_mut() as *mut u128 as *mut i128) }
+    }
+
+    pub fn into_inner(self) -> i128 {
+        self.inner.into_inner() as i128
+    }
+
+    pub fn as_ptr(&self) -> *mut i128 {
+        self.inner.as_ptr() as *mut i128
+    }
+
+    pub fn load(&self, order: Ordering) -> i128 {
+        self.inner.load(order) as i128
+    }
+
+    pub fn store(&self, val: i128, order: Ordering) {
+        self.inner.store(val as u128, order)
+    }
+
+    pub fn swap(&self, val: i128, order: Ordering) -> i128 {
+        self.inner.swap(val as u128, order) as i128
+    }
+
+    #[deprecated(note = "use `compare_exchange` or `compare_exchange_weak` instead")]
+    pub fn compare_and_swap(&self, current: i128, new: i128, order: Ordering) -> i128 {
+        match self.compare_exchange(current, new, order, Ordering::SeqCst) {
+            Ok(v) | Err(v) => v,
+        }
+    }
+
+    pub fn compare_exchange(&self, current: i128, new: i128, success: Ordering, failure: Ordering) -> Result<i128, i128> {
+        self.inner
+            .compare_exchange(current as u128, new as u128, success, failure)
+            .map(|v| v as i128)
+            .map_err(|v| v as i128)
+    }
+
+    pub fn compare_exchange_weak(&self, current: i128, new: i128, success: Ordering, failure: Ordering) -> Result<i128, i128> {
+        self.inner
+            .compare_exchange_weak(current as u128, new as u128, success, failure)
+            .map(|v| v as i128)
+            .map_err(|v| v as i128)
+    }
+
+    pub fn fetch_update<F>(&self, set_order: Ordering, fetch_order: Ordering, mut f: F) -> Result<i128, i128>
+    where
+        F: FnMut(i128) -> Option<i128>,
+    {
+        self.inner
+            .fetch_update(set_order, fetch_order, |v| f(v as i128).map(|v| v as u128))
+            .map(|v| v as i128)
+            .map_err(|v| v as i128)
+    }
+
+    pub fn fetch_add(&self, val: i128, order: Ordering) -> i128 {
+        self.inner.fetch_add(val as u128, order) as i128
+    }
+
+    pub fn fetch_sub(&self, val: i128, order: Ordering) -> i128 {
+        self.inner.fetch_sub(val as u128, order) as i128
+    }
+
+    pub fn fetch_and(&self, val: i128, order: Ordering) -> i128 {
+        self.inner.fetch_and(val as u128, order) as i128
+    }
+
+    pub fn fetch_nand(&self, val: i128, order: Ordering) -> i128 {
+        self.inner.fetch_nand(val as u128, order) as i128
+    }
+
+    pub fn fetch_or(&self, val: i128, order: Ordering) -> i128 {
+        self.inner.fetch_or(val as u128, order) as i128
+    }
+
+    pub fn fetch_xor(&self, val: i128, order: Ordering) -> i128 {
+        self.inner.fetch_xor(val as u128, order) as i128
+    }
+
+    pub fn fetch_max(&self, val: i128, order: Ordering) -> i128 {
+        match self.fetch_update(order, Ordering::SeqCst, |v| Some(v.max(val))) {
+            Ok(v) | Err(v) => v,
+        }
+    }
+
+    pub fn fetch_min(&self, val: i128, order: Ordering) -> i128 {
+        match self.fetch_update(order, Ordering::SeqCst, |v| Some(v.min(val))) {
+            Ok(v) | Err(v) => v,
+        }
+    }
+}
+
+impl From<i128> for AtomicI128 {
+    fn from(v: i128) -> Self {
+        Self::new(v)
+    }
+}
+
+impl fmt::Debug for AtomicI128 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt_atomic(f, "AtomicI128", &self.load(Ordering::SeqCst))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering::SeqCst;
+
+    use super::AtomicI128;
+
+    #[test]
+    fn test_signed_ops() {
+        let a = AtomicI128::new(-1);
+        assert_eq!(a.fetch_add(2, SeqCst), -1);
+        assert_eq!(a.fetch_max(-5, SeqCst), 1);
+        assert_eq!(a.fetch_min(-5, SeqCst), 1);
+        assert_eq!(a.compare_exchange(-5, i128::MIN, SeqCst, SeqCst), Ok(-5));
+        assert_eq!(a.fetch_sub(1, SeqCst), i128::MIN);
+        assert_eq!(a.into_inner(), i128::MAX);
+    }
+}
@@ -0,0 +1,59 @@
+use std::sync::atomic::Ordering::SeqCst;
+
+use AtomicU128;
+
+fn collect(cells: &[&AtomicU128]) -> Vec<u128> {
+    cells.iter().map(|cell| cell.load(SeqCst)).collect()
+}
+
+/// Reads several cells as one consistent cut, giving up after `attempts`
+/// double collects that didn't agree.
+///
+/// Two back-to-back collects that see identical values mean no cell changed
+/// in between, so the values all held at once. That only holds if a cell
+/// can't change and change back between the collects (ABA); cells that can
+/// should carry a version or counter in one half.
+pub fn try_snapshot(cells: &[&AtomicU128], attempts: usize) -> Option<Vec<u128>> {
+    let mut previous = collect(cells);
+    for _ in 0..attempts {
+        let current = collect(cells);
+        if current == previous {
+            return Some(current);
+        }
+        previous = current;
+    }
+    None
+}
+
+/// Like `try_snapshot`, but retries until the collects agree.
+pub fn snapshot(cells: &[&AtomicU128]) -> Vec<u128> {
+    let mut previous = collect(cells);
+    loop {
+        let current = collect(cells);
+        if current == previous {
+            return current;
+        }
+        previous = current;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{snapshot, try_snapshot};
+    use AtomicU128;
+
+    #[test]
+    fn test_snapshot() {
+        let a = AtomicU128::new(2 << 64 | 1);
+        let b = AtomicU128::new(4 << 64 | 3);
+        assert_eq!(snapshot(&[&a, &b]), vec![2 << 64 | 1, 4 << 64 | 3]);
+        assert_eq!(snapshot(&[]), vec![]);
+    }
+
+    #[test]
+    fn test_try_snapshot() {
+        let a = AtomicU128::new(2 << 64 | 1);
+        assert_eq!(try_snapshot(&[&a], 1), Some(vec![2 << 64 | 1]));
+        assert_eq!(try_snapshot(&[&a], 0), None);
+    }
+}
@@ -0,0 +1,41 @@
+// CAS loops that only spin between attempts, for the contexts that can't
+// yield, park or take a lock: signal handlers and interrupt handlers. They
+// can't deadlock there either, since the handler only preempts its own
+// thread or CPU and every other one keeps making progress.
+
+use core::hint;
+use core::sync::atomic::Ordering;
+
+use cas128;
+use AtomicU128;
+
+/// `AtomicU128::fetch_update` that only spins between attempts.
+pub fn fetch_update<F: FnMut(u128) -> Option<u128>>(word: &AtomicU128, mut f: F) -> Result<u128, u128> {
+    let mut current = word.load(Ordering::SeqCst);
+    loop {
+        let new = match f(current) {
+            Some(new) => new,
+            None => return Err(current),
+        };
+        if cas128(word, &mut current, new) {
+            return Ok(current);
+        }
+        hint::spin_loop();
+    }
+}
+
+/// `AtomicU128::swap` that only spins between attempts.
+pub fn swap(word: &AtomicU128, val: u128) -> u128 {
+    fetch_update(word, |_| Some(val)).unwrap()
+}
+
+/// `AtomicU128::fetch_add` that only spins between attempts.
+pub fn fetch_add(word: &AtomicU128, val: u128) -> u128 {
+    fetch_update(word, |v| Some(v.wrapping_add(val))).unwrap()
+}
+
+/// `AtomicU128::store` that only spins between attempts.
+#[cfg(all(feature = "irq", target_arch = "x86_64"))]
+pub fn store(word: &AtomicU128, val: u128) {
+    swap(word, val);
+}
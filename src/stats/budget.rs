@@ -0,0 +1,114 @@
+use halves::Halves;
+use AtomicU128;
+
+/// Bytes reserved against a memory ceiling, with the most ever reserved at
+/// once.
+///
+/// Usage and peak are one word, so `try_reserve` checks the limit, takes
+/// the bytes and raises the peak in a single CAS: two threads can't both
+/// squeeze under the limit, and the peak never lags a reservation that
+/// went in. The limit is per call, so a cache can shrink its ceiling
+/// without touching the tracker.
+#[derive(Debug, Default)]
+pub struct Budget {
+    // lo is the bytes in use, hi the peak.
+    word: AtomicU128,
+}
+
+impl Budget {
+    pub fn new() -> Self {
+        Budget::default()
+    }
+
+    /// Reserves `n` bytes if that keeps usage at or under `limit`, and
+    /// returns the usage after. Otherwise takes nothing and returns the
+    /// usage that left too little room.
+    pub fn try_reserve(&self, n: u64, limit: u64) -> Result<u64, u64> {
+        let mut current = self.word.load_halves();
+        loop {
+            let in_use = match current.lo.checked_add(n) {
+                Some(in_use) if in_use <= limit => in_use,
+                _ => return Err(current.lo),
+            };
+            match self.word.cas_halves(current, Halves::new(in_use, current.hi.max(in_use))) {
+                Ok(_) => return Ok(in_use),
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    /// Gives back `n` reserved bytes.
+    pub fn release(&self, n: u64) {
+        let mut current = self.word.load_halves();
+        loop {
+            debug_assert!(n <= current.lo, "released more than was reserved");
+            match self.word.cas_halves(current, Halves::new(current.lo.saturating_sub(n), current.hi)) {
+                Ok(_) => return,
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    pub fn in_use(&self) -> u64 {
+        self.word.load_halves().lo
+    }
+
+    pub fn peak(&self) -> u64 {
+        self.word.load_halves().hi
+    }
+
+    /// Starts a new peak from the current usage and returns the old one,
+    /// for reporting a high-water mark per interval.
+    pub fn reset_peak(&self) -> u64 {
+        let mut current = self.word.load_halves();
+        loop {
+            match self.word.cas_halves(current, Halves::new(current.lo, current.lo)) {
+                Ok(_) => return current.hi,
+                Err(actual) => current = actual,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::Budget;
+
+    #[test]
+    fn test_reserve_release_peak() {
+        let budget = Budget::new();
+        assert_eq!(budget.try_reserve(60, 100), Ok(60));
+        assert_eq!(budget.try_reserve(50, 100), Err(60));
+        assert_eq!(budget.try_reserve(40, 100), Ok(100));
+        budget.release(70);
+        assert_eq!((budget.in_use(), budget.peak()), (30, 100));
+        assert_eq!(budget.reset_peak(), 100);
+        assert_eq!(budget.peak(), 30);
+        assert_eq!(budget.try_reserve(u64::MAX, u64::MAX), Err(30));
+    }
+
+    #[test]
+    fn test_limit_never_exceeded() {
+        let budget = Arc::new(Budget::new());
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let budget = budget.clone();
+                thread::spawn(move || {
+                    for _ in 0..2000 {
+                        if budget.try_reserve(3, 10).is_ok() {
+                            budget.release(3);
+                        }
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(budget.in_use(), 0);
+        assert!(budget.peak() <= 9);
+    }
+}
@@ -0,0 +1,140 @@
+use std::cell::Cell;
+use std::sync::atomic::Ordering::SeqCst;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use AtomicU128;
+
+// How many adds go by between looks at the clock.
+const CLOCK_EVERY: u32 = 64;
+
+/// A counter for hot paths: each thread adds into its own buffer and only
+/// touches the shared `AtomicU128` once the buffer passes `threshold` or has
+/// been sitting for `budget`.
+///
+/// `read_approx` is the shared total alone, off by whatever is still
+/// buffered; `flush_all` folds every thread's buffer in first, so it's exact
+/// as of the call.
+pub struct BufferedCounter128 {
+    total: AtomicU128,
+    buffers: Mutex<Vec<Arc<AtomicU64>>>,
+    threshold: u64,
+    budget: Duration,
+}
+
+/// A thread's buffer in a `BufferedCounter128`. Flushes and unregisters on
+/// drop.
+pub struct LocalCounter<'a> {
+    counter: &'a BufferedCounter128,
+    pending: Arc<AtomicU64>,
+    adds: Cell<u32>,
+    flushed_at: Cell<Instant>,
+}
+
+impl BufferedCounter128 {
+    pub fn new(threshold: u64, budget: Duration) -> Self {
+        BufferedCounter128 { total: AtomicU128::new(0), buffers: Mutex::new(Vec::new()), threshold, budget }
+    }
+
+    /// Registers a buffer for the calling thread.
+    pub fn local(&self) -> LocalCounter<'_> {
+        let pending = Arc::new(AtomicU64::new(0));
+        self.buffers.lock().unwrap().push(pending.clone());
+        LocalCounter { counter: self, pending, adds: Cell::new(0), flushed_at: Cell::new(Instant::now()) }
+    }
+
+    /// The flushed total, without what threads still hold.
+    pub fn read_approx(&self) -> u128 {
+        self.total.load(SeqCst)
+    }
+
+    /// Flushes every thread's buffer and returns the exact total.
+    pub fn flush_all(&self) -> u128 {
+        for pending in self.buffers.lock().unwrap().iter() {
+            self.drain(pending);
+        }
+        self.read_approx()
+    }
+
+    fn drain(&self, pending: &AtomicU64) {
+        let n = pending.swap(0, Ordering::AcqRel);
+        if n != 0 {
+            self.total.fetch_add(n as u128, SeqCst);
+        }
+    }
+}
+
+impl<'a> LocalCounter<'a> {
+    pub fn add(&self, n: u64) {
+        // Only this thread adds, so the RMW stays on a line it owns; a
+        // concurrent flush_all just swaps it out from under us.
+        let buffered = self.pending.fetch_add(n, Ordering::Relaxed) + n;
+        let adds = self.adds.get() + 1;
+        self.adds.set(adds);
+        if buffered >= self.counter.threshold
+            || (adds.is_multiple_of(CLOCK_EVERY) && self.flushed_at.get().elapsed() >= self.counter.budget)
+        {
+            self.flush();
+        }
+    }
+
+    pub fn flush(&self) {
+        self.counter.drain(&self.pending);
+        self.flushed_at.set(Instant::now());
+    }
+}
+
+impl<'a> Drop for LocalCounter<'a> {
+    fn drop(&mut self) {
+        self.flush();
+        self.counter.buffers.lock().unwrap().retain(|pending| !Arc::ptr_eq(pending, &self.pending));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    use super::BufferedCounter128;
+
+    #[test]
+    fn test_threshold_and_flush_all() {
+        let counter = BufferedCounter128::new(10, Duration::from_secs(3600));
+        let local = counter.local();
+        for _ in 0..9 {
+            local.add(1);
+        }
+        assert_eq!(counter.read_approx(), 0);
+        local.add(1);
+        assert_eq!(counter.read_approx(), 10);
+        local.add(3);
+        assert_eq!(counter.flush_all(), 13);
+        drop(local);
+        assert_eq!(counter.flush_all(), 13);
+    }
+
+    #[test]
+    fn test_threads_add_up() {
+        let counter = Arc::new(BufferedCounter128::new(1000, Duration::from_millis(1)));
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let counter = counter.clone();
+                thread::spawn(move || {
+                    let local = counter.local();
+                    for _ in 0..2500 {
+                        local.add(1);
+                    }
+                    // A concurrent flush_all must neither lose nor double anything.
+                    counter.flush_all();
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(counter.read_approx(), 10_000);
+    }
+}
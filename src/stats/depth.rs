@@ -0,0 +1,109 @@
+use halves::Halves;
+use AtomicU128;
+
+/// How deep a queue is, from counts of what went in and what came out.
+///
+/// Producers bump one half and consumers the other, but both halves are one
+/// word, so `len` reads them at the same instant. With two separate
+/// counters a reader can load the dequeue count after a burst of dequeues
+/// it didn't see the enqueues for, and get a negative or huge length.
+#[derive(Debug, Default)]
+pub struct DepthCounter {
+    // lo is the total enqueued, hi the total dequeued.
+    word: AtomicU128,
+}
+
+impl DepthCounter {
+    pub fn new() -> Self {
+        DepthCounter::default()
+    }
+
+    fn update<F: Fn(Halves) -> Halves>(&self, f: F) -> Halves {
+        let mut current = self.word.load_halves();
+        loop {
+            let next = f(current);
+            match self.word.cas_halves(current, next) {
+                Ok(_) => return next,
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    /// Counts `n` enqueues and returns the depth after them.
+    pub fn enqueue(&self, n: u64) -> u64 {
+        let next = self.update(|c| Halves::new(c.lo.wrapping_add(n), c.hi));
+        next.lo.wrapping_sub(next.hi)
+    }
+
+    /// Counts `n` dequeues and returns the depth after them.
+    pub fn dequeue(&self, n: u64) -> u64 {
+        let next = self.update(|c| Halves::new(c.lo, c.hi.wrapping_add(n)));
+        next.lo.wrapping_sub(next.hi)
+    }
+
+    pub fn len(&self) -> u64 {
+        let (enqueued, dequeued) = self.totals();
+        enqueued.wrapping_sub(dequeued)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The `(enqueued, dequeued)` totals, from one load.
+    pub fn totals(&self) -> (u64, u64) {
+        let current = self.word.load_halves();
+        (current.lo, current.hi)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::DepthCounter;
+
+    #[test]
+    fn test_counts() {
+        let depth = DepthCounter::new();
+        assert!(depth.is_empty());
+        assert_eq!(depth.enqueue(3), 3);
+        assert_eq!(depth.dequeue(2), 1);
+        assert_eq!((depth.len(), depth.totals()), (1, (3, 2)));
+    }
+
+    #[test]
+    fn test_len_never_negative() {
+        // Each worker dequeues one item it enqueued, so the depth stays
+        // within 0..=2; a torn read would show up as a huge length.
+        let depth = Arc::new(DepthCounter::new());
+        let stop = Arc::new(AtomicBool::new(false));
+        let workers: Vec<_> = (0..2)
+            .map(|_| {
+                let depth = depth.clone();
+                thread::spawn(move || {
+                    for _ in 0..5000 {
+                        depth.enqueue(1);
+                        depth.dequeue(1);
+                    }
+                })
+            })
+            .collect();
+        let reader = {
+            let (depth, stop) = (depth.clone(), stop.clone());
+            thread::spawn(move || {
+                while !stop.load(Ordering::SeqCst) {
+                    assert!(depth.len() <= 2);
+                }
+            })
+        };
+        for worker in workers {
+            worker.join().unwrap();
+        }
+        stop.store(true, Ordering::SeqCst);
+        reader.join().unwrap();
+        assert_eq!(depth.totals(), (10000, 10000));
+    }
+}
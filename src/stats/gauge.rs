@@ -0,0 +1,75 @@
+use std::time::{Duration, Instant};
+
+use halves::Halves;
+use AtomicU128;
+
+/// A gauge that remembers when it was last set, so a reader can tell a
+/// value of zero from a writer that stopped updating.
+///
+/// The value and its timestamp are one word: a reader never sees a fresh
+/// timestamp next to a stale value or the reverse.
+#[derive(Debug)]
+pub struct Gauge128 {
+    // lo is the value, hi one more than the update time in nanos since
+    // `start`, 0 until the first set.
+    word: AtomicU128,
+    start: Instant,
+}
+
+impl Gauge128 {
+    pub fn new() -> Self {
+        Gauge128 { word: AtomicU128::new(0), start: Instant::now() }
+    }
+
+    fn now(&self) -> u64 {
+        self.start.elapsed().as_nanos() as u64 + 1
+    }
+
+    pub fn set(&self, value: u64) {
+        self.word.store_halves(Halves::new(value, self.now()));
+    }
+
+    /// The value and how long ago it was set, or `None` if it never was.
+    pub fn get(&self) -> Option<(u64, Duration)> {
+        let current = self.word.load_halves();
+        if current.hi == 0 {
+            return None;
+        }
+        Some((current.lo, Duration::from_nanos(self.now().saturating_sub(current.hi))))
+    }
+
+    /// The value, if it was set within `max_age`.
+    pub fn read_if_fresh(&self, max_age: Duration) -> Option<u64> {
+        match self.get() {
+            Some((value, age)) if age <= max_age => Some(value),
+            _ => None,
+        }
+    }
+}
+
+impl Default for Gauge128 {
+    fn default() -> Self {
+        Gauge128::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use std::time::Duration;
+
+    use super::Gauge128;
+
+    #[test]
+    fn test_freshness() {
+        let gauge = Gauge128::new();
+        assert_eq!(gauge.get(), None);
+        assert_eq!(gauge.read_if_fresh(Duration::from_secs(60)), None);
+        gauge.set(0);
+        assert_eq!(gauge.read_if_fresh(Duration::from_secs(60)), Some(0));
+        thread::sleep(Duration::from_millis(20));
+        assert_eq!(gauge.read_if_fresh(Duration::from_millis(5)), None);
+        let (value, age) = gauge.get().unwrap();
+        assert!(value == 0 && age >= Duration::from_millis(20));
+    }
+}
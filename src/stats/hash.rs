@@ -0,0 +1,132 @@
+use std::sync::atomic::Ordering::SeqCst;
+
+use AtomicU128;
+
+/// How a `HashAccumulator128` folds hashes in. Both are commutative, so the
+/// result doesn't depend on the order contributions arrive in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fold {
+    /// Wrapping addition: a multiset hash, where duplicates count and
+    /// `remove` undoes an `absorb`.
+    Add,
+    /// XOR: a set hash, where absorbing the same hash twice cancels it.
+    Xor,
+}
+
+/// A set or multiset hash that any number of workers contribute to with one
+/// atomic RMW each, no coordination needed, so two runs over the same data
+/// can be compared by `digest` however the work was split up.
+///
+/// `absorb` expects well-mixed 128-bit hashes; the fold itself is linear,
+/// which `digest` hides behind a finalizer.
+#[derive(Debug)]
+pub struct HashAccumulator128 {
+    word: AtomicU128,
+    fold: Fold,
+}
+
+impl HashAccumulator128 {
+    pub fn new(fold: Fold) -> Self {
+        HashAccumulator128 { word: AtomicU128::new(0), fold }
+    }
+
+    pub fn fold(&self) -> Fold {
+        self.fold
+    }
+
+    pub fn absorb(&self, h: u128) {
+        match self.fold {
+            Fold::Add => self.word.fetch_add(h, SeqCst),
+            Fold::Xor => self.word.fetch_xor(h, SeqCst),
+        };
+    }
+
+    /// Takes back a hash absorbed earlier.
+    pub fn remove(&self, h: u128) {
+        match self.fold {
+            Fold::Add => self.word.fetch_sub(h, SeqCst),
+            Fold::Xor => self.word.fetch_xor(h, SeqCst),
+        };
+    }
+
+    /// Absorbs everything `other` has, as if its hashes had come here.
+    pub fn merge(&self, other: &HashAccumulator128) {
+        assert_eq!(self.fold, other.fold, "can't merge accumulators with different folds");
+        self.absorb(other.raw());
+    }
+
+    /// The folded value itself.
+    pub fn raw(&self) -> u128 {
+        self.word.load(SeqCst)
+    }
+
+    /// The folded value run through a fixed finalizer, for comparing.
+    pub fn digest(&self) -> u128 {
+        finalize(self.raw())
+    }
+}
+
+// Two xorshift-multiply rounds per half, the halves mixed into each other.
+fn finalize(v: u128) -> u128 {
+    let mix = |mut x: u64| {
+        x ^= x >> 33;
+        x = x.wrapping_mul(0xff51_afd7_ed55_8ccd);
+        x ^= x >> 33;
+        x = x.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+        x ^ (x >> 33)
+    };
+    let (lo, hi) = (v as u64, (v >> 64) as u64);
+    let hi = mix(hi ^ mix(lo));
+    let lo = mix(lo ^ hi);
+    (hi as u128) << 64 | lo as u128
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::{Fold, HashAccumulator128};
+
+    fn hash(i: u128) -> u128 {
+        i.wrapping_mul(0x9e37_79b9_7f4a_7c15_f39c_c060_5ced_c835) ^ i << 7
+    }
+
+    #[test]
+    fn test_order_independent() {
+        for &fold in &[Fold::Add, Fold::Xor] {
+            let serial = HashAccumulator128::new(fold);
+            (0..4000).for_each(|i| serial.absorb(hash(i)));
+
+            let parallel = Arc::new(HashAccumulator128::new(fold));
+            let workers: Vec<_> = (0..4u128)
+                .map(|w| {
+                    let parallel = parallel.clone();
+                    thread::spawn(move || (0..1000).rev().for_each(|i| parallel.absorb(hash(i * 4 + w))))
+                })
+                .collect();
+            for worker in workers {
+                worker.join().unwrap();
+            }
+            assert_eq!(parallel.digest(), serial.digest());
+        }
+    }
+
+    #[test]
+    fn test_fold_semantics() {
+        let set = HashAccumulator128::new(Fold::Xor);
+        set.absorb(hash(1));
+        set.absorb(hash(1));
+        assert_eq!(set.raw(), 0);
+
+        let multiset = HashAccumulator128::new(Fold::Add);
+        let other = HashAccumulator128::new(Fold::Add);
+        multiset.absorb(hash(1));
+        other.absorb(hash(1));
+        other.absorb(hash(2));
+        multiset.merge(&other);
+        multiset.remove(hash(2));
+        assert_eq!(multiset.raw(), hash(1).wrapping_mul(2));
+        assert_ne!(multiset.digest(), set.digest());
+    }
+}
@@ -0,0 +1,104 @@
+use halves::Halves;
+use AtomicU128;
+
+/// Two adjacent 64-bit counters updated together, such as a histogram
+/// bucket's sample count and the total of those samples.
+#[derive(Debug, Default)]
+pub struct BucketPair {
+    // lo is the first counter (count), hi the second (total).
+    word: AtomicU128,
+}
+
+impl BucketPair {
+    pub fn new() -> Self {
+        BucketPair { word: AtomicU128::new(0) }
+    }
+
+    /// Adds to both counters in one CAS; both wrap on overflow.
+    pub fn add(&self, first: u64, second: u64) {
+        let mut current = self.word.load_halves();
+        loop {
+            let new = Halves::new(current.lo.wrapping_add(first), current.hi.wrapping_add(second));
+            match self.word.cas_halves(current, new) {
+                Ok(_) => return,
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    pub fn get(&self) -> (u64, u64) {
+        let word = self.word.load_halves();
+        (word.lo, word.hi)
+    }
+
+    /// Zeroes both counters and returns what they held.
+    pub fn take(&self) -> (u64, u64) {
+        let word = self.word.swap_halves(Halves::default());
+        (word.lo, word.hi)
+    }
+}
+
+/// Histogram whose buckets each keep a (count, total) `BucketPair`.
+///
+/// Bucket `i` holds samples up to and including `bounds[i]`; one extra
+/// bucket at the end takes everything larger.
+pub struct Histogram128 {
+    bounds: Vec<u64>,
+    buckets: Vec<BucketPair>,
+}
+
+impl Histogram128 {
+    pub fn with_bounds(bounds: Vec<u64>) -> Self {
+        assert!(bounds.windows(2).all(|w| w[0] < w[1]), "bounds must be strictly increasing");
+        let buckets = (0..bounds.len() + 1).map(|_| BucketPair::new()).collect();
+        Histogram128 { bounds, buckets }
+    }
+
+    pub fn bounds(&self) -> &[u64] {
+        &self.bounds
+    }
+
+    pub fn record(&self, value: u64) {
+        let i = match self.bounds.binary_search(&value) {
+            Ok(i) | Err(i) => i,
+        };
+        self.buckets[i].add(1, value);
+    }
+
+    /// The (count, total) of every bucket, including the overflow one. Each
+    /// pair is consistent by itself; the set is not one snapshot.
+    pub fn buckets(&self) -> Vec<(u64, u64)> {
+        self.buckets.iter().map(BucketPair::get).collect()
+    }
+
+    /// Like `buckets`, but zeroes each bucket as it goes.
+    pub fn take(&self) -> Vec<(u64, u64)> {
+        self.buckets.iter().map(BucketPair::take).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BucketPair, Histogram128};
+
+    #[test]
+    fn test_pair() {
+        let pair = BucketPair::new();
+        pair.add(1, 10);
+        pair.add(2, 5);
+        assert_eq!(pair.get(), (3, 15));
+        assert_eq!(pair.take(), (3, 15));
+        assert_eq!(pair.get(), (0, 0));
+    }
+
+    #[test]
+    fn test_histogram() {
+        let h = Histogram128::with_bounds(vec![10, 100]);
+        for &v in &[1, 10, 11, 100, 1000, 5000] {
+            h.record(v);
+        }
+        assert_eq!(h.buckets(), vec![(2, 11), (2, 111), (2, 6000)]);
+        assert_eq!(h.take().len(), 3);
+        assert_eq!(h.buckets(), vec![(0, 0); 3]);
+    }
+}
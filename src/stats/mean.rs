@@ -0,0 +1,72 @@
+use halves::Halves;
+use AtomicU128;
+
+/// Running sum and sample count in one word.
+///
+/// `record` adds to both in one CAS, so `mean` always divides a sum by the
+/// count of exactly the samples in it rather than pairing a sum from one
+/// moment with a count from another.
+#[derive(Debug, Default)]
+pub struct MeanAccumulator {
+    // lo is the sum, hi the count.
+    word: AtomicU128,
+}
+
+impl MeanAccumulator {
+    pub fn new() -> Self {
+        MeanAccumulator { word: AtomicU128::new(0) }
+    }
+
+    /// Adds a sample; the sum saturates rather than wrapping.
+    pub fn record(&self, x: u64) {
+        let mut current = self.word.load_halves();
+        loop {
+            match self.word.cas_halves(current, Halves::new(current.lo.saturating_add(x), current.hi + 1)) {
+                Ok(_) => return,
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    /// The (sum, count) pair as of one moment.
+    pub fn get(&self) -> (u64, u64) {
+        let word = self.word.load_halves();
+        (word.lo, word.hi)
+    }
+
+    pub fn count(&self) -> u64 {
+        self.word.load_halves().hi
+    }
+
+    pub fn mean(&self) -> Option<f64> {
+        let (sum, count) = self.get();
+        if count == 0 { None } else { Some(sum as f64 / count as f64) }
+    }
+
+    /// Zeroes the accumulator and returns the (sum, count) it held.
+    pub fn take(&self) -> (u64, u64) {
+        let word = self.word.swap_halves(Halves::default());
+        (word.lo, word.hi)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MeanAccumulator;
+
+    #[test]
+    fn test_mean() {
+        let acc = MeanAccumulator::new();
+        assert_eq!(acc.mean(), None);
+        acc.record(1);
+        acc.record(2);
+        acc.record(6);
+        assert_eq!(acc.get(), (9, 3));
+        assert_eq!(acc.mean(), Some(3.0));
+        assert_eq!(acc.take(), (9, 3));
+        assert_eq!(acc.count(), 0);
+        acc.record(u64::MAX);
+        acc.record(1);
+        assert_eq!(acc.get(), (u64::MAX, 2));
+    }
+}
@@ -0,0 +1,17 @@
+mod mean;
+mod watermark;
+mod histogram;
+mod counter;
+mod hash;
+mod gauge;
+mod depth;
+mod budget;
+
+pub use self::mean::MeanAccumulator;
+pub use self::watermark::Watermark;
+pub use self::histogram::{BucketPair, Histogram128};
+pub use self::counter::{BufferedCounter128, LocalCounter};
+pub use self::hash::{Fold, HashAccumulator128};
+pub use self::gauge::Gauge128;
+pub use self::depth::DepthCounter;
+pub use self::budget::Budget;
@@ -0,0 +1,83 @@
+use halves::Halves;
+use AtomicU128;
+
+/// Running maximum (or minimum) kept together with caller metadata, typically
+/// the time the extreme was seen.
+///
+/// The value and its metadata are one word, and a sample only goes in by a
+/// CAS after it has been compared against the current extreme, so readers
+/// never see a new maximum paired with the old one's timestamp.
+#[derive(Debug)]
+pub struct Watermark {
+    // lo is the extreme value, hi its metadata.
+    word: AtomicU128,
+    is_max: bool,
+}
+
+impl Watermark {
+    /// Tracks the largest sample; starts at (0, 0).
+    pub fn max() -> Self {
+        Watermark { word: AtomicU128::new(0), is_max: true }
+    }
+
+    /// Tracks the smallest sample; starts at (u64::MAX, 0).
+    pub fn min() -> Self {
+        Watermark { word: AtomicU128::from_halves(Halves::new(u64::MAX, 0)), is_max: false }
+    }
+
+    fn beats(&self, value: u64, current: u64) -> bool {
+        if self.is_max { value > current } else { value < current }
+    }
+
+    /// Records a sample, returning whether it became the new extreme. Ties
+    /// keep the earlier sample's metadata.
+    pub fn observe(&self, value: u64, meta: u64) -> bool {
+        let mut current = self.word.load_halves();
+        while self.beats(value, current.lo) {
+            match self.word.cas_halves(current, Halves::new(value, meta)) {
+                Ok(_) => return true,
+                Err(actual) => current = actual,
+            }
+        }
+        false
+    }
+
+    /// The current (value, metadata) pair.
+    pub fn get(&self) -> (u64, u64) {
+        let word = self.word.load_halves();
+        (word.lo, word.hi)
+    }
+
+    /// Puts the watermark back to its starting point and returns what it held.
+    pub fn reset(&self) -> (u64, u64) {
+        let start = if self.is_max { Halves::default() } else { Halves::new(u64::MAX, 0) };
+        let word = self.word.swap_halves(start);
+        (word.lo, word.hi)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Watermark;
+
+    #[test]
+    fn test_max() {
+        let w = Watermark::max();
+        assert!(w.observe(5, 100));
+        assert!(!w.observe(3, 101));
+        assert!(!w.observe(5, 102));
+        assert!(w.observe(9, 103));
+        assert_eq!(w.get(), (9, 103));
+        assert_eq!(w.reset(), (9, 103));
+        assert_eq!(w.get(), (0, 0));
+    }
+
+    #[test]
+    fn test_min() {
+        let w = Watermark::min();
+        assert!(w.observe(5, 1));
+        assert!(w.observe(2, 2));
+        assert!(!w.observe(4, 3));
+        assert_eq!(w.get(), (2, 2));
+    }
+}
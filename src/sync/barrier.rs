@@ -0,0 +1,164 @@
+#[cfg(not(feature = "std"))]
+use std::hint;
+#[cfg(feature = "std")]
+use std::sync::{Condvar, Mutex};
+#[cfg(feature = "std")]
+use std::thread;
+
+use halves::Halves;
+use AtomicU128;
+
+/// What `Barrier::wait` observed: the generation that was completed and
+/// whether this thread was the one that completed it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BarrierWaitResult {
+    pub generation: u64,
+    pub is_leader: bool,
+}
+
+/// Reusable barrier with (arrived, generation) in one word.
+///
+/// The last thread to arrive resets the count and advances the generation in
+/// one CAS; everyone else waits for the generation to move past the one they
+/// arrived in, so back-to-back rounds can't mix up their waiters.
+///
+/// Without the `std` feature only `wait_spin` is available, and it spins
+/// without yielding, so the barrier needs nothing from the OS.
+pub struct Barrier {
+    // lo is the number arrived, hi the generation.
+    word: AtomicU128,
+    parties: u64,
+    #[cfg(feature = "std")]
+    lock: Mutex<()>,
+    #[cfg(feature = "std")]
+    cond: Condvar,
+}
+
+impl Barrier {
+    pub fn new(parties: u64) -> Self {
+        assert!(parties > 0, "barrier needs at least one party");
+        Barrier {
+            word: AtomicU128::new(0),
+            parties,
+            #[cfg(feature = "std")]
+            lock: Mutex::new(()),
+            #[cfg(feature = "std")]
+            cond: Condvar::new(),
+        }
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.word.load_halves().hi
+    }
+
+    // Returns the generation arrived in and whether we completed it. The
+    // leader wakes sleeping waiters however it arrived itself, since
+    // `wait` and `wait_spin` can be mixed in one round.
+    fn arrive(&self) -> BarrierWaitResult {
+        let mut current = self.word.load_halves();
+        loop {
+            let arrived = current.lo + 1;
+            let new = if arrived == self.parties {
+                Halves::new(0, current.hi.wrapping_add(1))
+            } else {
+                Halves::new(arrived, current.hi)
+            };
+            match self.word.cas_halves(current, new) {
+                Ok(_) => {
+                    let result = BarrierWaitResult { generation: current.hi, is_leader: arrived == self.parties };
+                    if result.is_leader {
+                        self.wake();
+                    }
+                    return result;
+                }
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn wake(&self) {
+        let _guard = self.lock.lock().unwrap();
+        self.cond.notify_all();
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn wake(&self) {}
+
+    /// Waits for all parties, spinning (with yields) instead of sleeping.
+    pub fn wait_spin(&self) -> BarrierWaitResult {
+        let result = self.arrive();
+        if !result.is_leader {
+            while self.generation() == result.generation {
+                #[cfg(feature = "std")]
+                thread::yield_now();
+                #[cfg(not(feature = "std"))]
+                hint::spin_loop();
+            }
+        }
+        result
+    }
+
+    /// Waits for all parties, sleeping until the round completes.
+    #[cfg(feature = "std")]
+    pub fn wait(&self) -> BarrierWaitResult {
+        let result = self.arrive();
+        if !result.is_leader {
+            let mut guard = self.lock.lock().unwrap();
+            while self.generation() == result.generation {
+                guard = self.cond.wait(guard).unwrap();
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "std")]
+    use std::sync::Arc;
+    #[cfg(feature = "std")]
+    use std::thread;
+    #[cfg(feature = "std")]
+    use std::time::Duration;
+
+    use super::Barrier;
+
+    #[test]
+    fn test_single_party() {
+        let b = Barrier::new(1);
+        let r = b.wait_spin();
+        assert!(r.is_leader);
+        assert_eq!(r.generation, 0);
+        let r = b.wait_spin();
+        assert!(r.is_leader);
+        assert_eq!(r.generation, 1);
+        assert_eq!(b.generation(), 2);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_spinning_leader_wakes_sleepers() {
+        let b = Arc::new(Barrier::new(2));
+        let sleeper = {
+            let b = b.clone();
+            thread::spawn(move || b.wait())
+        };
+        // Give the sleeper time to get to its condvar first.
+        while b.word.load_halves().lo == 0 {
+            thread::yield_now();
+        }
+        thread::sleep(Duration::from_millis(10));
+        assert!(b.wait_spin().is_leader);
+        assert!(!sleeper.join().unwrap().is_leader);
+    }
+
+    #[test]
+    fn test_arrive() {
+        let b = Barrier::new(2);
+        assert!(!b.arrive().is_leader);
+        assert_eq!(b.generation(), 0);
+        assert!(b.arrive().is_leader);
+        assert_eq!(b.generation(), 1);
+    }
+}
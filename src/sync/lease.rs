@@ -0,0 +1,142 @@
+use halves::Halves;
+use AtomicU128;
+
+// The id of no leader.
+const NONE: u64 = 0;
+
+/// A leader-election lease: who leads and until when.
+///
+/// Leader and deadline are one word, so taking over an expired lease,
+/// renewing one and giving it up are each a single CAS, and no candidate
+/// can take the lease between a check that it expired and the claim.
+/// Ids are nonzero; times are in whatever clock every candidate shares,
+/// such as monotonic nanos for processes on one machine. The cell is laid
+/// out as a bare word, so `from_ref` can run an election over one in shared
+/// memory, which needs a lock-free backend.
+#[repr(transparent)]
+#[derive(Debug, Default)]
+pub struct LeaseCell {
+    // lo is the leader's id (NONE when released), hi the deadline.
+    word: AtomicU128,
+}
+
+impl LeaseCell {
+    pub fn new() -> Self {
+        LeaseCell::default()
+    }
+
+    /// Views a word, such as one of a `SharedCells` segment's, as a lease.
+    pub fn from_ref(word: &AtomicU128) -> &LeaseCell {
+        unsafe { &*(word as *const AtomicU128 as *const LeaseCell) }
+    }
+
+    /// The leader and its deadline, expired or not.
+    pub fn load(&self) -> Option<(u64, u64)> {
+        let current = self.word.load_halves();
+        if current.lo == NONE { None } else { Some((current.lo, current.hi)) }
+    }
+
+    /// The leader whose lease is still good at `now`.
+    pub fn leader(&self, now: u64) -> Option<u64> {
+        match self.load() {
+            Some((leader, deadline)) if now < deadline => Some(leader),
+            _ => None,
+        }
+    }
+
+    /// Takes the lease for `me` until `now + ttl` if it's free, expired or
+    /// already `me`'s, and returns the new deadline. Otherwise returns the
+    /// current leader and deadline.
+    pub fn try_acquire(&self, me: u64, now: u64, ttl: u64) -> Result<u64, (u64, u64)> {
+        assert!(me != NONE, "leader ids must be nonzero");
+        let deadline = now.saturating_add(ttl);
+        let mut current = self.word.load_halves();
+        loop {
+            if current.lo != NONE && current.lo != me && now < current.hi {
+                return Err((current.lo, current.hi));
+            }
+            match self.word.cas_halves(current, Halves::new(me, deadline)) {
+                Ok(_) => return Ok(deadline),
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    /// Extends `me`'s lease to `now + ttl`. Fails if `me` isn't the leader
+    /// or its lease already ran out, in which case it must `try_acquire`
+    /// again and assume someone else may have led meanwhile.
+    pub fn renew(&self, me: u64, now: u64, ttl: u64) -> bool {
+        let mut current = self.word.load_halves();
+        while current.lo == me && now < current.hi {
+            match self.word.cas_halves(current, Halves::new(me, now.saturating_add(ttl))) {
+                Ok(_) => return true,
+                Err(actual) => current = actual,
+            }
+        }
+        false
+    }
+
+    /// Gives up `me`'s lease so the next candidate needn't wait it out.
+    pub fn release(&self, me: u64) -> bool {
+        let mut current = self.word.load_halves();
+        while current.lo == me {
+            match self.word.cas_halves(current, Halves::new(NONE, 0)) {
+                Ok(_) => return true,
+                Err(actual) => current = actual,
+            }
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::LeaseCell;
+    use AtomicU128;
+
+    #[test]
+    fn test_acquire_renew_release() {
+        let lease = LeaseCell::new();
+        assert_eq!(lease.try_acquire(1, 100, 10), Ok(110));
+        assert_eq!(lease.try_acquire(2, 105, 10), Err((1, 110)));
+        assert!(lease.renew(1, 105, 10));
+        assert_eq!(lease.leader(114), Some(1));
+        assert!(!lease.renew(1, 115, 10));
+        assert_eq!(lease.try_acquire(2, 115, 10), Ok(125));
+        assert!(!lease.release(1));
+        assert!(lease.release(2));
+        assert_eq!(lease.load(), None);
+
+        let word = AtomicU128::new(0);
+        assert_eq!(LeaseCell::from_ref(&word).try_acquire(3, 0, 5), Ok(5));
+        assert_eq!(LeaseCell::from_ref(&word).leader(4), Some(3));
+    }
+
+    #[test]
+    fn test_one_leader_per_term() {
+        // Terms are ten ticks long and every candidate tries once per term,
+        // however far ahead of the others its clock is.
+        let lease = Arc::new(LeaseCell::new());
+        let wins: Arc<Vec<AtomicUsize>> = Arc::new((0..100).map(|_| AtomicUsize::new(0)).collect());
+        let handles: Vec<_> = (1..5u64)
+            .map(|me| {
+                let (lease, wins) = (lease.clone(), wins.clone());
+                thread::spawn(move || {
+                    for term in 0..100u64 {
+                        if lease.try_acquire(me, term * 10, 10).is_ok() {
+                            wins[term as usize].fetch_add(1, Ordering::SeqCst);
+                        }
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert!(wins.iter().all(|w| w.load(Ordering::SeqCst) <= 1));
+    }
+}
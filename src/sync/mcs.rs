@@ -0,0 +1,129 @@
+use std::cell::UnsafeCell;
+use std::ops::{Deref, DerefMut};
+use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicPtr, Ordering};
+
+use current_backoff;
+use halves::Halves;
+use AtomicU128;
+
+struct Node {
+    locked: AtomicBool,
+    next: AtomicPtr<Node>,
+}
+
+/// MCS queue lock: each waiter spins on its own node.
+///
+/// The tail is a (node pointer, count) pair, so the releasing thread's CAS
+/// can't be fooled by a new waiter whose node happens to reuse its address.
+/// Like `TicketLock`, it waits through the installed `Backoff`.
+pub struct McsLock<T> {
+    tail: AtomicU128,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: Send> Send for McsLock<T> {}
+unsafe impl<T: Send> Sync for McsLock<T> {}
+
+pub struct McsGuard<'a, T: 'a> {
+    lock: &'a McsLock<T>,
+    node: Box<Node>,
+}
+
+impl<T> McsLock<T> {
+    pub fn new(data: T) -> Self {
+        McsLock {
+            tail: AtomicU128::new(0),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    pub fn lock(&self) -> McsGuard<'_, T> {
+        let node = Box::new(Node {
+            locked: AtomicBool::new(true),
+            next: AtomicPtr::new(ptr::null_mut()),
+        });
+        let raw = &*node as *const Node as *mut Node;
+        let mut current = self.tail.load_halves();
+        loop {
+            let enqueued = Halves::new(raw as u64, current.hi.wrapping_add(1));
+            match self.tail.cas_halves(current, enqueued) {
+                Ok(_) => break,
+                Err(actual) => current = actual,
+            }
+        }
+        let prev = current.lo as *mut Node;
+        if !prev.is_null() {
+            unsafe { (*prev).next.store(raw, Ordering::Release) };
+            let mut attempt = 0;
+            while node.locked.load(Ordering::Acquire) {
+                attempt += 1;
+                current_backoff().wait(attempt);
+            }
+        }
+        McsGuard { lock: self, node }
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.tail.load_halves().lo != 0
+    }
+
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<'a, T> Deref for McsGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<'a, T> DerefMut for McsGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<'a, T> Drop for McsGuard<'a, T> {
+    fn drop(&mut self) {
+        let raw = &*self.node as *const Node as u64;
+        if self.node.next.load(Ordering::Acquire).is_null() {
+            let current = self.lock.tail.load_halves();
+            if current.lo == raw {
+                let empty = Halves::new(0, current.hi.wrapping_add(1));
+                if self.lock.tail.cas_halves(current, empty).is_ok() {
+                    return;
+                }
+            }
+            // A successor is between swapping the tail and linking itself in.
+            let mut attempt = 0;
+            while self.node.next.load(Ordering::Acquire).is_null() {
+                attempt += 1;
+                current_backoff().wait(attempt);
+            }
+        }
+        let next = self.node.next.load(Ordering::Acquire);
+        unsafe { (*next).locked.store(false, Ordering::Release) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::McsLock;
+
+    #[test]
+    fn test_lock() {
+        let lock = McsLock::new(vec![1]);
+        {
+            let mut guard = lock.lock();
+            guard.push(2);
+            assert!(lock.is_locked());
+        }
+        assert!(!lock.is_locked());
+        lock.lock().push(3);
+        assert_eq!(lock.into_inner(), vec![1, 2, 3]);
+    }
+}
@@ -0,0 +1,34 @@
+mod ticket;
+mod mcs;
+mod rw_state;
+mod semaphore;
+mod wait_group;
+mod barrier;
+mod seqlock;
+mod waker_slot;
+mod sequence;
+mod qsbr;
+mod publish;
+mod result_slot;
+mod olc;
+mod reentrant;
+mod lease;
+mod read_indicator;
+pub mod watch;
+
+pub use self::ticket::{TicketLock, TicketGuard};
+pub use self::mcs::{McsLock, McsGuard};
+pub use self::rw_state::RwState;
+pub use self::semaphore::Semaphore128;
+pub use self::wait_group::WaitGroup;
+pub use self::barrier::{Barrier, BarrierWaitResult};
+pub use self::seqlock::SeqLock;
+pub use self::waker_slot::WakerSlot;
+pub use self::sequence::SequenceClaimer;
+pub use self::qsbr::{Qsbr, QsbrReader};
+pub use self::publish::{publication, Publisher, Subscriber};
+pub use self::result_slot::{ResultSlot, ResultStatus};
+pub use self::olc::{OlcLock, OlcWriteGuard, Restart};
+pub use self::reentrant::{ReentrantGuard, ReentrantSpinLock};
+pub use self::lease::LeaseCell;
+pub use self::read_indicator::{ReadIndicator, ReadIndicatorGuard};
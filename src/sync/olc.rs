@@ -0,0 +1,200 @@
+use std::mem;
+use std::sync::atomic::{fence, Ordering};
+
+use current_backoff;
+use halves::Halves;
+use AtomicU128;
+
+const OBSOLETE: u64 = 0b01;
+const LOCKED: u64 = 0b10;
+
+/// The node changed under an optimistic reader, or is being or has been
+/// replaced: drop what was read and restart from the root.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Restart;
+
+/// Optimistic lock coupling for index nodes, as in ART and the B-trees
+/// built on it.
+///
+/// Readers take no lock: they note the version, read the node, and check
+/// the version hasn't moved before trusting what they read. Writers lock by
+/// a CAS from the version they read to it plus the lock bit, so a writer
+/// that upgrades knows nothing changed since it looked. Next to the version
+/// is a word of node metadata, such as a key count or node type, that a
+/// reader gets together with the version and a writer publishes with the
+/// unlock.
+///
+/// Readers race writers by design, so the node's fields must be atomics:
+/// read them with `Relaxed` loads and write them with `Relaxed` stores.
+/// `check_or_restart` and the write lock carry the fences that order those
+/// accesses against the version, as in a seqlock; plain or volatile reads
+/// of fields a writer may be changing are a data race.
+#[derive(Debug, Default)]
+pub struct OlcLock {
+    // lo is version << 2 | locked | obsolete, hi the metadata.
+    word: AtomicU128,
+}
+
+/// Write access to an `OlcLock`'s node. Unlocks, bumping the version, on
+/// drop.
+pub struct OlcWriteGuard<'a> {
+    lock: &'a OlcLock,
+    version: u64,
+    metadata: u64,
+}
+
+impl OlcLock {
+    pub fn new(metadata: u64) -> Self {
+        OlcLock { word: AtomicU128::from_halves(Halves::new(0, metadata)) }
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.word.load_halves().lo & LOCKED != 0
+    }
+
+    pub fn is_obsolete(&self) -> bool {
+        self.word.load_halves().lo & OBSOLETE != 0
+    }
+
+    /// Waits out a writer and returns the `(version, metadata)` to read
+    /// under, or `Restart` if the node is obsolete.
+    pub fn read_lock_or_restart(&self) -> Result<(u64, u64), Restart> {
+        let mut attempt = 0;
+        loop {
+            let current = self.word.load_halves();
+            if current.lo & OBSOLETE != 0 {
+                return Err(Restart);
+            }
+            if current.lo & LOCKED == 0 {
+                return Ok((current.lo, current.hi));
+            }
+            attempt += 1;
+            current_backoff().wait(attempt);
+        }
+    }
+
+    /// Whether everything read since `read_lock_or_restart` returned
+    /// `version` is still valid.
+    pub fn check_or_restart(&self, version: u64) -> Result<(), Restart> {
+        // Keeps the reader's Relaxed field loads before the version check.
+        fence(Ordering::Acquire);
+        if self.word.load_halves().lo == version { Ok(()) } else { Err(Restart) }
+    }
+
+    /// Turns an optimistic read into a write lock, if nothing has changed
+    /// since `read_lock_or_restart` returned `version`.
+    pub fn upgrade_to_write(&self, version: u64) -> Result<OlcWriteGuard<'_>, Restart> {
+        let current = self.word.load_halves();
+        if current.lo != version {
+            return Err(Restart);
+        }
+        match self.word.cas_halves(current, Halves::new(version + LOCKED, current.hi)) {
+            Ok(_) => {
+                // Keeps the writer's Relaxed field stores after the lock.
+                fence(Ordering::Release);
+                Ok(OlcWriteGuard { lock: self, version: version + LOCKED, metadata: current.hi })
+            }
+            Err(_) => Err(Restart),
+        }
+    }
+
+    pub fn write_lock_or_restart(&self) -> Result<OlcWriteGuard<'_>, Restart> {
+        loop {
+            let (version, _) = self.read_lock_or_restart()?;
+            if let Ok(guard) = self.upgrade_to_write(version) {
+                return Ok(guard);
+            }
+        }
+    }
+}
+
+impl<'a> OlcWriteGuard<'a> {
+    pub fn metadata(&self) -> u64 {
+        self.metadata
+    }
+
+    /// Sets the metadata readers see from the unlock on.
+    pub fn set_metadata(&mut self, metadata: u64) {
+        self.metadata = metadata;
+    }
+
+    /// Unlocks and marks the node obsolete, for one that's been unlinked
+    /// or replaced; readers and writers still on it restart.
+    pub fn mark_obsolete(self) {
+        self.lock.word.store_halves(Halves::new(self.version + LOCKED + OBSOLETE, self.metadata));
+        mem::forget(self);
+    }
+}
+
+impl<'a> Drop for OlcWriteGuard<'a> {
+    fn drop(&mut self) {
+        // Only the lock holder moves a locked word, so a store will do;
+        // adding LOCKED again carries into the version.
+        self.lock.word.store_halves(Halves::new(self.version + LOCKED, self.metadata));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicU64;
+    use std::sync::atomic::Ordering::Relaxed;
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::{OlcLock, Restart};
+
+    #[test]
+    fn test_protocol() {
+        let lock = OlcLock::new(3);
+        let (v, meta) = lock.read_lock_or_restart().unwrap();
+        assert_eq!(meta, 3);
+        assert_eq!(lock.check_or_restart(v), Ok(()));
+        {
+            let mut guard = lock.upgrade_to_write(v).unwrap();
+            assert!(lock.is_locked());
+            assert_eq!(lock.check_or_restart(v), Err(Restart));
+            guard.set_metadata(4);
+        }
+        assert_eq!(lock.upgrade_to_write(v).err(), Some(Restart));
+        let (v2, meta) = lock.read_lock_or_restart().unwrap();
+        assert!(v2 > v && meta == 4);
+        lock.write_lock_or_restart().unwrap().mark_obsolete();
+        assert!(lock.is_obsolete() && !lock.is_locked());
+        assert_eq!(lock.read_lock_or_restart(), Err(Restart));
+        assert_eq!(lock.write_lock_or_restart().err(), Some(Restart));
+    }
+
+    struct Node {
+        lock: OlcLock,
+        keys: [AtomicU64; 2],
+    }
+
+    #[test]
+    fn test_readers_never_trust_torn_nodes() {
+        // Writers keep both keys equal; validated reads must agree.
+        let node = Arc::new(Node { lock: OlcLock::new(0), keys: [AtomicU64::new(0), AtomicU64::new(0)] });
+        let rounds = if cfg!(miri) { 100 } else { 2000u64 };
+        let handles: Vec<_> = (0..4u64)
+            .map(|t| {
+                let node = node.clone();
+                thread::spawn(move || {
+                    for i in 0..rounds {
+                        if t % 2 == 0 {
+                            let _guard = node.lock.write_lock_or_restart().unwrap();
+                            node.keys[0].store(i, Relaxed);
+                            node.keys[1].store(i, Relaxed);
+                        } else if let Ok((v, _)) = node.lock.read_lock_or_restart() {
+                            let keys = [node.keys[0].load(Relaxed), node.keys[1].load(Relaxed)];
+                            if node.lock.check_or_restart(v).is_ok() {
+                                assert_eq!(keys[0], keys[1]);
+                            }
+                        }
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+    }
+}
@@ -0,0 +1,118 @@
+use std::marker::PhantomData;
+use std::mem;
+use std::ptr;
+use std::sync::atomic::Ordering::{Acquire, Release};
+use std::sync::Arc;
+
+use {AtomicU128, NoPadding};
+
+// The value's bytes come first in the word; the last byte is 1 while a
+// value is published.
+const VALID: usize = 15;
+
+fn to_word<T: NoPadding>(value: T) -> u128 {
+    let mut bytes = [0u8; 16];
+    unsafe { ptr::copy_nonoverlapping(&value as *const T as *const u8, bytes.as_mut_ptr(), mem::size_of::<T>()) };
+    bytes[VALID] = 1;
+    u128::from_ne_bytes(bytes)
+}
+
+fn from_word<T: NoPadding>(word: u128) -> Option<T> {
+    let bytes = word.to_ne_bytes();
+    if bytes[VALID] == 0 {
+        return None;
+    }
+    Some(unsafe { ptr::read_unaligned(bytes.as_ptr() as *const T) })
+}
+
+/// Creates a publication slot for values of up to 15 bytes, the last byte
+/// of the word being the flag that says whether one is published. Larger
+/// types fail to compile.
+///
+/// The halves only expose the right half of the protocol: the `Publisher`
+/// can only store with `Release` and the `Subscriber` can only load with
+/// `Acquire`, so whatever the publisher wrote before publishing is visible
+/// to a subscriber that sees the value.
+pub fn publication<T: NoPadding + Send>() -> (Publisher<T>, Subscriber<T>) {
+    const { assert!(mem::size_of::<T>() <= VALID, "publication needs a T of at most 15 bytes") };
+    let word = Arc::new(AtomicU128::new(0));
+    (Publisher { word: word.clone(), _marker: PhantomData }, Subscriber { word, _marker: PhantomData })
+}
+
+/// The one writing end of a `publication`.
+pub struct Publisher<T> {
+    word: Arc<AtomicU128>,
+    _marker: PhantomData<T>,
+}
+
+/// A reading end of a `publication`; clone it for more readers.
+pub struct Subscriber<T> {
+    word: Arc<AtomicU128>,
+    _marker: PhantomData<T>,
+}
+
+impl<T: NoPadding> Publisher<T> {
+    pub fn publish(&self, value: T) {
+        self.word.store(to_word(value), Release);
+    }
+
+    /// Withdraws the published value; subscribers see `None` again.
+    pub fn retract(&self) {
+        self.word.store(0, Release);
+    }
+}
+
+impl<T: NoPadding> Subscriber<T> {
+    /// The published value, if there is one.
+    pub fn load(&self) -> Option<T> {
+        from_word(self.word.load(Acquire))
+    }
+}
+
+impl<T> Clone for Subscriber<T> {
+    fn clone(&self) -> Self {
+        Subscriber { word: self.word.clone(), _marker: PhantomData }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicU64;
+    use std::sync::atomic::Ordering::Relaxed;
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::publication;
+
+    #[test]
+    fn test_publish_retract() {
+        let (publisher, subscriber) = publication::<[u32; 3]>();
+        assert_eq!(subscriber.load(), None);
+        publisher.publish([1, 2, 3]);
+        assert_eq!(subscriber.clone().load(), Some([1, 2, 3]));
+        publisher.retract();
+        assert_eq!(subscriber.load(), None);
+        publisher.publish([0, 0, 0]);
+        assert_eq!(subscriber.load(), Some([0, 0, 0]));
+    }
+
+    #[test]
+    fn test_published_data_is_visible() {
+        let data = Arc::new(AtomicU64::new(0));
+        let (publisher, subscriber) = publication::<u64>();
+        let writer = {
+            let data = data.clone();
+            thread::spawn(move || {
+                data.store(42, Relaxed);
+                publisher.publish(7);
+            })
+        };
+        loop {
+            if let Some(v) = subscriber.load() {
+                assert_eq!((v, data.load(Relaxed)), (7, 42));
+                break;
+            }
+        }
+        writer.join().unwrap();
+    }
+}
@@ -0,0 +1,214 @@
+use std::mem;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use current_backoff;
+use halves::Halves;
+use AtomicU128;
+
+// A reader that's offline never holds references, so it's past every grace
+// period.
+const OFFLINE: u64 = u64::MAX;
+
+type Deferred = Box<dyn FnOnce() + Send>;
+
+/// Quiescent-state-based reclamation for read-mostly data.
+///
+/// Readers take no lock at all: each registered reader just announces, from
+/// time to time, a quiescent state in which it holds no references to shared
+/// data. A writer that has unlinked something calls `synchronize` to wait
+/// until every reader has passed one, or `defer`s the cleanup to run after
+/// that. The grace period counter and the completed one share a word, so
+/// starting and finishing a grace period are single CASes and concurrent
+/// writers share grace periods instead of each running their own.
+pub struct Qsbr {
+    // lo is the last grace period started, hi the last one completed; they
+    // differ while a grace period is running.
+    word: AtomicU128,
+    readers: Mutex<Vec<Arc<AtomicU64>>>,
+    deferred: Mutex<Vec<(u64, Deferred)>>,
+}
+
+/// A registered reader, one per thread. Unregisters on drop.
+pub struct QsbrReader<'a> {
+    qsbr: &'a Qsbr,
+    seen: Arc<AtomicU64>,
+}
+
+impl Qsbr {
+    pub fn new() -> Self {
+        Qsbr { word: AtomicU128::new(0), readers: Mutex::new(Vec::new()), deferred: Mutex::new(Vec::new()) }
+    }
+
+    /// Registers the calling thread as a reader, online and quiescent.
+    pub fn register(&self) -> QsbrReader<'_> {
+        let seen = Arc::new(AtomicU64::new(self.word.load_halves().lo));
+        self.readers.lock().unwrap().push(seen.clone());
+        QsbrReader { qsbr: self, seen }
+    }
+
+    // The first grace period that starts after this call.
+    fn next_target(&self) -> u64 {
+        self.word.load_halves().lo + 1
+    }
+
+    /// Waits until every reader has passed a quiescent state since the call,
+    /// so nothing unlinked before it is still referenced. Must not be called
+    /// by a thread whose own reader is online, which would wait on itself.
+    pub fn synchronize(&self) {
+        let target = self.next_target();
+        let mut attempt = 0;
+        loop {
+            let current = self.word.load_halves();
+            if current.hi >= target {
+                return;
+            }
+            if current.lo == current.hi && self.word.cas_halves(current, Halves::new(current.lo + 1, current.hi)).is_ok() {
+                self.run_grace_period(current.lo + 1);
+                continue;
+            }
+            attempt += 1;
+            current_backoff().wait(attempt);
+        }
+    }
+
+    fn run_grace_period(&self, period: u64) {
+        let readers: Vec<Arc<AtomicU64>> = self.readers.lock().unwrap().clone();
+        for seen in &readers {
+            let mut attempt = 0;
+            while seen.load(Ordering::SeqCst) < period {
+                attempt += 1;
+                current_backoff().wait(attempt);
+            }
+        }
+        let mut current = self.word.load_halves();
+        while let Err(actual) = self.word.cas_halves(current, Halves::new(current.lo, period)) {
+            current = actual;
+        }
+        let ready = {
+            let mut deferred = self.deferred.lock().unwrap();
+            let (ready, waiting) = mem::take(&mut *deferred).into_iter().partition(|&(target, _)| target <= period);
+            *deferred = waiting;
+            ready
+        };
+        for (_, f) in ready {
+            f();
+        }
+    }
+
+    /// Runs `f` after a grace period that starts after this call, when the
+    /// next `synchronize` from any thread gets that far.
+    pub fn defer<F: FnOnce() + Send + 'static>(&self, f: F) {
+        let target = self.next_target();
+        self.deferred.lock().unwrap().push((target, Box::new(f)));
+    }
+
+    /// The last completed grace period.
+    pub fn completed(&self) -> u64 {
+        self.word.load_halves().hi
+    }
+}
+
+impl Default for Qsbr {
+    fn default() -> Self {
+        Qsbr::new()
+    }
+}
+
+impl Drop for Qsbr {
+    fn drop(&mut self) {
+        // Readers borrow the domain, so none are left.
+        for (_, f) in mem::take(self.deferred.get_mut().unwrap()) {
+            f();
+        }
+    }
+}
+
+impl<'a> QsbrReader<'a> {
+    /// Announces that this thread holds no references to shared data right
+    /// now. Pointers read before this call must not be used after it.
+    pub fn quiescent(&self) {
+        self.seen.store(self.qsbr.word.load_halves().lo, Ordering::SeqCst);
+    }
+
+    /// Takes this reader out of grace period accounting, for a thread about
+    /// to block or do unrelated work for a while.
+    pub fn offline(&self) {
+        self.seen.store(OFFLINE, Ordering::SeqCst);
+    }
+
+    /// Puts the reader back, quiescent.
+    pub fn online(&self) {
+        self.quiescent();
+    }
+}
+
+impl<'a> Drop for QsbrReader<'a> {
+    fn drop(&mut self) {
+        self.offline();
+        self.qsbr.readers.lock().unwrap().retain(|seen| !Arc::ptr_eq(seen, &self.seen));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::Qsbr;
+
+    #[test]
+    fn test_synchronize_and_defer() {
+        let q = Qsbr::new();
+        let runs = Arc::new(AtomicUsize::new(0));
+        let r = q.register();
+        let counter = runs.clone();
+        q.defer(move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+        r.offline();
+        q.synchronize();
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+        assert_eq!(q.completed(), 1);
+        r.online();
+        drop(r);
+        q.synchronize();
+        assert_eq!(q.completed(), 2);
+    }
+
+    #[test]
+    fn test_readers_never_see_freed() {
+        let q = Arc::new(Qsbr::new());
+        let ptr = Arc::new(AtomicPtr::new(Box::into_raw(Box::new(0u64))));
+        let stop = Arc::new(AtomicBool::new(false));
+        let readers: Vec<_> = (0..3)
+            .map(|_| {
+                let (q, ptr, stop) = (q.clone(), ptr.clone(), stop.clone());
+                thread::spawn(move || {
+                    let reader = q.register();
+                    while !stop.load(Ordering::SeqCst) {
+                        let value = unsafe { *ptr.load(Ordering::SeqCst) };
+                        assert!(value < 1000);
+                        reader.quiescent();
+                    }
+                })
+            })
+            .collect();
+        for i in 1..200u64 {
+            let old = ptr.swap(Box::into_raw(Box::new(i)), Ordering::SeqCst);
+            q.synchronize();
+            // Poison before freeing so a reader that still held it would
+            // trip the assertion.
+            unsafe {
+                *old = u64::MAX;
+                drop(Box::from_raw(old));
+            }
+        }
+        stop.store(true, Ordering::SeqCst);
+        for reader in readers {
+            reader.join().unwrap();
+        }
+        drop(unsafe { Box::from_raw(ptr.load(Ordering::SeqCst)) });
+    }
+}
@@ -0,0 +1,127 @@
+use std::sync::atomic::Ordering;
+
+use current_backoff;
+use halves::Halves;
+use AtomicU128;
+
+const DEPART: u128 = 1 << 64;
+
+/// Counts readers into and out of a write-rarely structure, so a writer can
+/// tell with one load that none are in flight.
+///
+/// Arrivals and departures are one word, each bumped by a single
+/// `fetch_add`, and the writer compares them from the same load; with two
+/// counters it could read departures after a reader it never saw arrive
+/// had left. How readers are kept out while the writer works, a flag they
+/// check after arriving for instance, is up to the structure. Arrivals
+/// carry into departures only after 2^64 of them.
+#[derive(Debug, Default)]
+pub struct ReadIndicator {
+    // lo is the arrivals, hi the departures.
+    word: AtomicU128,
+}
+
+/// A reader in flight; departs on drop.
+#[derive(Debug)]
+pub struct ReadIndicatorGuard<'a> {
+    indicator: &'a ReadIndicator,
+}
+
+impl ReadIndicator {
+    pub fn new() -> Self {
+        ReadIndicator::default()
+    }
+
+    pub fn arrive(&self) {
+        self.word.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub fn depart(&self) {
+        self.word.fetch_add(DEPART, Ordering::SeqCst);
+    }
+
+    /// Arrives and departs again when the guard drops.
+    pub fn enter(&self) -> ReadIndicatorGuard<'_> {
+        self.arrive();
+        ReadIndicatorGuard { indicator: self }
+    }
+
+    /// How many readers have arrived and not yet departed.
+    pub fn in_flight(&self) -> u64 {
+        let current = Halves::from_bits(self.word.load(Ordering::SeqCst));
+        current.lo.wrapping_sub(current.hi)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.in_flight() == 0
+    }
+
+    /// Waits until no reader is in flight. Readers that keep arriving can
+    /// hold this off indefinitely unless the caller stops them first.
+    pub fn wait_until_empty(&self) {
+        let mut attempt = 0;
+        while !self.is_empty() {
+            attempt += 1;
+            current_backoff().wait(attempt);
+        }
+    }
+}
+
+impl<'a> Drop for ReadIndicatorGuard<'a> {
+    fn drop(&mut self) {
+        self.indicator.depart();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::ReadIndicator;
+
+    #[test]
+    fn test_arrive_depart() {
+        let indicator = ReadIndicator::new();
+        let guard = indicator.enter();
+        indicator.arrive();
+        assert_eq!(indicator.in_flight(), 2);
+        drop(guard);
+        indicator.depart();
+        assert!(indicator.is_empty());
+        indicator.wait_until_empty();
+    }
+
+    #[test]
+    fn test_writer_excludes_readers() {
+        // Readers back out while the writer's flag is up; the writer waits
+        // for the ones already in, so nobody reads mid-write.
+        let indicator = Arc::new(ReadIndicator::new());
+        let writing = Arc::new(AtomicBool::new(false));
+        let data = Arc::new((AtomicU64::new(0), AtomicU64::new(0)));
+        let readers: Vec<_> = (0..3)
+            .map(|_| {
+                let (indicator, writing, data) = (indicator.clone(), writing.clone(), data.clone());
+                thread::spawn(move || {
+                    for _ in 0..2000 {
+                        let _guard = indicator.enter();
+                        if !writing.load(Ordering::SeqCst) {
+                            assert_eq!(data.0.load(Ordering::SeqCst), data.1.load(Ordering::SeqCst));
+                        }
+                    }
+                })
+            })
+            .collect();
+        for i in 1..200 {
+            writing.store(true, Ordering::SeqCst);
+            indicator.wait_until_empty();
+            data.0.store(i, Ordering::SeqCst);
+            data.1.store(i, Ordering::SeqCst);
+            writing.store(false, Ordering::SeqCst);
+        }
+        for reader in readers {
+            reader.join().unwrap();
+        }
+    }
+}
@@ -0,0 +1,165 @@
+use std::cell::{Cell, UnsafeCell};
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use current_backoff;
+use halves::Halves;
+use AtomicU128;
+
+const DEPTH: u64 = 0xffff_ffff;
+
+// A nonzero id for the calling thread, unique for the life of the process.
+fn thread_id() -> u64 {
+    static NEXT: AtomicU64 = AtomicU64::new(1);
+    thread_local!(static ID: Cell<u64> = const { Cell::new(0) });
+    ID.with(|id| {
+        if id.get() == 0 {
+            id.set(NEXT.fetch_add(1, Ordering::Relaxed));
+        }
+        id.get()
+    })
+}
+
+/// A spinlock its owner can take again while holding it, handing out shared
+/// references like `std`'s reentrant mutex does.
+///
+/// Owner, recursion depth and an acquisition epoch are one word: taking the
+/// lock fresh is one CAS from unowned, and re-entering or leaving is one
+/// store, since only the owner writes an owned word. There's no poisoning;
+/// a panic while holding the lock just unwinds through the guards.
+pub struct ReentrantSpinLock<T> {
+    // lo is the owner's thread id (0 when free), hi epoch << 32 | depth.
+    word: AtomicU128,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: Send> Send for ReentrantSpinLock<T> {}
+unsafe impl<T: Send> Sync for ReentrantSpinLock<T> {}
+
+pub struct ReentrantGuard<'a, T: 'a> {
+    lock: &'a ReentrantSpinLock<T>,
+    // Unlocking has to happen on the owning thread.
+    _not_send: PhantomData<*const ()>,
+}
+
+impl<T> ReentrantSpinLock<T> {
+    pub fn new(data: T) -> Self {
+        ReentrantSpinLock { word: AtomicU128::new(0), data: UnsafeCell::new(data) }
+    }
+
+    pub fn lock(&self) -> ReentrantGuard<'_, T> {
+        let mut attempt = 0;
+        loop {
+            if let Some(guard) = self.try_lock() {
+                return guard;
+            }
+            attempt += 1;
+            current_backoff().wait(attempt);
+        }
+    }
+
+    pub fn try_lock(&self) -> Option<ReentrantGuard<'_, T>> {
+        let me = thread_id();
+        let current = self.word.load_halves();
+        if current.lo == me {
+            assert!(current.hi & DEPTH != DEPTH, "ReentrantSpinLock recursion too deep");
+            self.word.store_halves(Halves::new(me, current.hi + 1));
+        } else {
+            let epoch = ((current.hi >> 32) as u32).wrapping_add(1) as u64;
+            if current.lo != 0 || self.word.cas_halves(current, Halves::new(me, epoch << 32 | 1)).is_err() {
+                return None;
+            }
+        }
+        Some(ReentrantGuard { lock: self, _not_send: PhantomData })
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.word.load_halves().lo != 0
+    }
+
+    /// Whether the calling thread holds the lock.
+    pub fn is_owned_by_current_thread(&self) -> bool {
+        self.word.load_halves().lo == thread_id()
+    }
+
+    /// Nested holds by the owner, 0 when free.
+    pub fn depth(&self) -> u32 {
+        (self.word.load_halves().hi & DEPTH) as u32
+    }
+
+    /// Fresh acquisitions so far; re-entering doesn't count.
+    pub fn epoch(&self) -> u32 {
+        (self.word.load_halves().hi >> 32) as u32
+    }
+
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<'a, T> Deref for ReentrantGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<'a, T> Drop for ReentrantGuard<'a, T> {
+    fn drop(&mut self) {
+        let current = self.lock.word.load_halves();
+        debug_assert_eq!(current.lo, thread_id(), "ReentrantSpinLock unlocked by a thread that doesn't own it");
+        let depth = current.hi & DEPTH;
+        let owner = if depth == 1 { 0 } else { current.lo };
+        self.lock.word.store_halves(Halves::new(owner, current.hi - 1));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::ReentrantSpinLock;
+
+    #[test]
+    fn test_reentry() {
+        let lock = ReentrantSpinLock::new(Cell::new(0));
+        {
+            let outer = lock.lock();
+            let inner = lock.try_lock().unwrap();
+            inner.set(inner.get() + 1);
+            assert_eq!((lock.depth(), lock.epoch()), (2, 1));
+            drop(inner);
+            assert!(lock.is_owned_by_current_thread());
+            outer.set(outer.get() + 1);
+        }
+        assert!(!lock.is_locked());
+        drop(lock.lock());
+        assert_eq!((lock.depth(), lock.epoch()), (0, 2));
+        assert_eq!(lock.into_inner().get(), 2);
+    }
+
+    #[test]
+    fn test_excludes_other_threads() {
+        let lock = Arc::new(ReentrantSpinLock::new(Cell::new(0u64)));
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let lock = lock.clone();
+                thread::spawn(move || {
+                    for _ in 0..1000 {
+                        let outer = lock.lock();
+                        let inner = lock.lock();
+                        inner.set(outer.get() + 1);
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(lock.lock().get(), 4000);
+    }
+}
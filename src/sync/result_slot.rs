@@ -0,0 +1,160 @@
+use std::marker::PhantomData;
+use std::task::{Context, Poll};
+
+use halves::Halves;
+use sync::WakerSlot;
+use AtomicU128;
+
+/// Where a `ResultSlot` is in its life.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResultStatus {
+    Pending,
+    Ok,
+    Err,
+    Taken,
+}
+
+const PENDING: u64 = 0;
+const OK: u64 = 1;
+const ERR: u64 = 2;
+const TAKEN: u64 = 3;
+
+/// A one-shot handoff of a `Result` from the thread that computes it to
+/// the one that wants it.
+///
+/// The status and a pointer to the boxed payload are one word, so
+/// completing is one CAS from pending and taking is one CAS from ok or err:
+/// there's no flag to set after an `Option` is filled, and exactly one
+/// `try_take` gets the value. `poll_take` registers with a `WakerSlot` that
+/// `complete` wakes.
+pub struct ResultSlot<T, E> {
+    // lo is a `Box<T>` or `Box<E>` pointer, hi the status.
+    word: AtomicU128,
+    waker: WakerSlot,
+    _marker: PhantomData<Result<Box<T>, Box<E>>>,
+}
+
+unsafe impl<T: Send, E: Send> Send for ResultSlot<T, E> {}
+unsafe impl<T: Send, E: Send> Sync for ResultSlot<T, E> {}
+
+impl<T, E> ResultSlot<T, E> {
+    pub fn new() -> Self {
+        ResultSlot { word: AtomicU128::new(0), waker: WakerSlot::new(), _marker: PhantomData }
+    }
+
+    pub fn status(&self) -> ResultStatus {
+        match self.word.load_halves().hi {
+            PENDING => ResultStatus::Pending,
+            OK => ResultStatus::Ok,
+            ERR => ResultStatus::Err,
+            _ => ResultStatus::Taken,
+        }
+    }
+
+    /// Fills the slot and wakes the task waiting on it. Only the first
+    /// completion goes in; a later one gets its result back.
+    pub fn complete(&self, result: Result<T, E>) -> Result<(), Result<T, E>> {
+        let (ptr, status) = match result {
+            Ok(value) => (Box::into_raw(Box::new(value)) as u64, OK),
+            Err(error) => (Box::into_raw(Box::new(error)) as u64, ERR),
+        };
+        if self.word.cas_halves(Halves::new(0, PENDING), Halves::new(ptr, status)).is_err() {
+            return Err(unsafe { unbox(ptr, status) });
+        }
+        self.waker.wake();
+        Ok(())
+    }
+
+    /// Takes the result if it's there and nobody has taken it yet.
+    pub fn try_take(&self) -> Option<Result<T, E>> {
+        let current = self.word.load_halves();
+        if current.hi != OK && current.hi != ERR {
+            return None;
+        }
+        // Nothing but a take moves the word on from here, so a mismatch
+        // means another take won.
+        self.word.cas_halves(current, Halves::new(0, TAKEN)).ok()?;
+        Some(unsafe { unbox(current.lo, current.hi) })
+    }
+
+    /// Takes the result, or arranges for `cx` to be woken when it's
+    /// completed. A slot that's already been taken stays pending.
+    pub fn poll_take(&self, cx: &mut Context) -> Poll<Result<T, E>> {
+        if let Some(result) = self.try_take() {
+            return Poll::Ready(result);
+        }
+        if self.waker.register(cx.waker()) {
+            if let Some(result) = self.try_take() {
+                return Poll::Ready(result);
+            }
+        }
+        Poll::Pending
+    }
+}
+
+unsafe fn unbox<T, E>(ptr: u64, status: u64) -> Result<T, E> {
+    if status == OK { Ok(*Box::from_raw(ptr as *mut T)) } else { Err(*Box::from_raw(ptr as *mut E)) }
+}
+
+impl<T, E> Default for ResultSlot<T, E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, E> Drop for ResultSlot<T, E> {
+    fn drop(&mut self) {
+        drop(self.try_take());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Wake, Waker};
+    use std::thread;
+
+    use super::{ResultSlot, ResultStatus};
+
+    struct Unpark(thread::Thread);
+
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    #[test]
+    fn test_complete_once_take_once() {
+        let slot: ResultSlot<String, u32> = ResultSlot::new();
+        assert_eq!((slot.status(), slot.try_take()), (ResultStatus::Pending, None));
+        assert_eq!(slot.complete(Err(404)), Ok(()));
+        assert_eq!(slot.complete(Ok("late".to_string())), Err(Ok("late".to_string())));
+        assert_eq!(slot.status(), ResultStatus::Err);
+        assert_eq!(slot.try_take(), Some(Err(404)));
+        assert_eq!((slot.status(), slot.try_take()), (ResultStatus::Taken, None));
+
+        let dropped: ResultSlot<String, u32> = ResultSlot::new();
+        dropped.complete(Ok("freed on drop".to_string())).unwrap();
+    }
+
+    #[test]
+    fn test_poll_is_woken() {
+        let slot = Arc::new(ResultSlot::<u64, ()>::new());
+        let waker = Waker::from(Arc::new(Unpark(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        assert_eq!(slot.poll_take(&mut cx), Poll::Pending);
+        let producer = {
+            let slot = slot.clone();
+            thread::spawn(move || slot.complete(Ok(42)).unwrap())
+        };
+        let result = loop {
+            match slot.poll_take(&mut cx) {
+                Poll::Ready(result) => break result,
+                Poll::Pending => thread::park(),
+            }
+        };
+        assert_eq!(result, Ok(42));
+        producer.join().unwrap();
+    }
+}
@@ -0,0 +1,141 @@
+use halves::Halves;
+use AtomicU128;
+
+const WRITER: u64 = 1;
+
+// lo is the version, hi is (readers << 1) | writer.
+fn readers(word: Halves) -> u64 {
+    word.hi >> 1
+}
+
+fn writer(word: Halves) -> bool {
+    word.hi & WRITER != 0
+}
+
+/// Reader count, writer flag and version packed into one word.
+///
+/// This is the state machine behind a reader-writer lock or an optimistic
+/// (version-validated) read scheme; every transition is one CAS over all three
+/// fields, and the version bumps whenever a writer lets go.
+#[derive(Debug, Default)]
+pub struct RwState {
+    word: AtomicU128,
+}
+
+impl RwState {
+    pub fn new() -> Self {
+        RwState { word: AtomicU128::new(0) }
+    }
+
+    pub fn readers(&self) -> u64 {
+        readers(self.word.load_halves())
+    }
+
+    pub fn is_write_locked(&self) -> bool {
+        writer(self.word.load_halves())
+    }
+
+    pub fn version(&self) -> u64 {
+        self.word.load_halves().lo
+    }
+
+    pub fn try_read_acquire(&self) -> bool {
+        self.transition(|w| if writer(w) { None } else { Some(Halves::new(w.lo, w.hi + 2)) })
+    }
+
+    pub fn read_release(&self) {
+        let released = self.transition(|w| {
+            debug_assert!(readers(w) > 0, "read_release without a reader");
+            Some(Halves::new(w.lo, w.hi - 2))
+        });
+        debug_assert!(released);
+    }
+
+    pub fn try_write_acquire(&self) -> bool {
+        self.transition(|w| if w.hi != 0 { None } else { Some(Halves::new(w.lo, WRITER)) })
+    }
+
+    pub fn write_release(&self) {
+        let released = self.transition(|w| {
+            debug_assert!(writer(w), "write_release without the writer");
+            Some(Halves::new(w.lo.wrapping_add(1), w.hi & !WRITER))
+        });
+        debug_assert!(released);
+    }
+
+    /// Turns the write lock into a read lock, publishing a new version.
+    pub fn downgrade(&self) {
+        let downgraded = self.transition(|w| {
+            debug_assert!(writer(w), "downgrade without the writer");
+            Some(Halves::new(w.lo.wrapping_add(1), 2))
+        });
+        debug_assert!(downgraded);
+    }
+
+    /// Turns the only read lock into the write lock.
+    pub fn try_upgrade(&self) -> bool {
+        self.transition(|w| if w.hi != 2 { None } else { Some(Halves::new(w.lo, WRITER)) })
+    }
+
+    /// Version to validate an optimistic read against, or `None` while a
+    /// writer holds the state.
+    pub fn read_version(&self) -> Option<u64> {
+        let w = self.word.load_halves();
+        if writer(w) { None } else { Some(w.lo) }
+    }
+
+    /// Whether no writer has been through since `read_version` returned `version`.
+    pub fn validate(&self, version: u64) -> bool {
+        self.read_version() == Some(version)
+    }
+
+    fn transition<F: Fn(Halves) -> Option<Halves>>(&self, f: F) -> bool {
+        let mut current = self.word.load_halves();
+        loop {
+            let new = match f(current) {
+                Some(new) => new,
+                None => return false,
+            };
+            match self.word.cas_halves(current, new) {
+                Ok(_) => return true,
+                Err(actual) => current = actual,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RwState;
+
+    #[test]
+    fn test_readers_writer() {
+        let s = RwState::new();
+        assert!(s.try_read_acquire());
+        assert!(s.try_read_acquire());
+        assert_eq!(s.readers(), 2);
+        assert!(!s.try_write_acquire());
+        s.read_release();
+        assert!(s.try_upgrade());
+        assert!(s.is_write_locked());
+        assert!(!s.try_read_acquire());
+        s.write_release();
+        assert_eq!(s.version(), 1);
+        assert!(s.try_write_acquire());
+        s.downgrade();
+        assert_eq!((s.readers(), s.version()), (1, 2));
+        assert!(!s.is_write_locked());
+    }
+
+    #[test]
+    fn test_optimistic() {
+        let s = RwState::new();
+        let v = s.read_version().unwrap();
+        assert!(s.validate(v));
+        assert!(s.try_write_acquire());
+        assert_eq!(s.read_version(), None);
+        assert!(!s.validate(v));
+        s.write_release();
+        assert!(!s.validate(v));
+    }
+}
@@ -0,0 +1,167 @@
+#[cfg(feature = "std")]
+use std::sync::{Condvar, Mutex};
+
+use current_backoff;
+use halves::Halves;
+use AtomicU128;
+
+/// Counting semaphore with permits (lo) and blocked waiters (hi) in one word.
+///
+/// Taking a permit, registering as a waiter and the releaser's "is anyone
+/// waiting" check are all the same CAS, so `release` only touches the
+/// mutex/condvar when there really is a sleeper to wake.
+///
+/// The sleeping `acquire` needs the `std` feature. Without it only
+/// `try_acquire` and `acquire_spin` are left, which wait through the
+/// installed `Backoff`; install `Spin` where there's no scheduler.
+pub struct Semaphore128 {
+    word: AtomicU128,
+    #[cfg(feature = "std")]
+    lock: Mutex<()>,
+    #[cfg(feature = "std")]
+    cond: Condvar,
+}
+
+impl Semaphore128 {
+    pub fn new(permits: u64) -> Self {
+        Semaphore128 {
+            word: AtomicU128::from_halves(Halves::new(permits, 0)),
+            #[cfg(feature = "std")]
+            lock: Mutex::new(()),
+            #[cfg(feature = "std")]
+            cond: Condvar::new(),
+        }
+    }
+
+    pub fn available_permits(&self) -> u64 {
+        self.word.load_halves().lo
+    }
+
+    pub fn waiters(&self) -> u64 {
+        self.word.load_halves().hi
+    }
+
+    pub fn try_acquire(&self) -> bool {
+        let mut current = self.word.load_halves();
+        while current.lo > 0 {
+            match self.word.cas_halves(current, Halves::new(current.lo - 1, current.hi)) {
+                Ok(_) => return true,
+                Err(actual) => current = actual,
+            }
+        }
+        false
+    }
+
+    /// Takes a permit, waiting with the global backoff policy until one is
+    /// available.
+    pub fn acquire_spin(&self) {
+        let mut attempt = 0;
+        while !self.try_acquire() {
+            attempt += 1;
+            current_backoff().wait(attempt);
+        }
+    }
+
+    /// Takes a permit, sleeping until one is released if none is available.
+    #[cfg(feature = "std")]
+    pub fn acquire(&self) {
+        let mut current = self.word.load_halves();
+        loop {
+            let new = if current.lo > 0 {
+                Halves::new(current.lo - 1, current.hi)
+            } else {
+                Halves::new(current.lo, current.hi + 1)
+            };
+            match self.word.cas_halves(current, new) {
+                Ok(_) if current.lo > 0 => return,
+                Ok(_) => break,
+                Err(actual) => current = actual,
+            }
+        }
+        // Registered as a waiter; the permit check below runs under the mutex
+        // that `release` takes before notifying, so a wakeup can't be missed.
+        let mut guard = self.lock.lock().unwrap();
+        loop {
+            let mut current = self.word.load_halves();
+            while current.lo > 0 {
+                let taken = Halves::new(current.lo - 1, current.hi - 1);
+                match self.word.cas_halves(current, taken) {
+                    Ok(_) => return,
+                    Err(actual) => current = actual,
+                }
+            }
+            guard = self.cond.wait(guard).unwrap();
+        }
+    }
+
+    pub fn release(&self) {
+        self.release_many(1);
+    }
+
+    pub fn release_many(&self, permits: u64) {
+        let mut current = self.word.load_halves();
+        loop {
+            match self.word.cas_halves(current, Halves::new(current.lo + permits, current.hi)) {
+                Ok(_) => break,
+                Err(actual) => current = actual,
+            }
+        }
+        if current.hi > 0 {
+            self.wake(permits);
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn wake(&self, permits: u64) {
+        let _guard = self.lock.lock().unwrap();
+        if permits == 1 {
+            self.cond.notify_one();
+        } else {
+            self.cond.notify_all();
+        }
+    }
+
+    // Nobody can be asleep without `acquire`.
+    #[cfg(not(feature = "std"))]
+    fn wake(&self, _: u64) {}
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "std")]
+    use std::sync::Arc;
+    #[cfg(feature = "std")]
+    use std::thread;
+
+    use super::Semaphore128;
+
+    #[test]
+    fn test_permits() {
+        let s = Semaphore128::new(2);
+        assert!(s.try_acquire());
+        s.acquire_spin();
+        assert!(!s.try_acquire());
+        assert_eq!(s.available_permits(), 0);
+        s.release();
+        s.acquire_spin();
+        s.release_many(3);
+        assert_eq!(s.available_permits(), 3);
+        assert_eq!(s.waiters(), 0);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_acquire_sleeps_until_release() {
+        let s = Arc::new(Semaphore128::new(0));
+        let sleeper = {
+            let s = s.clone();
+            thread::spawn(move || s.acquire())
+        };
+        while s.waiters() == 0 {
+            thread::yield_now();
+        }
+        s.release();
+        sleeper.join().unwrap();
+        assert_eq!((s.available_permits(), s.waiters()), (0, 0));
+    }
+}
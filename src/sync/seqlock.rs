@@ -0,0 +1,157 @@
+use std::marker::PhantomData;
+use std::mem::{self, MaybeUninit};
+use std::ptr;
+use std::sync::atomic::{fence, AtomicU64, Ordering};
+use std::thread;
+
+use halves::Halves;
+use {AtomicU128, NoPadding};
+
+fn chunks_for<T>() -> usize {
+    mem::size_of::<T>().div_ceil(8)
+}
+
+fn to_chunks<T: NoPadding>(value: &T) -> Vec<u64> {
+    let mut chunks = vec![0u64; chunks_for::<T>().max(1)];
+    unsafe {
+        ptr::copy_nonoverlapping(value as *const T as *const u8, chunks.as_mut_ptr() as *mut u8, mem::size_of::<T>());
+    }
+    chunks
+}
+
+/// Sequence lock for payloads of any size without padding.
+///
+/// The sequence number shares a word with the first eight bytes of the
+/// payload, so finishing a write and publishing that chunk is one CAS, and
+/// the rest lives in relaxed atomics beside it. Readers never block writers:
+/// they copy the payload and retry if the word moved underneath them. That
+/// makes them lock-free but not wait-free, since a steady stream of writes
+/// can send a reader back indefinitely. Writers take an odd sequence number
+/// by CAS, so they serialize against each other.
+pub struct SeqLock<T> {
+    // lo is the sequence (odd while a write is in progress), hi the first chunk.
+    word: AtomicU128,
+    rest: Box<[AtomicU64]>,
+    _marker: PhantomData<T>,
+}
+
+unsafe impl<T: NoPadding + Send> Send for SeqLock<T> {}
+unsafe impl<T: NoPadding + Send> Sync for SeqLock<T> {}
+
+impl<T: NoPadding> SeqLock<T> {
+    pub fn new(value: T) -> Self {
+        let chunks = to_chunks(&value);
+        SeqLock {
+            word: AtomicU128::from_halves(Halves::new(0, chunks[0])),
+            rest: chunks[1..].iter().map(|&c| AtomicU64::new(c)).collect::<Vec<_>>().into_boxed_slice(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Number of completed writes.
+    pub fn version(&self) -> u64 {
+        self.word.load_halves().lo / 2
+    }
+
+    /// Returns a copy of the payload, retrying until one isn't torn.
+    pub fn load(&self) -> T {
+        loop {
+            if let Some(value) = self.try_load() {
+                return value;
+            }
+            thread::yield_now();
+        }
+    }
+
+    /// Runs `f` on a consistent copy of the payload. `f` only ever sees a
+    /// validated copy, so it doesn't need to cope with torn data.
+    pub fn read<R, F: FnOnce(&T) -> R>(&self, f: F) -> R {
+        f(&self.load())
+    }
+
+    // One optimistic read; `None` if a writer was active or got in between.
+    fn try_load(&self) -> Option<T> {
+        let before = self.word.load_halves();
+        if before.lo & 1 == 1 {
+            return None;
+        }
+        let mut out = MaybeUninit::<T>::uninit();
+        let size = mem::size_of::<T>();
+        let dst = out.as_mut_ptr() as *mut u8;
+        let put = |i: usize, chunk: u64| {
+            let n = size.saturating_sub(i * 8).min(8);
+            unsafe { ptr::copy_nonoverlapping(&chunk as *const u64 as *const u8, dst.add(i * 8), n) };
+        };
+        put(0, before.hi);
+        for (i, chunk) in self.rest.iter().enumerate() {
+            put(i + 1, chunk.load(Ordering::Relaxed));
+        }
+        fence(Ordering::Acquire);
+        if self.word.load_halves() != before {
+            return None;
+        }
+        Some(unsafe { out.assume_init() })
+    }
+
+    pub fn write(&self, value: T) {
+        let chunks = to_chunks(&value);
+        let mut current = self.word.load_halves();
+        let locked = loop {
+            if current.lo & 1 == 1 {
+                thread::yield_now();
+                current = self.word.load_halves();
+                continue;
+            }
+            let locked = Halves::new(current.lo + 1, current.hi);
+            match self.word.cas_halves(current, locked) {
+                Ok(_) => break locked,
+                Err(actual) => current = actual,
+            }
+        };
+        // Keeps the relaxed chunk stores below from becoming visible before
+        // the odd sequence, which is what sends overlapping readers back.
+        fence(Ordering::Release);
+        for (slot, &chunk) in self.rest.iter().zip(&chunks[1..]) {
+            slot.store(chunk, Ordering::Relaxed);
+        }
+        fence(Ordering::Release);
+        let published = self.word.cas_halves(locked, Halves::new(locked.lo + 1, chunks[0]));
+        debug_assert!(published.is_ok());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SeqLock;
+    use NoPadding;
+
+    #[repr(C)]
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    struct Record {
+        id: u32,
+        flags: u32,
+        samples: [f64; 7],
+    }
+
+    unsafe impl NoPadding for Record {}
+
+    #[test]
+    fn test_large_payload() {
+        let a = Record { id: 1, flags: 0, samples: [0.5; 7] };
+        let b = Record { id: 2, flags: 3, samples: [1.5, 2.5, 3.5, 4.5, 5.5, 6.5, 7.5] };
+        let lock = SeqLock::new(a);
+        assert_eq!(lock.load(), a);
+        lock.write(b);
+        assert_eq!(lock.load(), b);
+        assert_eq!(lock.read(|r| r.samples[6]), 7.5);
+        assert_eq!(lock.version(), 1);
+    }
+
+    #[test]
+    fn test_small_payload() {
+        let lock = SeqLock::new([1u8, 2, 3]);
+        lock.write([4, 5, 6]);
+        assert_eq!(lock.load(), [4, 5, 6]);
+        assert_eq!(SeqLock::new(()).load(), ());
+    }
+}
@@ -0,0 +1,133 @@
+use std::ops::Range;
+
+use current_backoff;
+use halves::Halves;
+use AtomicU128;
+
+/// Sequence claiming for a multi-producer ring, in the style of the LMAX
+/// disruptor.
+///
+/// Producers claim ranges of sequence numbers, fill those slots, then publish
+/// them; consumers read every slot below `published()`. The next unclaimed
+/// sequence and the published watermark share a word, so a claim is one CAS
+/// and a publish is one CAS that both advances the watermark and checks it
+/// was this range's turn, with no ordering to get right between two
+/// separate counters.
+pub struct SequenceClaimer {
+    // lo is the next sequence to claim, hi the published watermark.
+    word: AtomicU128,
+}
+
+impl SequenceClaimer {
+    pub fn new() -> Self {
+        SequenceClaimer { word: AtomicU128::new(0) }
+    }
+
+    /// Claims the next `n` sequences.
+    pub fn claim(&self, n: u64) -> Range<u64> {
+        self.try_claim(n, u64::MAX).unwrap()
+    }
+
+    /// Claims the next `n` sequences unless that would go past `limit`,
+    /// typically the slowest consumer's position plus the ring's capacity.
+    pub fn try_claim(&self, n: u64, limit: u64) -> Option<Range<u64>> {
+        let mut current = self.word.load_halves();
+        loop {
+            let end = current.lo.checked_add(n).filter(|&end| end <= limit)?;
+            match self.word.cas_halves(current, Halves::new(end, current.hi)) {
+                Ok(_) => return Some(current.lo..end),
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    /// Publishes a claimed range. Ranges become visible in claim order, so
+    /// this waits, with the global backoff policy, for every earlier range to
+    /// be published first.
+    pub fn publish(&self, range: Range<u64>) {
+        let mut current = self.word.load_halves();
+        let mut attempt = 0;
+        loop {
+            if current.hi != range.start {
+                attempt += 1;
+                current_backoff().wait(attempt);
+                current = self.word.load_halves();
+                continue;
+            }
+            match self.word.cas_halves(current, Halves::new(current.lo, range.end)) {
+                Ok(_) => return,
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    /// Every sequence below this one has been published.
+    pub fn published(&self) -> u64 {
+        self.word.load_halves().hi
+    }
+
+    /// The next sequence a producer will claim.
+    pub fn claimed(&self) -> u64 {
+        self.word.load_halves().lo
+    }
+}
+
+impl Default for SequenceClaimer {
+    fn default() -> Self {
+        SequenceClaimer::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::SequenceClaimer;
+
+    #[test]
+    fn test_claim_publish() {
+        let s = SequenceClaimer::new();
+        let a = s.claim(3);
+        let b = s.claim(2);
+        assert_eq!((a.clone(), b.clone()), (0..3, 3..5));
+        assert_eq!(s.try_claim(1, 5), None);
+        assert_eq!(s.published(), 0);
+        s.publish(a);
+        assert_eq!(s.published(), 3);
+        s.publish(b);
+        assert_eq!((s.published(), s.claimed()), (5, 5));
+    }
+
+    #[test]
+    fn test_published_slots_are_filled() {
+        let s = Arc::new(SequenceClaimer::new());
+        let slots: Arc<Vec<AtomicU64>> = Arc::new((0..4000).map(|_| AtomicU64::new(0)).collect());
+        let producers: Vec<_> = (0..4)
+            .map(|_| {
+                let (s, slots) = (s.clone(), slots.clone());
+                thread::spawn(move || {
+                    for _ in 0..500 {
+                        let range = s.claim(2);
+                        for seq in range.clone() {
+                            slots[seq as usize].store(seq + 1, Ordering::Relaxed);
+                        }
+                        s.publish(range);
+                    }
+                })
+            })
+            .collect();
+        let mut read = 0;
+        while read < 4000 {
+            let published = s.published();
+            for seq in read..published {
+                assert_eq!(slots[seq as usize].load(Ordering::Relaxed), seq + 1);
+            }
+            read = published;
+        }
+        for producer in producers {
+            producer.join().unwrap();
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use std::cell::UnsafeCell;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::Ordering;
+
+use current_backoff;
+use halves::Halves;
+use AtomicU128;
+
+const TICKET: u128 = 1 << 64;
+
+/// Fair spinlock handing out tickets in arrival order.
+///
+/// The ticket being served (lo) and the next ticket (hi) share one word, so
+/// taking a ticket is a single `fetch_add`, and `try_lock` and `is_locked`
+/// each see both at once. The next ticket is the high half so that it wraps
+/// off the top of the word instead of carrying into the other. Waiting goes
+/// through the installed `Backoff`; install `Spin` where there's no
+/// scheduler to yield to.
+pub struct TicketLock<T> {
+    tickets: AtomicU128,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: Send> Send for TicketLock<T> {}
+unsafe impl<T: Send> Sync for TicketLock<T> {}
+
+pub struct TicketGuard<'a, T: 'a> {
+    lock: &'a TicketLock<T>,
+}
+
+impl<T> TicketLock<T> {
+    pub fn new(data: T) -> Self {
+        TicketLock {
+            tickets: AtomicU128::new(0),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    pub fn lock(&self) -> TicketGuard<'_, T> {
+        let ticket = Halves::from_bits(self.tickets.fetch_add(TICKET, Ordering::SeqCst)).hi;
+        let mut attempt = 0;
+        while self.tickets.load_halves().lo != ticket {
+            attempt += 1;
+            current_backoff().wait(attempt);
+        }
+        TicketGuard { lock: self }
+    }
+
+    pub fn try_lock(&self) -> Option<TicketGuard<'_, T>> {
+        let current = self.tickets.load_halves();
+        if current.lo != current.hi {
+            return None;
+        }
+        let taken = Halves::new(current.lo, current.hi.wrapping_add(1));
+        match self.tickets.cas_halves(current, taken) {
+            Ok(_) => Some(TicketGuard { lock: self }),
+            Err(_) => None,
+        }
+    }
+
+    pub fn is_locked(&self) -> bool {
+        let current = self.tickets.load_halves();
+        current.lo != current.hi
+    }
+
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+
+    fn unlock(&self) {
+        let mut current = self.tickets.load_halves();
+        loop {
+            let served = Halves::new(current.lo.wrapping_add(1), current.hi);
+            match self.tickets.cas_halves(current, served) {
+                Ok(_) => return,
+                Err(actual) => current = actual,
+            }
+        }
+    }
+}
+
+impl<'a, T> Deref for TicketGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<'a, T> DerefMut for TicketGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<'a, T> Drop for TicketGuard<'a, T> {
+    fn drop(&mut self) {
+        self.lock.unlock();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::TicketLock;
+
+    #[test]
+    fn test_lock() {
+        let lock = TicketLock::new(1);
+        {
+            let mut guard = lock.lock();
+            *guard += 1;
+            assert!(lock.is_locked());
+            assert!(lock.try_lock().is_none());
+        }
+        assert!(!lock.is_locked());
+        assert_eq!(*lock.try_lock().unwrap(), 2);
+        assert_eq!(lock.into_inner(), 2);
+    }
+
+    #[test]
+    fn test_threads() {
+        let lock = Arc::new(TicketLock::new(0u64));
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let lock = lock.clone();
+                thread::spawn(move || {
+                    for _ in 0..1000 {
+                        *lock.lock() += 1;
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(*lock.lock(), 4000);
+    }
+}
@@ -0,0 +1,121 @@
+use halves::Halves;
+use AtomicU128;
+
+/// Countdown latch with (count, generation) in one word.
+///
+/// The `done` that brings the count to zero bumps the generation in the same
+/// CAS. `wait` watches the generation rather than the count, so a waiter is
+/// released by the round it joined even if the group is immediately reused.
+/// Waiters sleep on the word and the completing `done` wakes them.
+#[derive(Debug, Default)]
+pub struct WaitGroup {
+    // lo is the count, hi the generation.
+    word: AtomicU128,
+}
+
+impl WaitGroup {
+    pub fn new() -> Self {
+        WaitGroup { word: AtomicU128::new(0) }
+    }
+
+    pub fn count(&self) -> u64 {
+        self.word.load_halves().lo
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.word.load_halves().hi
+    }
+
+    pub fn add(&self, n: u64) {
+        let mut current = self.word.load_halves();
+        loop {
+            let new = Halves::new(current.lo.checked_add(n).expect("WaitGroup count overflow"), current.hi);
+            match self.word.cas_halves(current, new) {
+                Ok(_) => return,
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    /// Returns true for the call that completed the round.
+    pub fn done(&self) -> bool {
+        let mut current = self.word.load_halves();
+        loop {
+            assert!(current.lo > 0, "WaitGroup::done called more times than add");
+            let count = current.lo - 1;
+            let generation = if count == 0 { current.hi.wrapping_add(1) } else { current.hi };
+            match self.word.cas_halves(current, Halves::new(count, generation)) {
+                Ok(_) if count == 0 => {
+                    self.word.wake_all();
+                    return true;
+                }
+                Ok(_) => return false,
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    /// Waits until the current round finishes; returns at once if the count is zero.
+    pub fn wait(&self) {
+        let start = self.word.load_halves();
+        if start.lo == 0 {
+            return;
+        }
+        let mut current = start;
+        // Other `add`s and `done`s change the word without waking; `wait`
+        // returns at once for those and this sleeps again on the new value.
+        while current.hi == start.hi {
+            self.word.wait(current.bits());
+            current = self.word.load_halves();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::WaitGroup;
+
+    #[test]
+    fn test_rounds() {
+        let wg = WaitGroup::new();
+        wg.wait();
+        wg.add(2);
+        assert!(!wg.done());
+        assert_eq!(wg.count(), 1);
+        assert!(wg.done());
+        assert_eq!(wg.generation(), 1);
+        wg.wait();
+        wg.add(1);
+        assert!(wg.done());
+        assert_eq!(wg.generation(), 2);
+    }
+
+    #[test]
+    fn test_waiters_are_woken() {
+        let wg = Arc::new(WaitGroup::new());
+        wg.add(3);
+        let waiters: Vec<_> = (0..2)
+            .map(|_| {
+                let wg = wg.clone();
+                thread::spawn(move || wg.wait())
+            })
+            .collect();
+        for _ in 0..3 {
+            let wg = wg.clone();
+            thread::spawn(move || wg.done()).join().unwrap();
+        }
+        for waiter in waiters {
+            waiter.join().unwrap();
+        }
+        assert_eq!(wg.generation(), 1);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_done_underflow() {
+        WaitGroup::new().done();
+    }
+}
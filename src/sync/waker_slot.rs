@@ -0,0 +1,133 @@
+use std::task::Waker;
+
+use halves::Halves;
+use AtomicU128;
+
+const READY: u64 = 1;
+
+// lo is a boxed waker (0 if none), hi is (epoch << 1) | ready.
+fn is_ready(word: Halves) -> bool {
+    word.hi & READY != 0
+}
+
+/// Slot for one task's waker plus a ready flag and wake epoch.
+///
+/// Registering a waker and checking readiness is one CAS over both halves, so
+/// a `wake` can't land between the check and the registration and get lost.
+/// Whichever CAS takes a waker out of the slot owns it, so nobody ever
+/// touches a waker another thread might be freeing.
+pub struct WakerSlot {
+    word: AtomicU128,
+}
+
+impl WakerSlot {
+    pub fn new() -> Self {
+        WakerSlot { word: AtomicU128::new(0) }
+    }
+
+    pub fn is_ready(&self) -> bool {
+        is_ready(self.word.load_halves())
+    }
+
+    /// Number of `wake` calls so far.
+    pub fn epoch(&self) -> u64 {
+        self.word.load_halves().hi >> 1
+    }
+
+    /// Stores `waker` to be woken by the next `wake`, unless the slot is
+    /// already ready, in which case nothing is stored and this returns true.
+    pub fn register(&self, waker: &Waker) -> bool {
+        let boxed = Box::into_raw(Box::new(waker.clone()));
+        let mut current = self.word.load_halves();
+        loop {
+            if is_ready(current) {
+                drop(unsafe { Box::from_raw(boxed) });
+                return true;
+            }
+            match self.word.cas_halves(current, Halves::new(boxed as u64, current.hi)) {
+                Ok(_) => break,
+                Err(actual) => current = actual,
+            }
+        }
+        if current.lo != 0 {
+            drop(unsafe { Box::from_raw(current.lo as *mut Waker) });
+        }
+        false
+    }
+
+    /// Marks the slot ready, bumps the epoch and wakes the registered waker.
+    pub fn wake(&self) {
+        let mut current = self.word.load_halves();
+        loop {
+            let woken = Halves::new(0, ((current.hi >> 1).wrapping_add(1) << 1) | READY);
+            match self.word.cas_halves(current, woken) {
+                Ok(_) => break,
+                Err(actual) => current = actual,
+            }
+        }
+        if current.lo != 0 {
+            unsafe { Box::from_raw(current.lo as *mut Waker) }.wake();
+        }
+    }
+
+    /// Clears the ready flag, returning whether it was set.
+    pub fn take_ready(&self) -> bool {
+        let mut current = self.word.load_halves();
+        while is_ready(current) {
+            match self.word.cas_halves(current, Halves::new(current.lo, current.hi & !READY)) {
+                Ok(_) => return true,
+                Err(actual) => current = actual,
+            }
+        }
+        false
+    }
+}
+
+impl Default for WakerSlot {
+    fn default() -> Self {
+        WakerSlot::new()
+    }
+}
+
+impl Drop for WakerSlot {
+    fn drop(&mut self) {
+        let word = self.word.load_halves();
+        if word.lo != 0 {
+            drop(unsafe { Box::from_raw(word.lo as *mut Waker) });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::task::{Wake, Waker};
+    use super::WakerSlot;
+
+    struct Count(AtomicUsize);
+
+    impl Wake for Count {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn test_register_wake() {
+        let count = Arc::new(Count(AtomicUsize::new(0)));
+        let waker = Waker::from(count.clone());
+        let slot = WakerSlot::new();
+        assert!(!slot.register(&waker));
+        assert!(!slot.register(&waker));
+        slot.wake();
+        assert_eq!(count.0.load(Ordering::SeqCst), 1);
+        assert!(slot.register(&waker));
+        assert!(slot.take_ready());
+        assert!(!slot.take_ready());
+        assert_eq!(slot.epoch(), 1);
+        assert!(!slot.register(&waker));
+        drop(slot);
+        assert_eq!(Arc::strong_count(&count), 2);
+    }
+}
@@ -0,0 +1,325 @@
+//! A single-value channel that always holds the latest value sent.
+//!
+//! The shared state is one word: the current value as an `Arc` pointer and,
+//! next to it, a version that every `send` bumps. Receivers compare versions
+//! to notice new values, and block on the word itself with `wait`, so
+//! neither side ever takes a lock.
+//!
+//! A reader takes its own strong count in a window it marks in the word,
+//! and a send only swaps the value out when no reader is inside one. That
+//! way the old value's count is never released under a reader still taking
+//! one. Readers never wait for senders; a send may wait out the few
+//! instructions a reader spends in the window.
+
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+#[cfg(feature = "async")]
+use std::future::Future;
+#[cfg(feature = "async")]
+use std::pin::Pin;
+#[cfg(feature = "async")]
+use std::task::{Context, Poll};
+
+use halves::Halves;
+#[cfg(feature = "async")]
+use wait::WaitAsync;
+use {current_backoff, AtomicU128};
+
+// lo is a pointer from `Arc::into_raw`, which owns one strong count; hi is
+// (version << 32) | closed | pending, where pending counts readers between
+// pinning the pointer and taking their own strong count.
+const CLOSED: u64 = 1 << 31;
+const PENDING: u64 = CLOSED - 1;
+const PIN: u128 = 1 << 64;
+
+fn version(word: Halves) -> u32 {
+    (word.hi >> 32) as u32
+}
+
+fn is_closed(word: Halves) -> bool {
+    word.hi & CLOSED != 0
+}
+
+// Marks a newer version seen, or reports the channel closed with nothing
+// newer to see.
+fn check(seen: &mut u32, current: Halves) -> Option<Result<(), Closed>> {
+    if version(current) != *seen {
+        *seen = version(current);
+        Some(Ok(()))
+    } else if is_closed(current) {
+        Some(Err(Closed))
+    } else {
+        None
+    }
+}
+
+struct Shared<T> {
+    word: AtomicU128,
+    senders: AtomicUsize,
+    _marker: PhantomData<Arc<T>>,
+}
+
+impl<T> Shared<T> {
+    fn load(&self) -> (Arc<T>, u32) {
+        // No send swaps the pointer out while we're pinned, so the word's
+        // own count keeps the value alive until we have one of our own.
+        let pinned = Halves::from_bits(self.word.fetch_add(PIN, Ordering::SeqCst));
+        let ptr = pinned.lo as *const T;
+        let value = unsafe {
+            Arc::increment_strong_count(ptr);
+            Arc::from_raw(ptr)
+        };
+        self.word.fetch_sub(PIN, Ordering::SeqCst);
+        (value, version(pinned))
+    }
+
+    fn send(&self, value: T) {
+        let new = Arc::into_raw(Arc::new(value)) as u64;
+        let backoff = current_backoff();
+        let mut current = self.word.load_halves();
+        let mut attempt = 0;
+        loop {
+            if current.hi & PENDING != 0 {
+                attempt += 1;
+                backoff.wait(attempt);
+                current = self.word.load_halves();
+                continue;
+            }
+            let next = Halves::new(new, (version(current).wrapping_add(1) as u64) << 32 | current.hi & CLOSED);
+            match self.word.cas_halves(current, next) {
+                Ok(_) => break,
+                Err(actual) => current = actual,
+            }
+        }
+        // Nobody was pinned when the old value left the word, so every
+        // reader of it already holds its own count.
+        unsafe { Arc::decrement_strong_count(current.lo as *const T) };
+        self.word.wake_all();
+    }
+
+    fn close(&self) {
+        let mut current = self.word.load_halves();
+        loop {
+            match self.word.cas_halves(current, Halves::new(current.lo, current.hi | CLOSED)) {
+                Ok(_) => break,
+                Err(actual) => current = actual,
+            }
+        }
+        self.word.wake_all();
+    }
+}
+
+impl<T> Drop for Shared<T> {
+    fn drop(&mut self) {
+        drop(unsafe { Arc::from_raw(self.word.load_halves().lo as *const T) });
+    }
+}
+
+/// Every sender has been dropped, so no new value can arrive.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Closed;
+
+/// The sending half; clones send into the same channel.
+pub struct Sender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+/// The receiving half. Each receiver remembers the version it last marked
+/// seen; clones start from the same one.
+pub struct Receiver<T> {
+    shared: Arc<Shared<T>>,
+    seen: u32,
+}
+
+/// A channel holding `init` until the first `send`.
+pub fn channel<T>(init: T) -> (Sender<T>, Receiver<T>) {
+    let shared = Arc::new(Shared {
+        word: AtomicU128::from_halves(Halves::new(Arc::into_raw(Arc::new(init)) as u64, 0)),
+        senders: AtomicUsize::new(1),
+        _marker: PhantomData,
+    });
+    (Sender { shared: shared.clone() }, Receiver { shared, seen: 0 })
+}
+
+impl<T> Sender<T> {
+    /// Replaces the value and wakes every receiver waiting for a change.
+    pub fn send(&self, value: T) {
+        self.shared.send(value);
+    }
+
+    pub fn borrow(&self) -> Arc<T> {
+        self.shared.load().0
+    }
+
+    /// A new receiver that has seen the current value.
+    pub fn subscribe(&self) -> Receiver<T> {
+        let seen = version(self.shared.word.load_halves());
+        Receiver { shared: self.shared.clone(), seen }
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.shared.senders.fetch_add(1, Ordering::Relaxed);
+        Sender { shared: self.shared.clone() }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        if self.shared.senders.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.shared.close();
+        }
+    }
+}
+
+impl<T> Receiver<T> {
+    /// The current value, without marking it seen.
+    pub fn borrow(&self) -> Arc<T> {
+        self.shared.load().0
+    }
+
+    /// The current value, marking it seen.
+    pub fn borrow_and_update(&mut self) -> Arc<T> {
+        let (value, version) = self.shared.load();
+        self.seen = version;
+        value
+    }
+
+    /// Whether a value has been sent since the last one marked seen.
+    pub fn has_changed(&self) -> bool {
+        version(self.shared.word.load_halves()) != self.seen
+    }
+
+    /// Blocks until a value newer than the last one seen is sent and marks
+    /// it seen, or fails once every sender is gone.
+    pub fn changed(&mut self) -> Result<(), Closed> {
+        loop {
+            let current = self.shared.word.load_halves();
+            if let Some(result) = check(&mut self.seen, current) {
+                return result;
+            }
+            self.shared.word.wait(current.bits());
+        }
+    }
+}
+
+impl<T> Clone for Receiver<T> {
+    fn clone(&self) -> Self {
+        Receiver { shared: self.shared.clone(), seen: self.seen }
+    }
+}
+
+/// Future returned by `Receiver::changed_async`.
+#[cfg(feature = "async")]
+pub struct Changed<'a> {
+    wait: WaitAsync<'a, Box<dyn FnMut(u128) -> bool + Send + 'a>>,
+    seen: &'a mut u32,
+}
+
+#[cfg(feature = "async")]
+impl<T> Receiver<T> {
+    /// `changed` for async code: resolves instead of blocking.
+    pub fn changed_async(&mut self) -> Changed<'_> {
+        let Receiver { ref shared, ref mut seen } = *self;
+        let last = *seen;
+        let predicate = move |bits| {
+            let current = Halves::from_bits(bits);
+            version(current) != last || is_closed(current)
+        };
+        Changed { wait: shared.word.wait_async(Box::new(predicate)), seen }
+    }
+}
+
+#[cfg(feature = "async")]
+impl<'a> Future for Changed<'a> {
+    type Output = Result<(), Closed>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Closed>> {
+        let this = self.get_mut();
+        match Pin::new(&mut this.wait).poll(cx) {
+            Poll::Ready(bits) => Poll::Ready(check(this.seen, Halves::from_bits(bits)).unwrap()),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::{channel, Closed};
+
+    #[test]
+    fn test_send_and_changed() {
+        let (tx, mut rx) = channel(String::from("a"));
+        assert!(!rx.has_changed());
+        assert_eq!(*rx.borrow(), "a");
+        tx.send(String::from("b"));
+        assert!(rx.has_changed());
+        assert_eq!(*rx.borrow_and_update(), "b");
+        assert!(!rx.has_changed());
+
+        let sender = thread::spawn(move || {
+            tx.send(String::from("c"));
+        });
+        assert_eq!(rx.changed(), Ok(()));
+        assert_eq!(*rx.borrow(), "c");
+        sender.join().unwrap();
+        assert_eq!(rx.changed(), Err(Closed));
+    }
+
+    #[test]
+    fn test_values_outlive_swaps() {
+        // Small enough for Miri, which checks that no count is taken on a
+        // value already freed.
+        let rounds: u64 = if cfg!(miri) { 40 } else { 2000 };
+        let (tx, rx) = channel(Arc::new(0u64));
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let rx = rx.clone();
+                thread::spawn(move || {
+                    let mut last = 0;
+                    for _ in 0..rounds {
+                        let value = **rx.borrow();
+                        assert!(value >= last);
+                        last = value;
+                    }
+                })
+            })
+            .collect();
+        let witness = Arc::new(rounds);
+        for i in 1..rounds {
+            tx.send(Arc::new(i));
+        }
+        tx.send(witness.clone());
+        for reader in readers {
+            reader.join().unwrap();
+        }
+        drop(tx);
+        drop(rx);
+        assert_eq!(Arc::strong_count(&witness), 1);
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_changed_async() {
+        use std::future::Future;
+        use std::pin::Pin;
+        use std::task::{Context, Poll, Waker};
+
+        let (tx, mut rx) = channel(1);
+        let mut cx = Context::from_waker(Waker::noop());
+        {
+            let mut fut = rx.changed_async();
+            assert!(Pin::new(&mut fut).poll(&mut cx).is_pending());
+            tx.send(2);
+            assert_eq!(Pin::new(&mut fut).poll(&mut cx), Poll::Ready(Ok(())));
+        }
+        assert_eq!(*rx.borrow(), 2);
+        drop(tx);
+        assert_eq!(Pin::new(&mut rx.changed_async()).poll(&mut cx), Poll::Ready(Err(Closed)));
+    }
+}
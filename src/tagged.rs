@@ -0,0 +1,93 @@
+use core::fmt;
+use core::marker::PhantomData;
+
+use halves::Halves;
+use {fmt_atomic, AtomicU128};
+
+/// A pointer next to a count of the changes made to it, for lock-free
+/// structures that recycle nodes.
+///
+/// Every successful `store`, `swap` or `compare_exchange` bumps the tag, and
+/// `compare_exchange` compares pointer and tag together, so a node that was
+/// removed and pushed back at the same address can't make a stale CAS
+/// succeed (ABA) short of 2^64 changes in between.
+pub struct AtomicTaggedPtr<T> {
+    // lo is the pointer, hi the tag.
+    word: AtomicU128,
+    _marker: PhantomData<*mut T>,
+}
+
+unsafe impl<T> Send for AtomicTaggedPtr<T> {}
+unsafe impl<T> Sync for AtomicTaggedPtr<T> {}
+
+impl<T> AtomicTaggedPtr<T> {
+    pub fn new(ptr: *mut T) -> Self {
+        AtomicTaggedPtr { word: AtomicU128::from_halves(Halves::new(ptr as u64, 0)), _marker: PhantomData }
+    }
+
+    /// The pointer and its tag.
+    pub fn load(&self) -> (*mut T, u64) {
+        unpack(self.word.load_halves())
+    }
+
+    pub fn store(&self, ptr: *mut T) {
+        self.swap(ptr);
+    }
+
+    /// Stores `ptr` and returns the pointer and tag it replaced.
+    pub fn swap(&self, ptr: *mut T) -> (*mut T, u64) {
+        let mut current = self.word.load_halves();
+        loop {
+            match self.word.cas_halves(current, Halves::new(ptr as u64, current.hi.wrapping_add(1))) {
+                Ok(_) => return unpack(current),
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    /// Stores `new` if the pointer and tag are still `current`, bumping the
+    /// tag. On failure returns what they are now.
+    pub fn compare_exchange(&self, current: (*mut T, u64), new: *mut T) -> Result<(*mut T, u64), (*mut T, u64)> {
+        let current = Halves::new(current.0 as u64, current.1);
+        self.word
+            .cas_halves(current, Halves::new(new as u64, current.hi.wrapping_add(1)))
+            .map(unpack)
+            .map_err(unpack)
+    }
+}
+
+fn unpack<T>(word: Halves) -> (*mut T, u64) {
+    (word.lo as *mut T, word.hi)
+}
+
+impl<T> Default for AtomicTaggedPtr<T> {
+    fn default() -> Self {
+        Self::new(::core::ptr::null_mut())
+    }
+}
+
+impl<T> fmt::Debug for AtomicTaggedPtr<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt_atomic(f, "AtomicTaggedPtr", &self.load())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ptr;
+
+    use super::AtomicTaggedPtr;
+
+    #[test]
+    fn test_stale_cas_fails_after_aba() {
+        let (mut a, mut b) = (1, 2);
+        let p = AtomicTaggedPtr::new(&mut a as *mut i32);
+        let seen = p.load();
+        assert_eq!(p.swap(&mut b), (&mut a as *mut i32, 0));
+        p.store(&mut a);
+        assert_eq!(p.load(), (&mut a as *mut i32, 2));
+        assert_eq!(p.compare_exchange(seen, ptr::null_mut()), Err((&mut a as *mut i32, 2)));
+        assert_eq!(p.compare_exchange(p.load(), ptr::null_mut()), Ok((&mut a as *mut i32, 2)));
+        assert_eq!(p.load(), (ptr::null_mut(), 3));
+    }
+}
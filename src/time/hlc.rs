@@ -0,0 +1,123 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use halves::Halves;
+use AtomicU128;
+
+/// A hybrid logical clock reading: wall-clock nanos plus a logical counter
+/// that orders events within the same (or a lagging) wall reading.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct HlcTimestamp {
+    pub wall: u64,
+    pub logical: u64,
+}
+
+/// Hybrid logical clock shared by many threads.
+///
+/// The (wall, logical) pair is one word, so every `now` and `observe` is a
+/// single CAS from one reading to the next and the clock never goes backwards
+/// even when the system clock does.
+pub struct HlcClock {
+    // lo is the wall component, hi the logical counter.
+    word: AtomicU128,
+}
+
+fn physical_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos() as u64).unwrap_or(0)
+}
+
+impl HlcClock {
+    pub fn new() -> Self {
+        HlcClock { word: AtomicU128::new(0) }
+    }
+
+    /// The last timestamp handed out, without advancing the clock.
+    pub fn last(&self) -> HlcTimestamp {
+        let word = self.word.load_halves();
+        HlcTimestamp { wall: word.lo, logical: word.hi }
+    }
+
+    /// A timestamp for a local or send event.
+    pub fn now(&self) -> HlcTimestamp {
+        self.now_at(physical_now())
+    }
+
+    /// Merges a timestamp received from another node and returns one that
+    /// orders after both it and everything this clock has handed out.
+    pub fn observe(&self, remote: HlcTimestamp) -> HlcTimestamp {
+        self.observe_at(remote, physical_now())
+    }
+
+    fn now_at(&self, physical: u64) -> HlcTimestamp {
+        self.advance(|last| {
+            if physical > last.wall {
+                HlcTimestamp { wall: physical, logical: 0 }
+            } else {
+                HlcTimestamp { wall: last.wall, logical: last.logical + 1 }
+            }
+        })
+    }
+
+    fn observe_at(&self, remote: HlcTimestamp, physical: u64) -> HlcTimestamp {
+        self.advance(|last| {
+            let wall = physical.max(last.wall).max(remote.wall);
+            let logical = if wall == last.wall && wall == remote.wall {
+                last.logical.max(remote.logical) + 1
+            } else if wall == last.wall {
+                last.logical + 1
+            } else if wall == remote.wall {
+                remote.logical + 1
+            } else {
+                0
+            };
+            HlcTimestamp { wall, logical }
+        })
+    }
+
+    fn advance<F: Fn(HlcTimestamp) -> HlcTimestamp>(&self, f: F) -> HlcTimestamp {
+        let mut current = self.word.load_halves();
+        loop {
+            let next = f(HlcTimestamp { wall: current.lo, logical: current.hi });
+            match self.word.cas_halves(current, Halves::new(next.wall, next.logical)) {
+                Ok(_) => return next,
+                Err(actual) => current = actual,
+            }
+        }
+    }
+}
+
+impl Default for HlcClock {
+    fn default() -> Self {
+        HlcClock::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HlcClock, HlcTimestamp};
+
+    fn ts(wall: u64, logical: u64) -> HlcTimestamp {
+        HlcTimestamp { wall, logical }
+    }
+
+    #[test]
+    fn test_now_monotonic() {
+        let clock = HlcClock::new();
+        assert_eq!(clock.now_at(10), ts(10, 0));
+        assert_eq!(clock.now_at(10), ts(10, 1));
+        assert_eq!(clock.now_at(5), ts(10, 2));
+        assert_eq!(clock.now_at(11), ts(11, 0));
+        let t = clock.now();
+        assert!(t > ts(11, 0));
+        assert_eq!(clock.last(), t);
+    }
+
+    #[test]
+    fn test_observe() {
+        let clock = HlcClock::new();
+        clock.now_at(10);
+        assert_eq!(clock.observe_at(ts(20, 3), 12), ts(20, 4));
+        assert_eq!(clock.observe_at(ts(20, 7), 12), ts(20, 8));
+        assert_eq!(clock.observe_at(ts(15, 9), 12), ts(20, 9));
+        assert_eq!(clock.observe_at(ts(15, 9), 30), ts(30, 0));
+    }
+}
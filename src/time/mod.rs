@@ -0,0 +1,13 @@
+mod rate_limiter;
+mod hlc;
+mod id_gen;
+mod vector_clock;
+mod timer_slot;
+mod stamp;
+
+pub use self::rate_limiter::RateLimiter;
+pub use self::hlc::{HlcClock, HlcTimestamp};
+pub use self::id_gen::IdGen128;
+pub use self::vector_clock::VectorClock;
+pub use self::timer_slot::TimerSlot;
+pub use self::stamp::{MonotonicStamp, Stamp};
@@ -0,0 +1,127 @@
+use std::time::Instant;
+
+use halves::Halves;
+use AtomicU128;
+
+const NANOS_PER_SEC: u128 = 1_000_000_000;
+
+// The word after crediting the tokens accrued by `now`. The timestamp only
+// moves forward by the time the credited tokens account for, so partial
+// tokens aren't lost between calls.
+fn refill(word: Halves, now: u64, capacity: u64, per_sec: u64) -> Halves {
+    let elapsed = now.saturating_sub(word.hi) as u128;
+    let accrued = elapsed * per_sec as u128 / NANOS_PER_SEC;
+    let tokens = word.lo as u128 + accrued;
+    if tokens >= capacity as u128 {
+        Halves::new(capacity, now.max(word.hi))
+    } else {
+        Halves::new(tokens as u64, word.hi + (accrued * NANOS_PER_SEC / per_sec as u128) as u64)
+    }
+}
+
+/// Token bucket with the token count and last refill time in one word.
+///
+/// Refills happen lazily inside `try_acquire`: the caller works out how many
+/// tokens have accrued since the stored timestamp and takes its share in the
+/// same CAS that records the refill, so two threads can't both spend the same
+/// accrual.
+pub struct RateLimiter {
+    // lo is the tokens available, hi the refill time in nanos since `start`.
+    word: AtomicU128,
+    capacity: u64,
+    per_sec: u64,
+    start: Instant,
+}
+
+impl RateLimiter {
+    /// A full bucket of `capacity` tokens, refilled at `per_sec` tokens a second.
+    pub fn new(capacity: u64, per_sec: u64) -> Self {
+        assert!(per_sec > 0, "refill rate must be positive");
+        RateLimiter { word: AtomicU128::from_halves(Halves::new(capacity, 0)), capacity, per_sec, start: Instant::now() }
+    }
+
+    pub fn capacity(&self) -> u64 {
+        self.capacity
+    }
+
+    /// Tokens that a `try_acquire` right now could take from.
+    pub fn available(&self) -> u64 {
+        self.refilled(self.word.load_halves(), self.now()).lo
+    }
+
+    pub fn try_acquire(&self, n: u64) -> bool {
+        self.try_acquire_at(n, self.now())
+    }
+
+    fn now(&self) -> u64 {
+        self.start.elapsed().as_nanos() as u64
+    }
+
+    fn refilled(&self, word: Halves, now: u64) -> Halves {
+        refill(word, now, self.capacity, self.per_sec)
+    }
+
+    fn try_acquire_at(&self, n: u64, now: u64) -> bool {
+        let mut current = self.word.load_halves();
+        loop {
+            let refilled = self.refilled(current, now);
+            if refilled.lo < n {
+                return false;
+            }
+            match self.word.cas_halves(current, Halves::new(refilled.lo - n, refilled.hi)) {
+                Ok(_) => return true,
+                Err(actual) => current = actual,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RateLimiter;
+
+    const MS: u64 = 1_000_000;
+
+    #[test]
+    fn test_burst_and_refill() {
+        let limiter = RateLimiter::new(3, 1000);
+        assert!(limiter.try_acquire_at(2, 0));
+        assert!(limiter.try_acquire_at(1, 0));
+        assert!(!limiter.try_acquire_at(1, 0));
+        assert!(limiter.try_acquire_at(1, MS));
+        assert!(!limiter.try_acquire_at(1, MS + MS / 2));
+        assert!(limiter.try_acquire_at(1, 2 * MS));
+        assert!(!limiter.try_acquire_at(4, 100 * MS));
+        assert!(limiter.try_acquire_at(3, 100 * MS));
+    }
+
+    #[test]
+    fn test_fractional_refill() {
+        let limiter = RateLimiter::new(1, 3);
+        assert!(limiter.try_acquire_at(1, 0));
+        assert!(!limiter.try_acquire_at(1, 200 * MS));
+        assert!(limiter.try_acquire_at(1, 400 * MS));
+        assert!(limiter.available() <= limiter.capacity());
+    }
+}
+
+#[cfg(kani)]
+mod proofs {
+    use super::refill;
+    use halves::Halves;
+
+    // Refilling never overfills, never loses tokens and never moves the
+    // timestamp past `now` (unless it was already there).
+    #[kani::proof]
+    fn refill_bounds() {
+        let (tokens, stamp, now, capacity, per_sec): (u64, u64, u64, u64, u64) =
+            (kani::any(), kani::any(), kani::any(), kani::any(), kani::any());
+        kani::assume(per_sec > 0 && per_sec <= 1_000_000_000);
+        kani::assume(tokens <= capacity);
+        let after = refill(Halves::new(tokens, stamp), now, capacity, per_sec);
+        assert!(after.lo <= capacity);
+        assert!(after.lo >= tokens);
+        assert!(after.hi >= stamp);
+        assert!(after.hi <= now.max(stamp));
+    }
+}
@@ -0,0 +1,122 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use halves::Halves;
+use AtomicU128;
+
+/// A reading of a `MonotonicStamp`: nanos since the Unix epoch and a
+/// counter breaking ties between stamps taken in the same nanosecond.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Stamp {
+    pub nanos: u64,
+    pub seq: u64,
+}
+
+impl Stamp {
+    /// The stamp as one integer, ordered the same way, for use as a key.
+    pub fn as_u128(&self) -> u128 {
+        (self.nanos as u128) << 64 | self.seq as u128
+    }
+}
+
+/// A timestamp that only moves forward, shared by many threads.
+///
+/// Nanos and tie-break counter are one word, so `next_unique` is one CAS
+/// from the last stamp to a later one and no two calls, on any thread, get
+/// the same stamp, even when the system clock stalls or steps back.
+#[derive(Debug, Default)]
+pub struct MonotonicStamp {
+    // lo is the nanos, hi the tie-break counter.
+    word: AtomicU128,
+}
+
+fn physical_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos() as u64).unwrap_or(0)
+}
+
+impl MonotonicStamp {
+    pub fn new() -> Self {
+        MonotonicStamp::default()
+    }
+
+    pub fn load(&self) -> Stamp {
+        let current = self.word.load_halves();
+        Stamp { nanos: current.lo, seq: current.hi }
+    }
+
+    /// Moves the stamp forward to `now` if it's behind, and returns the
+    /// stamp either way.
+    pub fn advance_to(&self, now: u64) -> Stamp {
+        self.update(|last| if now > last.nanos { Some(Stamp { nanos: now, seq: 0 }) } else { None })
+    }
+
+    /// A stamp later than every one handed out before, read off the system
+    /// clock.
+    pub fn next_unique(&self) -> Stamp {
+        self.next_unique_at(physical_now())
+    }
+
+    /// Like `next_unique`, with the caller's reading of the clock.
+    pub fn next_unique_at(&self, now: u64) -> Stamp {
+        self.update(|last| {
+            Some(if now > last.nanos { Stamp { nanos: now, seq: 0 } } else { Stamp { nanos: last.nanos, seq: last.seq + 1 } })
+        })
+    }
+
+    fn update<F: Fn(Stamp) -> Option<Stamp>>(&self, f: F) -> Stamp {
+        let mut current = self.word.load_halves();
+        loop {
+            let next = match f(Stamp { nanos: current.lo, seq: current.hi }) {
+                Some(next) => next,
+                None => return Stamp { nanos: current.lo, seq: current.hi },
+            };
+            match self.word.cas_halves(current, Halves::new(next.nanos, next.seq)) {
+                Ok(_) => return next,
+                Err(actual) => current = actual,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::{MonotonicStamp, Stamp};
+
+    #[test]
+    fn test_advance_and_ties() {
+        let stamp = MonotonicStamp::new();
+        assert_eq!(stamp.advance_to(100), Stamp { nanos: 100, seq: 0 });
+        assert_eq!(stamp.advance_to(50), Stamp { nanos: 100, seq: 0 });
+        assert_eq!(stamp.next_unique_at(90), Stamp { nanos: 100, seq: 1 });
+        assert_eq!(stamp.next_unique_at(100), Stamp { nanos: 100, seq: 2 });
+        assert_eq!(stamp.next_unique_at(101).as_u128(), 101 << 64);
+        let now = stamp.next_unique();
+        assert!(now > Stamp { nanos: 101, seq: 0 });
+        assert_eq!(stamp.load(), now);
+    }
+
+    #[test]
+    fn test_unique_across_threads() {
+        let stamp = Arc::new(MonotonicStamp::new());
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let stamp = stamp.clone();
+                thread::spawn(move || {
+                    let mut seen = Vec::new();
+                    for i in 0..1000 {
+                        let next = stamp.next_unique_at(i / 10);
+                        assert!(seen.last().is_none_or(|&last| next > last));
+                        seen.push(next);
+                    }
+                    seen
+                })
+            })
+            .collect();
+        let mut all: Vec<Stamp> = handles.into_iter().flat_map(|h| h.join().unwrap()).collect();
+        all.sort();
+        all.dedup();
+        assert_eq!(all.len(), 4000);
+    }
+}
@@ -0,0 +1,119 @@
+use halves::Halves;
+use AtomicU128;
+
+// An empty slot's deadline, later than any real one.
+const NEVER: u64 = u64::MAX;
+
+/// One slot of a timer wheel: the soonest deadline armed in it and the
+/// handle of the timer it belongs to.
+///
+/// Deadline and handle change together in one CAS, so a timer thread that
+/// fires the slot always gets the handle that goes with the deadline that
+/// was due, and of several threads arming the slot at once the soonest
+/// deadline stays. Deadlines are in whatever units the wheel ticks in.
+#[derive(Debug)]
+pub struct TimerSlot {
+    // lo is the handle, hi the deadline (NEVER when empty).
+    word: AtomicU128,
+}
+
+impl TimerSlot {
+    pub fn new() -> Self {
+        TimerSlot { word: AtomicU128::from_halves(Halves::new(0, NEVER)) }
+    }
+
+    /// The armed `(deadline, handle)`, if any.
+    pub fn load(&self) -> Option<(u64, u64)> {
+        let current = self.word.load_halves();
+        if current.hi == NEVER { None } else { Some((current.hi, current.lo)) }
+    }
+
+    /// Arms the slot with `handle` if it's empty or `deadline` is sooner
+    /// than what's armed. Returns whether it was armed.
+    pub fn arm_if_sooner(&self, deadline: u64, handle: u64) -> bool {
+        assert!(deadline != NEVER, "deadline u64::MAX is reserved for empty slots");
+        let mut current = self.word.load_halves();
+        while deadline < current.hi {
+            match self.word.cas_halves(current, Halves::new(handle, deadline)) {
+                Ok(_) => return true,
+                Err(actual) => current = actual,
+            }
+        }
+        false
+    }
+
+    /// Empties the slot if its deadline is at or before `now`, and returns
+    /// the `(deadline, handle)` that fired.
+    pub fn fire_if_due(&self, now: u64) -> Option<(u64, u64)> {
+        let mut current = self.word.load_halves();
+        while current.hi != NEVER && current.hi <= now {
+            match self.word.cas_halves(current, Halves::new(0, NEVER)) {
+                Ok(_) => return Some((current.hi, current.lo)),
+                Err(actual) => current = actual,
+            }
+        }
+        None
+    }
+
+    /// Empties the slot if `handle` is what's armed in it.
+    pub fn cancel(&self, handle: u64) -> bool {
+        let mut current = self.word.load_halves();
+        while current.hi != NEVER && current.lo == handle {
+            match self.word.cas_halves(current, Halves::new(0, NEVER)) {
+                Ok(_) => return true,
+                Err(actual) => current = actual,
+            }
+        }
+        false
+    }
+}
+
+impl Default for TimerSlot {
+    fn default() -> Self {
+        TimerSlot::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::TimerSlot;
+
+    #[test]
+    fn test_arm_fire_cancel() {
+        let slot = TimerSlot::new();
+        assert_eq!(slot.fire_if_due(u64::MAX - 1), None);
+        assert!(slot.arm_if_sooner(100, 1));
+        assert!(!slot.arm_if_sooner(150, 2));
+        assert!(slot.arm_if_sooner(90, 3));
+        assert_eq!(slot.fire_if_due(89), None);
+        assert_eq!(slot.fire_if_due(95), Some((90, 3)));
+        assert_eq!(slot.load(), None);
+        slot.arm_if_sooner(10, 4);
+        assert!(!slot.cancel(3));
+        assert!(slot.cancel(4));
+        assert_eq!(slot.load(), None);
+    }
+
+    #[test]
+    fn test_soonest_wins() {
+        let slot = Arc::new(TimerSlot::new());
+        let handles: Vec<_> = (0..4u64)
+            .map(|t| {
+                let slot = slot.clone();
+                thread::spawn(move || {
+                    for i in 0..1000u64 {
+                        let deadline = 10_000 - (i * 4 + t);
+                        slot.arm_if_sooner(deadline, deadline + 1);
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(slot.load(), Some((6001, 6002)));
+    }
+}
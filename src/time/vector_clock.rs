@@ -0,0 +1,155 @@
+use std::cmp::Ordering;
+
+use halves::Halves;
+use snapshot;
+use AtomicU128;
+
+/// A vector clock for `nodes` nodes, updated from any number of threads.
+///
+/// Counters are packed two to a word, node `2i` in the low half of word `i`
+/// and node `2i + 1` in the high half. Each update is a CAS on one word, and
+/// counters only grow, so a double collect that agrees is a cut in which
+/// every counter held at once.
+pub struct VectorClock {
+    words: Box<[AtomicU128]>,
+    nodes: usize,
+}
+
+impl VectorClock {
+    pub fn new(nodes: usize) -> Self {
+        VectorClock { words: (0..nodes.div_ceil(2)).map(|_| AtomicU128::new(0)).collect(), nodes }
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes == 0
+    }
+
+    fn slot(&self, node: usize) -> (&AtomicU128, bool) {
+        assert!(node < self.nodes, "node out of range");
+        (&self.words[node / 2], node % 2 == 1)
+    }
+
+    pub fn get(&self, node: usize) -> u64 {
+        let (word, high) = self.slot(node);
+        let value = word.load_halves();
+        if high { value.hi } else { value.lo }
+    }
+
+    /// Records an event on `node` and returns its new counter.
+    pub fn increment(&self, node: usize) -> u64 {
+        let (word, high) = self.slot(node);
+        let mut current = word.load_halves();
+        loop {
+            let next = if high {
+                Halves::new(current.lo, current.hi + 1)
+            } else {
+                Halves::new(current.lo + 1, current.hi)
+            };
+            match word.cas_halves(current, next) {
+                Ok(_) => return if high { next.hi } else { next.lo },
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    /// Raises every counter to at least the matching one in `other`, a
+    /// snapshot from another clock. Nodes beyond this clock's length are
+    /// ignored.
+    pub fn merge(&self, other: &[u64]) {
+        for (i, (word, pair)) in self.words.iter().zip(other.chunks(2)).enumerate() {
+            let lo = pair[0];
+            let hi = if 2 * i + 1 < self.nodes { pair.get(1).cloned().unwrap_or(0) } else { 0 };
+            let mut current = word.load_halves();
+            while current.lo < lo || current.hi < hi {
+                match word.cas_halves(current, Halves::new(current.lo.max(lo), current.hi.max(hi))) {
+                    Ok(_) => break,
+                    Err(actual) => current = actual,
+                }
+            }
+        }
+    }
+
+    /// All counters as of one instant.
+    pub fn snapshot(&self) -> Vec<u64> {
+        let cells: Vec<&AtomicU128> = self.words.iter().collect();
+        let mut counters: Vec<u64> = snapshot(&cells)
+            .into_iter()
+            .flat_map(|bits| {
+                let value = Halves::from_bits(bits);
+                vec![value.lo, value.hi]
+            })
+            .collect();
+        counters.truncate(self.nodes);
+        counters
+    }
+
+    /// How two snapshots are causally related: `Less` if `a` happened before
+    /// `b`, `Greater` if after, `Equal` if identical and `None` if they're
+    /// concurrent. Missing trailing counters count as zero.
+    pub fn compare(a: &[u64], b: &[u64]) -> Option<Ordering> {
+        let (mut less, mut greater) = (false, false);
+        for i in 0..a.len().max(b.len()) {
+            let (x, y) = (a.get(i).cloned().unwrap_or(0), b.get(i).cloned().unwrap_or(0));
+            less |= x < y;
+            greater |= x > y;
+        }
+        match (less, greater) {
+            (false, false) => Some(Ordering::Equal),
+            (true, false) => Some(Ordering::Less),
+            (false, true) => Some(Ordering::Greater),
+            (true, true) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Ordering;
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::VectorClock;
+
+    #[test]
+    fn test_increment_merge() {
+        let clock = VectorClock::new(3);
+        assert_eq!(clock.increment(0), 1);
+        assert_eq!(clock.increment(2), 1);
+        assert_eq!(clock.increment(2), 2);
+        assert_eq!(clock.snapshot(), vec![1, 0, 2]);
+        clock.merge(&[0, 5, 1, 9]);
+        assert_eq!(clock.snapshot(), vec![1, 5, 2]);
+        assert_eq!(clock.get(1), 5);
+    }
+
+    #[test]
+    fn test_compare() {
+        assert_eq!(VectorClock::compare(&[1, 2], &[1, 2]), Some(Ordering::Equal));
+        assert_eq!(VectorClock::compare(&[1], &[1, 2]), Some(Ordering::Less));
+        assert_eq!(VectorClock::compare(&[2, 0], &[1, 0]), Some(Ordering::Greater));
+        assert_eq!(VectorClock::compare(&[2, 0], &[1, 1]), None);
+    }
+
+    #[test]
+    fn test_concurrent_increments() {
+        let clock = Arc::new(VectorClock::new(4));
+        let handles: Vec<_> = (0..4)
+            .map(|node| {
+                let clock = clock.clone();
+                thread::spawn(move || {
+                    for _ in 0..1000 {
+                        clock.increment(node);
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(clock.snapshot(), vec![1000; 4]);
+    }
+}
@@ -0,0 +1,86 @@
+// Trace events for contended operations, behind the `tracing` feature.
+// Without it every function here is empty and inlines away. Each event
+// carries the word's address and, through `#[track_caller]` on the public
+// entry points, the location of the caller's code rather than this crate's.
+
+#[cfg(feature = "tracing")]
+use core::panic::Location;
+
+use AtomicU128;
+
+// CAS loops report once they've failed this many times, then again at every
+// power of two, so a livelocked loop shows up without flooding the log.
+#[cfg(any(feature = "tracing", test))]
+pub const CONTENDED_RETRIES: u32 = 64;
+
+#[cfg(any(feature = "tracing", test))]
+fn should_report(retries: u32) -> bool {
+    retries == CONTENDED_RETRIES || (retries > CONTENDED_RETRIES && retries.is_power_of_two())
+}
+
+#[cfg(feature = "tracing")]
+fn addr(word: &AtomicU128) -> usize {
+    word as *const AtomicU128 as usize
+}
+
+#[inline]
+#[cfg_attr(feature = "tracing", track_caller)]
+pub fn cas_retry(word: &AtomicU128, retries: u32) {
+    #[cfg(feature = "tracing")]
+    {
+        if should_report(retries) {
+            ::tracing::warn!(
+                target: "atomic128",
+                addr = %format_args!("{:#x}", addr(word)),
+                retries = retries,
+                location = %Location::caller(),
+                "contended CAS loop"
+            );
+        }
+    }
+    #[cfg(not(feature = "tracing"))]
+    let _ = (word, retries);
+}
+
+// Only the blocking waits in `wait` report these.
+#[cfg(feature = "std")]
+#[inline]
+#[cfg_attr(feature = "tracing", track_caller)]
+pub fn wait(word: &AtomicU128) {
+    #[cfg(feature = "tracing")]
+    ::tracing::debug!(
+        target: "atomic128",
+        addr = %format_args!("{:#x}", addr(word)),
+        location = %Location::caller(),
+        "waiting on word"
+    );
+    #[cfg(not(feature = "tracing"))]
+    let _ = word;
+}
+
+#[cfg(feature = "std")]
+#[inline]
+#[cfg_attr(feature = "tracing", track_caller)]
+pub fn wake(word: &AtomicU128, woken: usize) {
+    #[cfg(feature = "tracing")]
+    ::tracing::debug!(
+        target: "atomic128",
+        addr = %format_args!("{:#x}", addr(word)),
+        woken = woken,
+        location = %Location::caller(),
+        "woke waiters"
+    );
+    #[cfg(not(feature = "tracing"))]
+    let _ = (word, woken);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{should_report, CONTENDED_RETRIES};
+
+    #[test]
+    fn test_report_thresholds() {
+        let reported: Vec<u32> = (0..1000).filter(|&r| should_report(r)).collect();
+        assert_eq!(reported, vec![CONTENDED_RETRIES, 128, 256, 512]);
+    }
+}
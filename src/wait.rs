@@ -0,0 +1,256 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, Thread};
+#[cfg(feature = "async")]
+use std::future::Future;
+#[cfg(feature = "async")]
+use std::pin::Pin;
+#[cfg(feature = "async")]
+use std::sync::atomic::AtomicU64;
+#[cfg(feature = "async")]
+use std::task::{Context, Poll, Waker};
+
+use trace;
+use AtomicU128;
+
+// Threads and tasks waiting on a word, hashed by the word's address. Several
+// words can share a bucket, so each entry remembers its address and wakes
+// only match on it.
+struct Waiter {
+    addr: usize,
+    wake: Wake,
+}
+
+enum Wake {
+    Thread(Thread, Arc<AtomicBool>),
+    #[cfg(feature = "async")]
+    Task(u64, Waker),
+}
+
+const BUCKETS: usize = 64;
+
+static PARKED: [Mutex<Vec<Waiter>>; BUCKETS] = [const { Mutex::new(Vec::new()) }; BUCKETS];
+
+fn bucket_index(addr: usize) -> usize {
+    // Words are 16 bytes, so the low four bits carry nothing. The multiply is
+    // 64-bit on every target, as in `stripe_index`.
+    (((addr >> 4) as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15) >> 58) as usize
+}
+
+fn bucket(addr: usize) -> &'static Mutex<Vec<Waiter>> {
+    &PARKED[bucket_index(addr)]
+}
+
+impl AtomicU128 {
+    /// Blocks while the word still holds `expected`.
+    ///
+    /// The check happens under the same lock `wake_one`/`wake_all` take, so a
+    /// change followed by a wake can't slip in between the check and the sleep.
+    /// Returns as soon as it's woken; callers recheck the value themselves.
+    #[cfg_attr(feature = "tracing", track_caller)]
+    pub fn wait(&self, expected: u128) {
+        let addr = self as *const AtomicU128 as usize;
+        let woken = Arc::new(AtomicBool::new(false));
+        {
+            let mut parked = bucket(addr).lock().unwrap();
+            if self.load(Ordering::SeqCst) != expected {
+                return;
+            }
+            parked.push(Waiter { addr, wake: Wake::Thread(thread::current(), woken.clone()) });
+        }
+        trace::wait(self);
+        while !woken.load(Ordering::Acquire) {
+            thread::park();
+        }
+    }
+
+    /// Wakes one thread or task waiting on this word, if any. Returns
+    /// whether one was woken.
+    #[cfg_attr(feature = "tracing", track_caller)]
+    pub fn wake_one(&self) -> bool {
+        self.wake(1) == 1
+    }
+
+    /// Wakes every thread and task waiting on this word; returns how many.
+    #[cfg_attr(feature = "tracing", track_caller)]
+    pub fn wake_all(&self) -> usize {
+        self.wake(usize::MAX)
+    }
+
+    #[cfg_attr(feature = "tracing", track_caller)]
+    fn wake(&self, max: usize) -> usize {
+        let addr = self as *const AtomicU128 as usize;
+        let mut woken = Vec::new();
+        {
+            let mut parked = bucket(addr).lock().unwrap();
+            let mut i = 0;
+            while i < parked.len() && woken.len() < max {
+                if parked[i].addr == addr {
+                    woken.push(parked.remove(i));
+                } else {
+                    i += 1;
+                }
+            }
+        }
+        let n = woken.len();
+        if n > 0 {
+            trace::wake(self, n);
+        }
+        for waiter in woken {
+            match waiter.wake {
+                Wake::Thread(thread, flag) => {
+                    flag.store(true, Ordering::Release);
+                    thread.unpark();
+                }
+                #[cfg(feature = "async")]
+                Wake::Task(_, waker) => waker.wake(),
+            }
+        }
+        n
+    }
+}
+
+#[cfg(feature = "async")]
+static NEXT_TOKEN: AtomicU64 = AtomicU64::new(0);
+
+/// Future returned by `AtomicU128::wait_async`.
+///
+/// While pending it has an entry in the same table `wait` parks threads in,
+/// so the usual `wake_one`/`wake_all` after a change reach it too. Dropping
+/// the future takes the entry out.
+#[cfg(feature = "async")]
+pub struct WaitAsync<'a, P> {
+    cell: &'a AtomicU128,
+    predicate: P,
+    token: u64,
+}
+
+// Nothing is pinned in place; the table holds a token, not a pointer.
+#[cfg(feature = "async")]
+impl<'a, P> Unpin for WaitAsync<'a, P> {}
+
+#[cfg(feature = "async")]
+impl AtomicU128 {
+    /// Resolves with the first value for which `predicate` holds, checking
+    /// again each time the word is woken.
+    pub fn wait_async<P: FnMut(u128) -> bool>(&self, predicate: P) -> WaitAsync<'_, P> {
+        WaitAsync { cell: self, predicate, token: NEXT_TOKEN.fetch_add(1, Ordering::Relaxed) }
+    }
+}
+
+#[cfg(feature = "async")]
+impl<'a, P> WaitAsync<'a, P> {
+    fn addr(&self) -> usize {
+        self.cell as *const AtomicU128 as usize
+    }
+}
+
+#[cfg(feature = "async")]
+fn is_task(waiter: &Waiter, addr: usize, token: u64) -> bool {
+    match waiter.wake {
+        Wake::Task(t, _) => waiter.addr == addr && t == token,
+        _ => false,
+    }
+}
+
+#[cfg(feature = "async")]
+impl<'a, P: FnMut(u128) -> bool> Future for WaitAsync<'a, P> {
+    type Output = u128;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<u128> {
+        let this = self.get_mut();
+        let addr = this.addr();
+        let mut parked = bucket(addr).lock().unwrap();
+        let entry = parked.iter().position(|w| is_task(w, addr, this.token));
+        let value = this.cell.load(Ordering::SeqCst);
+        if (this.predicate)(value) {
+            if let Some(i) = entry {
+                parked.remove(i);
+            }
+            return Poll::Ready(value);
+        }
+        let wake = Wake::Task(this.token, cx.waker().clone());
+        match entry {
+            Some(i) => parked[i].wake = wake,
+            None => parked.push(Waiter { addr, wake }),
+        }
+        Poll::Pending
+    }
+}
+
+#[cfg(feature = "async")]
+impl<'a, P> Drop for WaitAsync<'a, P> {
+    fn drop(&mut self) {
+        let addr = self.addr();
+        let mut parked = bucket(addr).lock().unwrap();
+        if let Some(i) = parked.iter().position(|w| is_task(w, addr, self.token)) {
+            parked.remove(i);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering::SeqCst;
+    use std::sync::Arc;
+    use std::thread;
+    use AtomicU128;
+
+    #[test]
+    fn test_wait_changed() {
+        let a = AtomicU128::new(2 << 64 | 1);
+        a.wait(3 << 64 | 1);
+        assert!(!a.wake_one());
+        assert_eq!(a.wake_all(), 0);
+    }
+
+    #[test]
+    fn test_wait_wake() {
+        let a = Arc::new(AtomicU128::new(0));
+        let waiter = {
+            let a = a.clone();
+            thread::spawn(move || {
+                while a.load(SeqCst) == 0 {
+                    a.wait(0);
+                }
+            })
+        };
+        a.store(1 << 64, SeqCst);
+        a.wake_all();
+        waiter.join().unwrap();
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_wait_async() {
+        use std::future::Future;
+        use std::pin::Pin;
+        use std::task::{Context, Poll, Waker};
+
+        let a = AtomicU128::new(0);
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut fut = a.wait_async(|v| v >> 64 >= 2);
+        assert!(Pin::new(&mut fut).poll(&mut cx).is_pending());
+        a.store(1 << 64, SeqCst);
+        assert!(a.wake_one());
+        assert!(Pin::new(&mut fut).poll(&mut cx).is_pending());
+        a.store(2 << 64, SeqCst);
+        assert_eq!(Pin::new(&mut fut).poll(&mut cx), Poll::Ready(2 << 64));
+        assert!(!a.wake_one());
+
+        let mut fut = a.wait_async(|v| v as u64 == 1);
+        assert!(Pin::new(&mut fut).poll(&mut cx).is_pending());
+        drop(fut);
+        assert_eq!(a.wake_all(), 0);
+    }
+}
+
+#[cfg(kani)]
+mod proofs {
+    use super::{bucket_index, BUCKETS};
+
+    #[kani::proof]
+    fn bucket_index_in_range() {
+        assert!(bucket_index(kani::any()) < BUCKETS);
+    }
+}
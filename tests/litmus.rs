@@ -0,0 +1,204 @@
+// Classic memory-model litmus tests on AtomicU128, ignored by default:
+//   cargo test --release --test litmus -- --ignored --nocapture
+// Each runs LITMUS_ITERS rounds (default 1,000,000) on real threads and
+// prints how often every outcome turned up. Outcomes the orderings forbid
+// fail the test; ones they merely allow are reported, since whether they
+// show up depends on the architecture and backend.
+
+extern crate atomic128;
+
+use std::collections::BTreeMap;
+use std::env;
+use std::hint;
+use std::sync::atomic::Ordering::{self, Acquire, Relaxed, Release, SeqCst};
+use std::sync::atomic::AtomicUsize;
+use std::sync::Arc;
+use std::thread;
+
+use atomic128::AtomicU128;
+
+// Written values straddle both halves, so a torn access shows as its own
+// outcome instead of passing for 0 or V.
+const V: u128 = 1 << 64 | 1;
+
+struct Locations {
+    x: AtomicU128,
+    y: AtomicU128,
+}
+
+type Actor = Box<dyn Fn(&Locations) -> u128 + Send + Sync>;
+
+fn iterations() -> usize {
+    env::var("LITMUS_ITERS").ok().and_then(|s| s.parse().ok()).unwrap_or(1_000_000)
+}
+
+// 0 for the initial value, 1 for V, and 2 for anything else.
+fn seen(value: u128) -> u128 {
+    match value {
+        0 => 0,
+        V => 1,
+        _ => 2,
+    }
+}
+
+fn spin_until<F: Fn() -> bool>(ready: F) {
+    let mut spins = 0u32;
+    while !ready() {
+        spins += 1;
+        if spins.is_multiple_of(1024) {
+            thread::yield_now();
+        } else {
+            hint::spin_loop();
+        }
+    }
+}
+
+// Runs every actor once per round, all released together from zeroed
+// locations, and counts each combination of what they returned.
+fn run(name: &str, actors: Vec<Actor>) -> BTreeMap<Vec<u128>, usize> {
+    let iters = iterations();
+    let locations = Arc::new(Locations { x: AtomicU128::new(0), y: AtomicU128::new(0) });
+    let round = Arc::new(AtomicUsize::new(0));
+    let done = Arc::new(AtomicUsize::new(0));
+    let results: Arc<Vec<AtomicU128>> = Arc::new(actors.iter().map(|_| AtomicU128::new(0)).collect());
+    let n = actors.len();
+    let handles: Vec<_> = actors
+        .into_iter()
+        .enumerate()
+        .map(|(i, actor)| {
+            let (locations, round, done, results) = (locations.clone(), round.clone(), done.clone(), results.clone());
+            thread::spawn(move || {
+                for r in 1..=iters {
+                    spin_until(|| round.load(Acquire) == r);
+                    results[i].store(actor(&locations), Relaxed);
+                    done.fetch_add(1, Release);
+                }
+            })
+        })
+        .collect();
+
+    let mut outcomes = BTreeMap::new();
+    for r in 1..=iters {
+        locations.x.store(0, SeqCst);
+        locations.y.store(0, SeqCst);
+        done.store(0, Relaxed);
+        round.store(r, Release);
+        spin_until(|| done.load(Acquire) == n);
+        let outcome = results.iter().map(|result| result.load(Relaxed)).collect();
+        *outcomes.entry(outcome).or_insert(0) += 1;
+    }
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    println!("{} ({} rounds):", name, iters);
+    for (outcome, count) in &outcomes {
+        println!("  {:?}: {}", outcome, count);
+    }
+    outcomes
+}
+
+fn never_torn(outcomes: &BTreeMap<Vec<u128>, usize>) {
+    assert!(outcomes.keys().flatten().all(|&r| r & 0b1010 == 0), "torn value observed");
+}
+
+// Store buffering: each thread writes one location and reads the other.
+// Both reading 0 needs a store to be delayed past the other thread's load.
+fn store_buffering(store: Ordering, load: Ordering) -> BTreeMap<Vec<u128>, usize> {
+    let name = format!("SB store={:?} load={:?}", store, load);
+    run(
+        &name,
+        vec![
+            Box::new(move |l: &Locations| {
+                l.x.store(V, store);
+                seen(l.y.load(load))
+            }),
+            Box::new(move |l: &Locations| {
+                l.y.store(V, store);
+                seen(l.x.load(load))
+            }),
+        ],
+    )
+}
+
+#[test]
+#[ignore]
+fn litmus_store_buffering_seq_cst() {
+    let outcomes = store_buffering(SeqCst, SeqCst);
+    never_torn(&outcomes);
+    assert!(!outcomes.contains_key(&vec![0, 0]), "SeqCst SB saw both loads miss");
+}
+
+#[test]
+#[ignore]
+fn litmus_store_buffering_release_acquire() {
+    // Both reading 0 is allowed here; report it.
+    never_torn(&store_buffering(Release, Acquire));
+}
+
+// Message passing: data, then a flag; a reader that sees the flag must see
+// the data.
+#[test]
+#[ignore]
+fn litmus_message_passing() {
+    let outcomes = run(
+        "MP data=Relaxed flag=Release/Acquire",
+        vec![
+            Box::new(|l: &Locations| {
+                l.x.store(V, Relaxed);
+                l.y.store(V, Release);
+                0
+            }),
+            Box::new(|l: &Locations| {
+                let flag = seen(l.y.load(Acquire));
+                let data = seen(l.x.load(Relaxed));
+                flag << 2 | data
+            }),
+        ],
+    );
+    never_torn(&outcomes);
+    assert!(!outcomes.contains_key(&vec![0, 0b0100]), "flag seen without its data");
+}
+
+// Independent reads of independent writes: two readers must agree on the
+// order of the two writes.
+fn iriw(load: Ordering) -> BTreeMap<Vec<u128>, usize> {
+    let name = format!("IRIW store=SeqCst load={:?}", load);
+    run(
+        &name,
+        vec![
+            Box::new(|l: &Locations| {
+                l.x.store(V, SeqCst);
+                0
+            }),
+            Box::new(|l: &Locations| {
+                l.y.store(V, SeqCst);
+                0
+            }),
+            Box::new(move |l: &Locations| {
+                let x = seen(l.x.load(load));
+                x << 2 | seen(l.y.load(load))
+            }),
+            Box::new(move |l: &Locations| {
+                let y = seen(l.y.load(load));
+                y << 2 | seen(l.x.load(load))
+            }),
+        ],
+    )
+}
+
+#[test]
+#[ignore]
+fn litmus_iriw_seq_cst() {
+    let outcomes = iriw(SeqCst);
+    never_torn(&outcomes);
+    assert!(!outcomes.contains_key(&vec![0, 0, 0b0100, 0b0100]), "readers disagreed on the write order");
+}
+
+#[test]
+#[ignore]
+fn litmus_iriw_acquire() {
+    // Disagreement is allowed without SeqCst, and shows up on machines
+    // without multi-copy atomicity; report it.
+    never_torn(&iriw(Acquire));
+}
@@ -0,0 +1,137 @@
+// Differential tests: random operation sequences run against AtomicU128 and
+// against a Mutex<u128> model, sequentially and from several threads at once.
+
+extern crate atomic128;
+extern crate proptest;
+
+use std::sync::atomic::Ordering::SeqCst;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use atomic128::AtomicU128;
+use proptest::prelude::*;
+
+#[derive(Clone, Copy, Debug)]
+enum Op {
+    Load,
+    Store(u64),
+    Swap(u64),
+    Cas(u64, u64),
+}
+
+// What an operation returned: the value it saw and, for CAS, whether it won.
+type Outcome = (u128, bool);
+
+// Small seeds so that CAS expectations actually match now and then; each
+// seed spreads over both halves of the word.
+fn value(seed: u64) -> u128 {
+    ((seed as u128) << 64) | (seed * 3) as u128
+}
+
+fn run(a: &AtomicU128, op: Op) -> Outcome {
+    match op {
+        Op::Load => (a.load(SeqCst), false),
+        Op::Store(v) => {
+            a.store(value(v), SeqCst);
+            (0, false)
+        }
+        Op::Swap(v) => (a.swap(value(v), SeqCst), false),
+        Op::Cas(e, n) => match a.compare_exchange(value(e), value(n), SeqCst, SeqCst) {
+            Ok(prev) => (prev, true),
+            Err(actual) => (actual, false),
+        },
+    }
+}
+
+fn apply(state: &mut u128, op: Op) -> Outcome {
+    let prev = *state;
+    match op {
+        Op::Load => (prev, false),
+        Op::Store(v) => {
+            *state = value(v);
+            (0, false)
+        }
+        Op::Swap(v) => {
+            *state = value(v);
+            (prev, false)
+        }
+        Op::Cas(e, n) => {
+            if prev == value(e) {
+                *state = value(n);
+                (prev, true)
+            } else {
+                (prev, false)
+            }
+        }
+    }
+}
+
+struct Model(Mutex<u128>);
+
+impl Model {
+    fn run(&self, op: Op) -> Outcome {
+        apply(&mut self.0.lock().unwrap(), op)
+    }
+}
+
+// Whether some interleaving of the per-thread histories, each kept in
+// program order, replays on the model with the same outcomes and ends in
+// `last`.
+fn linearizes(state: u128, histories: &[Vec<(Op, Outcome)>], next: &mut [usize], last: u128) -> bool {
+    if next.iter().zip(histories).all(|(&i, h)| i == h.len()) {
+        return state == last;
+    }
+    for t in 0..histories.len() {
+        if next[t] == histories[t].len() {
+            continue;
+        }
+        let (op, seen) = histories[t][next[t]];
+        let mut after = state;
+        if apply(&mut after, op) != seen {
+            continue;
+        }
+        next[t] += 1;
+        let found = linearizes(after, histories, next, last);
+        next[t] -= 1;
+        if found {
+            return true;
+        }
+    }
+    false
+}
+
+fn op() -> impl Strategy<Value = Op> {
+    prop_oneof![
+        Just(Op::Load),
+        (0..4u64).prop_map(Op::Store),
+        (0..4u64).prop_map(Op::Swap),
+        (0..4u64, 0..4u64).prop_map(|(e, n)| Op::Cas(e, n)),
+    ]
+}
+
+proptest! {
+    #[test]
+    fn sequential_matches_model(ops in prop::collection::vec(op(), 0..64)) {
+        let a = AtomicU128::new(0);
+        let model = Model(Mutex::new(0));
+        for &op in &ops {
+            prop_assert_eq!(run(&a, op), model.run(op));
+        }
+        prop_assert_eq!(a.load(SeqCst), *model.0.lock().unwrap());
+    }
+
+    #[test]
+    fn concurrent_is_linearizable(threads in prop::collection::vec(prop::collection::vec(op(), 0..5), 1..4)) {
+        let a = Arc::new(AtomicU128::new(0));
+        let handles: Vec<_> = threads
+            .into_iter()
+            .map(|ops| {
+                let a = a.clone();
+                thread::spawn(move || ops.into_iter().map(|op| (op, run(&a, op))).collect::<Vec<_>>())
+            })
+            .collect();
+        let histories: Vec<_> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+        let mut next = vec![0; histories.len()];
+        prop_assert!(linearizes(0, &histories, &mut next, a.load(SeqCst)), "{:?}", histories);
+    }
+}
@@ -0,0 +1,86 @@
+// Record/replay tests. Run with
+//   RUSTFLAGS="--cfg replay" cargo test --test replay
+#![cfg(replay)]
+
+extern crate atomic128;
+
+use std::sync::atomic::Ordering::SeqCst;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use atomic128::replay::{self, Schedule};
+use atomic128::AtomicU128;
+
+// The recorder is global, so the tests here take turns.
+static SERIAL: Mutex<()> = Mutex::new(());
+
+// Two threads append their id to a shared number; the result spells out the
+// order their CASes won in.
+fn race() -> u128 {
+    let a = Arc::new(AtomicU128::new(0));
+    let threads: Vec<_> = (1..3u64)
+        .map(|id| {
+            let a = a.clone();
+            thread::spawn(move || {
+                replay::set_thread(id as usize);
+                for _ in 0..3 {
+                    let mut current = a.load(SeqCst);
+                    loop {
+                        match a.compare_exchange_weak(current, current * 10 + id as u128, SeqCst, SeqCst) {
+                            Ok(_) => break,
+                            Err(actual) => current = actual,
+                        }
+                        thread::yield_now();
+                    }
+                }
+            })
+        })
+        .collect();
+    for t in threads {
+        t.join().unwrap();
+    }
+    a.load(SeqCst)
+}
+
+#[test]
+fn test_replay_repeats_interleaving() {
+    let _serial = SERIAL.lock().unwrap();
+    let mut recorded_result = 0;
+    let (schedule, result) = replay::record(|| recorded_result = race());
+    assert!(result.is_ok());
+    assert!(!schedule.is_empty());
+    for _ in 0..5 {
+        let mut replayed_result = 0;
+        let (replayed, result) = replay::replay(&schedule, || replayed_result = race());
+        assert!(result.is_ok());
+        assert_eq!(replayed_result, recorded_result);
+        let shape = |s: &Schedule| s.events().iter().map(|e| (e.thread, e.succeeded)).collect::<Vec<_>>();
+        assert_eq!(shape(&replayed), shape(&schedule));
+    }
+}
+
+#[test]
+fn test_replay_detects_divergence() {
+    let _serial = SERIAL.lock().unwrap();
+    let (schedule, _) = replay::record(|| {
+        let a = AtomicU128::new(0);
+        assert!(a.compare_exchange(0, 1, SeqCst, SeqCst).is_ok());
+    });
+    let (_, result) = replay::replay(&schedule, || {
+        let a = AtomicU128::new(0);
+        let _ = a.compare_exchange(5, 1, SeqCst, SeqCst);
+    });
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_schedule_text_roundtrip() {
+    let _serial = SERIAL.lock().unwrap();
+    let (schedule, _) = replay::record(|| {
+        race();
+    });
+    let parsed: Schedule = schedule.to_string().parse().unwrap();
+    assert_eq!(parsed, schedule);
+    assert_eq!(schedule.prefix(2).len(), 2);
+    assert!("0 1 2".parse::<Schedule>().is_err());
+}
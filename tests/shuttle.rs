@@ -0,0 +1,94 @@
+// Randomized-schedule tests of the shared structures. Run with
+//   RUSTFLAGS="--cfg shuttle" cargo test --test shuttle --release
+#![cfg(shuttle)]
+
+extern crate atomic128;
+extern crate shuttle;
+
+use atomic128::cells::Once128;
+use atomic128::collections::{Queue, Stack};
+use shuttle::sync::Arc;
+use shuttle::thread;
+
+const ITERATIONS: usize = 1000;
+
+#[test]
+fn test_stack_conserves_items() {
+    shuttle::check_random(
+        || {
+            let stack = Arc::new(Stack::new());
+            let threads: Vec<_> = (0..2u32)
+                .map(|i| {
+                    let stack = stack.clone();
+                    thread::spawn(move || {
+                        stack.push(2 * i);
+                        stack.push(2 * i + 1);
+                        stack.pop()
+                    })
+                })
+                .collect();
+            let mut seen: Vec<u32> = threads.into_iter().filter_map(|t| t.join().unwrap()).collect();
+            while let Some(v) = stack.pop() {
+                seen.push(v);
+            }
+            seen.sort();
+            assert_eq!(seen, vec![0, 1, 2, 3]);
+        },
+        ITERATIONS,
+    );
+}
+
+#[test]
+fn test_queue_keeps_per_producer_order() {
+    shuttle::check_random(
+        || {
+            let queue = Arc::new(Queue::new());
+            let producers: Vec<_> = (0..2u32)
+                .map(|p| {
+                    let queue = queue.clone();
+                    thread::spawn(move || {
+                        for i in 0..3 {
+                            queue.push((p, i));
+                        }
+                    })
+                })
+                .collect();
+            let consumer = {
+                let queue = queue.clone();
+                thread::spawn(move || (0..3).filter_map(|_| queue.pop()).collect::<Vec<_>>())
+            };
+            for p in producers {
+                p.join().unwrap();
+            }
+            let mut popped = consumer.join().unwrap();
+            while let Some(item) = queue.pop() {
+                popped.push(item);
+            }
+            assert_eq!(popped.len(), 6);
+            for p in 0..2 {
+                let order: Vec<u32> = popped.iter().filter(|&&(q, _)| q == p).map(|&(_, i)| i).collect();
+                assert_eq!(order, vec![0, 1, 2]);
+            }
+        },
+        ITERATIONS,
+    );
+}
+
+#[test]
+fn test_once_runs_one_initializer() {
+    shuttle::check_random(
+        || {
+            let once = Arc::new(Once128::new());
+            let threads: Vec<_> = (0..3u32)
+                .map(|i| {
+                    let once = once.clone();
+                    thread::spawn(move || *once.get_or_init(|| i))
+                })
+                .collect();
+            let values: Vec<u32> = threads.into_iter().map(|t| t.join().unwrap()).collect();
+            assert!(values.iter().all(|&v| v == values[0]));
+            assert_eq!(once.get(), Some(&values[0]));
+        },
+        ITERATIONS,
+    );
+}
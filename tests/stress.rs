@@ -0,0 +1,136 @@
+// Multi-threaded stress tests, ignored by default:
+//   cargo test --release --test stress -- --ignored
+// Each runs for STRESS_SECS seconds (default 2) on every available CPU.
+
+extern crate atomic128;
+
+use std::collections::HashSet;
+use std::env;
+use std::sync::atomic::Ordering::SeqCst;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use atomic128::AtomicU128;
+
+fn join(lo: u64, hi: u64) -> u128 {
+    (hi as u128) << 64 | lo as u128
+}
+
+fn split(v: u128) -> (u64, u64) {
+    (v as u64, (v >> 64) as u64)
+}
+
+fn threads() -> usize {
+    thread::available_parallelism().map(|n| n.get()).unwrap_or(4).max(2)
+}
+
+fn duration() -> Duration {
+    let secs = env::var("STRESS_SECS").ok().and_then(|s| s.parse().ok()).unwrap_or(2);
+    Duration::from_secs(secs)
+}
+
+// Runs `f(thread_index)` on every thread until the time is up and returns
+// what each thread handed back.
+fn hammer<T, F>(f: F) -> Vec<T>
+where
+    T: Send + 'static,
+    F: Fn(usize, &AtomicBool) -> T + Send + Sync + 'static,
+{
+    let stop = Arc::new(AtomicBool::new(false));
+    let f = Arc::new(f);
+    let handles: Vec<_> = (0..threads())
+        .map(|i| {
+            let (stop, f) = (stop.clone(), f.clone());
+            thread::spawn(move || f(i, &stop))
+        })
+        .collect();
+    let deadline = Instant::now() + duration();
+    while Instant::now() < deadline {
+        thread::sleep(Duration::from_millis(10));
+    }
+    stop.store(true, Ordering::Relaxed);
+    handles.into_iter().map(|h| h.join().unwrap()).collect()
+}
+
+fn increment(a: &AtomicU128) {
+    let mut current = a.load(SeqCst);
+    loop {
+        match a.compare_exchange(current, current + 1, SeqCst, SeqCst) {
+            Ok(_) => return,
+            Err(actual) => current = actual,
+        }
+    }
+}
+
+#[test]
+#[ignore]
+fn stress_cas_increment_counts_every_success() {
+    let counter = Arc::new(AtomicU128::new((u64::MAX - 1000) as u128));
+    let c = counter.clone();
+    let counts = hammer(move |_, stop| {
+        let mut n = 0u128;
+        while !stop.load(Ordering::Relaxed) {
+            increment(&c);
+            n += 1;
+        }
+        n
+    });
+    let total = counter.load(SeqCst) - (u64::MAX - 1000) as u128;
+    assert_eq!(total, counts.iter().sum::<u128>());
+}
+
+#[test]
+#[ignore]
+fn stress_transfers_conserve_total() {
+    // Two balances in one word; every transfer moves an amount between them.
+    const TOTAL: u64 = 1 << 40;
+    let accounts = Arc::new(AtomicU128::new(join(TOTAL / 2, TOTAL / 2)));
+    let a = accounts.clone();
+    hammer(move |i, stop| {
+        let mut amount = i as u64 + 1;
+        while !stop.load(Ordering::Relaxed) {
+            let current = a.load(SeqCst);
+            let (lo, hi) = split(current);
+            assert_eq!(lo + hi, TOTAL);
+            let next = if amount & 1 == 0 && lo >= amount {
+                join(lo - amount, hi + amount)
+            } else if hi >= amount {
+                join(lo + amount, hi - amount)
+            } else {
+                continue;
+            };
+            let _ = a.compare_exchange(current, next, SeqCst, SeqCst);
+            amount = amount * 7 % 1000 + 1;
+        }
+    });
+    let (lo, hi) = split(accounts.load(SeqCst));
+    assert_eq!(lo + hi, TOTAL);
+}
+
+#[test]
+#[ignore]
+fn stress_swap_never_duplicates_or_tears() {
+    // Every value swapped in is unique and has hi == !lo; each must come back
+    // out of a swap (or be the final value) exactly once.
+    let cell = Arc::new(AtomicU128::new(join(0, !0)));
+    let c = cell.clone();
+    let taken = hammer(move |i, stop| {
+        let mut taken = Vec::new();
+        let mut seq = 0u64;
+        while !stop.load(Ordering::Relaxed) {
+            seq += 1;
+            let mine = ((i as u64 + 1) << 48) | seq;
+            let (lo, hi) = split(c.swap(join(mine, !mine), SeqCst));
+            assert_eq!(hi, !lo, "torn value {:x}:{:x}", hi, lo);
+            taken.push(lo);
+        }
+        taken
+    });
+    let mut seen = HashSet::new();
+    for v in taken.into_iter().flatten().chain(Some(split(cell.load(SeqCst)).0)) {
+        assert!(seen.insert(v), "value {:x} seen twice", v);
+    }
+    assert!(seen.contains(&0));
+}